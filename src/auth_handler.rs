@@ -0,0 +1,93 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use std::{fmt, sync::Arc};
+
+use crate::consts::CapabilityFlags;
+
+/// State of the authentication exchange passed to `AuthHandler`.
+#[derive(Clone, Copy)]
+pub struct AuthContext<'a> {
+    /// Name of the authentication plugin requested by the server.
+    pub plugin: &'a [u8],
+    /// Scramble (salt) sent by the server in the handshake or in the auth switch request.
+    pub scramble: &'a [u8],
+    /// Capabilities negotiated with the server.
+    pub capabilities: CapabilityFlags,
+    /// Server version (`(0, 0, 0)` if it couldn't be parsed).
+    pub server_version: (u16, u16, u16),
+    /// User name (see `OptsBuilder::user`).
+    pub user: Option<&'a str>,
+    /// Password (see `OptsBuilder::pass`).
+    pub pass: Option<&'a str>,
+}
+
+impl fmt::Debug for AuthContext<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthContext")
+            .field("plugin", &String::from_utf8_lossy(self.plugin))
+            .field("scramble", &self.scramble)
+            .field("capabilities", &self.capabilities)
+            .field("server_version", &self.server_version)
+            .field("user", &self.user)
+            .field("pass", &self.pass.map(|_| "***"))
+            .finish()
+    }
+}
+
+/// Trait used to authenticate using plugins that are unknown to the driver
+/// (e.g. custom corporate plugins).
+///
+/// The driver handles the handshake, framing and auth switch requests, the handler only
+/// computes authentication data. Plugins known to the driver (`mysql_native_password`,
+/// `caching_sha2_password` and `sha256_password`) are never passed to the handler.
+pub trait AuthHandler: Sync + Send {
+    /// Returns authentication data sent to the server in the handshake response
+    /// (or in response to an auth switch request).
+    ///
+    /// Returns `None` if the plugin isn't supported by this handler, in which case connection
+    /// fails with `DriverError::UnknownAuthPlugin`.
+    fn auth_data(&self, context: AuthContext<'_>) -> Option<Vec<u8>>;
+
+    /// Returns response to an additional data packet (`AuthMoreData`) sent by the server
+    /// during a multi-step exchange. `data` is the packet payload without the `0x01` header.
+    ///
+    /// Returns `None` if the packet isn't expected (the default), in which case connection
+    /// fails with `DriverError::UnexpectedPacket`.
+    fn more_data(&self, _context: AuthContext<'_>, _data: &[u8]) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+/// Object used to wrap `T: AuthHandler` inside of Opts.
+#[derive(Clone)]
+pub struct AuthHandlerObject(Arc<dyn AuthHandler>);
+
+impl AuthHandlerObject {
+    pub fn new<T: AuthHandler + 'static>(handler: T) -> Self {
+        AuthHandlerObject(Arc::new(handler))
+    }
+
+    pub fn clone_inner(&self) -> Arc<dyn AuthHandler> {
+        self.0.clone()
+    }
+}
+
+impl PartialEq for AuthHandlerObject {
+    fn eq(&self, other: &AuthHandlerObject) -> bool {
+        self.0.as_ref() as *const dyn AuthHandler == other.0.as_ref() as *const dyn AuthHandler
+    }
+}
+
+impl Eq for AuthHandlerObject {}
+
+impl fmt::Debug for AuthHandlerObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Auth handler object")
+    }
+}
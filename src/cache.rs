@@ -0,0 +1,417 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Serde support for `Value` and `Row` (requires the `serde-rows` feature), so that query
+//! results could be stored in a cache (using bincode, CBOR, JSON, etc.) and rehydrated later:
+//!
+//! ```rust,ignore
+//! let cached = serde_json::to_vec(&CachedRow::from(row))?;
+//! let row: Row = serde_json::from_slice::<CachedRow>(&cached)?.into();
+//! ```
+//!
+//! `CachedRow` embeds minimal column metadata (name, type and flags) so that the rehydrated
+//! row supports access by column name and `FromRow`/`FromValue` conversions.
+
+use mysql_common::{packets::column_from_payload, row::new_row};
+use serde::{
+    de::{self, Deserialize, Deserializer, EnumAccess, SeqAccess, VariantAccess, Visitor},
+    ser::{Serialize, SerializeTupleVariant, Serializer},
+};
+
+use std::{fmt, sync::Arc};
+
+use crate::{Row, Value};
+
+const VALUE_VARIANTS: &[&str] = &["NULL", "Bytes", "Int", "UInt", "Float", "Date", "Time"];
+
+/// `Value` that implements `Serialize` and `Deserialize`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedValue(pub Value);
+
+impl From<Value> for CachedValue {
+    fn from(value: Value) -> CachedValue {
+        CachedValue(value)
+    }
+}
+
+impl From<CachedValue> for Value {
+    fn from(value: CachedValue) -> Value {
+        value.0
+    }
+}
+
+/// `Row` that implements `Serialize` and `Deserialize` (see the module docs).
+#[derive(Debug, Clone)]
+pub struct CachedRow(pub Row);
+
+impl From<Row> for CachedRow {
+    fn from(row: Row) -> CachedRow {
+        CachedRow(row)
+    }
+}
+
+impl From<CachedRow> for Row {
+    fn from(row: CachedRow) -> Row {
+        row.0
+    }
+}
+
+struct ValueRef<'a>(&'a Value);
+
+impl Serialize for ValueRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self.0 {
+            Value::NULL => serializer.serialize_unit_variant("Value", 0, "NULL"),
+            Value::Bytes(ref bytes) => {
+                serializer.serialize_newtype_variant("Value", 1, "Bytes", &BytesRef(bytes))
+            }
+            Value::Int(x) => serializer.serialize_newtype_variant("Value", 2, "Int", &x),
+            Value::UInt(x) => serializer.serialize_newtype_variant("Value", 3, "UInt", &x),
+            Value::Float(x) => serializer.serialize_newtype_variant("Value", 4, "Float", &x),
+            Value::Date(year, month, day, hour, minute, second, micros) => {
+                let mut state = serializer.serialize_tuple_variant("Value", 5, "Date", 7)?;
+                state.serialize_field(&year)?;
+                state.serialize_field(&month)?;
+                state.serialize_field(&day)?;
+                state.serialize_field(&hour)?;
+                state.serialize_field(&minute)?;
+                state.serialize_field(&second)?;
+                state.serialize_field(&micros)?;
+                state.end()
+            }
+            Value::Time(is_neg, days, hours, minutes, seconds, micros) => {
+                let mut state = serializer.serialize_tuple_variant("Value", 6, "Time", 6)?;
+                state.serialize_field(&is_neg)?;
+                state.serialize_field(&days)?;
+                state.serialize_field(&hours)?;
+                state.serialize_field(&minutes)?;
+                state.serialize_field(&seconds)?;
+                state.serialize_field(&micros)?;
+                state.end()
+            }
+        }
+    }
+}
+
+impl Serialize for CachedValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ValueRef(&self.0).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CachedValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_enum("Value", VALUE_VARIANTS, ValueVisitor)
+    }
+}
+
+impl Serialize for CachedRow {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let row = &self.0;
+        let columns = row
+            .columns_ref()
+            .iter()
+            .map(|column| {
+                (
+                    column.name_str(),
+                    column.column_type() as u8,
+                    column.flags().bits(),
+                )
+            })
+            .collect::<Vec<_>>();
+        // Values taken out of the row are serialized as `NULL`.
+        let values = (0..row.len())
+            .map(|i| ValueRef(row.as_ref(i).unwrap_or(&Value::NULL)))
+            .collect::<Vec<_>>();
+        (columns, values).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CachedRow {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (columns, values): (Vec<(String, u8, u16)>, Vec<CachedValue>) =
+            Deserialize::deserialize(deserializer)?;
+        if columns.len() != values.len() {
+            return Err(de::Error::invalid_length(
+                values.len(),
+                &"as many values as columns",
+            ));
+        }
+        let columns = columns
+            .into_iter()
+            .map(|(name, column_type, flags)| {
+                column_from_payload(column_payload(&name, column_type, flags))
+                    .map_err(de::Error::custom)
+            })
+            .collect::<Result<Vec<_>, D::Error>>()?;
+        let values = values.into_iter().map(Value::from).collect();
+        Ok(CachedRow(new_row(values, Arc::new(columns))))
+    }
+}
+
+/// Returns payload of a column definition packet with the given metadata.
+fn column_payload(name: &str, column_type: u8, flags: u16) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(name.len() * 2 + 24);
+    for part in &["def", "", "", "", name, name] {
+        write_lenenc_str(&mut payload, part.as_bytes());
+    }
+    payload.push(0x0c);
+    // utf8mb4_general_ci
+    payload.extend_from_slice(&45u16.to_le_bytes());
+    payload.extend_from_slice(&0u32.to_le_bytes());
+    payload.push(column_type);
+    payload.extend_from_slice(&flags.to_le_bytes());
+    // decimals and filler
+    payload.extend_from_slice(&[0, 0, 0]);
+    payload
+}
+
+fn write_lenenc_str(buf: &mut Vec<u8>, bytes: &[u8]) {
+    let len = bytes.len() as u64;
+    if len < 251 {
+        buf.push(len as u8);
+    } else if len < 1 << 16 {
+        buf.push(0xfc);
+        buf.extend_from_slice(&(len as u16).to_le_bytes());
+    } else if len < 1 << 24 {
+        buf.push(0xfd);
+        buf.extend_from_slice(&(len as u32).to_le_bytes()[..3]);
+    } else {
+        buf.push(0xfe);
+        buf.extend_from_slice(&len.to_le_bytes());
+    }
+    buf.extend_from_slice(bytes);
+}
+
+struct BytesRef<'a>(&'a [u8]);
+
+impl Serialize for BytesRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+struct ByteBuf(Vec<u8>);
+
+impl<'de> Deserialize<'de> for ByteBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_byte_buf(ByteBufVisitor)
+    }
+}
+
+struct ByteBufVisitor;
+
+impl<'de> Visitor<'de> for ByteBufVisitor {
+    type Value = ByteBuf;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("bytes")
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<ByteBuf, E> {
+        Ok(ByteBuf(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<ByteBuf, E> {
+        Ok(ByteBuf(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<ByteBuf, E> {
+        Ok(ByteBuf(v.as_bytes().to_vec()))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ByteBuf, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(ByteBuf(bytes))
+    }
+}
+
+enum ValueTag {
+    Null,
+    Bytes,
+    Int,
+    UInt,
+    Float,
+    Date,
+    Time,
+}
+
+impl<'de> Deserialize<'de> for ValueTag {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_identifier(ValueTagVisitor)
+    }
+}
+
+struct ValueTagVisitor;
+
+impl<'de> Visitor<'de> for ValueTagVisitor {
+    type Value = ValueTag;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("`Value` variant")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<ValueTag, E> {
+        match VALUE_VARIANTS.get(v as usize) {
+            Some(name) => self.visit_str(name),
+            None => Err(de::Error::invalid_value(de::Unexpected::Unsigned(v), &self)),
+        }
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<ValueTag, E> {
+        match v {
+            "NULL" => Ok(ValueTag::Null),
+            "Bytes" => Ok(ValueTag::Bytes),
+            "Int" => Ok(ValueTag::Int),
+            "UInt" => Ok(ValueTag::UInt),
+            "Float" => Ok(ValueTag::Float),
+            "Date" => Ok(ValueTag::Date),
+            "Time" => Ok(ValueTag::Time),
+            _ => Err(de::Error::unknown_variant(v, VALUE_VARIANTS)),
+        }
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = CachedValue;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("`Value`")
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<CachedValue, A::Error> {
+        let (tag, variant) = data.variant::<ValueTag>()?;
+        let value = match tag {
+            ValueTag::Null => {
+                variant.unit_variant()?;
+                Value::NULL
+            }
+            ValueTag::Bytes => Value::Bytes(variant.newtype_variant::<ByteBuf>()?.0),
+            ValueTag::Int => Value::Int(variant.newtype_variant()?),
+            ValueTag::UInt => Value::UInt(variant.newtype_variant()?),
+            ValueTag::Float => Value::Float(variant.newtype_variant()?),
+            ValueTag::Date => variant.tuple_variant(7, DateVisitor)?,
+            ValueTag::Time => variant.tuple_variant(6, TimeVisitor)?,
+        };
+        Ok(CachedValue(value))
+    }
+}
+
+/// Returns the next element of a tuple variant.
+fn next_field<'de, A, T>(
+    seq: &mut A,
+    index: usize,
+    expected: &dyn de::Expected,
+) -> Result<T, A::Error>
+where
+    A: SeqAccess<'de>,
+    T: Deserialize<'de>,
+{
+    seq.next_element()?
+        .ok_or_else(|| de::Error::invalid_length(index, expected))
+}
+
+struct DateVisitor;
+
+impl<'de> Visitor<'de> for DateVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("`Value::Date` fields")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        Ok(Value::Date(
+            next_field(&mut seq, 0, &self)?,
+            next_field(&mut seq, 1, &self)?,
+            next_field(&mut seq, 2, &self)?,
+            next_field(&mut seq, 3, &self)?,
+            next_field(&mut seq, 4, &self)?,
+            next_field(&mut seq, 5, &self)?,
+            next_field(&mut seq, 6, &self)?,
+        ))
+    }
+}
+
+struct TimeVisitor;
+
+impl<'de> Visitor<'de> for TimeVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("`Value::Time` fields")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        Ok(Value::Time(
+            next_field(&mut seq, 0, &self)?,
+            next_field(&mut seq, 1, &self)?,
+            next_field(&mut seq, 2, &self)?,
+            next_field(&mut seq, 3, &self)?,
+            next_field(&mut seq, 4, &self)?,
+            next_field(&mut seq, 5, &self)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mysql_common::row::new_row;
+
+    use std::sync::Arc;
+
+    use super::{column_payload, CachedRow, CachedValue};
+    use crate::{consts::ColumnType, Row, Value};
+
+    #[test]
+    fn should_serialize_values_and_rows() {
+        let values = vec![
+            Value::NULL,
+            Value::Bytes(b"foo\0".to_vec()),
+            Value::Int(-1),
+            Value::UInt(u64::max_value()),
+            Value::Float(0.5),
+            Value::Date(2019, 1, 2, 3, 4, 5, 6),
+            Value::Time(true, 1, 2, 3, 4, 5),
+        ];
+        for value in values {
+            let json = serde_json::to_string(&CachedValue(value.clone())).unwrap();
+            let decoded: CachedValue = serde_json::from_str(&json).unwrap();
+            assert_eq!(decoded.0, value);
+        }
+
+        let columns = vec![
+            mysql_common::packets::column_from_payload(column_payload(
+                "id",
+                ColumnType::MYSQL_TYPE_LONGLONG as u8,
+                0,
+            ))
+            .unwrap(),
+            mysql_common::packets::column_from_payload(column_payload(
+                "name",
+                ColumnType::MYSQL_TYPE_VAR_STRING as u8,
+                0,
+            ))
+            .unwrap(),
+        ];
+        let row = new_row(
+            vec![Value::Int(1), Value::Bytes(b"foo".to_vec())],
+            Arc::new(columns),
+        );
+        let json = serde_json::to_string(&CachedRow(row)).unwrap();
+        let row: Row = serde_json::from_str::<CachedRow>(&json).unwrap().into();
+        assert_eq!(row.get::<i64, _>("id"), Some(1));
+        assert_eq!(row.get::<String, _>("name"), Some("foo".into()));
+        assert_eq!(row.columns_ref()[1].name_str(), "name");
+    }
+}
@@ -0,0 +1,142 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use crate::{
+    consts::{ColumnFlags, ColumnType},
+    Column,
+};
+
+/// Id of the `binary` character set (and collation).
+const BINARY_CHARSET: u16 = 63;
+
+/// Convenience accessors of result set metadata.
+///
+/// Raw values are available via `Column::flags` and `Column::character_set`. The protocol
+/// doesn't report whether a column is generated or invisible, use `Conn::table_columns`
+/// to get this information.
+pub trait ColumnExt {
+    /// `true` if values of this column are integers (including `YEAR`).
+    fn is_integer(&self) -> bool;
+
+    /// `true` if `UNSIGNED_FLAG` is set.
+    ///
+    /// Values of unsigned integer columns are decoded as `Value::UInt` by the binary protocol.
+    fn is_unsigned(&self) -> bool;
+
+    /// `true` if `ZEROFILL_FLAG` is set (text protocol values are padded with zeros).
+    fn is_zerofill(&self) -> bool;
+
+    /// `true` unless `NOT_NULL_FLAG` is set.
+    ///
+    /// Note that the server reports expressions (e.g. `SELECT a + 1`) as nullable
+    /// only if it can't prove otherwise.
+    fn is_nullable(&self) -> bool;
+
+    /// `true` if `BINARY_FLAG` is set, i.e. values are compared byte by byte
+    /// (e.g. `VARBINARY`, `BLOB` or a column with a `_bin` collation).
+    fn is_binary(&self) -> bool;
+
+    /// `true` if the column has the `binary` character set, i.e. its values are byte strings
+    /// rather than text (numeric and temporal columns also have this character set).
+    fn is_binary_charset(&self) -> bool;
+}
+
+impl ColumnExt for Column {
+    fn is_integer(&self) -> bool {
+        use ColumnType::*;
+
+        match self.column_type() {
+            MYSQL_TYPE_TINY | MYSQL_TYPE_SHORT | MYSQL_TYPE_INT24 | MYSQL_TYPE_LONG
+            | MYSQL_TYPE_LONGLONG | MYSQL_TYPE_YEAR => true,
+            _ => false,
+        }
+    }
+
+    fn is_unsigned(&self) -> bool {
+        self.flags().contains(ColumnFlags::UNSIGNED_FLAG)
+    }
+
+    fn is_zerofill(&self) -> bool {
+        self.flags().contains(ColumnFlags::ZEROFILL_FLAG)
+    }
+
+    fn is_nullable(&self) -> bool {
+        !self.flags().contains(ColumnFlags::NOT_NULL_FLAG)
+    }
+
+    fn is_binary(&self) -> bool {
+        self.flags().contains(ColumnFlags::BINARY_FLAG)
+    }
+
+    fn is_binary_charset(&self) -> bool {
+        self.character_set() == BINARY_CHARSET
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mysql_common::packets::column_from_payload;
+
+    use super::ColumnExt;
+    use crate::{queryable::read_bin_values, Column, Value::*};
+
+    fn column(column_type: u8, flags: u16, charset: u16) -> Column {
+        let mut payload = Vec::new();
+        for part in &["def", "", "", "", "c", "c"] {
+            payload.push(part.len() as u8);
+            payload.extend_from_slice(part.as_bytes());
+        }
+        payload.push(0x0c);
+        payload.extend_from_slice(&charset.to_le_bytes());
+        payload.extend_from_slice(&[0x0b, 0, 0, 0, column_type]);
+        payload.extend_from_slice(&flags.to_le_bytes());
+        payload.extend_from_slice(&[0, 0, 0]);
+        column_from_payload(payload).unwrap()
+    }
+
+    #[test]
+    fn should_expose_column_flags() {
+        // INT UNSIGNED ZEROFILL
+        let int = column(0x03, 0x20 | 0x40, 63);
+        assert!(int.is_integer() && int.is_unsigned() && int.is_zerofill());
+        assert!(int.is_binary_charset() && int.is_nullable());
+
+        // INT NOT NULL
+        assert!(!column(0x03, 0x01, 63).is_nullable());
+
+        // VARCHAR .. COLLATE utf8mb4_bin
+        let varchar = column(0xfd, 0x80, 46);
+        assert!(!varchar.is_integer() && !varchar.is_unsigned());
+        assert!(varchar.is_binary() && !varchar.is_binary_charset());
+    }
+
+    #[test]
+    fn should_decode_unsigned_integers_as_uint() {
+        let columns = vec![
+            column(0x01, 0x20, 63), // TINYINT UNSIGNED
+            column(0x01, 0x00, 63), // TINYINT
+            column(0x03, 0x20, 63), // INT UNSIGNED
+            column(0x08, 0x20, 63), // BIGINT UNSIGNED
+        ];
+        let mut payload = vec![0x00, 0x00];
+        payload.push(0xff);
+        payload.push(0xff);
+        payload.extend_from_slice(&u32::max_value().to_le_bytes());
+        payload.extend_from_slice(&u64::max_value().to_le_bytes());
+
+        assert_eq!(
+            read_bin_values(&payload, &columns).unwrap(),
+            vec![
+                UInt(255),
+                Int(-1),
+                UInt(u64::from(u32::max_value())),
+                UInt(u64::max_value())
+            ]
+        );
+    }
+}
@@ -0,0 +1,97 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use futures::{Future, Poll};
+
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use crate::{
+    conn::Conn,
+    error::*,
+    opts::Opts,
+    queryable::{futures::Query, Queryable},
+    MyFuture, QueryResult, TextProtocol,
+};
+
+/// Token that cancels a query started by `Conn::query_cancellable`.
+#[derive(Debug, Clone)]
+pub struct CancelToken {
+    opts: Opts,
+    conn_id: u32,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    /// Returns future that cancels the query.
+    ///
+    /// It establishes a short-lived side connection and issues `KILL QUERY` for the connection
+    /// executing the query. The query future then resolves to `DriverError::Cancelled`.
+    pub fn cancel(&self) -> impl MyFuture<()> {
+        self.cancelled.store(true, Ordering::SeqCst);
+        let conn_id = self.conn_id;
+        Conn::new(self.opts.clone())
+            .and_then(move |conn| conn.kill(conn_id))
+            .and_then(|conn| conn.disconnect())
+    }
+
+    /// Returns `true` if `CancelToken::cancel` was called.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// Future that resolves to a result of a query started by `Conn::query_cancellable`.
+pub struct QueryHandle {
+    fut: Query<Conn>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl fmt::Debug for QueryHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QueryHandle")
+            .field("cancelled", &self.cancelled.load(Ordering::SeqCst))
+            .finish()
+    }
+}
+
+impl Future for QueryHandle {
+    type Item = QueryResult<Conn, TextProtocol>;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let result = self.fut.poll();
+        if self.cancelled.load(Ordering::SeqCst) {
+            // Interrupted query either fails or returns a partial result.
+            match result {
+                Ok(futures::Async::NotReady) => (),
+                _ => return Err(DriverError::Cancelled.into()),
+            }
+        }
+        result
+    }
+}
+
+pub fn new(conn: Conn, query: String) -> (QueryHandle, CancelToken) {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let token = CancelToken {
+        opts: conn.inner.opts.clone(),
+        conn_id: conn.inner.id,
+        cancelled: cancelled.clone(),
+    };
+    let handle = QueryHandle {
+        fut: conn.query(query),
+        cancelled,
+    };
+    (handle, token)
+}
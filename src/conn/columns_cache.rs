@@ -0,0 +1,143 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use mysql_common::packets::{column_from_payload, Column, RawPacket};
+
+use std::{collections::VecDeque, sync::Arc};
+
+use crate::error::*;
+
+/// Cache of result set metadata.
+///
+/// A query that is executed over and over again yields byte-identical column definitions,
+/// so instead of parsing (and allocating) them for every result set the connection hands out
+/// the same `Arc<Vec<Column>>` (and so column names are shared by all rows of all such
+/// result sets).
+#[derive(Debug)]
+pub struct ColumnsCache {
+    cap: usize,
+    /// Column definition payloads (each prefixed with its length) and parsed columns,
+    /// most recently used last.
+    entries: VecDeque<(Vec<u8>, Arc<Vec<Column>>)>,
+}
+
+impl ColumnsCache {
+    pub fn new(cap: usize) -> ColumnsCache {
+        ColumnsCache {
+            cap,
+            entries: VecDeque::with_capacity(cap),
+        }
+    }
+
+    /// Returns columns defined by the given column definition packets.
+    pub fn get_or_parse(&mut self, packets: Vec<RawPacket>) -> Result<Arc<Vec<Column>>> {
+        if let Some(pos) = self
+            .entries
+            .iter()
+            .position(|(key, _)| matches(key, &packets))
+        {
+            let entry = self.entries.remove(pos).expect("position is in bounds");
+            let columns = entry.1.clone();
+            self.entries.push_back(entry);
+            return Ok(columns);
+        }
+
+        let mut key = Vec::with_capacity(packets.iter().map(|p| p.0.len() + 4).sum());
+        for packet in packets.iter() {
+            key.extend_from_slice(&(packet.0.len() as u32).to_le_bytes());
+            key.extend_from_slice(&*packet.0);
+        }
+
+        let columns = packets
+            .into_iter()
+            .map(|packet| column_from_payload(packet.0).map_err(Error::from))
+            .collect::<Result<Vec<Column>>>()?;
+        let columns = Arc::new(columns);
+
+        if self.cap > 0 {
+            if self.entries.len() >= self.cap {
+                self.entries.pop_front();
+            }
+            self.entries.push_back((key, columns.clone()));
+        }
+
+        Ok(columns)
+    }
+}
+
+/// Returns `true` if `key` consists of exactly the given packets.
+fn matches(mut key: &[u8], packets: &[RawPacket]) -> bool {
+    for packet in packets {
+        if key.len() < 4 {
+            return false;
+        }
+        let mut len = [0u8; 4];
+        len.copy_from_slice(&key[..4]);
+        let len = u32::from_le_bytes(len) as usize;
+        if len != packet.0.len() || key.len() < 4 + len || key[4..4 + len] != packet.0[..] {
+            return false;
+        }
+        key = &key[4 + len..];
+    }
+    key.is_empty()
+}
+
+#[cfg(test)]
+mod test {
+    use mysql_common::packets::RawPacket;
+
+    use std::sync::Arc;
+
+    use super::ColumnsCache;
+
+    fn column_payload(name: &str) -> Vec<u8> {
+        let mut payload = Vec::new();
+        for part in &["def", "", "", "", name, name] {
+            payload.push(part.len() as u8);
+            payload.extend_from_slice(part.as_bytes());
+        }
+        payload.extend_from_slice(&[0x0c, 0x3f, 0, 0x0b, 0, 0, 0, 0x03, 0, 0, 0, 0, 0]);
+        payload
+    }
+
+    fn packets(names: &[&str]) -> Vec<RawPacket> {
+        names
+            .iter()
+            .map(|name| RawPacket(column_payload(name)))
+            .collect()
+    }
+
+    #[test]
+    fn should_share_identical_columns() {
+        let mut cache = ColumnsCache::new(2);
+        let a = cache.get_or_parse(packets(&["a", "b"])).unwrap();
+        let b = cache.get_or_parse(packets(&["a", "b"])).unwrap();
+        assert!(Arc::ptr_eq(&a, &b));
+        assert_eq!(a[1].name_str(), "b");
+
+        let c = cache.get_or_parse(packets(&["a"])).unwrap();
+        let d = cache.get_or_parse(packets(&["ab"])).unwrap();
+        assert!(!Arc::ptr_eq(&c, &d));
+
+        // ["a", "b"] is evicted as the least recently used entry
+        let e = cache.get_or_parse(packets(&["a", "b"])).unwrap();
+        assert!(!Arc::ptr_eq(&a, &e));
+        assert!(Arc::ptr_eq(
+            &d,
+            &cache.get_or_parse(packets(&["ab"])).unwrap()
+        ));
+    }
+
+    #[test]
+    fn should_not_cache_if_disabled() {
+        let mut cache = ColumnsCache::new(0);
+        let a = cache.get_or_parse(packets(&["a"])).unwrap();
+        let b = cache.get_or_parse(packets(&["a"])).unwrap();
+        assert!(!Arc::ptr_eq(&a, &b));
+    }
+}
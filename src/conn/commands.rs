@@ -0,0 +1,125 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use std::str::FromStr;
+
+use crate::error::*;
+
+/// Option toggled by `COM_SET_OPTION` (see `Conn::set_option`).
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum MultiStatements {
+    /// `MYSQL_OPTION_MULTI_STATEMENTS_ON`.
+    On = 0,
+    /// `MYSQL_OPTION_MULTI_STATEMENTS_OFF`.
+    Off = 1,
+}
+
+/// Server statistics reported by `COM_STATISTICS` (see `Conn::statistics`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerStatistics {
+    /// Number of seconds the server has been running.
+    pub uptime: u64,
+    /// Number of currently open connections.
+    pub threads: u64,
+    /// Number of statements executed by the server.
+    pub questions: u64,
+    pub slow_queries: u64,
+    /// Number of tables that have been opened.
+    pub opens: u64,
+    pub flush_tables: u64,
+    pub open_tables: u64,
+    pub queries_per_second_avg: f64,
+}
+
+impl ServerStatistics {
+    /// Parses a `COM_STATISTICS` response, e.g. `Uptime: 3  Threads: 1  Questions: 2 ...`.
+    ///
+    /// Unknown fields are ignored.
+    pub(crate) fn parse(payload: &[u8]) -> Result<ServerStatistics> {
+        let mut stats = ServerStatistics {
+            uptime: 0,
+            threads: 0,
+            questions: 0,
+            slow_queries: 0,
+            opens: 0,
+            flush_tables: 0,
+            open_tables: 0,
+            queries_per_second_avg: 0.0,
+        };
+        let mut found = 0;
+
+        for field in String::from_utf8_lossy(payload).split("  ") {
+            let mut parts = field.splitn(2, ':');
+            let (name, value) = match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) => (name.trim(), value.trim()),
+                _ => continue,
+            };
+            let counter = match name {
+                "Uptime" => &mut stats.uptime,
+                "Threads" => &mut stats.threads,
+                "Questions" => &mut stats.questions,
+                "Slow queries" => &mut stats.slow_queries,
+                "Opens" => &mut stats.opens,
+                "Flush tables" => &mut stats.flush_tables,
+                "Open tables" => &mut stats.open_tables,
+                "Queries per second avg" => {
+                    stats.queries_per_second_avg =
+                        f64::from_str(value).map_err(|_| unexpected(payload))?;
+                    found += 1;
+                    continue;
+                }
+                _ => continue,
+            };
+            *counter = u64::from_str(value).map_err(|_| unexpected(payload))?;
+            found += 1;
+        }
+
+        if found == 8 {
+            Ok(stats)
+        } else {
+            Err(unexpected(payload))
+        }
+    }
+}
+
+fn unexpected(payload: &[u8]) -> Error {
+    DriverError::UnexpectedPacket {
+        payload: payload.into(),
+    }
+    .into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::ServerStatistics;
+
+    #[test]
+    fn should_parse_server_statistics() {
+        let stats = ServerStatistics::parse(
+            b"Uptime: 3606  Threads: 2  Questions: 41  Slow queries: 1  Opens: 117  \
+              Flush tables: 3  Open tables: 36  Queries per second avg: 0.011",
+        )
+        .unwrap();
+        assert_eq!(
+            stats,
+            ServerStatistics {
+                uptime: 3606,
+                threads: 2,
+                questions: 41,
+                slow_queries: 1,
+                opens: 117,
+                flush_tables: 3,
+                open_tables: 36,
+                queries_per_second_avg: 0.011,
+            }
+        );
+
+        assert!(ServerStatistics::parse(b"Uptime: 3606  Threads: 2").is_err());
+        assert!(ServerStatistics::parse(b"Uptime: 3606  Threads: two").is_err());
+    }
+}
@@ -0,0 +1,272 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use std::borrow::Cow;
+
+use futures::future::{loop_fn, Either::*, Future, IntoFuture, Loop};
+use tokio_io::{io::write_all, AsyncWrite};
+
+use crate::{
+    conn::{schema::quote_table_name, Conn},
+    consts::ColumnType,
+    error::*,
+    escape::{escape_identifier, escape_literal},
+    prelude::ColumnExt,
+    queryable::query_result::value_to_text,
+    queryable::Queryable,
+    Column, MyFuture, Row, Value,
+};
+
+/// Number of rows fetched per query by `Conn::dump_table`.
+const DUMP_BATCH_SIZE: usize = 1000;
+
+/// Output format of `Conn::dump_table`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum DumpFormat {
+    /// RFC 4180 CSV with a header line. `NULL` is written as an empty unquoted field
+    /// (empty strings are quoted).
+    Csv,
+    /// Tab separated values as produced by `SELECT ... INTO OUTFILE` (`NULL` is written as `\N`).
+    Tsv,
+    /// One JSON object per line keyed by column names. Integer and floating point columns
+    /// are written as numbers, values that aren't valid UTF-8 (e.g. `BLOB`s) are written
+    /// as arrays of bytes.
+    JsonLines,
+}
+
+/// Returns future that writes rows of a `table` having primary key greater than `after`
+/// (or all rows) into `writer`. Resolves to the number of rows written.
+///
+/// Rows are read using the text protocol, so values are written exactly as the server
+/// renders them (binary strings are written as is).
+pub(crate) fn dump_table<T, W>(
+    conn: Conn,
+    table: T,
+    writer: W,
+    format: DumpFormat,
+    after: Option<Value>,
+) -> impl MyFuture<(Conn, W, u64)>
+where
+    T: AsRef<str>,
+    W: AsyncWrite + Send + 'static,
+{
+    let table = quote_table_name(table.as_ref());
+    let keys_query = format!("SHOW KEYS FROM {} WHERE Key_name = 'PRIMARY'", table);
+    let header = after.is_none() && format == DumpFormat::Csv;
+
+    conn.query(keys_query)
+        .and_then(|result| result.collect_and_drop::<Row>())
+        .and_then(move |(conn, keys)| {
+            let key = match &*keys {
+                [key] => key.get::<String, _>("Column_name"),
+                _ => None,
+            };
+            key.map(|key| (conn, key))
+                .ok_or_else(|| {
+                    Error::from(DriverError::NoSingleColumnKey {
+                        table: table.clone(),
+                    })
+                })
+                .into_future()
+                .map(move |(conn, key)| (conn, table, key))
+        })
+        .and_then(move |(conn, table, key)| {
+            let first_page = format!(
+                "SELECT * FROM {} ORDER BY {key} LIMIT {}",
+                table,
+                DUMP_BATCH_SIZE,
+                key = escape_identifier(&key)
+            );
+            let quoted_key = escape_identifier(&key);
+
+            loop_fn(
+                (conn, writer, after, header, 0),
+                move |(conn, writer, after, header, count)| {
+                    let fut = match after {
+                        Some(value) => A(conn.query(format!(
+                            "SELECT * FROM {} WHERE {key} > {} ORDER BY {key} LIMIT {}",
+                            table,
+                            // `binary` keeps the literal comparable with any key column
+                            escape_literal(&value, "binary"),
+                            DUMP_BATCH_SIZE,
+                            key = quoted_key
+                        ))),
+                        None => B(conn.query(first_page.clone())),
+                    };
+                    let key = key.clone();
+                    fut.and_then(|result| result.collect_and_drop::<Row>())
+                        .and_then(move |(conn, rows)| {
+                            let mut buf = Vec::new();
+                            if header {
+                                if let Some(row) = rows.first() {
+                                    write_csv_header(&mut buf, row);
+                                }
+                            }
+                            for row in &rows {
+                                write_row(&mut buf, row, format);
+                            }
+                            let last_key = rows.last().and_then(|row| row.get::<Value, _>(&*key));
+                            let written = rows.len();
+                            write_all(writer, buf)
+                                .map_err(Error::from)
+                                .map(move |(writer, _)| {
+                                    let count = count + written as u64;
+                                    match last_key {
+                                        Some(last_key) if written == DUMP_BATCH_SIZE => {
+                                            Loop::Continue((
+                                                conn,
+                                                writer,
+                                                Some(last_key),
+                                                false,
+                                                count,
+                                            ))
+                                        }
+                                        _ => Loop::Break((conn, writer, count)),
+                                    }
+                                })
+                        })
+                },
+            )
+        })
+}
+
+fn write_csv_header(buf: &mut Vec<u8>, row: &Row) {
+    for (i, column) in row.columns_ref().iter().enumerate() {
+        if i > 0 {
+            buf.push(b',');
+        }
+        write_csv_field(buf, column.name_str().as_bytes());
+    }
+    buf.extend_from_slice(b"\r\n");
+}
+
+fn write_row(buf: &mut Vec<u8>, row: &Row, format: DumpFormat) {
+    match format {
+        DumpFormat::Csv => {
+            for i in 0..row.len() {
+                if i > 0 {
+                    buf.push(b',');
+                }
+                match row.as_ref(i) {
+                    Some(Value::NULL) | None => (),
+                    Some(value) => write_csv_field(buf, &value_to_bytes(value)),
+                }
+            }
+            buf.extend_from_slice(b"\r\n");
+        }
+        DumpFormat::Tsv => {
+            for i in 0..row.len() {
+                if i > 0 {
+                    buf.push(b'\t');
+                }
+                match row.as_ref(i) {
+                    Some(Value::NULL) | None => buf.extend_from_slice(b"\\N"),
+                    Some(value) => write_tsv_field(buf, &value_to_bytes(value)),
+                }
+            }
+            buf.push(b'\n');
+        }
+        DumpFormat::JsonLines => {
+            let mut object = serde_json::Map::new();
+            for (i, column) in row.columns_ref().iter().enumerate() {
+                let value = match row.as_ref(i) {
+                    Some(Value::NULL) | None => serde_json::Value::Null,
+                    Some(value) => json_value(column, &value_to_bytes(value)),
+                };
+                object.insert(column.name_str().into_owned(), value);
+            }
+            buf.extend_from_slice(serde_json::Value::Object(object).to_string().as_bytes());
+            buf.push(b'\n');
+        }
+    }
+}
+
+/// Returns the text representation of a value as sent by the server.
+///
+/// Rows are read using the text protocol, so values are `Value::Bytes` (or `NULL`).
+fn value_to_bytes(value: &Value) -> Cow<[u8]> {
+    match value {
+        Value::Bytes(bytes) => Cow::Borrowed(&**bytes),
+        value => Cow::Owned(value_to_text(value.clone()).into_bytes()),
+    }
+}
+
+fn json_value(column: &Column, bytes: &[u8]) -> serde_json::Value {
+    let text = match std::str::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(_) => return bytes.to_vec().into(),
+    };
+    let number = if column.is_integer() && column.is_unsigned() {
+        text.parse::<u64>().ok().map(Into::into)
+    } else if column.is_integer() {
+        text.parse::<i64>().ok().map(Into::into)
+    } else {
+        match column.column_type() {
+            ColumnType::MYSQL_TYPE_FLOAT | ColumnType::MYSQL_TYPE_DOUBLE => {
+                text.parse::<f64>().ok().map(Into::into)
+            }
+            _ => None,
+        }
+    };
+    number.unwrap_or_else(|| text.into())
+}
+
+fn write_csv_field(buf: &mut Vec<u8>, bytes: &[u8]) {
+    let needs_quotes = bytes.is_empty()
+        || bytes
+            .iter()
+            .any(|&x| x == b',' || x == b'"' || x == b'\r' || x == b'\n');
+    if needs_quotes {
+        buf.push(b'"');
+        for &byte in bytes {
+            if byte == b'"' {
+                buf.push(b'"');
+            }
+            buf.push(byte);
+        }
+        buf.push(b'"');
+    } else {
+        buf.extend_from_slice(bytes);
+    }
+}
+
+fn write_tsv_field(buf: &mut Vec<u8>, bytes: &[u8]) {
+    for &byte in bytes {
+        match byte {
+            b'\\' => buf.extend_from_slice(b"\\\\"),
+            b'\t' => buf.extend_from_slice(b"\\t"),
+            b'\n' => buf.extend_from_slice(b"\\n"),
+            b'\r' => buf.extend_from_slice(b"\\r"),
+            b'\0' => buf.extend_from_slice(b"\\0"),
+            byte => buf.push(byte),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{write_csv_field, write_tsv_field};
+
+    #[test]
+    fn should_escape_fields() {
+        let mut buf = Vec::new();
+        write_csv_field(&mut buf, b"a,\"b\"");
+        write_csv_field(&mut buf, b"");
+        write_csv_field(&mut buf, b"plain");
+        assert_eq!(buf, b"\"a,\"\"b\"\"\"\"\"plain".to_vec());
+
+        let mut buf = Vec::new();
+        write_tsv_field(&mut buf, b"a\tb\\c\n");
+        assert_eq!(buf, b"a\\tb\\\\c\\n".to_vec());
+
+        let mut buf = Vec::new();
+        write_csv_field(&mut buf, b"\xff\x00,");
+        write_tsv_field(&mut buf, b"\xff\x00");
+        assert_eq!(buf, b"\"\xff\x00,\"\xff\\0".to_vec());
+    }
+}
@@ -0,0 +1,129 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use ::futures::{
+    future::{ok, Either::*, Future},
+    sync::{mpsc, oneshot},
+    Stream,
+};
+use tokio::executor::{DefaultExecutor, Executor};
+
+use crate::{
+    conn::{
+        pool::Pool,
+        request::{execute, QueryRequest, QueryResponse},
+        Conn,
+    },
+    error::*,
+    opts::Opts,
+    queryable::Queryable,
+    MyFuture, Params,
+};
+
+type Job = (QueryRequest, oneshot::Sender<Result<QueryResponse>>);
+
+/// Cloneable handle to a connection owned by a background task.
+///
+/// Requests of all clones are queued and executed by the task one at a time in the order
+/// they were sent, so a single connection could be shared without passing it around
+/// by value. If a request fails, the error is returned to its sender and the task
+/// establishes a new connection for the next request (taking it from the pool the original
+/// connection belongs to, if any), so session state (e.g. temporary tables) is lost.
+///
+/// Connection is returned to its pool (or disconnected) once all clones are dropped.
+#[derive(Debug, Clone)]
+pub struct ConnHandle {
+    jobs: mpsc::UnboundedSender<Job>,
+}
+
+impl ConnHandle {
+    /// Spawns a task that owns `conn` on the default executor and returns a handle to it.
+    ///
+    /// If there is no executor (e.g. outside of a `tokio` runtime), requests will fail
+    /// with `DriverError::ConnectionClosed`.
+    pub fn new(conn: Conn) -> ConnHandle {
+        let (jobs, queue) = mpsc::unbounded();
+        let _ = DefaultExecutor::current().spawn(Box::new(run(conn, queue)));
+        ConnHandle { jobs }
+    }
+
+    /// Returns future that performs a text `query` (see `ConnHandle::request`).
+    pub fn query<Q: Into<String>>(&self, query: Q) -> impl MyFuture<QueryResponse> {
+        self.request(QueryRequest::new(query, ()))
+    }
+
+    /// Returns future that prepares and executes a statement (see `ConnHandle::request`).
+    pub fn prep_exec<Q, P>(&self, query: Q, params: P) -> impl MyFuture<QueryResponse>
+    where
+        Q: Into<String>,
+        P: Into<Params>,
+    {
+        self.request(QueryRequest::new(query, params))
+    }
+
+    /// Enqueues `request` and returns future that resolves to its response.
+    ///
+    /// Request is executed even if the returned future is dropped.
+    pub fn request(&self, request: QueryRequest) -> impl MyFuture<QueryResponse> {
+        let (tx, rx) = oneshot::channel();
+        // Receiver is dropped if the task is gone, so `rx` will resolve to `Canceled`.
+        let _ = self.jobs.unbounded_send((request, tx));
+        rx.then(|result| match result {
+            Ok(result) => result,
+            Err(oneshot::Canceled) => Err(DriverError::ConnectionClosed.into()),
+        })
+    }
+}
+
+/// Source of a new connection for the task (see `ConnHandle`).
+enum Reconnect {
+    Pool(Pool),
+    Opts(Opts),
+}
+
+impl Reconnect {
+    fn connect(&self) -> impl MyFuture<Conn> {
+        match *self {
+            Reconnect::Pool(ref pool) => A(pool.get_conn()),
+            Reconnect::Opts(ref opts) => B(Conn::new(opts.clone())),
+        }
+    }
+}
+
+/// Executes queued jobs until all handles are dropped.
+fn run(
+    conn: Conn,
+    queue: mpsc::UnboundedReceiver<Job>,
+) -> impl Future<Item = (), Error = ()> + Send + 'static {
+    let reconnect = match conn.inner.pool {
+        Some(ref pool) => Reconnect::Pool(pool.clone()),
+        None => Reconnect::Opts(conn.inner.opts.clone()),
+    };
+
+    queue
+        .fold(Some(conn), move |conn, (request, tx)| {
+            let conn = match conn {
+                Some(conn) => A(ok(conn)),
+                None => B(reconnect.connect()),
+            };
+            conn.and_then(|conn| execute(conn, request))
+                .then(move |result| {
+                    let (conn, result) = match result {
+                        Ok((conn, response)) => (Some(conn), Ok(response)),
+                        Err(error) => (None, Err(error)),
+                    };
+                    let _ = tx.send(result);
+                    Ok::<_, ()>(conn)
+                })
+        })
+        .and_then(|conn| match conn {
+            Some(ref conn) if conn.inner.pool.is_some() => A(ok(())),
+            Some(conn) => B(conn.disconnect().then(|_| Ok(()))),
+            None => A(ok(())),
+        })
+}
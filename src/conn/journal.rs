@@ -0,0 +1,120 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{consts::Command, QueryDigest};
+
+/// Outcome of a command recorded in a connection journal.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CommandOutcome {
+    /// Command was sent, but the response wasn't (fully) read.
+    Pending,
+    /// Server responded with OK packet.
+    Ok,
+    /// Server responded with an error having this code.
+    Error(u16),
+}
+
+/// Command recorded in a connection journal (see `OptsBuilder::command_journal_size`).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CommandRecord {
+    command: Command,
+    digest: Option<u64>,
+    timestamp: SystemTime,
+    duration: Option<Duration>,
+    outcome: CommandOutcome,
+    started: Instant,
+}
+
+impl CommandRecord {
+    pub fn command(&self) -> Command {
+        self.command
+    }
+
+    /// Hash of a normalized query text (for `COM_QUERY` and `COM_STMT_PREPARE`,
+    /// see `QueryDigest::hash`).
+    pub fn digest(&self) -> Option<u64> {
+        self.digest
+    }
+
+    /// Time the command was sent at.
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+
+    /// Time elapsed till the last response packet (`None` if the command is pending).
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration
+    }
+
+    pub fn outcome(&self) -> CommandOutcome {
+        self.outcome
+    }
+
+    fn new(command: Command, data: &[u8]) -> CommandRecord {
+        let digest = match command {
+            Command::COM_QUERY | Command::COM_STMT_PREPARE => {
+                Some(QueryDigest::new(String::from_utf8_lossy(data)).hash())
+            }
+            _ => None,
+        };
+        CommandRecord {
+            command,
+            digest,
+            timestamp: SystemTime::now(),
+            duration: None,
+            outcome: CommandOutcome::Pending,
+            started: Instant::now(),
+        }
+    }
+}
+
+/// Ring buffer of last commands executed on a connection.
+#[derive(Debug)]
+pub struct Journal {
+    cap: usize,
+    records: VecDeque<CommandRecord>,
+}
+
+impl Journal {
+    pub fn new(cap: usize) -> Journal {
+        Journal {
+            cap,
+            records: VecDeque::with_capacity(cap),
+        }
+    }
+
+    pub fn push(&mut self, command: Command, data: &[u8]) {
+        if self.cap == 0 {
+            return;
+        }
+        if self.records.len() == self.cap {
+            self.records.pop_front();
+        }
+        self.records.push_back(CommandRecord::new(command, data));
+    }
+
+    /// Updates outcome of the last command (error outcome is final).
+    pub fn complete(&mut self, outcome: CommandOutcome) {
+        if let Some(record) = self.records.back_mut() {
+            if let CommandOutcome::Error(_) = record.outcome {
+                return;
+            }
+            record.outcome = outcome;
+            record.duration = Some(record.started.elapsed());
+        }
+    }
+
+    pub fn records(&self) -> Vec<CommandRecord> {
+        self.records.iter().cloned().collect()
+    }
+}
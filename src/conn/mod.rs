@@ -8,32 +8,76 @@
 
 pub use mysql_common::named_params;
 
+use byteorder::{ByteOrder, LittleEndian};
 use futures::future::{err, loop_fn, ok, Either::*, Future, IntoFuture, Loop};
 use mysql_common::{
     crypto,
     packets::{
-        parse_auth_switch_request, parse_handshake_packet, AuthPlugin, AuthSwitchRequest,
-        HandshakeResponse, SslRequest,
+        parse_auth_switch_request, parse_err_packet, parse_handshake_packet, AuthPlugin,
+        AuthSwitchRequest, HandshakeResponse, SslRequest,
     },
 };
 
-use std::{fmt, mem, str::FromStr, sync::Arc};
+use tokio_io::AsyncWrite;
+
+use std::{
+    cmp, fmt, mem,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use crate::{
-    conn::{pool::Pool, stmt_cache::StmtCache},
-    connection_like::{streamless::Streamless, ConnectionLike, StmtCacheResult},
+    auth_handler::AuthContext,
+    conn::{
+        cancel::{CancelToken, QueryHandle},
+        columns_cache::ColumnsCache,
+        commands::{MultiStatements, ServerStatistics},
+        dump::{self, DumpFormat},
+        journal::{CommandOutcome, CommandRecord, Journal},
+        pool::{endpoints::EndpointGuard, Pool},
+        query_multi::QueryMulti,
+        replication::{MasterStatus, ReplicaStatus},
+        schema::{self, StmtDescription, TableColumn},
+        stmt_cache::StmtCache,
+        warnings::Warning,
+    },
+    connection_like::{
+        read_packet::ReadPacket, streamless::Streamless, ConnectionLike, Cursor, LastStatement,
+        StmtCacheResult,
+    },
     consts::{self, CapabilityFlags},
     error::*,
     io::Stream,
     local_infile_handler::LocalInfileHandler,
-    opts::Opts,
-    queryable::{query_result, BinaryProtocol, Queryable, TextProtocol},
+    opts::{CharsetCheck, Opts, OptsBuilder},
+    prelude::FromRow,
+    queryable::{
+        query_result, transaction::TransactionOptions, BinaryProtocol, Queryable, TextProtocol,
+    },
     time::SteadyTime,
-    BoxFuture, Column, MyFuture,
+    BoxFuture, Column, MyFuture, Params, QueryDigest, Value,
 };
 
+pub mod cancel;
+pub mod columns_cache;
+pub mod commands;
+pub mod dump;
+pub mod handle;
+pub mod journal;
 pub mod pool;
+pub mod query_multi;
+pub mod replication;
+pub mod request;
+pub mod schema;
 pub mod stmt_cache;
+pub mod warnings;
+
+#[cfg(feature = "tracing")]
+use crate::connection_like::spans;
+
+/// Name of the `sha256_password` authentication plugin.
+const SHA256_PASSWORD: &[u8] = b"sha256_password";
 
 /// Mysql connection
 struct ConnInner {
@@ -47,14 +91,56 @@ struct ConnInner {
     status: consts::StatusFlags,
     last_insert_id: u64,
     affected_rows: u64,
+    /// Human-readable info of the last OK packet (see `Conn::info`).
+    info: Option<String>,
     warnings: u16,
+    /// Warnings of the last statement (see `OptsBuilder::fetch_warnings`).
+    last_warnings: Vec<Warning>,
     pool: Option<Pool>,
+    endpoint_guard: Option<EndpointGuard>,
+    /// Token of the pool options this connection was established with (see `Pool::rotate`).
+    pool_generation: Option<Arc<()>>,
     has_result: Option<(Arc<Vec<Column>>, Option<StmtCacheResult>)>,
+    cursor: Option<Cursor>,
+    result_bytes: u64,
+    /// Text of the last query, if it is needed to report events
+    /// or `DriverError::ReadOnlyViolation`.
+    last_query: Option<String>,
+    /// Id of the statement if the last statement was executed using `COM_STMT_EXECUTE`.
+    executed_stmt: Option<u32>,
+    /// Span of a pending command.
+    #[cfg(feature = "tracing")]
+    span: Option<tracing::Span>,
+    /// Number of rows read by a pending command.
+    #[cfg(feature = "tracing")]
+    span_rows: u64,
+    /// Span of an active transaction.
+    #[cfg(feature = "tracing")]
+    transaction_span: Option<tracing::Span>,
+    journal: Journal,
+    /// Start time of a pending query (or statement execution).
+    query_started: Option<Instant>,
+    /// Bytes sent and received by the time a pending query (or statement execution) was sent.
+    query_io_started: (u64, u64),
+    pending_responses: usize,
+    idempotency_table_ready: bool,
     in_transaction: bool,
+    /// Time this connection was established at.
+    created: Instant,
+    /// Number of times this connection was taken from a pool.
+    uses: usize,
+    /// Index of the init command (see `OptsBuilder::init`) that is being executed.
+    init_query: Option<usize>,
+    /// Incremented every time server-side prepared statements of this connection are discarded
+    /// (i.e. the session is reset or re-established).
+    session_generation: u64,
     opts: Opts,
     last_io: SteadyTime,
     wait_timeout: u32,
+    /// Value of `sql_select_limit` set by the driver (`None` is the server's default).
+    sql_select_limit: Option<u64>,
     stmt_cache: StmtCache,
+    columns_cache: ColumnsCache,
     nonce: Vec<u8>,
     auth_plugin: AuthPlugin<'static>,
     auth_switched: bool,
@@ -68,6 +154,10 @@ impl fmt::Debug for ConnInner {
             .field("pool", &self.pool)
             .field("has result", &self.has_result.is_some())
             .field("in transaction", &self.in_transaction)
+            .field(
+                "init command",
+                &self.init_query.and_then(|i| self.opts.get_init().get(i)),
+            )
             .field("options", &self.opts)
             .finish()
     }
@@ -82,18 +172,46 @@ impl ConnInner {
             status: consts::StatusFlags::empty(),
             last_insert_id: 0,
             affected_rows: 0,
+            info: None,
             stream: None,
             seq_id: 0,
-            max_allowed_packet: 1024 * 1024,
+            max_allowed_packet: opts
+                .get_max_allowed_packet()
+                .map(u64::from)
+                .unwrap_or(1024 * 1024),
             warnings: 0,
+            last_warnings: Vec::new(),
             version: (0, 0, 0),
             id: 0,
             has_result: None,
+            cursor: None,
+            result_bytes: 0,
+            last_query: None,
+            executed_stmt: None,
+            #[cfg(feature = "tracing")]
+            span: None,
+            #[cfg(feature = "tracing")]
+            span_rows: 0,
+            #[cfg(feature = "tracing")]
+            transaction_span: None,
+            journal: Journal::new(opts.get_command_journal_size()),
+            query_started: None,
+            query_io_started: (0, 0),
+            pending_responses: 0,
+            idempotency_table_ready: false,
             pool: None,
+            endpoint_guard: None,
+            pool_generation: None,
             in_transaction: false,
+            created: Instant::now(),
+            uses: 0,
+            init_query: None,
+            session_generation: 0,
             last_io: SteadyTime::now(),
             wait_timeout: 0,
+            sql_select_limit: None,
             stmt_cache: StmtCache::new(opts.get_stmt_cache_size()),
+            columns_cache: ColumnsCache::new(opts.get_columns_cache_size()),
             opts: opts,
             nonce: Vec::default(),
             auth_plugin: AuthPlugin::MysqlNativePassword,
@@ -102,6 +220,10 @@ impl ConnInner {
     }
 }
 
+/// MySql server connection.
+///
+/// A connection dropped outside of a pool is closed without `COM_QUIT` (the server will
+/// count it as aborted). Use `Queryable::disconnect` to close it gracefully.
 #[derive(Debug)]
 pub struct Conn {
     inner: Box<ConnInner>,
@@ -121,6 +243,277 @@ impl Conn {
         self.get_affected_rows()
     }
 
+    /// Returns the human-readable info the server sent with the result of the last command
+    /// (e.g. `Records: 3  Duplicates: 0  Warnings: 0` for a multi-row `INSERT`).
+    /// Returns `None` if there was no info or if the last command returned a result set.
+    pub fn info(&self) -> Option<&str> {
+        self.get_info()
+    }
+
+    /// Returns the connection id assigned by the server (see `CONNECTION_ID()`).
+    pub fn id(&self) -> u32 {
+        self.inner.id
+    }
+
+    /// Returns the version of the server this connection is established to.
+    pub fn server_version(&self) -> (u16, u16, u16) {
+        self.inner.version
+    }
+
+    /// Returns future that terminates a statement currently executing on the connection with
+    /// the given `id` (using `KILL QUERY`).
+    ///
+    /// Connection with the given `id` stays open and the interrupted statement resolves
+    /// to an error (or to a partial result, e.g. `SLEEP` returns `1`).
+    pub fn kill(self, id: u32) -> impl MyFuture<Self> {
+        self.drop_query(format!("KILL QUERY {}", id))
+    }
+
+    /// Returns future that streams all rows of a `table` into `writer` using the given `format`.
+    /// Resolves to the number of rows written.
+    ///
+    /// Table must have a single-column primary key. Rows are fetched in batches ordered by
+    /// the key, so the table isn't locked for the whole duration of the dump
+    /// (see `Conn::dump_table_after` to resume an interrupted dump).
+    pub fn dump_table<T, W>(
+        self,
+        table: T,
+        writer: W,
+        format: DumpFormat,
+    ) -> impl MyFuture<(Self, W, u64)>
+    where
+        T: AsRef<str>,
+        W: AsyncWrite + Send + 'static,
+    {
+        dump::dump_table(self, table, writer, format, None)
+    }
+
+    /// Same as `Conn::dump_table`, but only rows with primary key greater than `after`
+    /// are written (CSV header is omitted).
+    pub fn dump_table_after<T, W, V>(
+        self,
+        table: T,
+        writer: W,
+        format: DumpFormat,
+        after: V,
+    ) -> impl MyFuture<(Self, W, u64)>
+    where
+        T: AsRef<str>,
+        W: AsyncWrite + Send + 'static,
+        V: Into<Value>,
+    {
+        dump::dump_table(self, table, writer, format, Some(after.into()))
+    }
+
+    /// Returns future that resolves to the binary log position of the server
+    /// (`SHOW MASTER STATUS`), or to `None` if binary logging is disabled.
+    pub fn master_status(self) -> impl MyFuture<(Self, Option<MasterStatus>)> {
+        self.first::<_, crate::Row>("SHOW MASTER STATUS")
+            .and_then(|(this, row)| match row {
+                Some(row) => MasterStatus::from_row(&row).map(|status| (this, Some(status))),
+                None => Ok((this, None)),
+            })
+    }
+
+    /// Returns future that resolves to the set of GTIDs executed on the server
+    /// (`@@GLOBAL.gtid_executed`).
+    pub fn gtid_executed(self) -> impl MyFuture<(Self, String)> {
+        self.first::<_, (Option<String>,)>("SELECT @@GLOBAL.gtid_executed")
+            .map(|(this, row)| {
+                let gtid_set = row.and_then(|(gtid_set,)| gtid_set).unwrap_or_default();
+                (this, gtid_set)
+            })
+    }
+
+    /// Returns future that resolves to the replication status of the server
+    /// (`SHOW SLAVE STATUS`), or to `None` if the server isn't a replica.
+    pub fn replica_status(self) -> impl MyFuture<(Self, Option<ReplicaStatus>)> {
+        self.first::<_, crate::Row>("SHOW SLAVE STATUS")
+            .and_then(|(this, row)| match row {
+                Some(row) => ReplicaStatus::from_row(&row).map(|status| (this, Some(status))),
+                None => Ok((this, None)),
+            })
+    }
+
+    /// Returns future that resolves to column definitions of a `table` ordered by position,
+    /// including generated and invisible columns (see `TableColumn`).
+    ///
+    /// Table name may be qualified with a schema name, otherwise the current database is used.
+    /// Resolves to an empty `Vec` if the table doesn't exist.
+    pub fn table_columns<T: AsRef<str>>(self, table: T) -> impl MyFuture<(Self, Vec<TableColumn>)> {
+        let (schema, table) = schema::split_table_name(table.as_ref());
+        self.prep_exec(schema::TABLE_COLUMNS, (schema, table))
+            .and_then(|result| result.collect_and_drop::<crate::Row>())
+            .and_then(|(this, rows)| {
+                let columns = rows
+                    .iter()
+                    .map(TableColumn::from_row)
+                    .collect::<Result<Vec<_>>>()?;
+                Ok((this, columns))
+            })
+    }
+
+    /// Returns future that prepares a `query` and resolves to metadata of its parameters
+    /// and result set columns (see `StmtDescription`) without executing it.
+    ///
+    /// Statement is closed right away unless it is kept in the statement cache
+    /// (see `OptsBuilder::stmt_cache_size`).
+    pub fn describe<Q: AsRef<str>>(self, query: Q) -> impl MyFuture<(Self, StmtDescription)> {
+        self.prepare(query).and_then(|stmt| {
+            let description = StmtDescription::new(&stmt);
+            stmt.close().map(|this| (this, description))
+        })
+    }
+
+    /// Returns future that resolves to server statistics (`COM_STATISTICS`).
+    pub fn statistics(self) -> impl MyFuture<(Self, ServerStatistics)> {
+        self.write_raw_command(consts::Command::COM_STATISTICS, &[])
+            .and_then(|(this, payload)| {
+                ServerStatistics::parse(&*payload).map(|stats| (this, stats))
+            })
+    }
+
+    /// Returns future that toggles support of multiple statements per query for this connection
+    /// (`COM_SET_OPTION`).
+    ///
+    /// The option stays in effect until the connection is closed, even if it is returned
+    /// to a `Pool`. Note that `Conn::pipeline` requires multiple statements to be enabled.
+    pub fn set_option(self, option: MultiStatements) -> impl MyFuture<Self> {
+        let mut data = [0u8; 2];
+        LittleEndian::write_u16(&mut data, option as u16);
+        self.write_raw_command(consts::Command::COM_SET_OPTION, &data)
+            .map(|(this, _)| this)
+    }
+
+    /// Returns future that sets the `sql_select_limit` session variable of this connection
+    /// (`None` restores the server's default).
+    ///
+    /// The value stays in effect until the connection is closed, even if it is returned
+    /// to a `Pool`, but it is restored to `OptsBuilder::sql_select_limit` by `Conn::reset`.
+    pub fn set_sql_select_limit(self, limit: Option<u64>) -> impl MyFuture<Self> {
+        let query = match limit {
+            Some(limit) => format!("SET SESSION sql_select_limit = {}", limit),
+            None => "SET SESSION sql_select_limit = DEFAULT".into(),
+        };
+        self.drop_query(query).map(move |mut this| {
+            this.inner.sql_select_limit = limit;
+            this
+        })
+    }
+
+    /// Returns the value of `sql_select_limit` set for this connection (see
+    /// `OptsBuilder::sql_select_limit` and `Conn::set_sql_select_limit`).
+    pub fn sql_select_limit(&self) -> Option<u64> {
+        self.inner.sql_select_limit
+    }
+
+    /// Returns future that writes `command` with the given `data` and resolves to the payload
+    /// of the response packet (server error is returned as `Error::Server`).
+    ///
+    /// This is an escape hatch for protocol experimentation (e.g. `COM_DEBUG`). Exactly one
+    /// response packet is read, so commands that respond with several packets (e.g. `COM_QUERY`
+    /// or `COM_STMT_PREPARE`) or change the state of the session the driver relies on
+    /// (e.g. `COM_CHANGE_USER`) will break the connection.
+    pub fn write_raw_command(
+        self,
+        command: consts::Command,
+        data: &[u8],
+    ) -> impl MyFuture<(Self, Vec<u8>)> {
+        self.write_command_data(command, data)
+            .and_then(|this| this.read_packet())
+            .map(|(this, packet)| (this, packet.0))
+    }
+
+    /// Returns warnings of the last statement executed on this connection.
+    ///
+    /// Warnings are only read if `OptsBuilder::fetch_warnings` is set, and are kept
+    /// until the next command is sent.
+    pub fn last_warnings(&self) -> &[Warning] {
+        &*self.inner.last_warnings
+    }
+
+    /// Returns last commands executed on this connection, oldest first.
+    ///
+    /// Commands are only recorded if `OptsBuilder::command_journal_size` is set.
+    pub fn recent_commands(&self) -> Vec<CommandRecord> {
+        self.get_recent_commands()
+    }
+
+    /// Returns the number of bytes sent to the server over this connection.
+    ///
+    /// MySql packets (including packet headers) are counted, so the value doesn't include
+    /// TLS overhead.
+    pub fn bytes_sent(&self) -> u64 {
+        self.io_bytes().0
+    }
+
+    /// Returns the number of bytes received from the server over this connection.
+    ///
+    /// MySql packets (including packet headers) are counted, so the value doesn't include
+    /// TLS overhead.
+    pub fn bytes_received(&self) -> u64 {
+        self.io_bytes().1
+    }
+
+    /// Returns numbers of bytes sent and received over the current stream.
+    fn io_bytes(&self) -> (u64, u64) {
+        self.inner.stream.as_ref().map_or((0, 0), |stream| {
+            (stream.bytes_sent(), stream.bytes_received())
+        })
+    }
+
+    /// Returns `true` if the server reported that more result sets exist.
+    fn more_results_exists(&self) -> bool {
+        self.inner
+            .status
+            .contains(consts::StatusFlags::SERVER_MORE_RESULTS_EXISTS)
+    }
+
+    /// Calls `EventHandler::query_io` and `EventHandler::slow_query` (if the pending query
+    /// took longer than the threshold).
+    fn finish_query(&mut self) {
+        #[cfg(feature = "tracing")]
+        let span = self.inner.span.take();
+        let duration = match self.inner.query_started.take() {
+            Some(started) => started.elapsed(),
+            None => return,
+        };
+        #[cfg(feature = "tracing")]
+        {
+            if let Some(span) = span {
+                spans::finish(
+                    &span,
+                    self.inner.span_rows,
+                    self.inner.affected_rows,
+                    duration,
+                );
+            }
+        }
+        if let Some(handler) = self.inner.opts.get_event_handler() {
+            let digest = self.last_query_digest();
+            let (sent, received) = self.io_bytes();
+            let (sent_before, received_before) = self.inner.query_io_started;
+            handler.query_io(
+                digest.as_ref(),
+                sent.saturating_sub(sent_before),
+                received.saturating_sub(received_before),
+            );
+            if let Some(threshold) = self.inner.opts.get_slow_query_threshold() {
+                if duration > threshold {
+                    handler.slow_query(digest.as_ref(), duration);
+                }
+            }
+        }
+    }
+
+    /// Returns host and port this connection is established to.
+    fn endpoint(&self) -> (String, u16) {
+        (
+            self.inner.opts.get_ip_or_hostname().into(),
+            self.inner.opts.get_tcp_port(),
+        )
+    }
+
     fn is_secure(&self) -> bool {
         if let Some(ref stream) = self.inner.stream {
             stream.is_secure()
@@ -131,7 +524,9 @@ impl Conn {
 
     /// Hacky way to move connection through &mut. `self` becomes unusable.
     fn take(&mut self) -> Conn {
-        let inner = mem::replace(&mut *self.inner, ConnInner::empty(Default::default()));
+        // `Opts` is behind an `Arc`, so cloning it here is cheap.
+        let opts = self.inner.opts.clone();
+        let inner = mem::replace(&mut *self.inner, ConnInner::empty(opts));
         Conn {
             inner: Box::new(inner),
         }
@@ -147,6 +542,10 @@ impl Conn {
         if let Some(stream) = self.inner.stream.take() {
             stream.set_keepalive_ms(self.inner.opts.get_tcp_keepalive())?;
             stream.set_tcp_nodelay(self.inner.opts.get_tcp_nodelay())?;
+            stream.set_buffer_sizes(
+                self.inner.opts.get_tcp_send_buffer_size(),
+                self.inner.opts.get_tcp_recv_buffer_size(),
+            )?;
             self.inner.stream = Some(stream);
             Ok(self)
         } else {
@@ -171,12 +570,14 @@ impl Conn {
                     conn.inner.id = handshake.connection_id();
                     conn.inner.status = handshake.status_flags();
                     conn.inner.auth_plugin = match handshake.auth_plugin() {
-                        Some(AuthPlugin::MysqlNativePassword) => AuthPlugin::MysqlNativePassword,
-                        Some(AuthPlugin::CachingSha2Password) => AuthPlugin::CachingSha2Password,
-                        Some(AuthPlugin::Other(ref name)) => {
+                        Some(AuthPlugin::Other(ref name))
+                            if &**name != SHA256_PASSWORD
+                                && conn.inner.opts.get_auth_handler().is_none() =>
+                        {
                             let name = String::from_utf8_lossy(name).into();
                             return Err(DriverError::UnknownAuthPlugin { name }.into());
                         }
+                        Some(plugin) => plugin.into_owned(),
                         None => AuthPlugin::MysqlNativePassword,
                     };
                     Ok(conn)
@@ -210,11 +611,97 @@ impl Conn {
         }
     }
 
+    /// Returns state of the authentication exchange passed to `OptsBuilder::auth_handler`.
+    fn auth_context<'a>(&'a self, plugin: &'a [u8]) -> AuthContext<'a> {
+        AuthContext {
+            plugin,
+            scramble: &*self.inner.nonce,
+            capabilities: self.inner.capabilities,
+            server_version: self.inner.version,
+            user: self.inner.opts.get_user(),
+            pass: self.inner.opts.get_pass(),
+        }
+    }
+
+    /// Returns authentication data of the current auth plugin.
+    ///
+    /// Data of plugins unknown to the driver is computed by `OptsBuilder::auth_handler`.
+    fn auth_data(&self) -> Result<Option<Vec<u8>>> {
+        match self.inner.auth_plugin {
+            AuthPlugin::Other(ref name) if &**name == SHA256_PASSWORD => {
+                self.sha256_password_data().map(Some)
+            }
+            AuthPlugin::Other(ref name) => self
+                .inner
+                .opts
+                .get_auth_handler()
+                .and_then(|handler| handler.auth_data(self.auth_context(name)))
+                .map(Some)
+                .ok_or_else(|| {
+                    let name = String::from_utf8_lossy(name).into();
+                    DriverError::UnknownAuthPlugin { name }.into()
+                }),
+            ref plugin => Ok(plugin.gen_data(self.inner.opts.get_pass(), &*self.inner.nonce)),
+        }
+    }
+
+    /// Returns initial authentication data of the `sha256_password` plugin.
+    ///
+    /// Password is sent as is over TLS, otherwise it is encrypted with the server's public key
+    /// (see `OptsBuilder::server_public_key_path`), which is requested if it isn't known.
+    fn sha256_password_data(&self) -> Result<Vec<u8>> {
+        match self.inner.opts.get_pass() {
+            Some(pass) if !pass.is_empty() => {
+                if self.is_secure() {
+                    let mut data = Vec::from(pass);
+                    data.push(0);
+                    Ok(data)
+                } else {
+                    match self.inner.opts.get_server_public_key() {
+                        Some(key) => Ok(self.encrypt_password(key)),
+                        // public key request
+                        None => Ok(vec![0x01]),
+                    }
+                }
+            }
+            _ => Ok(vec![0x00]),
+        }
+    }
+
+    /// Returns the password (with a trailing NUL) scrambled with the nonce and encrypted
+    /// with the server's public `key` (in PEM format).
+    fn encrypt_password(&self, key: &[u8]) -> Vec<u8> {
+        let mut pass = self.inner.opts.get_pass().map(Vec::from).unwrap_or(vec![]);
+        pass.push(0);
+        for i in 0..pass.len() {
+            pass[i] ^= self.inner.nonce[i % self.inner.nonce.len()];
+        }
+        crypto::encrypt(&*pass, key)
+    }
+
+    /// Returns response to an `AuthMoreData` packet.
+    ///
+    /// The only such packet of `sha256_password` is the server's public key, other plugins
+    /// unknown to the driver are handled by `OptsBuilder::auth_handler`.
+    fn more_auth_data(&self, data: &[u8]) -> Option<Vec<u8>> {
+        match self.inner.auth_plugin {
+            AuthPlugin::Other(ref name) if &**name == SHA256_PASSWORD => {
+                Some(self.encrypt_password(data))
+            }
+            AuthPlugin::Other(ref name) => self
+                .inner
+                .opts
+                .get_auth_handler()?
+                .more_data(self.auth_context(name), data),
+            _ => None,
+        }
+    }
+
     fn do_handshake_response(self) -> impl MyFuture<Conn> {
-        let auth_data = self
-            .inner
-            .auth_plugin
-            .gen_data(self.inner.opts.get_pass(), &*self.inner.nonce);
+        let auth_data = match self.auth_data() {
+            Ok(auth_data) => auth_data,
+            Err(error) => return A(err(error)),
+        };
 
         let handshake_response = HandshakeResponse::new(
             &auth_data,
@@ -225,7 +712,13 @@ impl Conn {
             self.get_capabilities(),
         );
 
-        self.write_packet(handshake_response.as_ref())
+        let mut data = handshake_response.as_ref().to_vec();
+        if let Some(max_allowed_packet) = self.inner.opts.get_max_allowed_packet() {
+            // `max_packet_size` field follows capability flags
+            LittleEndian::write_u32(&mut data[4..8], max_allowed_packet);
+        }
+
+        B(self.write_packet(data))
     }
 
     fn perform_auth_switch(
@@ -236,11 +729,10 @@ impl Conn {
             self.inner.auth_switched = true;
             self.inner.nonce = auth_switch_request.plugin_data().into();
             self.inner.auth_plugin = auth_switch_request.auth_plugin().clone().into_owned();
-            let plugin_data = self
-                .inner
-                .auth_plugin
-                .gen_data(self.inner.opts.get_pass(), &*self.inner.nonce)
-                .unwrap_or_else(Vec::new);
+            let plugin_data = match self.auth_data() {
+                Ok(plugin_data) => plugin_data.unwrap_or_else(Vec::new),
+                Err(error) => return Box::new(err(error)),
+            };
             let fut = self.write_packet(plugin_data).and_then(Conn::continue_auth);
             // We'll box it to avoid recursion.
             Box::new(fut)
@@ -252,11 +744,44 @@ impl Conn {
     fn continue_auth(self) -> impl MyFuture<Conn> {
         match self.inner.auth_plugin {
             AuthPlugin::MysqlNativePassword => A(self.continue_mysql_native_password_auth()),
-            AuthPlugin::CachingSha2Password => B(self.continue_caching_sha2_password_auth()),
-            _ => unreachable!(),
+            AuthPlugin::CachingSha2Password => B(A(self.continue_caching_sha2_password_auth())),
+            AuthPlugin::Other(_) => B(B(self.continue_external_auth())),
         }
     }
 
+    /// Continues authentication using `OptsBuilder::auth_handler`
+    /// (or the `sha256_password` plugin).
+    fn continue_external_auth(self) -> impl MyFuture<Conn> {
+        loop_fn(self, |conn| {
+            conn.read_packet()
+                .and_then(|(conn, packet)| match packet.0.get(0) {
+                    Some(0x00) => A(ok(Loop::Break(conn))),
+                    Some(0xfe) if !conn.inner.auth_switched => {
+                        let fut = parse_auth_switch_request(packet.as_ref())
+                            .map(AuthSwitchRequest::into_owned)
+                            .map_err(Error::from)
+                            .into_future()
+                            .and_then(|auth_switch_request| {
+                                conn.perform_auth_switch(auth_switch_request)
+                            })
+                            .map(Loop::Break);
+                        B(A(fut))
+                    }
+                    Some(0x01) => match conn.more_auth_data(&packet.0[1..]) {
+                        Some(data) => B(B(A(conn.write_packet(data).map(Loop::Continue)))),
+                        None => B(B(B(err(DriverError::UnexpectedPacket {
+                            payload: packet.0,
+                        }
+                        .into())))),
+                    },
+                    _ => B(B(B(err(DriverError::UnexpectedPacket {
+                        payload: packet.0,
+                    }
+                    .into())))),
+                })
+        })
+    }
+
     fn continue_caching_sha2_password_auth(self) -> impl MyFuture<Conn> {
         self.read_packet()
             .and_then(|(conn, packet)| match packet.as_ref().get(0) {
@@ -266,22 +791,26 @@ impl Conn {
                         A(conn.drop_packet())
                     }
                     Some(0x04) => {
-                        let mut pass = conn.inner.opts.get_pass().map(Vec::from).unwrap_or(vec![]);
-                        pass.push(0);
                         let fut = if conn.is_secure() {
+                            let mut pass =
+                                conn.inner.opts.get_pass().map(Vec::from).unwrap_or(vec![]);
+                            pass.push(0);
                             A(conn.write_packet(&*pass))
                         } else {
-                            B(conn
-                                .write_packet(&[0x02][..])
-                                .and_then(Conn::read_packet)
-                                .and_then(move |(conn, packet)| {
-                                    let key = &packet.as_ref()[1..];
-                                    for i in 0..pass.len() {
-                                        pass[i] ^= conn.inner.nonce[i % conn.inner.nonce.len()];
-                                    }
-                                    let encrypted_pass = crypto::encrypt(&*pass, key);
-                                    conn.write_packet(&*encrypted_pass)
-                                }))
+                            match conn.inner.opts.get_server_public_key() {
+                                Some(key) => {
+                                    let encrypted_pass = conn.encrypt_password(key);
+                                    B(A(conn.write_packet(&*encrypted_pass)))
+                                }
+                                None => B(B(conn
+                                    .write_packet(&[0x02][..])
+                                    .and_then(Conn::read_packet)
+                                    .and_then(|(conn, packet)| {
+                                        let key = &packet.as_ref()[1..];
+                                        let encrypted_pass = conn.encrypt_password(key);
+                                        conn.write_packet(&*encrypted_pass)
+                                    }))),
+                            }
                         };
                         B(A(fut.and_then(Conn::drop_packet)))
                     }
@@ -332,32 +861,113 @@ impl Conn {
         self.read_packet().map(|(conn, _)| conn)
     }
 
-    fn run_init_commands(self) -> impl MyFuture<Conn> {
-        let init = self
-            .inner
-            .opts
-            .get_init()
-            .iter()
-            .map(Clone::clone)
-            .collect();
+    /// Returns future that performs a text `query` and resolves to a server error (if any)
+    /// instead of failing, so that the connection survives the error.
+    fn drop_query_lenient(mut self, query: String) -> impl MyFuture<(Conn, Option<ServerError>)> {
+        self.set_last_query(&query);
+        self.write_command_data(consts::Command::COM_QUERY, query.into_bytes())
+            .and_then(ReadPacket::lenient)
+            .and_then(|(conn, packet)| {
+                match parse_err_packet(&*packet.0, conn.get_capabilities()) {
+                    Ok(err_packet) => A(ok((conn, Some(ServerError::from(err_packet))))),
+                    Err(_) => B(conn
+                        .handle_result_packet::<TextProtocol>(packet, None)
+                        .and_then(|result| result.drop_result())
+                        .map(|conn| (conn, None))),
+                }
+            })
+    }
 
-        loop_fn(
-            (init, self),
-            |(mut init, conn): (Vec<String>, Conn)| match init.pop() {
-                None => A(ok(Loop::Break(conn))),
-                Some(query) => {
-                    let fut = conn
-                        .drop_query(query)
-                        .map(|conn| Loop::Continue((init, conn)));
-                    B(fut)
+    /// Returns future that executes init commands (see `OptsBuilder::init`) in order.
+    fn run_init_commands(self) -> impl MyFuture<Conn> {
+        loop_fn((0, self), |(index, mut conn)| {
+            let command = match conn.inner.opts.get_init_commands().get(index) {
+                Some(command) => command.clone(),
+                None => {
+                    conn.inner.init_query = None;
+                    return A(ok(Loop::Break(conn)));
                 }
-            },
-        )
+            };
+            conn.inner.init_query = Some(index);
+
+            let query = command.query().to_owned();
+            let fut = if command.is_best_effort() {
+                A(conn
+                    .drop_query_lenient(query.clone())
+                    .map(move |(conn, error)| {
+                        if let Some(error) = error {
+                            if let Some(handler) = conn.inner.opts.get_event_handler() {
+                                handler.init_command_failed(&*query, &error);
+                            }
+                        }
+                        conn
+                    }))
+            } else {
+                let fut = match command.params() {
+                    Some(params) => A(conn.drop_exec(query.clone(), params.clone())),
+                    None => B(conn.drop_query(query.clone())),
+                };
+                B(fut.map_err(move |error| {
+                    Error::from(DriverError::InitCommandFailed {
+                        index,
+                        query,
+                        error: Box::new(error),
+                    })
+                }))
+            };
+            B(fut.map(move |conn| Loop::Continue((index + 1, conn))))
+        })
     }
 
     /// Returns future that resolves to `Conn`.
+    ///
+    /// Hosts are tried in turn until connection is established
+    /// (see `OptsBuilder::additional_hosts`). If more than one host was tried,
+    /// errors of all of them are returned as `DriverError::AllHostsFailed`.
+    ///
+    /// Invalid options (see `OptsBuilder::build`) are reported before connecting.
     pub fn new<T: Into<Opts>>(opts: T) -> impl MyFuture<Conn> {
-        let mut conn = Conn::empty(opts.into());
+        let opts = opts.into();
+        if let Err(error) = opts.validate() {
+            return A(err(error));
+        }
+        let hosts = opts.get_hosts_to_try();
+        let fut = loop_fn(
+            (opts, hosts.into_iter(), Vec::new()),
+            |(opts, mut hosts, mut errors)| match hosts.next() {
+                Some((host, port)) => {
+                    let host_opts =
+                        if host == opts.get_ip_or_hostname() && port == opts.get_tcp_port() {
+                            opts.clone()
+                        } else {
+                            let mut builder = OptsBuilder::from_opts(opts.clone());
+                            builder.ip_or_hostname(&*host).tcp_port(port);
+                            builder.into()
+                        };
+                    let fut = Conn::connect(host_opts).then(move |result| match result {
+                        Ok(conn) => Ok(Loop::Break(conn)),
+                        Err(error) => {
+                            opts.blacklist_host(&*host, port);
+                            let stop = error.is_auth_error() && !opts.get_failover_on_auth_error();
+                            errors.push((host, port, error));
+                            if stop {
+                                Err(hosts_error(errors))
+                            } else {
+                                Ok(Loop::Continue((opts, hosts, errors)))
+                            }
+                        }
+                    });
+                    A(fut)
+                }
+                None => B(err(hosts_error(errors))),
+            },
+        );
+        B(fut)
+    }
+
+    /// Returns future that connects to a host specified in `opts`.
+    fn connect(opts: Opts) -> impl MyFuture<Conn> {
+        let mut conn = Conn::empty(opts);
 
         Stream::connect((
             conn.inner.opts.get_ip_or_hostname(),
@@ -371,10 +981,17 @@ impl Conn {
         .and_then(Conn::handle_handshake)
         .and_then(Conn::switch_to_ssl_if_needed)
         .and_then(Conn::do_handshake_response)
+        .and_then(|conn| {
+            #[cfg(feature = "failpoints")]
+            crate::failpoints::on_auth()?;
+            Ok::<_, Error>(conn)
+        })
         .and_then(Conn::continue_auth)
         .and_then(Conn::read_max_allowed_packet)
         .and_then(Conn::read_wait_timeout)
+        .and_then(Conn::apply_sql_select_limit)
         .and_then(Conn::run_init_commands)
+        .and_then(Conn::check_charset)
     }
 
     /// Returns future that resolves to `Conn`.
@@ -386,14 +1003,88 @@ impl Conn {
     }
 
     /// Returns future that resolves to `Conn` with `max_allowed_packet` stored in it.
+    ///
+    /// Stored value is the minimum of the server's and the client's limits.
     fn read_max_allowed_packet(self) -> impl MyFuture<Self> {
         self.first("SELECT @@max_allowed_packet")
             .map(|(mut this, row_opt)| {
-                this.inner.max_allowed_packet = row_opt.unwrap_or((1024 * 1024 * 2,)).0;
+                let server_max_allowed_packet = row_opt.unwrap_or((1024 * 1024 * 2,)).0;
+                this.inner.max_allowed_packet = match this.inner.opts.get_max_allowed_packet() {
+                    Some(max_allowed_packet) => {
+                        cmp::min(server_max_allowed_packet, u64::from(max_allowed_packet))
+                    }
+                    None => server_max_allowed_packet,
+                };
                 this
             })
     }
 
+    /// Returns future that compares session character sets with the character set requested
+    /// in the handshake (see `OptsBuilder::charset_check`).
+    fn check_charset(self) -> impl MyFuture<Self> {
+        let check = self.inner.opts.get_charset_check();
+        if check == CharsetCheck::Off {
+            return A(ok(self));
+        }
+        // `HandshakeResponse` requests `utf8mb4` if it is supported by the server.
+        let requested = if self.inner.version >= (5, 5, 3) {
+            "utf8mb4"
+        } else {
+            "utf8mb3"
+        };
+        let fut = self
+            .first::<_, (Option<String>, Option<String>, Option<String>)>(
+                "SELECT @@character_set_client, @@character_set_connection, \
+                 @@character_set_results",
+            )
+            .and_then(move |(this, row)| {
+                let (client, connection, results) = row.unwrap_or_default();
+                let mismatch = vec![
+                    ("character_set_client", client),
+                    ("character_set_connection", connection),
+                    // `NULL` means that results aren't converted
+                    ("character_set_results", results),
+                ]
+                .into_iter()
+                .filter_map(|(variable, actual)| actual.map(|actual| (variable, actual)))
+                .find(|(_, actual)| {
+                    let actual = if actual == "utf8" {
+                        "utf8mb3"
+                    } else {
+                        &**actual
+                    };
+                    actual != requested
+                });
+                match mismatch {
+                    Some((variable, actual)) if check == CharsetCheck::Fail => {
+                        Err(DriverError::CharsetMismatch {
+                            variable: variable.into(),
+                            requested: requested.into(),
+                            actual,
+                        }
+                        .into())
+                    }
+                    Some((variable, actual)) => {
+                        if let Some(handler) = this.inner.opts.get_event_handler() {
+                            handler.charset_mismatch(variable, requested, &*actual);
+                        }
+                        Ok(this)
+                    }
+                    None => Ok(this),
+                }
+            });
+        B(fut)
+    }
+
+    /// Returns future that sets `sql_select_limit` of a new or reset session
+    /// (see `OptsBuilder::sql_select_limit`).
+    fn apply_sql_select_limit(self) -> impl MyFuture<Self> {
+        match self.inner.opts.get_sql_select_limit() {
+            Some(limit) => A(self.set_sql_select_limit(Some(limit))),
+            None => B(ok(self)),
+        }
+    }
+
     /// Returns future that resolves to `Conn` with `wait_timeout` stored in it.
     fn read_wait_timeout(self) -> impl MyFuture<Self> {
         self.first("SELECT @@wait_timeout")
@@ -403,6 +1094,169 @@ impl Conn {
             })
     }
 
+    /// Performs a multi-statement `query` and returns a stream of per-statement results,
+    /// so that a script could be executed and audited statement by statement.
+    ///
+    /// Stream fails on the first failed statement (subsequent statements aren't executed
+    /// by the server). Connection is available via `QueryMulti::into_conn` once the stream
+    /// is exhausted.
+    pub fn query_multi<Q: AsRef<str>>(self, query: Q) -> QueryMulti {
+        QueryMulti::new(Box::new(self.query(query)))
+    }
+
+    /// Starts a `query` that could be cancelled using the returned `CancelToken`.
+    ///
+    /// `QueryHandle` resolves to the query result, or to `DriverError::Cancelled`
+    /// if the token was triggered before the query completed. Note that `KILL QUERY` affects
+    /// whatever statement the connection executes at the time the token is triggered.
+    pub fn query_cancellable<Q: Into<String>>(self, query: Q) -> (QueryHandle, CancelToken) {
+        cancel::new(self, query.into())
+    }
+
+    /// Returns future that performs a `query` known to return no rows (e.g. DDL statement)
+    /// and resolves to the number of affected rows.
+    ///
+    /// Response is expected to be an OK packet, so no `QueryResult` is created. If the query
+    /// returns rows anyway, they are dropped and the future resolves to
+    /// `DriverError::UnexpectedResultSet`. Affected rows of a multi-statement query are summed.
+    pub fn exec_ddl<Q: AsRef<str>>(mut self, query: Q) -> impl MyFuture<(Self, u64)> {
+        self.set_last_query(query.as_ref());
+        self.write_command_data(consts::Command::COM_QUERY, query.as_ref().as_bytes())
+            .and_then(|this| {
+                loop_fn((this, 0), |(this, affected_rows): (Self, u64)| {
+                    this.read_packet().and_then(move |(this, packet)| {
+                        if packet.0[0] == 0x00 {
+                            let affected_rows = affected_rows + this.get_affected_rows();
+                            if this
+                                .get_status()
+                                .contains(consts::StatusFlags::SERVER_MORE_RESULTS_EXISTS)
+                            {
+                                A(ok(Loop::Continue((this, affected_rows))))
+                            } else {
+                                A(ok(Loop::Break((this, affected_rows))))
+                            }
+                        } else {
+                            // Result set (or local infile request) is handled as usual.
+                            let fut = this
+                                .handle_result_packet::<TextProtocol>(packet, None)
+                                .and_then(move |result| {
+                                    let columns = result.columns_ref().len();
+                                    let result_affected_rows = result.affected_rows();
+                                    result.drop_result().and_then(move |this| {
+                                        if columns > 0 {
+                                            let error =
+                                                DriverError::UnexpectedResultSet { columns };
+                                            Err(error.into())
+                                        } else {
+                                            let affected_rows =
+                                                affected_rows + result_affected_rows;
+                                            Ok(Loop::Break((this, affected_rows)))
+                                        }
+                                    })
+                                });
+                            B(fut)
+                        }
+                    })
+                })
+            })
+    }
+
+    /// Returns future that performs `queries` using pipelining, i.e. all the queries are written
+    /// to a server before the first result is read, so that a scripted sequence of queries
+    /// (like `SET ...; SELECT ...`) takes a single round trip.
+    ///
+    /// Resolves to the first result set of every query (remaining result sets are dropped).
+    ///
+    /// Pipelined queries should produce small results, because a server won't read the next
+    /// query until the previous result is sent. Connection will be closed (rather than returned
+    /// to a pool) if any of the queries fails.
+    pub fn pipeline<Q, R>(mut self, queries: Vec<Q>) -> impl MyFuture<(Self, Vec<Vec<R>>)>
+    where
+        Q: AsRef<str> + Send + 'static,
+        R: FromRow + Send + 'static,
+    {
+        self.inner.pending_responses = queries.len();
+        loop_fn(
+            (self, queries.into_iter()),
+            |(this, mut queries)| match queries.next() {
+                Some(query) => A(this
+                    .write_command_data(consts::Command::COM_QUERY, query.as_ref().as_bytes())
+                    .map(|this| Loop::Continue((this, queries)))),
+                None => B(ok(Loop::Break(this))),
+            },
+        )
+        .and_then(|this| {
+            loop_fn((this, Vec::new()), |(this, mut results)| {
+                if this.inner.pending_responses == 0 {
+                    A(ok(Loop::Break((this, results))))
+                } else {
+                    let fut = this
+                        .read_result_set::<TextProtocol>(None)
+                        .and_then(|result| result.collect_and_drop::<R>())
+                        .map(|(mut this, rows)| {
+                            this.inner.pending_responses -= 1;
+                            results.push(rows);
+                            Loop::Continue((this, results))
+                        });
+                    B(fut)
+                }
+            })
+        })
+    }
+
+    /// Returns future that performs a write (`query` with `params`) at most once per `key`.
+    ///
+    /// The write is executed in a transaction together with an insertion of the `key` into
+    /// the idempotency table (see `OptsBuilder::idempotency_table`), so retry layers could
+    /// safely re-run it after an ambiguous failure (i.e. connection loss during commit).
+    ///
+    /// Resolves to `false` if a write with the same `key` was already performed.
+    pub fn exec_idempotent<K, Q, P>(
+        self,
+        key: K,
+        query: Q,
+        params: P,
+    ) -> impl MyFuture<(Self, bool)>
+    where
+        K: Into<String>,
+        Q: AsRef<str> + Send + 'static,
+        P: Into<Params>,
+    {
+        let key = key.into();
+        let params = params.into();
+        let table = schema::quote_table_name(self.inner.opts.get_idempotency_table());
+        let insert_key = format!("INSERT IGNORE INTO {} (idempotency_key) VALUES (?)", table);
+
+        let fut = if self.inner.idempotency_table_ready {
+            A(ok(self))
+        } else {
+            let create_table = format!(
+                "CREATE TABLE IF NOT EXISTS {} (\
+                 idempotency_key VARBINARY(255) NOT NULL PRIMARY KEY, \
+                 created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP) ENGINE=InnoDB",
+                table
+            );
+            B(self.drop_query(create_table).map(|mut this| {
+                this.inner.idempotency_table_ready = true;
+                this
+            }))
+        };
+
+        fut.and_then(|this| this.start_transaction(TransactionOptions::new()))
+            .and_then(move |transaction| transaction.drop_exec(insert_key, (key,)))
+            .and_then(|transaction| {
+                if transaction.get_affected_rows() == 0 {
+                    // key is already stored, so the write was performed
+                    A(transaction.rollback().map(|this| (this, false)))
+                } else {
+                    B(transaction
+                        .drop_exec(query, params)
+                        .and_then(|transaction| transaction.commit())
+                        .map(|this| (this, true)))
+                }
+            })
+    }
+
     /// Returns true if time since last io exceeds wait_timeout (or conn_ttl if specified in opts).
     fn expired(&self) -> bool {
         let idle_duration = SteadyTime::now() - self.inner.last_io;
@@ -414,25 +1268,69 @@ impl Conn {
         idle_duration.num_milliseconds() > ttl * 1000
     }
 
+    /// Returns `true` if there was no io on this connection for at least `duration`.
+    fn idle_for(&self, duration: Duration) -> bool {
+        let idle_duration = SteadyTime::now() - self.inner.last_io;
+        idle_duration.num_milliseconds() >= duration.as_millis() as i64
+    }
+
+    /// Returns `true` if the connection should be recycled by a pool
+    /// (see `OptsBuilder::pool_max_lifetime` and `OptsBuilder::pool_max_uses`).
+    fn exceeds_pool_limits(&self) -> bool {
+        let opts = &self.inner.opts;
+        let too_old = opts
+            .get_pool_max_lifetime()
+            .map_or(false, |lifetime| self.inner.created.elapsed() >= lifetime);
+        let too_used = opts
+            .get_pool_max_uses()
+            .map_or(false, |max_uses| self.inner.uses >= max_uses);
+        too_old || too_used
+    }
+
     /// Returns future that resolves to a `Conn` with `COM_RESET_CONNECTION` executed on it.
-    pub fn reset(self) -> impl MyFuture<Conn> {
+    pub fn reset(mut self) -> impl MyFuture<Conn> {
         let pool = self.inner.pool.clone();
+        let endpoint_guard = self.inner.endpoint_guard.take();
+        let pool_generation = self.inner.pool_generation.take();
+        let session_generation = self.inner.session_generation;
         let fut = if self.inner.version > (5, 7, 2) {
             let fut = self
                 .write_command_data(consts::Command::COM_RESET_CONNECTION, &[])
                 .and_then(|conn| conn.read_packet())
-                .map(|(conn, _)| conn);
+                .and_then(|(mut conn, _)| {
+                    conn.inner.sql_select_limit = None;
+                    conn.apply_sql_select_limit()
+                });
             (ok(pool), A(fut))
         } else {
             (ok(pool), B(Conn::new(self.inner.opts.clone())))
         };
         fut.into_future().map(|(pool, mut conn)| {
             conn.inner.stmt_cache.clear();
+            conn.inner.session_generation = session_generation + 1;
             conn.inner.pool = pool;
+            // Connection might be reestablished to another host.
+            conn.inner.endpoint_guard = endpoint_guard.map(|guard| guard.rebind(conn.endpoint()));
+            conn.inner.pool_generation = pool_generation;
             conn
         })
     }
 
+    /// Returns digest of the last query reported to `EventHandler` (if known).
+    fn last_query_digest(&self) -> Option<QueryDigest> {
+        self.inner.last_query.as_ref().map(QueryDigest::new)
+    }
+
+    /// Returns `true` if there is no pending result set, but the server reported that more
+    /// result sets exists (i.e. `QueryResult` was dropped between result sets).
+    fn has_unconsumed_result_sets(&self) -> bool {
+        self.inner.has_result.is_none()
+            && self
+                .inner
+                .status
+                .contains(consts::StatusFlags::SERVER_MORE_RESULTS_EXISTS)
+    }
+
     fn rollback_transaction(mut self) -> impl MyFuture<Self> {
         assert!(self.inner.in_transaction);
         self.inner.in_transaction = false;
@@ -476,10 +1374,18 @@ impl ConnectionLike for Conn {
         &mut self.inner.stmt_cache
     }
 
+    fn columns_cache_mut(&mut self) -> &mut ColumnsCache {
+        &mut self.inner.columns_cache
+    }
+
     fn get_affected_rows(&self) -> u64 {
         self.inner.affected_rows
     }
 
+    fn get_info(&self) -> Option<&str> {
+        self.inner.info.as_ref().map(String::as_str)
+    }
+
     fn get_capabilities(&self) -> consts::CapabilityFlags {
         self.inner.capabilities
     }
@@ -515,10 +1421,22 @@ impl ConnectionLike for Conn {
         self.inner.has_result.as_ref()
     }
 
+    fn get_cursor(&self) -> Option<Cursor> {
+        self.inner.cursor
+    }
+
     fn get_seq_id(&self) -> u8 {
         self.inner.seq_id
     }
 
+    fn get_warnings(&self) -> u16 {
+        self.inner.warnings
+    }
+
+    fn get_session_generation(&self) -> u64 {
+        self.inner.session_generation
+    }
+
     fn get_server_version(&self) -> (u16, u16, u16) {
         self.inner.version
     }
@@ -531,8 +1449,20 @@ impl ConnectionLike for Conn {
         self.inner.affected_rows = affected_rows;
     }
 
+    fn set_info(&mut self, info: Option<String>) {
+        self.inner.info = info;
+    }
+
     fn set_in_transaction(&mut self, in_transaction: bool) {
         self.inner.in_transaction = in_transaction;
+        #[cfg(feature = "tracing")]
+        {
+            self.inner.transaction_span = if in_transaction {
+                Some(spans::transaction_span(self.inner.id))
+            } else {
+                None
+            };
+        }
     }
 
     fn set_last_command(&mut self, last_command: consts::Command) {
@@ -544,45 +1474,195 @@ impl ConnectionLike for Conn {
     }
 
     fn set_pending_result(&mut self, meta: Option<(Arc<Vec<Column>>, Option<StmtCacheResult>)>) {
+        if self.inner.has_result.is_some() {
+            // previous result set is finished
+            if let Some(handler) = self.inner.opts.get_event_handler() {
+                handler
+                    .result_set_memory(self.last_query_digest().as_ref(), self.inner.result_bytes);
+            }
+            if meta.is_none() && !self.more_results_exists() {
+                self.finish_query();
+            }
+        }
+        if meta.is_some() {
+            self.inner.result_bytes = 0;
+        }
         self.inner.has_result = meta;
     }
 
-    fn set_status(&mut self, status: consts::StatusFlags) -> () {
-        self.inner.status = status;
+    fn set_cursor(&mut self, cursor: Option<Cursor>) {
+        self.inner.cursor = cursor;
     }
 
-    fn set_warnings(&mut self, warnings: u16) -> () {
-        self.inner.warnings = warnings;
+    fn set_last_query(&mut self, query: &str) {
+        let opts = &self.inner.opts;
+        if opts.get_event_handler().is_some() || opts.get_detect_read_only_server() {
+            self.inner.last_query = if self.inner.opts.get_redact_queries() {
+                Some(QueryDigest::new(query).into_text())
+            } else {
+                Some(query.into())
+            };
+        }
     }
 
-    fn set_seq_id(&mut self, seq_id: u8) -> () {
-        self.inner.seq_id = seq_id;
+    fn take_last_statement(&mut self) -> LastStatement {
+        LastStatement {
+            query: self.inner.last_query.take(),
+            executed_stmt: self.inner.executed_stmt.take(),
+        }
     }
 
-    fn touch(&mut self) -> () {
-        self.inner.last_io = SteadyTime::now();
+    fn set_last_statement(&mut self, statement: LastStatement) {
+        self.inner.last_query = statement.query;
+        self.inner.executed_stmt = statement.executed_stmt;
     }
 
-    fn on_disconnect(&mut self) {
-        self.inner.pool = None;
+    fn record_command(&mut self, command: consts::Command, data: &[u8]) {
+        self.inner.journal.push(command, data);
+        if command != consts::Command::COM_STMT_CLOSE {
+            self.inner.last_warnings.clear();
+            self.inner.info = None;
+        }
+        match command {
+            consts::Command::COM_QUERY | consts::Command::COM_STMT_PREPARE => {
+                self.inner.executed_stmt = None;
+            }
+            consts::Command::COM_STMT_EXECUTE if data.len() >= 4 => {
+                self.inner.executed_stmt = Some(LittleEndian::read_u32(&data[..4]));
+            }
+            _ => (),
+        }
+        #[cfg(feature = "tracing")]
+        {
+            let inner = &mut *self.inner;
+            let stmt_cache = &inner.stmt_cache;
+            let executed_stmt = inner.executed_stmt;
+            inner.span_rows = 0;
+            inner.span =
+                spans::command_span(inner.id, command, inner.transaction_span.as_ref(), || {
+                    match command {
+                        consts::Command::COM_STMT_EXECUTE => executed_stmt
+                            .and_then(|id| stmt_cache.query_by_id(id))
+                            .map(QueryDigest::new),
+                        _ => Some(QueryDigest::new(String::from_utf8_lossy(data))),
+                    }
+                });
+        }
+        self.inner.query_started = match command {
+            consts::Command::COM_QUERY | consts::Command::COM_STMT_EXECUTE => Some(Instant::now()),
+            _ => None,
+        };
+        if self.inner.query_started.is_some() {
+            self.inner.query_io_started = self.io_bytes();
+        }
     }
-}
 
-#[cfg(test)]
-mod test {
-    use futures::Future;
+    fn record_outcome(&mut self, outcome: CommandOutcome) {
+        self.inner.journal.complete(outcome);
+        #[cfg(feature = "tracing")]
+        {
+            if let (CommandOutcome::Error(code), Some(span)) = (outcome, &self.inner.span) {
+                spans::fail(span, code);
+            }
+        }
+        let done = match outcome {
+            CommandOutcome::Error(_) => true,
+            // Rows of a result set may look like OK packets, so the end of a result set
+            // is handled by `set_pending_result`.
+            _ => self.inner.has_result.is_none() && !self.more_results_exists(),
+        };
+        if done {
+            self.finish_query();
+        }
+    }
 
-    #[cfg(feature = "ssl")]
-    use crate::SslOpts;
-    use crate::{
-        from_row, params, prelude::*, test_misc::DATABASE_URL, Conn, OptsBuilder,
-        TransactionOptions, WhiteListFsLocalInfileHandler,
-    };
+    fn get_recent_commands(&self) -> Vec<CommandRecord> {
+        self.inner.journal.records()
+    }
 
-    /// Same as `tokio::run`, but will panic if future panics and will return the result
-    /// of future execution.
-    fn run<F, T, U>(future: F) -> Result<T, U>
-    where
+    fn read_only_violation(&mut self, error: ServerError) -> Error {
+        if let Some(ref pool) = self.inner.pool {
+            pool.mark_demoted();
+        }
+        let digest = match self.inner.executed_stmt {
+            Some(statement_id) => self
+                .inner
+                .stmt_cache
+                .query_by_id(statement_id)
+                .map(QueryDigest::new),
+            None => self.last_query_digest(),
+        };
+        DriverError::ReadOnlyViolation { digest, error }.into()
+    }
+
+    fn get_result_bytes(&self) -> u64 {
+        self.inner.result_bytes
+    }
+
+    fn add_result_bytes(&mut self, bytes: u64) {
+        // called once per row
+        self.inner.result_bytes += bytes;
+        #[cfg(feature = "tracing")]
+        {
+            self.inner.span_rows += 1;
+        }
+    }
+
+    fn set_status(&mut self, status: consts::StatusFlags) -> () {
+        self.inner.status = status;
+    }
+
+    fn set_warnings(&mut self, warnings: u16) -> () {
+        self.inner.warnings = warnings;
+    }
+
+    fn set_last_warnings(&mut self, warnings: Vec<Warning>) {
+        self.inner.last_warnings = warnings;
+    }
+
+    fn set_seq_id(&mut self, seq_id: u8) -> () {
+        self.inner.seq_id = seq_id;
+    }
+
+    fn touch(&mut self) -> () {
+        self.inner.last_io = SteadyTime::now();
+    }
+
+    fn on_disconnect(&mut self) {
+        self.inner.pool = None;
+    }
+}
+
+/// Returns the error of the only tried host or `DriverError::AllHostsFailed`.
+fn hosts_error(mut errors: Vec<(String, u16, Error)>) -> Error {
+    if errors.len() == 1 {
+        errors.pop().expect("one error").2
+    } else {
+        DriverError::AllHostsFailed {
+            errors: HostErrors(errors),
+        }
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::future::{Either::*, Future};
+
+    #[cfg(feature = "ssl")]
+    use crate::SslOpts;
+    use crate::{
+        chrono, consts, from_row, params,
+        prelude::*,
+        test_misc::{server_version_at_least, DATABASE_URL},
+        ColumnDecode, CommandOutcome, Conn, DecodeOverrides, OptsBuilder, QueryDigest,
+        TransactionOptions, WhiteListFsLocalInfileHandler,
+    };
+
+    /// Same as `tokio::run`, but will panic if future panics and will return the result
+    /// of future execution.
+    fn run<F, T, U>(future: F) -> Result<T, U>
+    where
         F: Future<Item = T, Error = U> + Send + 'static,
         T: Send + 'static,
         U: Send + 'static,
@@ -608,29 +1688,1525 @@ mod test {
     }
 
     #[test]
-    fn opts_should_satisfy_send_and_sync() {
-        struct A<T: Sync + Send>(T);
-        A(get_opts());
+    fn opts_should_satisfy_send_and_sync() {
+        struct A<T: Sync + Send>(T);
+        A(get_opts());
+    }
+
+    #[test]
+    fn should_connect() {
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| Queryable::ping(conn))
+            .and_then(|conn| Queryable::disconnect(conn));
+
+        run(fut).unwrap();
+    }
+
+    /// Creates a user authenticated with the `sha256_password` plugin
+    /// and returns options to connect as this user.
+    ///
+    /// Returns `None` if the server doesn't support `CREATE USER IF NOT EXISTS`.
+    fn create_sha256_user(
+        conn: Conn,
+    ) -> impl Future<Item = (Conn, Option<OptsBuilder>), Error = crate::error::Error> {
+        if !server_version_at_least(&conn, (5, 7, 6)) {
+            return A(futures::future::ok((conn, None)));
+        }
+        let fut = conn
+            .drop_query(
+                "CREATE USER IF NOT EXISTS 'mysql_async_sha256'@'%' \
+                 IDENTIFIED WITH sha256_password BY 'sha256_pass'",
+            )
+            .map(|conn| {
+                let mut opts = get_opts();
+                opts.user(Some("mysql_async_sha256"))
+                    .pass(Some("sha256_pass"))
+                    .db_name(None::<String>);
+                (conn, Some(opts))
+            });
+        B(fut)
+    }
+
+    #[test]
+    fn should_connect_with_sha256_password_over_rsa() {
+        let key_path = "sha256_public_key.pem";
+        let fut = Conn::new(get_opts())
+            .and_then(create_sha256_user)
+            .and_then(|(conn, opts)| {
+                conn.first::<_, (String, String)>("SHOW STATUS LIKE 'Rsa_public_key'")
+                    .map(|(conn, key)| {
+                        let key = key.map(|(_, key)| key).filter(|key| !key.is_empty());
+                        (conn, opts, key)
+                    })
+            })
+            .and_then(move |(conn, opts, key)| match (opts, key) {
+                (Some(mut opts), Some(key)) => {
+                    std::fs::write(key_path, key).unwrap();
+                    opts.ssl_opts(None);
+                    let mut key_path_opts = opts.clone();
+                    key_path_opts.server_public_key_path(Some(key_path));
+                    // the key is requested from the server, then read from the file
+                    let fut = Conn::new(opts)
+                        .and_then(Queryable::ping)
+                        .and_then(Queryable::disconnect)
+                        .and_then(move |_| Conn::new(key_path_opts))
+                        .and_then(Queryable::ping)
+                        .and_then(Queryable::disconnect)
+                        .then(move |result| {
+                            std::fs::remove_file(key_path).unwrap();
+                            result
+                        })
+                        .and_then(|_| conn.disconnect());
+                    A(fut)
+                }
+                // server doesn't support the plugin or wasn't built with RSA support
+                _ => B(conn.disconnect()),
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[cfg(feature = "ssl")]
+    #[test]
+    fn should_connect_with_sha256_password_over_tls() {
+        let fut = Conn::new(get_opts())
+            .and_then(create_sha256_user)
+            .and_then(|(conn, opts)| match opts {
+                Some(opts) => {
+                    let fut = Conn::new(opts)
+                        .and_then(|user_conn| {
+                            assert!(user_conn.is_secure());
+                            user_conn.ping()
+                        })
+                        .and_then(Queryable::disconnect)
+                        .and_then(|_| conn.disconnect());
+                    A(fut)
+                }
+                None => B(conn.disconnect()),
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_authenticate_with_auth_handler_after_plugin_switch() {
+        use crate::AuthContext;
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        /// Implements the `mysql_clear_password` client plugin (unknown to the driver).
+        struct Handler(Arc<AtomicUsize>);
+
+        impl AuthHandler for Handler {
+            fn auth_data(&self, context: AuthContext<'_>) -> Option<Vec<u8>> {
+                if context.plugin != b"mysql_clear_password" {
+                    return None;
+                }
+                self.0.fetch_add(1, Ordering::SeqCst);
+                let mut data = Vec::from(context.pass.unwrap_or_default());
+                data.push(0);
+                Some(data)
+            }
+        }
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut opts = get_opts();
+        opts.user(Some("mysql_async_cleartext"))
+            .pass(Some("cleartext_pass"))
+            .db_name(None::<String>)
+            .auth_handler(Some(Handler(calls.clone())));
+
+        // `cleartext_plugin_server` (of the `auth_test_plugin` library shipped with the server)
+        // switches the client to `mysql_clear_password`
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| {
+                conn.first::<_, (u64,)>(
+                    "SELECT COUNT(*) FROM information_schema.plugins \
+                     WHERE plugin_name = 'cleartext_plugin_server'",
+                )
+            })
+            .and_then(|(conn, count)| {
+                let installed = count.map(|(count,)| count > 0).unwrap_or(false);
+                if installed || !server_version_at_least(&conn, (5, 7, 6)) {
+                    A(futures::future::ok((conn, installed)))
+                } else {
+                    let fut = conn
+                        .drop_query(
+                            "INSTALL PLUGIN cleartext_plugin_server SONAME 'auth_test_plugin.so'",
+                        )
+                        .map(|conn| (conn, true))
+                        .or_else(|_| Conn::new(get_opts()).map(|conn| (conn, false)));
+                    B(fut)
+                }
+            })
+            .and_then(move |(conn, installed)| {
+                if !installed {
+                    // server doesn't ship the test plugin
+                    return A(conn.disconnect());
+                }
+                let fut = conn
+                    .drop_query(
+                        "CREATE USER IF NOT EXISTS 'mysql_async_cleartext'@'%' \
+                         IDENTIFIED WITH cleartext_plugin_server AS 'cleartext_pass'",
+                    )
+                    .and_then(|conn| Conn::new(opts).map(|user_conn| (conn, user_conn)))
+                    .and_then(|(conn, user_conn)| {
+                        user_conn
+                            .ping()
+                            .and_then(Queryable::disconnect)
+                            .map(|_| conn)
+                    })
+                    .and_then(move |conn| {
+                        assert_eq!(calls.load(Ordering::SeqCst), 1);
+                        conn.disconnect()
+                    });
+                B(fut)
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_report_found_rows() {
+        let mut opts = OptsBuilder::from_opts(get_opts());
+        opts.client_found_rows(true);
+
+        let fut = Conn::new(get_opts())
+            .join(Conn::new(opts))
+            .and_then(|(conn, found_rows_conn)| {
+                let update = |conn: Conn| {
+                    conn.drop_query("CREATE TEMPORARY TABLE tmp (id INT PRIMARY KEY, a INT)")
+                        .and_then(|conn| conn.drop_query("INSERT INTO tmp VALUES (1, 1)"))
+                        .and_then(|conn| conn.drop_query("UPDATE tmp SET a = 1 WHERE id = 1"))
+                        .map(|conn| {
+                            let affected_rows = conn.affected_rows();
+                            (conn, affected_rows)
+                        })
+                };
+                update(conn).join(update(found_rows_conn))
+            })
+            .and_then(|((conn, changed), (found_rows_conn, matched))| {
+                assert_eq!(changed, 0);
+                assert_eq!(matched, 1);
+                conn.disconnect().join(found_rows_conn.disconnect())
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_expose_ok_packet_after_drop_result() {
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| {
+                conn.drop_query(
+                    "CREATE TEMPORARY TABLE tmp (id INT AUTO_INCREMENT PRIMARY KEY, a INT)",
+                )
+            })
+            .and_then(|conn| conn.drop_query("INSERT INTO tmp (a) VALUES (1), (2)"))
+            .and_then(|conn| {
+                assert_eq!(conn.last_insert_id(), Some(1));
+                assert_eq!(conn.affected_rows(), 2);
+                assert_eq!(conn.info(), Some("Records: 2  Duplicates: 0  Warnings: 0"));
+                conn.drop_exec("INSERT INTO tmp (a) VALUES (?)", (3,))
+            })
+            .and_then(|conn| {
+                assert_eq!(conn.last_insert_id(), Some(3));
+                assert_eq!(conn.affected_rows(), 1);
+                assert_eq!(conn.info(), None);
+                conn.drop_query("UPDATE tmp SET a = 0 WHERE a < 3")
+            })
+            .and_then(|conn| {
+                assert_eq!(conn.affected_rows(), 2);
+                assert_eq!(
+                    conn.info(),
+                    Some("Rows matched: 2  Changed: 2  Warnings: 0")
+                );
+                conn.drop_query("SELECT * FROM tmp")
+            })
+            .and_then(|conn| {
+                assert_eq!(conn.info(), None);
+                conn.disconnect()
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_kill_query() {
+        let fut = Conn::new(get_opts())
+            .join(Conn::new(get_opts()))
+            .and_then(|(conn, other)| {
+                assert_ne!(conn.id(), other.id());
+                assert!(conn.server_version() > (5, 0, 0));
+                let id = conn.id();
+                let sleep = conn.first::<_, (u8,)>("SELECT SLEEP(10)");
+                let kill = other
+                    .drop_query("DO SLEEP(0.5)")
+                    .and_then(move |other| other.kill(id));
+                sleep.join(kill)
+            })
+            .and_then(|((conn, row), other)| {
+                // Interrupted `SLEEP` returns `1`.
+                assert_eq!(row, Some((1,)));
+                conn.disconnect().join(other.disconnect())
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_exec_ddl() {
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| conn.exec_ddl("CREATE TEMPORARY TABLE tmp (id INT)"))
+            .and_then(|(conn, affected_rows)| {
+                assert_eq!(affected_rows, 0);
+                conn.exec_ddl("INSERT INTO tmp VALUES (1), (2); INSERT INTO tmp VALUES (3)")
+            })
+            .and_then(|(conn, affected_rows)| {
+                assert_eq!(affected_rows, 3);
+                conn.exec_ddl("SELECT * FROM tmp")
+                    .then(|result| match result {
+                        Err(crate::error::Error::Driver(
+                            crate::error::DriverError::UnexpectedResultSet { columns: 1 },
+                        )) => Ok(()),
+                        _ => panic!("expected UnexpectedResultSet error"),
+                    })
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_cancel_query() {
+        let fut = Conn::new(get_opts()).and_then(|conn| {
+            let (handle, token) = conn.query_cancellable("SELECT SLEEP(10)");
+            assert!(!token.is_cancelled());
+            let cancel = tokio::timer::Delay::new(
+                std::time::Instant::now() + std::time::Duration::from_millis(500),
+            )
+            .then(move |_| token.cancel());
+            handle
+                .then(|result| match result {
+                    Err(crate::error::Error::Driver(crate::error::DriverError::Cancelled)) => {
+                        Ok(())
+                    }
+                    _ => panic!("expected Cancelled error"),
+                })
+                .join(cancel)
+        });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_query_multi() {
+        use crate::ResultSummary;
+        use futures::Stream;
+
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| {
+                conn.query_multi(
+                    "CREATE TEMPORARY TABLE tmp (id INT); INSERT INTO tmp VALUES (1), (2); \
+                     SELECT id FROM tmp ORDER BY id; SELECT 'foo' FROM tmp WHERE 1 = 0",
+                )
+                .collect()
+            })
+            .map(|summaries| {
+                assert_eq!(summaries.len(), 4);
+                match summaries[1] {
+                    ResultSummary::Ok { affected_rows, .. } => assert_eq!(affected_rows, 2),
+                    _ => panic!("expected OK summary"),
+                }
+                match summaries[2] {
+                    ResultSummary::Rows { ref rows, .. } => {
+                        let ids = rows
+                            .iter()
+                            .map(|row| row.get::<u8, _>(0).unwrap())
+                            .collect::<Vec<_>>();
+                        assert_eq!(ids, vec![1, 2]);
+                    }
+                    _ => panic!("expected rows"),
+                }
+                match summaries[3] {
+                    ResultSummary::Rows { ref rows, .. } => assert!(rows.is_empty()),
+                    _ => panic!("expected rows"),
+                }
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_read_result_sets_of_procedure() {
+        use crate::ResultSummary;
+        use futures::Stream;
+
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| conn.drop_query("DROP PROCEDURE IF EXISTS result_sets_proc"))
+            .and_then(|conn| {
+                conn.drop_query(
+                    "CREATE PROCEDURE result_sets_proc() \
+                     BEGIN SELECT 1 AS a, 2 AS b; SELECT 'foo' AS c; END",
+                )
+            })
+            .and_then(|conn| conn.query("CALL result_sets_proc()"))
+            .and_then(|result| result.next_result_set())
+            .and_then(|(result, more)| {
+                assert!(more);
+                result.collect::<(String,)>()
+            })
+            .and_then(|(result, rows)| {
+                assert_eq!(rows, vec![("foo".into(),)]);
+                assert!(result.is_empty());
+                result.drop_result()
+            })
+            .and_then(|conn| conn.query("CALL result_sets_proc()"))
+            .and_then(|result| result.result_sets().collect())
+            .and_then(|summaries| {
+                assert_eq!(summaries.len(), 3);
+                match summaries[0] {
+                    ResultSummary::Rows {
+                        ref columns,
+                        ref rows,
+                    } => {
+                        assert_eq!(columns.len(), 2);
+                        assert_eq!(rows[0].get::<u8, _>("b"), Some(2));
+                    }
+                    _ => panic!("expected rows"),
+                }
+                match summaries[1] {
+                    ResultSummary::Rows { ref columns, .. } => assert_eq!(columns.len(), 1),
+                    _ => panic!("expected rows"),
+                }
+                match summaries[2] {
+                    ResultSummary::Ok { .. } => (),
+                    _ => panic!("expected OK summary"),
+                }
+                Conn::new(get_opts())
+            })
+            .and_then(|conn| conn.drop_query("DROP PROCEDURE result_sets_proc"))
+            .and_then(|conn| conn.disconnect());
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_call_procedure_with_out_params() {
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| conn.drop_query("DROP PROCEDURE IF EXISTS out_params_proc"))
+            .and_then(|conn| {
+                conn.drop_query(
+                    "CREATE PROCEDURE out_params_proc(IN x INT, OUT y INT, OUT z TEXT) \
+                     BEGIN SELECT x AS a; SET y = x * 2; SET z = 'foo'; END",
+                )
+            })
+            .and_then(|conn| {
+                conn.call_procedure::<_, _, (i32, String)>("CALL out_params_proc(?, @y, @z)", (21,))
+            })
+            .and_then(|(conn, summaries, out)| {
+                assert_eq!(summaries.len(), 2);
+                assert_eq!(out, Some((42, "foo".into())));
+                conn.call_procedure::<_, _, (i32, String)>(
+                    "CALL out_params_proc(?, ?, ?)",
+                    (1, 0, ""),
+                )
+            })
+            .and_then(|(conn, summaries, out)| {
+                assert_eq!(summaries.len(), 2);
+                assert_eq!(out, Some((2, "foo".into())));
+                conn.drop_query("DROP PROCEDURE out_params_proc")
+            })
+            .and_then(|conn| conn.disconnect());
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_read_replication_status() {
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| conn.master_status())
+            .and_then(|(conn, status)| {
+                if let Some(status) = status {
+                    assert!(!status.file.is_empty());
+                }
+                conn.gtid_executed()
+            })
+            .and_then(|(conn, _)| conn.replica_status())
+            .and_then(|(conn, status)| {
+                // test server isn't a replica
+                assert_eq!(status, None);
+                conn.disconnect()
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_account_result_set_memory() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        struct Handler(Arc<AtomicUsize>);
+
+        impl EventHandler for Handler {
+            fn result_set_memory(&self, query: Option<&QueryDigest>, bytes: u64) {
+                assert_eq!(
+                    query.map(QueryDigest::text),
+                    Some("SELECT REPEAT(...) UNION ALL SELECT ?")
+                );
+                self.0.store(bytes as usize, Ordering::SeqCst);
+            }
+        }
+
+        let reported = Arc::new(AtomicUsize::new(0));
+        let mut opts = get_opts();
+        opts.event_handler(Some(Handler(reported.clone())));
+
+        let fut = Conn::new(opts)
+            .and_then(|conn| conn.query("SELECT REPEAT('a', 1000) UNION ALL SELECT 'b'"))
+            .and_then(|result| result.collect::<(String,)>())
+            .and_then(move |(result, _)| {
+                let usage = result.memory_usage();
+                assert!(usage > 1001);
+                assert_eq!(reported.load(Ordering::SeqCst) as u64, usage);
+                result.drop_result()
+            })
+            .and_then(|conn| conn.disconnect());
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_offload_decoding_of_wide_rows() {
+        let query = (0..300)
+            .map(|x| format!("SELECT {0}, '{0}'", x))
+            .collect::<Vec<_>>()
+            .join(" UNION ALL ");
+        let mut opts = get_opts();
+        opts.decode_offload_threshold(Some(2));
+
+        let fut = Conn::new(opts)
+            .and_then(move |conn| conn.query(format!("{}; SELECT 1", query)))
+            .and_then(|result| result.collect::<(u32, String)>())
+            .and_then(|(result, rows)| {
+                assert_eq!(rows.len(), 300);
+                for (i, (x, text)) in rows.into_iter().enumerate() {
+                    assert_eq!(x as usize, i);
+                    assert_eq!(text, i.to_string());
+                }
+                result.collect::<(u8,)>()
+            })
+            .and_then(|(result, rows)| {
+                assert_eq!(rows, vec![(1,)]);
+                result.drop_result()
+            })
+            .and_then(|conn| conn.prep_exec("SELECT ?, ?", (1, 2)))
+            .and_then(|result| result.collect_and_drop::<(u8, u8)>())
+            .and_then(|(conn, rows)| {
+                assert_eq!(rows, vec![(1, 2)]);
+                conn.disconnect()
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_count_io_bytes() {
+        use std::sync::{Arc, Mutex};
+
+        const QUERY: &str = "SELECT REPEAT('a', 1000)";
+
+        struct Handler(Arc<Mutex<Vec<(u64, u64)>>>);
+
+        impl EventHandler for Handler {
+            fn query_io(&self, query: Option<&QueryDigest>, bytes_sent: u64, bytes_received: u64) {
+                if query.map(QueryDigest::text) == Some("SELECT REPEAT(...)") {
+                    self.0.lock().unwrap().push((bytes_sent, bytes_received));
+                }
+            }
+        }
+
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let mut opts = get_opts();
+        opts.event_handler(Some(Handler(reported.clone())));
+
+        let fut = Conn::new(opts)
+            .and_then(|conn| {
+                let sent = conn.bytes_sent();
+                let received = conn.bytes_received();
+                assert!(sent > 0);
+                assert!(received > 0);
+                conn.drop_query(QUERY).map(move |conn| {
+                    // command byte and packet header
+                    assert_eq!(conn.bytes_sent() - sent, QUERY.len() as u64 + 5);
+                    assert!(conn.bytes_received() - received > 1000);
+                    conn
+                })
+            })
+            .and_then(|conn| conn.disconnect())
+            .map(move |_| {
+                let reported = reported.lock().unwrap();
+                assert_eq!(reported.len(), 1);
+                assert_eq!(reported[0].0, QUERY.len() as u64 + 5);
+                assert!(reported[0].1 > 1000);
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_rewrite_queries() {
+        use std::sync::{Arc, Mutex};
+
+        struct Rewriter;
+
+        impl QueryRewriter for Rewriter {
+            fn rewrite(&self, query: &str) -> Option<String> {
+                if query.contains("'original'") {
+                    Some(query.replace("'original'", "'rewritten'"))
+                } else {
+                    None
+                }
+            }
+        }
+
+        struct Handler(Arc<Mutex<Vec<(String, String)>>>);
+
+        impl EventHandler for Handler {
+            fn query_rewritten(&self, original: &str, rewritten: &str) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push((original.into(), rewritten.into()));
+            }
+        }
+
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let mut opts = get_opts();
+        opts.query_rewriter(Some(Rewriter))
+            .event_handler(Some(Handler(reported.clone())));
+
+        let fut = Conn::new(opts)
+            .and_then(|conn| conn.first::<_, (String,)>("SELECT 'original'"))
+            .and_then(|(conn, row)| {
+                assert_eq!(row.unwrap().0, "rewritten");
+                conn.first_exec::<_, _, (String, u8)>("SELECT 'original', ?", (1,))
+            })
+            .and_then(|(conn, row)| {
+                assert_eq!(row.unwrap(), ("rewritten".into(), 1));
+                conn.disconnect()
+            })
+            .map(move |_| {
+                let reported = reported.lock().unwrap();
+                assert_eq!(
+                    *reported,
+                    vec![
+                        ("SELECT 'original'".into(), "SELECT 'rewritten'".into()),
+                        (
+                            "SELECT 'original', ?".into(),
+                            "SELECT 'rewritten', ?".into()
+                        ),
+                    ]
+                );
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn should_verify_row_order() {
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| conn.query("SELECT 1 AS x UNION ALL SELECT 2 UNION ALL SELECT 10"))
+            .and_then(|result| result.verify_order("x", false).collect_and_drop::<(u8,)>())
+            .and_then(|(conn, rows)| {
+                assert_eq!(rows, vec![(1,), (2,), (10,)]);
+                conn.query("SELECT 'b' AS x UNION ALL SELECT 'a'")
+            })
+            .and_then(|result| result.verify_order("x", false).collect::<(String,)>())
+            .then(|result| match result {
+                Err(crate::error::Error::Driver(crate::error::DriverError::RowOrder {
+                    ref column,
+                    ..
+                })) if column == "x" => Ok(()),
+                _ => panic!("expected RowOrder error"),
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_convert_rows_without_panicking() {
+        const QUERY: &str = "SELECT 'a' UNION ALL SELECT '1'";
+
+        let mut opts = get_opts();
+        opts.strict_conversions(true);
+
+        let fut = Conn::new(opts)
+            .and_then(|conn| conn.query(QUERY))
+            .and_then(|result| result.collect_opt::<(u8,)>())
+            .and_then(|(result, rows)| {
+                assert_eq!(rows.len(), 2);
+                assert!(rows[0].is_err());
+                assert_eq!(*rows[1].as_ref().unwrap(), (1,));
+                result.drop_result()
+            })
+            .and_then(|conn| conn.query(QUERY))
+            .and_then(|result| result.map_opt(|(x,): (u8,)| x * 2))
+            .and_then(|(result, rows)| {
+                assert!(rows[0].is_err());
+                assert_eq!(*rows[1].as_ref().unwrap(), 2);
+                result.drop_result()
+            })
+            .and_then(|conn| conn.first::<_, (u8,)>(QUERY))
+            .then(|result| match result {
+                Err(crate::error::Error::Driver(crate::error::DriverError::FromRow { .. })) => {
+                    Ok(())
+                }
+                _ => panic!("expected FromRow error"),
+            })
+            .and_then(|_| {
+                let mut opts = get_opts();
+                opts.strict_conversions(true);
+                Conn::new(opts)
+            })
+            .and_then(|conn| conn.query(QUERY))
+            .and_then(|result| result.collect::<(u8,)>())
+            .then(|result| match result {
+                Err(crate::error::Error::Driver(crate::error::DriverError::FromRow { .. })) => {
+                    Ok::<_, Error>(())
+                }
+                _ => panic!("expected FromRow error"),
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_report_slow_queries() {
+        use std::{
+            sync::{
+                atomic::{AtomicUsize, Ordering},
+                Arc,
+            },
+            time::Duration,
+        };
+
+        struct Handler(Arc<AtomicUsize>);
+
+        impl EventHandler for Handler {
+            fn slow_query(&self, query: Option<&QueryDigest>, duration: Duration) {
+                assert_eq!(query.map(QueryDigest::text), Some("SELECT SLEEP(...)"));
+                assert!(duration >= Duration::from_millis(300));
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let reported = Arc::new(AtomicUsize::new(0));
+        let mut opts = get_opts();
+        opts.slow_query_threshold(Some(Duration::from_millis(200)))
+            .event_handler(Some(Handler(reported.clone())));
+
+        let fut = Conn::new(opts)
+            .and_then(|conn| conn.drop_exec("SELECT SLEEP(?)", (0.3,)))
+            .and_then(|conn| conn.drop_query("SELECT 1"))
+            .and_then(move |conn| {
+                assert_eq!(reported.load(Ordering::SeqCst), 1);
+                conn.disconnect()
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn should_emit_tracing_spans() {
+        use lazy_static::lazy_static;
+        use tracing::{
+            field::{Field, Visit},
+            span::{Attributes, Id, Record},
+            Event, Metadata, Subscriber,
+        };
+
+        use std::{
+            collections::{BTreeMap, HashMap},
+            fmt,
+            sync::{
+                atomic::{AtomicUsize, Ordering},
+                Mutex, Once,
+            },
+        };
+
+        use crate::error::Error;
+
+        struct CapturedSpan {
+            name: &'static str,
+            parent: Option<u64>,
+            fields: HashMap<&'static str, u64>,
+        }
+
+        struct Fields<'a>(&'a mut HashMap<&'static str, u64>);
+
+        impl<'a> Visit for Fields<'a> {
+            fn record_u64(&mut self, field: &Field, value: u64) {
+                self.0.insert(field.name(), value);
+            }
+
+            fn record_debug(&mut self, _: &Field, _: &dyn fmt::Debug) {}
+        }
+
+        lazy_static! {
+            static ref SPANS: Mutex<BTreeMap<u64, CapturedSpan>> = Mutex::new(BTreeMap::new());
+        }
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+        static INIT: Once = Once::new();
+
+        /// Global subscriber (spans are created on runtime threads) that captures all spans.
+        struct Capture;
+
+        impl Subscriber for Capture {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+                let id = NEXT_ID.fetch_add(1, Ordering::SeqCst) as u64;
+                let mut span = CapturedSpan {
+                    name: attrs.metadata().name(),
+                    parent: attrs.parent().map(Id::into_u64),
+                    fields: HashMap::new(),
+                };
+                attrs.record(&mut Fields(&mut span.fields));
+                SPANS.lock().unwrap().insert(id, span);
+                Id::from_u64(id)
+            }
+
+            fn record(&self, id: &Id, values: &Record<'_>) {
+                if let Some(span) = SPANS.lock().unwrap().get_mut(&id.into_u64()) {
+                    values.record(&mut Fields(&mut span.fields));
+                }
+            }
+
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+            fn event(&self, _: &Event<'_>) {}
+
+            fn enter(&self, _: &Id) {}
+
+            fn exit(&self, _: &Id) {}
+        }
+
+        INIT.call_once(|| tracing::subscriber::set_global_default(Capture).unwrap());
+
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| conn.drop_query("CREATE TEMPORARY TABLE tmp (id INT)"))
+            .and_then(|conn| conn.query("SELECT 1 UNION ALL SELECT 2 UNION ALL SELECT 3"))
+            .and_then(|result| result.collect_and_drop::<(u8,)>())
+            .and_then(|(conn, _)| conn.prep_exec("SELECT ?", (1,)))
+            .and_then(|result| result.collect_and_drop::<(u8,)>())
+            .and_then(|(conn, _)| conn.start_transaction(TransactionOptions::new()))
+            .and_then(|transaction| transaction.drop_query("INSERT INTO tmp VALUES (1), (2)"))
+            .and_then(|transaction| transaction.commit())
+            .and_then(|conn| {
+                let conn_id = u64::from(conn.id());
+                conn.drop_query("SELECT * FROM no_such_table")
+                    .then(move |result| match result {
+                        Err(Error::Server(ref err)) if err.code == 1146 => Ok::<_, Error>(conn_id),
+                        _ => panic!("expected ER_NO_SUCH_TABLE"),
+                    })
+            });
+
+        let conn_id = run(fut).unwrap();
+        let spans = SPANS.lock().unwrap();
+        let spans = spans
+            .iter()
+            .filter(|(_, span)| span.fields.get("conn_id") == Some(&conn_id))
+            .collect::<Vec<_>>();
+        let find = |name: &str, field: &str, value: u64| {
+            spans
+                .iter()
+                .find(|(_, span)| span.name == name && span.fields.get(field) == Some(&value))
+                .map(|(_, span)| *span)
+                .unwrap_or_else(|| panic!("no {} span with {} = {}", name, field, value))
+        };
+
+        // `rows` counts rows of the result, i.e. `add_result_bytes` is called once per row
+        let select = find("mysql.query", "rows", 3);
+        assert_eq!(select.parent, None);
+        assert!(select.fields.contains_key("duration_ms"));
+        assert!(spans.iter().any(|(_, span)| span.name == "mysql.prepare"));
+        find("mysql.execute", "rows", 1);
+
+        let (transaction_id, _) = spans
+            .iter()
+            .find(|(_, span)| span.name == "mysql.transaction")
+            .expect("transaction span");
+        let insert = find("mysql.query", "affected_rows", 2);
+        assert_eq!(insert.parent, Some(**transaction_id));
+
+        let failed = find("mysql.query", "error_code", 1146);
+        assert_eq!(failed.parent, None);
+    }
+
+    #[test]
+    fn should_dump_table() {
+        use crate::DumpFormat;
+
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| {
+                conn.drop_query(
+                    "CREATE TEMPORARY TABLE tmp (id INT PRIMARY KEY, name TEXT, score DOUBLE)",
+                )
+            })
+            .and_then(|conn| {
+                conn.drop_query(
+                    r#"INSERT INTO tmp VALUES (1, 'a,"b"', 1.5), (2, NULL, NULL), (3, '', 0)"#,
+                )
+            })
+            .and_then(|conn| conn.dump_table("tmp", Vec::new(), DumpFormat::Csv))
+            .and_then(|(conn, csv, count)| {
+                assert_eq!(count, 3);
+                assert_eq!(
+                    String::from_utf8(csv).unwrap(),
+                    "id,name,score\r\n1,\"a,\"\"b\"\"\",1.5\r\n2,,\r\n3,\"\",0\r\n"
+                );
+                conn.dump_table_after("tmp", Vec::new(), DumpFormat::Tsv, 1)
+            })
+            .and_then(|(conn, tsv, count)| {
+                assert_eq!(count, 2);
+                assert_eq!(String::from_utf8(tsv).unwrap(), "2\t\\N\t\\N\n3\t\t0\n");
+                conn.dump_table_after("tmp", Vec::new(), DumpFormat::JsonLines, 2)
+            })
+            .and_then(|(conn, json, count)| {
+                assert_eq!(count, 1);
+                let row: serde_json::Value = serde_json::from_slice(&json).unwrap();
+                assert_eq!(row["id"], 3);
+                assert_eq!(row["name"], "");
+                conn.disconnect()
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_round_trip_dumped_binary_and_date() {
+        use std::sync::{Arc, Mutex};
+
+        use bytes::Bytes;
+
+        use crate::{DumpFormat, InfileStream, StreamLocalInfileHandler};
+
+        let dumped = Arc::new(Mutex::new(Vec::new()));
+        let infile = dumped.clone();
+        let mut opts = OptsBuilder::from_opts(get_opts());
+        opts.local_infile_handler(Some(StreamLocalInfileHandler::new(move |_: &[u8]| {
+            let chunks = vec![Bytes::from(infile.lock().unwrap().clone())];
+            Box::new(futures::stream::iter_ok(chunks)) as InfileStream
+        })));
+
+        let fut = Conn::new(opts)
+            .and_then(|conn| {
+                conn.drop_query(
+                    "CREATE TEMPORARY TABLE tmp (id INT PRIMARY KEY, data BLOB, day DATE)",
+                )
+            })
+            .and_then(|conn| {
+                conn.drop_query(
+                    "CREATE TEMPORARY TABLE tmp2 (id INT PRIMARY KEY, data BLOB, day DATE)",
+                )
+            })
+            .and_then(|conn| {
+                conn.drop_query("INSERT INTO tmp VALUES (1, X'00FF5C0A2C', '2019-01-02')")
+            })
+            .and_then(|conn| conn.dump_table("tmp", Vec::new(), DumpFormat::Tsv))
+            .and_then(move |(conn, tsv, count)| {
+                assert_eq!(count, 1);
+                assert_eq!(tsv, b"1\t\\0\xff\\\\\\n,\t2019-01-02\n".to_vec());
+                *dumped.lock().unwrap() = tsv;
+                conn.drop_query("LOAD DATA LOCAL INFILE 'dump' INTO TABLE tmp2")
+            })
+            .and_then(|conn| conn.query("SELECT data, day FROM tmp2"))
+            .and_then(|result| result.collect_and_drop::<(Vec<u8>, String)>())
+            .and_then(|(conn, rows)| {
+                assert_eq!(
+                    rows,
+                    vec![(b"\x00\xff\\\n,".to_vec(), "2019-01-02".to_string())]
+                );
+                conn.disconnect()
+            })
+            .then(|result| match result {
+                Err(crate::error::Error::Server(ref err)) if err.code == 1148 => Ok(()),
+                result => result,
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_check_charset() {
+        use crate::{
+            error::{DriverError, Error},
+            CharsetCheck,
+        };
+
+        let mut opts = get_opts();
+        opts.charset_check(CharsetCheck::Fail);
+        let fut = Conn::new(opts.clone()).and_then(|conn| conn.disconnect());
+        run(fut).unwrap();
+
+        opts.init(vec!["SET NAMES latin1"]);
+        let fut = Conn::new(opts).then(|result| match result {
+            Err(Error::Driver(DriverError::CharsetMismatch { actual, .. })) => {
+                assert_eq!(actual, "latin1");
+                Ok::<_, Error>(())
+            }
+            _ => panic!("expected charset mismatch"),
+        });
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_get_table_columns() {
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| conn.drop_query("DROP TABLE IF EXISTS table_columns"))
+            .and_then(|conn| {
+                conn.drop_query(
+                    "CREATE TABLE table_columns (id INT UNSIGNED AUTO_INCREMENT PRIMARY KEY, \
+                     a INT NOT NULL DEFAULT 1, b INT AS (a + 1) VIRTUAL, c INT AS (a * 2) STORED, \
+                     d TIMESTAMP NULL DEFAULT CURRENT_TIMESTAMP)",
+                )
+            })
+            .and_then(|conn| conn.table_columns("table_columns"))
+            .and_then(|(conn, columns)| {
+                let names = columns.iter().map(|c| &*c.name).collect::<Vec<_>>();
+                assert_eq!(names, vec!["id", "a", "b", "c", "d"]);
+                assert!(columns[0].auto_increment && columns[0].key == "PRI");
+                assert!(!columns[1].nullable && columns[1].default == Some("1".into()));
+                assert_eq!(columns[1].generated, None);
+                let generated = columns[2].generated.as_ref().unwrap();
+                assert!(!generated.stored && generated.expression.contains("a"));
+                assert!(columns[3].generated.as_ref().unwrap().stored);
+                assert_eq!(columns[4].generated, None);
+                assert!(columns[4].default.is_some());
+                assert!(columns.iter().all(|c| !c.invisible));
+                conn.drop_query("DROP TABLE table_columns")
+            })
+            .and_then(|conn| conn.disconnect());
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_describe_statement() {
+        use crate::{consts::ColumnType, prelude::ColumnExt};
+
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| {
+                conn.drop_query("CREATE TEMPORARY TABLE tmp (id INT NOT NULL, name TEXT)")
+            })
+            .and_then(|conn| conn.describe("SELECT id, name FROM tmp WHERE id = :id"))
+            .and_then(|(conn, description)| {
+                assert_eq!(description.params.len(), 1);
+                assert_eq!(description.named_params, vec!["id".to_string()]);
+                let columns = &description.columns;
+                assert_eq!(columns.len(), 2);
+                assert_eq!(columns[0].name_str(), "id");
+                assert_eq!(columns[0].column_type(), ColumnType::MYSQL_TYPE_LONG);
+                assert!(!columns[0].is_nullable() && columns[1].is_nullable());
+                conn.describe("DO 1")
+            })
+            .and_then(|(conn, description)| {
+                assert!(description.params.is_empty() && description.columns.is_empty());
+                conn.disconnect()
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_fold_numeric_columns() {
+        use futures::Stream;
+
+        use crate::{
+            error::{DriverError, Error},
+            Aggregation,
+        };
+
+        let query = "SELECT 3 AS x UNION ALL SELECT NULL UNION ALL SELECT -1.5 UNION ALL SELECT 7";
+        let fut = Conn::new(get_opts())
+            .and_then(move |conn| {
+                futures::stream::iter_ok(vec![
+                    (Aggregation::Count, Some(3.0)),
+                    (Aggregation::Sum, Some(8.5)),
+                    (Aggregation::Min, Some(-1.5)),
+                    (Aggregation::Max, Some(7.0)),
+                ])
+                .fold(conn, move |conn, (aggregation, expected)| {
+                    conn.query(query)
+                        .and_then(move |result| result.fold_numeric_and_drop("x", aggregation))
+                        .map(move |(conn, output)| {
+                            assert_eq!(output, expected);
+                            conn
+                        })
+                })
+            })
+            .and_then(|conn| conn.prep_exec("SELECT ? AS x LIMIT 0", (1,)))
+            .and_then(|result| result.fold_numeric_and_drop("x", Aggregation::Max))
+            .and_then(|(conn, output)| {
+                assert_eq!(output, None);
+                conn.query("SELECT 'foo' AS x UNION ALL SELECT NULL")
+            })
+            .and_then(|result| result.fold_numeric_and_drop("x", Aggregation::Count))
+            .and_then(|(conn, output)| {
+                // values aren't converted to count them
+                assert_eq!(output, Some(1.0));
+                conn.query("SELECT 'foo' AS x")
+            })
+            .and_then(|result| result.fold_numeric_and_drop("y", Aggregation::Count))
+            .then(|result| match result {
+                Err(Error::Driver(DriverError::MissingColumn { column })) => {
+                    assert_eq!(column, "y");
+                    Ok::<_, Error>(())
+                }
+                Ok(_) => panic!("should fail"),
+                Err(error) => Err(error),
+            })
+            .and_then(|_| Conn::new(get_opts()))
+            .and_then(|conn| conn.query("SELECT 1 AS x FROM DUAL WHERE 1 = 0"))
+            .and_then(|result| result.fold_numeric_and_drop("y", Aggregation::Count))
+            .then(|result| match result {
+                Err(Error::Driver(DriverError::MissingColumn { column })) => {
+                    assert_eq!(column, "y");
+                    Ok::<_, Error>(())
+                }
+                Ok(_) => panic!("should fail on an empty result set"),
+                Err(error) => Err(error),
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_share_connection_using_handle() {
+        use crate::{from_row, ConnHandle};
+
+        let fut = Conn::new(get_opts()).and_then(|conn| {
+            let handle = ConnHandle::new(conn);
+            let ids = handle
+                .query("SELECT CONNECTION_ID()")
+                .join(handle.prep_exec("SELECT CONNECTION_ID(), ?", (42,)));
+            let failed = handle.query("SELECT * FROM nonexistent_table").then(Ok);
+            ids.join(failed).and_then(move |((first, second), failed)| {
+                assert_eq!(
+                    from_row::<u32>(first.rows[0].clone()),
+                    from_row::<(u32, u8)>(second.rows[0].clone()).0,
+                );
+                assert!(failed.is_err());
+                handle.query("DO 1").map(|response| {
+                    assert!(response.rows.is_empty());
+                })
+            })
+        });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_convert_rows_to_dynamic_rows() {
+        use crate::{prelude::RowExt, DynamicRow, Value};
+
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| conn.query("SELECT 1 AS a, 'foo' AS b, NULL AS a"))
+            .and_then(|result| result.collect_and_drop::<DynamicRow>())
+            .and_then(|(conn, rows)| {
+                let row = &rows[0];
+                assert_eq!(row.len(), 3);
+                assert_eq!(row.names().collect::<Vec<_>>(), vec!["a", "b", "a"]);
+                assert_eq!(row.get("a"), Some(&Value::Bytes(b"1".to_vec())));
+                assert_eq!(row.get_all("a").count(), 2);
+                assert_eq!(
+                    row.get_index(1),
+                    Some(("b", &Value::Bytes(b"foo".to_vec())))
+                );
+                assert_eq!(row.get("c"), None);
+                conn.first::<_, crate::Row>("SELECT 1 AS a, 'foo' AS b")
+            })
+            .and_then(|(conn, row)| {
+                let map = row.unwrap().into_map();
+                assert_eq!(map.len(), 2);
+                assert_eq!(map["b"], Value::Bytes(b"foo".to_vec()));
+                conn.disconnect()
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_bind_limit_params() {
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| conn.drop_query("CREATE TEMPORARY TABLE tmp (id INT PRIMARY KEY)"))
+            .and_then(|conn| conn.drop_query("INSERT INTO tmp VALUES (1), (2), (3), (4), (5)"))
+            .and_then(|conn| {
+                conn.prep_exec(
+                    "SELECT id FROM tmp WHERE id > ? ORDER BY id LIMIT ?, ?",
+                    ("1", "1", 2.0),
+                )
+            })
+            .and_then(|result| result.collect::<u32>())
+            .and_then(|(result, ids)| {
+                assert_eq!(ids, vec![3, 4]);
+                result.drop_result()
+            })
+            .and_then(|conn| {
+                conn.prep_exec(
+                    "SELECT id FROM tmp ORDER BY id LIMIT :limit OFFSET :offset",
+                    params! { "limit" => "2", "offset" => 3_u64 },
+                )
+            })
+            .and_then(|result| result.collect_and_drop::<u32>())
+            .and_then(|(conn, ids)| {
+                assert_eq!(ids, vec![4, 5]);
+                conn.prepare("SELECT id FROM tmp ORDER BY id LIMIT ? OFFSET ?")
+            })
+            .and_then(|stmt| {
+                let (limit, offset) = (crate::Value::from("2"), crate::Value::from(1.0));
+                stmt.execute_ref(&[&limit, &offset])
+            })
+            .and_then(|result| result.collect_and_drop::<u32>())
+            .and_then(|(stmt, ids)| {
+                assert_eq!(ids, vec![2, 3]);
+                stmt.close()
+            })
+            .and_then(|conn| conn.disconnect());
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_paginate_by_key() {
+        use futures::{
+            future::{loop_fn, Loop},
+            Stream,
+        };
+
+        /// Collects pages and returns the connection back.
+        fn paginate(
+            conn: Conn,
+            query: &str,
+            key_column: &str,
+        ) -> impl Future<Item = (Conn, Vec<Vec<u32>>), Error = crate::error::Error> {
+            let pages = conn.paginate_by_key(query, key_column, 2);
+            loop_fn((pages, Vec::new()), |(pages, mut output)| {
+                pages
+                    .into_future()
+                    .map_err(|(error, _)| error)
+                    .map(|(rows, pages)| match rows {
+                        Some(rows) => {
+                            output.push(rows.into_iter().map(from_row::<u32>).collect());
+                            Loop::Continue((pages, output))
+                        }
+                        None => Loop::Break((pages.into_inner().unwrap(), output)),
+                    })
+            })
+        }
+
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| {
+                conn.drop_query("CREATE TEMPORARY TABLE tmp (id INT PRIMARY KEY, `a.id` INT)")
+            })
+            .and_then(|conn| {
+                conn.drop_query("INSERT INTO tmp VALUES (5, 1), (1, 5), (4, 2), (2, 4), (3, 3)")
+            })
+            .and_then(|conn| paginate(conn, "SELECT id FROM tmp WHERE id > 1", "id"))
+            .and_then(|(conn, pages)| {
+                assert_eq!(pages, vec![vec![2, 3], vec![4, 5]]);
+                paginate(conn, "SELECT t.id FROM tmp AS t WHERE t.id < 4", "t.id")
+            })
+            .and_then(|(conn, pages)| {
+                assert_eq!(pages, vec![vec![1, 2], vec![3]]);
+                paginate(conn, "SELECT `a.id` FROM tmp", "`a.id`")
+            })
+            .and_then(|(conn, pages)| {
+                assert_eq!(pages, vec![vec![1, 2], vec![3, 4], vec![5]]);
+                conn.disconnect()
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_execute_with_shared_params() {
+        use std::borrow::Cow;
+
+        use crate::{SharedParam, Value};
+
+        static BLOB: &[u8] = &[0xAB; 100_000];
+
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| conn.prepare("SELECT ?, LENGTH(?), ?"))
+            .and_then(|stmt| {
+                stmt.execute_shared(vec![
+                    SharedParam::from(Value::from(42)),
+                    SharedParam::from(Cow::Borrowed(BLOB)),
+                    SharedParam::from(bytes::Bytes::new()),
+                ])
+            })
+            .and_then(|result| result.collect_and_drop::<(u32, usize, Vec<u8>)>())
+            .and_then(|(stmt, rows)| {
+                assert_eq!(rows, vec![(42, BLOB.len(), Vec::new())]);
+                stmt.close()
+            })
+            .and_then(|conn| conn.disconnect());
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_try_additional_hosts() {
+        let opts = crate::Opts::from(get_opts());
+        let mut builder = OptsBuilder::from_opts(opts.clone());
+        builder
+            .ip_or_hostname("127.0.0.1")
+            .tcp_port(1)
+            .additional_hosts(vec![(opts.get_ip_or_hostname(), opts.get_tcp_port())]);
+        let fut = Conn::new(builder).and_then(move |conn| {
+            assert_eq!(conn.inner.opts.get_tcp_port(), opts.get_tcp_port());
+            conn.disconnect()
+        });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_report_errors_of_every_tried_host() {
+        use crate::error::{DriverError, Error};
+
+        let opts = crate::Opts::from(get_opts());
+        let mut builder = OptsBuilder::from_opts(opts.clone());
+        builder
+            .pass(Some("definitely wrong password"))
+            .additional_hosts(vec![("127.0.0.1", 1)]);
+
+        match run(Conn::new(builder.clone())) {
+            Err(Error::Driver(DriverError::AllHostsFailed { errors })) => {
+                assert_eq!(errors.0.len(), 2);
+                assert!(errors.0[0].2.is_auth_error());
+                assert_eq!(errors.0[1].1, 1);
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+
+        builder.failover_on_auth_error(false);
+        match run(Conn::new(builder)) {
+            Err(ref error) if error.is_access_denied() => (),
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn should_execute_init_queries_on_new_connection() {
+        let mut opts_builder = OptsBuilder::from_opts(get_opts());
+        opts_builder.init(vec!["SET @a = 42", "SET @b = 'foo'"]);
+        let fut = Conn::new(opts_builder)
+            .and_then(|conn| Queryable::query(conn, "SELECT @a, @b"))
+            .and_then(|result| result.collect_and_drop::<(u8, String)>())
+            .and_then(|(conn, rows)| Queryable::disconnect(conn).map(|_| rows))
+            .map(|result| assert_eq!(result, vec![(42, "foo".into())]));
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_apply_init_command_failure_policy() {
+        use crate::{
+            error::{DriverError, Error},
+            InitCommand,
+        };
+
+        let mut opts_builder = OptsBuilder::from_opts(get_opts());
+        opts_builder.init(vec![
+            InitCommand::new("SET @a = 1"),
+            InitCommand::new("SET @a = @a * 10 + ?").with_params((2,)),
+            InitCommand::new("SELECT * FROM no_such_table").best_effort(true),
+            InitCommand::new("SET @a = @a * 10 + 3"),
+        ]);
+        let fut = Conn::new(opts_builder)
+            .and_then(|conn| conn.first::<_, (u16,)>("SELECT @a"))
+            .and_then(|(conn, row)| {
+                assert_eq!(row, Some((123,)));
+                conn.disconnect()
+            })
+            .and_then(|_| {
+                let mut opts_builder = OptsBuilder::from_opts(get_opts());
+                opts_builder.init(vec!["SET @a = 1", "SELECT * FROM no_such_table"]);
+                Conn::new(opts_builder)
+            })
+            .then(|result| match result {
+                Ok(_) => panic!("should fail"),
+                Err(ref error) => {
+                    match error {
+                        Error::Driver(DriverError::InitCommandFailed { index, query, .. }) => {
+                            assert_eq!(*index, 1);
+                            assert_eq!(query, "SELECT * FROM no_such_table");
+                        }
+                        _ => panic!("unexpected error {}", error),
+                    }
+                    assert_eq!(error.server_error().map(|e| e.code), Some(1146));
+                    Ok::<_, Error>(())
+                }
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_fail_on_invalid_init_command() {
+        use crate::error::{DriverError, Error};
+
+        let mut opts_builder = OptsBuilder::from_opts(get_opts());
+        opts_builder.init(vec!["SET @a = 1", "DELIMITER //"]);
+        let fut = Conn::new(opts_builder.clone()).then(|result| match result {
+            Err(Error::Driver(DriverError::InvalidInitQuery { index: 1, .. })) => Ok(()),
+            Ok(_) => panic!("should fail"),
+            Err(error) => Err(error),
+        });
+        run(fut).unwrap();
+
+        let pool = crate::Pool::new(opts_builder);
+        let fut = pool.get_conn().then(|result| match result {
+            Err(Error::Driver(DriverError::InvalidInitQuery { index: 1, .. })) => Ok(()),
+            Ok(_) => panic!("should fail"),
+            Err(error) => Err(error),
+        });
+        run(fut).unwrap();
     }
 
     #[test]
-    fn should_connect() {
+    fn should_enforce_read_only_mode() {
+        use crate::error::{DriverError, Error};
+
+        let mut opts_builder = OptsBuilder::from_opts(get_opts());
+        opts_builder.enforce_read_only(true);
+        let fut = Conn::new(opts_builder)
+            .and_then(|conn| conn.drop_query("SET @a = 1; SELECT @a"))
+            .and_then(|conn| conn.drop_exec("SELECT ?", (1,)))
+            .and_then(|conn| conn.drop_query("SELECT 1; DELETE FROM no_such_table"))
+            .then(|result| match result {
+                Err(Error::Driver(DriverError::WriteInReadOnlyMode { ref verb })) => {
+                    assert_eq!(verb, "DELETE");
+                    Ok(())
+                }
+                Ok(_) => panic!("should fail"),
+                Err(error) => Err(error),
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_detect_read_only_server_if_enabled() {
+        use crate::error::{DriverError, Error};
+
+        // the server reports `super_read_only` this way
+        const QUERY: &str = "SIGNAL SQLSTATE 'HY000' SET MYSQL_ERRNO = 1290, MESSAGE_TEXT = \
+            'The MySQL server is running with the --super-read-only option \
+            so it cannot execute this statement'";
+
         let fut = Conn::new(get_opts())
-            .and_then(|conn| Queryable::ping(conn))
-            .and_then(|conn| Queryable::disconnect(conn));
+            .and_then(|conn| conn.drop_query(QUERY))
+            .then(|result| match result {
+                Err(Error::Server(ref error)) if error.code == 1290 => {
+                    assert!(error.is_read_only());
+                    let mut opts = get_opts();
+                    opts.detect_read_only_server(true);
+                    Ok(opts)
+                }
+                _ => panic!("expected Error::Server"),
+            })
+            .and_then(Conn::new)
+            .and_then(|conn| conn.drop_query(QUERY))
+            .then(|result| match result {
+                Err(Error::Driver(DriverError::ReadOnlyViolation { digest, error })) => {
+                    assert_eq!(error.code, 1290);
+                    assert_eq!(
+                        digest.as_ref().map(QueryDigest::text),
+                        Some("SIGNAL SQLSTATE ? SET MYSQL_ERRNO = ?, MESSAGE_TEXT = ?")
+                    );
+                    Ok::<_, Error>(())
+                }
+                _ => panic!("expected ReadOnlyViolation"),
+            });
 
         run(fut).unwrap();
     }
 
     #[test]
-    fn should_execute_init_queries_on_new_connection() {
+    fn should_reject_server_side_files() {
+        use crate::error::{DriverError, Error};
+
         let mut opts_builder = OptsBuilder::from_opts(get_opts());
-        opts_builder.init(vec!["SET @a = 42", "SET @b = 'foo'"]);
+        opts_builder.reject_server_side_files(true);
         let fut = Conn::new(opts_builder)
-            .and_then(|conn| Queryable::query(conn, "SELECT @a, @b"))
-            .and_then(|result| result.collect_and_drop::<(u8, String)>())
-            .and_then(|(conn, rows)| Queryable::disconnect(conn).map(|_| rows))
-            .map(|result| assert_eq!(result, vec![(42, "foo".into())]));
+            .and_then(|conn| conn.drop_query("SELECT 'INTO OUTFILE' INTO @a"))
+            .and_then(|conn| conn.drop_query("SELECT 1 INTO OUTFILE '/tmp/mysql_async.csv'"))
+            .then(|result| match result {
+                Err(Error::Driver(error @ DriverError::ServerSideFile { .. })) => {
+                    assert!(error.help().unwrap().contains("dump_table"));
+                    Ok(())
+                }
+                Ok(_) => panic!("should fail"),
+                Err(error) => Err(error),
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_report_server_side_files_if_enabled() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        struct Handler(Arc<AtomicUsize>);
+
+        impl EventHandler for Handler {
+            fn server_side_file(&self, query: &str) {
+                assert!(query.contains("INTO OUTFILE"));
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        const QUERY: &str = "SELECT 1 INTO OUTFILE '/tmp/mysql_async_report.csv'";
+
+        let reported = Arc::new(AtomicUsize::new(0));
+        let mut opts = OptsBuilder::from_opts(get_opts());
+        opts.event_handler(Some(Handler(reported.clone())));
+        let mut reporting_opts = opts.clone();
+        reporting_opts.report_server_side_files(true);
+
+        // statements are only prepared, so files aren't written
+        let fut = Conn::new(opts)
+            .and_then(|conn| {
+                conn.prepare(QUERY)
+                    .then(|_| Ok::<_, crate::error::Error>(()))
+            })
+            .and_then({
+                let reported = reported.clone();
+                move |_| {
+                    assert_eq!(reported.load(Ordering::SeqCst), 0);
+                    Conn::new(reporting_opts)
+                }
+            })
+            .and_then(|conn| {
+                conn.prepare(QUERY)
+                    .then(|_| Ok::<_, crate::error::Error>(()))
+            })
+            .map(move |_| assert_eq!(reported.load(Ordering::SeqCst), 1));
 
         run(fut).unwrap();
     }
@@ -646,6 +3222,120 @@ mod test {
         run(fut).unwrap();
     }
 
+    #[test]
+    fn should_set_sql_select_limit() {
+        let mut opts_builder = OptsBuilder::from_opts(get_opts());
+        opts_builder.sql_select_limit(Some(2));
+        let fut = Conn::new(opts_builder)
+            .and_then(|conn| {
+                assert_eq!(conn.sql_select_limit(), Some(2));
+                conn.drop_query("CREATE TEMPORARY TABLE tmp (id INT)")
+            })
+            .and_then(|conn| conn.drop_query("INSERT INTO tmp VALUES (1), (2), (3)"))
+            .and_then(|conn| conn.query("SELECT id FROM tmp"))
+            .and_then(|result| result.collect_and_drop::<u32>())
+            .and_then(|(conn, ids)| {
+                assert_eq!(ids.len(), 2);
+                conn.set_sql_select_limit(None)
+            })
+            .and_then(|conn| conn.query("SELECT id FROM tmp"))
+            .and_then(|result| result.collect_and_drop::<u32>())
+            .and_then(|(conn, ids)| {
+                assert_eq!(ids.len(), 3);
+                assert_eq!(conn.sql_select_limit(), None);
+                conn.reset()
+            })
+            .and_then(|conn| conn.first::<_, (u64,)>("SELECT @@sql_select_limit"))
+            .and_then(|(conn, row)| {
+                assert_eq!(row, Some((2,)));
+                assert_eq!(conn.sql_select_limit(), Some(2));
+                conn.disconnect()
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_execute_statement_with_borrowed_params() {
+        use crate::Value;
+
+        let blob = Value::Bytes(vec![b'a'; 100_000]);
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| conn.prepare("SELECT ?, ?, ?"))
+            .and_then(move |stmt| {
+                stmt.execute_ref(&[&Value::Int(-1), &Value::NULL, &blob])
+                    .and_then(|result| result.collect_and_drop::<(i64, Option<i64>, Vec<u8>)>())
+                    .map(|(stmt, rows)| {
+                        assert_eq!(rows, vec![(-1, None, vec![b'a'; 100_000])]);
+                        stmt
+                    })
+            })
+            .and_then(|stmt| {
+                stmt.execute_ref(&[]).then(|result| match result {
+                    Err(crate::error::Error::Driver(
+                        crate::error::DriverError::StmtParamsMismatch {
+                            required: 3,
+                            supplied: 0,
+                        },
+                    )) => Ok(()),
+                    _ => panic!("expected StmtParamsMismatch"),
+                })
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_share_columns_of_repeated_queries() {
+        const QUERY: &str = "SELECT 1 AS a, 'x' AS b";
+
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| conn.query(QUERY))
+            .and_then(|result| {
+                let columns = result.columns().unwrap();
+                result.drop_result().map(|conn| (conn, columns))
+            })
+            .and_then(|(conn, columns)| {
+                conn.query(QUERY).and_then(move |result| {
+                    let same = result.columns().unwrap();
+                    assert!(std::sync::Arc::ptr_eq(&columns, &same));
+                    assert_eq!(same[1].name_str(), "b");
+                    result.drop_result()
+                })
+            })
+            .and_then(|conn| conn.disconnect());
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_reprepare_statement_after_session_reset() {
+        let mut opts = OptsBuilder::from_opts(get_opts());
+        opts.stmt_cache_size(0);
+        let fut = Conn::new(get_opts())
+            .join(Conn::new(opts))
+            .and_then(|(conn, uncached_conn)| {
+                conn.prepare("SELECT ?")
+                    .join(uncached_conn.prepare("SELECT ?"))
+            })
+            .and_then(|(stmt, uncached_stmt)| {
+                stmt.reset_connection()
+                    .join(uncached_stmt.reset_connection())
+            })
+            .and_then(|(stmt, uncached_stmt)| {
+                stmt.first::<_, (u8,)>((42,))
+                    .join(uncached_stmt.first::<_, (u8,)>((43,)))
+            })
+            .and_then(|((stmt, row), (uncached_stmt, uncached_row))| {
+                assert_eq!(row, Some((42,)));
+                assert_eq!(uncached_row, Some((43,)));
+                stmt.close().join(uncached_stmt.close())
+            })
+            .and_then(|(conn, uncached_conn)| conn.disconnect().join(uncached_conn.disconnect()));
+
+        run(fut).unwrap();
+    }
+
     #[test]
     fn should_not_cache_statements_if_stmt_cache_size_is_zero() {
         let mut opts = OptsBuilder::from_opts(get_opts());
@@ -706,48 +3396,331 @@ mod test {
     }
 
     #[test]
-    fn should_perform_queries() {
-        let long_string = ::std::iter::repeat('A')
-            .take(18 * 1024 * 1024)
-            .collect::<String>();
-        let long_string_clone = long_string.clone();
+    fn should_perform_queries() {
+        let long_string = ::std::iter::repeat('A')
+            .take(18 * 1024 * 1024)
+            .collect::<String>();
+        let long_string_clone = long_string.clone();
+        let fut = Conn::new(get_opts())
+            .and_then(move |conn| {
+                Queryable::query(
+                    conn,
+                    format!(
+                        r"SELECT 'hello', 123
+                        UNION ALL
+                        SELECT '{}', 231",
+                        long_string_clone
+                    ),
+                )
+            })
+            .and_then(move |result| {
+                result.reduce_and_drop(vec![], move |mut acc, row| {
+                    acc.push(from_row(row));
+                    acc
+                })
+            })
+            .and_then(move |(conn, out)| Queryable::disconnect(conn).map(|_| out))
+            .map(move |result| {
+                assert_eq!((String::from("hello"), 123), result[0]);
+                assert_eq!((long_string, 231), result[1]);
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_pipeline_queries() {
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| {
+                conn.pipeline::<_, (u8,)>(vec![
+                    "SET @a = 42",
+                    "SELECT @a",
+                    "SELECT 1 UNION ALL SELECT 2",
+                ])
+            })
+            .and_then(|(conn, results)| {
+                assert_eq!(results, vec![vec![], vec![(42,)], vec![(1,), (2,)]]);
+                conn.pipeline::<_, (u8,)>(vec!["DO 1", "SELECT 1 FROM unknown_table", "DO 2"])
+            })
+            .then(|result| match result {
+                Ok(_) => panic!("should fail"),
+                Err(crate::error::Error::Server(ref err)) if err.code == 1146 => Ok(()),
+                Err(err) => Err(err),
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_enforce_max_allowed_packet() {
+        let mut opts = get_opts();
+        opts.max_allowed_packet(Some(4096));
+        let fut = Conn::new(opts)
+            .and_then(|conn| {
+                assert_eq!(conn.inner.max_allowed_packet, 4096);
+                conn.drop_query(format!("SELECT '{}'", "A".repeat(4096)))
+            })
+            .then(|result| match result {
+                Ok(_) => panic!("should fail"),
+                Err(crate::error::Error::Driver(crate::error::DriverError::PacketTooLarge {
+                    len,
+                    max_allowed_packet,
+                })) => {
+                    assert_eq!(len, 4096 + 10);
+                    assert_eq!(max_allowed_packet, 4096);
+                    Ok(())
+                }
+                Err(err) => Err(err),
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_exec_idempotent() {
+        let mut opts = get_opts();
+        // name is quoted, so it may contain characters that aren't allowed in bare identifiers
+        opts.idempotency_table("mysql_async_test_idempotency-keys");
+        let fut = Conn::new(opts)
+            .and_then(|conn| conn.drop_query("CREATE TEMPORARY TABLE tmp (id INT)"))
+            .and_then(|conn| conn.exec_idempotent("foo", "INSERT INTO tmp VALUES (?)", (1,)))
+            .and_then(|(conn, performed)| {
+                assert!(performed);
+                conn.exec_idempotent("foo", "INSERT INTO tmp VALUES (?)", (1,))
+            })
+            .and_then(|(conn, performed)| {
+                assert!(!performed);
+                conn.first::<_, (u8,)>("SELECT COUNT(*) FROM tmp")
+            })
+            .and_then(|(conn, count)| {
+                assert_eq!(count, Some((1,)));
+                conn.drop_query("DROP TABLE `mysql_async_test_idempotency-keys`")
+            })
+            .and_then(|conn| conn.disconnect());
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_record_recent_commands() {
+        let mut opts = get_opts();
+        opts.command_journal_size(2);
+        let fut = Conn::new(opts)
+            .and_then(|conn| conn.drop_query("DO 1"))
+            .and_then(|conn| conn.drop_query("DO 2"))
+            .and_then(|conn| {
+                let recent_commands = conn.recent_commands();
+                assert_eq!(recent_commands.len(), 2);
+                for record in recent_commands {
+                    assert_eq!(record.command(), consts::Command::COM_QUERY);
+                    assert_eq!(record.outcome(), CommandOutcome::Ok);
+                    assert!(record.digest().is_some() && record.duration().is_some());
+                }
+                conn.drop_query("SELECT * FROM unknown_table")
+            })
+            .then(|result| match result {
+                Ok(_) => panic!("should fail"),
+                Err(crate::error::Error::Server(ref err)) if err.code == 1146 => {
+                    let outcomes = err
+                        .recent_commands()
+                        .iter()
+                        .map(|record| record.outcome())
+                        .collect::<Vec<_>>();
+                    assert_eq!(
+                        outcomes,
+                        vec![CommandOutcome::Ok, CommandOutcome::Error(1146)]
+                    );
+                    Ok(())
+                }
+                Err(err) => Err(err),
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_return_named_futures() {
+        use crate::futures::{DropQuery, Ping, PrepExec, Query};
+
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| -> Ping<Conn> { conn.ping() })
+            .and_then(|conn| -> DropQuery<Conn> { conn.drop_query("SELECT 1 UNION SELECT 2") })
+            .and_then(|conn| -> Query<Conn> { conn.query("DO 1") })
+            .and_then(|result| {
+                assert!(result.is_empty());
+                result.drop_result()
+            })
+            .and_then(|conn| -> PrepExec<Conn> { conn.prep_exec("SELECT ?", (1,)) })
+            .and_then(|result| result.collect_and_drop::<(u8,)>())
+            .and_then(|(conn, rows)| {
+                assert_eq!(rows, vec![(1,)]);
+                conn.disconnect()
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_run_raw_commands() {
+        use crate::{consts::Command, MultiStatements};
+
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| conn.statistics())
+            .and_then(|(conn, stats)| {
+                assert!(stats.threads > 0);
+                assert!(stats.questions > 0);
+                conn.set_option(MultiStatements::Off)
+            })
+            .and_then(|conn| {
+                conn.drop_query("SELECT 1; SELECT 2")
+                    .then(|result| match result {
+                        Ok(_) => panic!("multiple statements should be disabled"),
+                        Err(Error::Server(_)) => Ok(()),
+                        Err(err) => Err(err),
+                    })
+            })
+            .and_then(|_| Conn::new(get_opts()))
+            .and_then(|conn| conn.set_option(MultiStatements::Off))
+            .and_then(|conn| conn.set_option(MultiStatements::On))
+            .and_then(|conn| conn.drop_query("SELECT 1; SELECT 2"))
+            .and_then(|conn| conn.write_raw_command(Command::COM_PING, &[]))
+            .and_then(|(conn, payload)| {
+                assert_eq!(payload[0], 0x00);
+                conn.disconnect()
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_fetch_warnings() {
+        let mut opts = get_opts();
+        opts.fetch_warnings(true);
+
+        let fut = Conn::new(opts)
+            .and_then(|conn| conn.prep_exec("SELECT CAST(? AS SIGNED)", ("abc",)))
+            .and_then(|result| result.collect_and_drop::<(i64,)>())
+            .and_then(|(conn, rows)| {
+                assert_eq!(rows, vec![(0,)]);
+                let warnings = conn.last_warnings();
+                assert_eq!(warnings.len(), 1);
+                assert_eq!(warnings[0].level, "Warning");
+                assert_eq!(warnings[0].code, 1292);
+                assert!(warnings[0].message.contains("abc"));
+                conn.drop_query("DO 1")
+            })
+            .and_then(|conn| {
+                assert!(conn.last_warnings().is_empty());
+                conn.drop_query("DO CAST('abc' AS SIGNED)")
+            })
+            .and_then(|conn| {
+                // `DO` returns an OK packet, i.e. the result isn't read by `drop_result`
+                assert_eq!(conn.last_warnings().len(), 1);
+                conn.disconnect()
+            })
+            .and_then(|_| Conn::new(get_opts()))
+            .and_then(|conn| conn.drop_query("DO CAST('abc' AS SIGNED)"))
+            .and_then(|conn| {
+                assert!(conn.last_warnings().is_empty());
+                conn.disconnect()
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_drop_query() {
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| {
+                conn.drop_query("CREATE TEMPORARY TABLE tmp (id int DEFAULT 10, name text)")
+            })
+            .and_then(|conn| Queryable::drop_query(conn, "INSERT INTO tmp VALUES (1, 'foo')"))
+            .and_then(|conn| Queryable::first::<_, (u8,)>(conn, "SELECT COUNT(*) FROM tmp"))
+            .and_then(|(conn, row)| conn.disconnect().map(move |_| row))
+            .map(|result| assert_eq!(result, Some((1,))));
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_apply_decode_overrides() {
+        let overrides = DecodeOverrides::new()
+            .column("name", ColumnDecode::StringLossy)
+            .index(2, ColumnDecode::Json);
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| {
+                Queryable::prep_exec(conn, "SELECT 42, X'61FF62' AS name, '[1, 2]'", ())
+            })
+            .and_then(move |result| {
+                result
+                    .decode_overrides(overrides.index(0, ColumnDecode::Bytes))
+                    .collect_and_drop::<(Vec<u8>, String, String)>()
+            })
+            .and_then(|(conn, rows)| {
+                assert_eq!(
+                    rows,
+                    vec![(b"42".to_vec(), "a\u{FFFD}b".into(), "[1,2]".into())]
+                );
+                Queryable::query(conn, "SELECT 'not a json'")
+            })
+            .and_then(|result| {
+                result
+                    .decode_overrides(DecodeOverrides::new().index(0, ColumnDecode::Json))
+                    .collect_and_drop::<(String,)>()
+            })
+            .then(|result| match result {
+                Ok(_) => panic!("should fail"),
+                Err(crate::error::Error::Driver(crate::error::DriverError::ColumnDecode {
+                    ..
+                })) => Ok(()),
+                Err(err) => Err(err),
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_apply_decode_overrides_to_subsequent_result_sets() {
+        let overrides = DecodeOverrides::new().index(0, ColumnDecode::StringLossy);
         let fut = Conn::new(get_opts())
-            .and_then(move |conn| {
-                Queryable::query(
-                    conn,
-                    format!(
-                        r"SELECT 'hello', 123
-                        UNION ALL
-                        SELECT '{}', 231",
-                        long_string_clone
-                    ),
-                )
+            .and_then(|conn| Queryable::query(conn, "DO 1; SELECT X'61FF62'; SELECT X'63FF64'"))
+            .and_then(move |result| result.decode_overrides(overrides).next_result_set())
+            .and_then(|(result, more)| {
+                assert!(more);
+                result.collect::<(String,)>()
             })
-            .and_then(move |result| {
-                result.reduce_and_drop(vec![], move |mut acc, row| {
-                    acc.push(from_row(row));
-                    acc
-                })
+            .and_then(|(result, rows)| {
+                assert_eq!(rows, vec![("a\u{FFFD}b".into(),)]);
+                result.collect_and_drop::<(String,)>()
             })
-            .and_then(move |(conn, out)| Queryable::disconnect(conn).map(|_| out))
-            .map(move |result| {
-                assert_eq!((String::from("hello"), 123), result[0]);
-                assert_eq!((long_string, 231), result[1]);
+            .and_then(|(conn, rows)| {
+                assert_eq!(rows, vec![("c\u{FFFD}d".into(),)]);
+                conn.disconnect()
             });
 
         run(fut).unwrap();
     }
 
     #[test]
-    fn should_drop_query() {
+    fn should_convert_bit_columns() {
+        use crate::Bit;
+
+        let overrides = DecodeOverrides::new().index(0, ColumnDecode::Bit);
         let fut = Conn::new(get_opts())
             .and_then(|conn| {
-                conn.drop_query("CREATE TEMPORARY TABLE tmp (id int DEFAULT 10, name text)")
+                conn.drop_query("CREATE TEMPORARY TABLE tmp (bits BIT(16), number INT)")
             })
-            .and_then(|conn| Queryable::drop_query(conn, "INSERT INTO tmp VALUES (1, 'foo')"))
-            .and_then(|conn| Queryable::first::<_, (u8,)>(conn, "SELECT COUNT(*) FROM tmp"))
-            .and_then(|(conn, row)| conn.disconnect().map(move |_| row))
-            .map(|result| assert_eq!(result, Some((1,))));
+            .and_then(|conn| conn.drop_query("INSERT INTO tmp VALUES (b'100000101', 5)"))
+            .and_then(|conn| conn.query("SELECT bits, number, bits FROM tmp"))
+            .and_then(move |result| {
+                result
+                    .decode_overrides(overrides)
+                    .collect_and_drop::<(Bit, Bit, Vec<u8>)>()
+            })
+            .and_then(|(conn, rows)| {
+                assert_eq!(rows, vec![(Bit(261), Bit(5), vec![0x01, 0x05])]);
+                conn.disconnect()
+            });
 
         run(fut).unwrap();
     }
@@ -900,6 +3873,32 @@ mod test {
         run(fut).unwrap();
     }
 
+    #[test]
+    fn should_compute_result_set_digest() {
+        const QUERY: &str = "SELECT 'foo', 1, NULL UNION ALL SELECT 'bar', 2, 3.5";
+
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| Queryable::query(conn, QUERY))
+            .and_then(|result| result.digest_and_drop())
+            .and_then(|(conn, digest_1)| {
+                Queryable::query(conn, QUERY)
+                    .and_then(|result| result.digest_and_drop())
+                    .map(move |(conn, digest_2)| (conn, digest_1, digest_2))
+            })
+            .and_then(|(conn, digest_1, digest_2)| {
+                assert_eq!(digest_1, digest_2);
+                Queryable::query(conn, "SELECT 'bar', 2, 3.5 UNION ALL SELECT 'foo', 1, NULL")
+                    .and_then(|result| result.digest_and_drop())
+                    .map(move |(conn, digest_3)| (conn, digest_1, digest_3))
+            })
+            .and_then(|(conn, digest_1, digest_3)| {
+                assert_ne!(digest_1, digest_3);
+                conn.disconnect()
+            });
+
+        run(fut).unwrap();
+    }
+
     #[test]
     fn should_iterate_over_resultset() {
         use std::sync::{
@@ -962,6 +3961,20 @@ mod test {
             .and_then(|conn| conn.disconnect());
 
         run(fut).unwrap();
+
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| Queryable::prepare(conn, r"SELECT ?, ? AS foo, 42"))
+            .and_then(|stmt| {
+                assert!(stmt.id() > 0);
+                assert_eq!(stmt.num_params(), 2);
+                assert_eq!(stmt.params_ref().len(), 2);
+                assert_eq!(stmt.num_columns(), 3);
+                assert_eq!(stmt.columns_ref()[1].name_str(), "foo");
+                stmt.close()
+            })
+            .and_then(|conn| conn.disconnect());
+
+        run(fut).unwrap();
     }
 
     #[test]
@@ -1037,6 +4050,143 @@ mod test {
         run(fut).unwrap();
     }
 
+    #[test]
+    fn should_assert_result_type() {
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| Queryable::prepare(conn, r"SELECT 1, 'foo', NOW()"))
+            .and_then(|stmt| {
+                stmt.assert_result_type::<(u8, String, String)>()
+                    .unwrap_err();
+                stmt.assert_result_type::<(u8, String)>().unwrap_err();
+                stmt.assert_result_type::<(u8, String, chrono::NaiveDateTime)>()?;
+                stmt.assert_result_type::<(u8, Option<String>, Option<chrono::NaiveDateTime>)>()?;
+                Ok(stmt)
+            })
+            .and_then(|stmt| stmt.close())
+            .and_then(|conn| Queryable::prepare(conn, r"SELECT CAST(42 AS CHAR)"))
+            .and_then(|stmt| {
+                // string column may hold a number
+                stmt.assert_result_type::<(i64,)>()?;
+                stmt.assert_result_type::<(f64,)>()?;
+                stmt.assert_result_type::<(String,)>()?;
+                Ok(stmt)
+            })
+            .and_then(|stmt| stmt.close())
+            .and_then(|conn| conn.disconnect());
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_refresh_statement_metadata() {
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| Queryable::drop_query(conn, "CREATE TEMPORARY TABLE tmp (a INT)"))
+            .and_then(|conn| Queryable::drop_query(conn, "INSERT INTO tmp VALUES (1)"))
+            .and_then(|conn| Queryable::drop_exec(conn, "SELECT * FROM tmp", ()))
+            .and_then(|conn| Queryable::drop_query(conn, "ALTER TABLE tmp ADD COLUMN b INT"))
+            .and_then(|conn| Queryable::prepare(conn, "SELECT * FROM tmp"))
+            .and_then(|stmt| stmt.execute(()))
+            .and_then(|result| result.collect_and_drop::<(u8, Option<u8>)>())
+            .and_then(|(stmt, rows)| {
+                assert_eq!(rows, vec![(1, None)]);
+                stmt.assert_result_type::<(u8, Option<u8>)>()?;
+                Ok(stmt)
+            })
+            .and_then(|stmt| stmt.close())
+            .and_then(|conn| conn.disconnect());
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_fetch_rows_using_cursor() {
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| {
+                Queryable::prepare(
+                    conn,
+                    r"SELECT 1 UNION ALL SELECT 2 UNION ALL SELECT 3 UNION ALL SELECT 4",
+                )
+            })
+            .and_then(|stmt| stmt.fetch_hint(Some(3)).execute(()))
+            .and_then(|result| result.collect_and_drop::<(u8,)>())
+            .and_then(|(stmt, rows)| {
+                assert_eq!(rows, vec![(1,), (2,), (3,), (4,)]);
+                stmt.fetch_hint(Some(1)).execute(())
+            })
+            // remaining rows should not be fetched
+            .and_then(|result| result.drop_result())
+            .and_then(|stmt| stmt.fetch_hint(None).first::<_, (u8,)>(()))
+            .and_then(|(stmt, row)| {
+                assert_eq!(row, Some((1,)));
+                stmt.close()
+            })
+            .and_then(|conn| conn.disconnect());
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_read_rows_if_cursor_is_not_opened() {
+        let fut = Conn::new(get_opts())
+            // cursors are opened only for `SELECT` statements
+            .and_then(|conn| Queryable::prepare(conn, r"SHOW VARIABLES LIKE ?"))
+            .and_then(|stmt| stmt.fetch_hint(Some(1)).execute(("max_allowed_packet",)))
+            .and_then(|result| result.collect_and_drop::<(String, u64)>())
+            .and_then(|(stmt, rows)| {
+                assert_eq!(rows.len(), 1);
+                assert_eq!(rows[0].0, "max_allowed_packet");
+                stmt.execute(("no_such_variable",))
+            })
+            .and_then(|result| result.collect_and_drop::<(String, String)>())
+            .and_then(|(stmt, rows)| {
+                assert!(rows.is_empty());
+                stmt.close()
+            })
+            .and_then(|conn| Queryable::prepare(conn, r"SELECT 1 FROM DUAL WHERE 1 = 0"))
+            .and_then(|stmt| stmt.fetch_hint(Some(1)).execute(()))
+            .and_then(|result| result.collect_and_drop::<(u8,)>())
+            .and_then(|(stmt, rows)| {
+                assert!(rows.is_empty());
+                stmt.first::<_, (u8,)>(())
+            })
+            .and_then(|(stmt, row)| {
+                assert_eq!(row, None);
+                stmt.close()
+            })
+            .and_then(|conn| conn.disconnect());
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_reset_statement() {
+        let long_string = ::std::iter::repeat('A')
+            .take(18 * 1024 * 1024)
+            .collect::<String>();
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| Queryable::prepare(conn, r"SELECT ?"))
+            .and_then(|stmt| stmt.reset())
+            .and_then({
+                let long_string = long_string.clone();
+                move |stmt| stmt.first::<_, (String,)>((long_string,))
+            })
+            .and_then({
+                let long_string = long_string.clone();
+                move |(stmt, row)| {
+                    assert_eq!(row, Some((long_string.clone(),)));
+                    stmt.first::<_, (String,)>((long_string,))
+                }
+            })
+            .and_then(move |(stmt, row)| {
+                assert_eq!(row, Some((long_string,)));
+                stmt.reset()
+            })
+            .and_then(|stmt| stmt.close())
+            .and_then(|conn| conn.disconnect());
+
+        run(fut).unwrap();
+    }
+
     #[test]
     fn should_prep_exec_statement() {
         let fut = Conn::new(get_opts())
@@ -1055,6 +4205,51 @@ mod test {
         run(fut).unwrap();
     }
 
+    #[test]
+    fn should_batch_exec_bulk() {
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| {
+                Queryable::drop_query(conn, "CREATE TEMPORARY TABLE tmp (id INT, name TEXT)")
+            })
+            .and_then(|conn| {
+                let params = (0..10).map(|id| params! { "id" => id, "name" => format!("{}", id) });
+                Queryable::batch_exec_bulk(
+                    conn,
+                    r"INSERT INTO tmp (id, name) VALUES (:id, :name)",
+                    params.collect::<Vec<_>>(),
+                    3,
+                )
+            })
+            .and_then(|conn| {
+                Queryable::first::<_, (u32, u32)>(conn, "SELECT COUNT(*), SUM(id) FROM tmp")
+            })
+            .and_then(|(conn, row)| {
+                assert_eq!(row, Some((10, 45)));
+                let rows: Vec<Vec<crate::Value>> = vec![
+                    vec![10.into(), "10".into()],
+                    vec![11.into()],
+                    vec![12.into(), "12".into()],
+                ];
+                Queryable::batch_exec_bulk(
+                    conn,
+                    "INSERT INTO tmp (id, name) VALUES (?, ?)",
+                    rows,
+                    3,
+                )
+            })
+            .then(|result| match result {
+                Err(crate::error::Error::Driver(
+                    crate::error::DriverError::StmtParamsMismatch {
+                        required: 2,
+                        supplied: 1,
+                    },
+                )) => Ok::<_, crate::error::Error>(()),
+                _ => panic!("expected StmtParamsMismatch"),
+            });
+
+        run(fut).unwrap();
+    }
+
     #[test]
     fn should_first_exec_statement() {
         let fut = Conn::new(get_opts())
@@ -1114,6 +4309,57 @@ mod test {
         run(fut).unwrap();
     }
 
+    #[test]
+    fn should_commit_pipelined() {
+        let fut = Conn::new(get_opts())
+            .and_then(|conn| conn.drop_query("CREATE TEMPORARY TABLE tmp (id INT)"))
+            .and_then(|conn| conn.start_transaction(Default::default()))
+            .and_then(|transaction| transaction.drop_query("INSERT INTO tmp VALUES (1)"))
+            .and_then(|transaction| {
+                transaction.commit_pipelined::<_, (u8,)>(
+                    Default::default(),
+                    vec!["INSERT INTO tmp VALUES (2)", "SELECT COUNT(*) FROM tmp"],
+                )
+            })
+            .and_then(|(transaction, results)| {
+                assert_eq!(results, vec![vec![], vec![(2,)]]);
+                transaction.rollback()
+            })
+            .and_then(|conn| conn.first::<_, (u8,)>("SELECT COUNT(*) FROM tmp"))
+            .and_then(|(conn, count)| {
+                assert_eq!(count, Some((1,)));
+                conn.disconnect()
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_run_read_only_transactions() {
+        let fut = Conn::new(get_opts()).and_then(|conn| {
+            if !server_version_at_least(&conn, (5, 6, 5)) {
+                // `SET TRANSACTION READ ONLY` is not supported
+                return A(conn.disconnect());
+            }
+            let mut options = TransactionOptions::new();
+            options.set_readonly(true);
+            let fut = conn
+                .start_transaction(options)
+                .and_then(|transaction| {
+                    transaction
+                        .drop_query("UPDATE time_zone SET Time_zone_id = 1 WHERE Time_zone_id = 1")
+                })
+                .then(|result| match result {
+                    Err(crate::error::Error::Server(ref err)) if err.code == 1792 => Ok(()),
+                    Err(err) => Err(err),
+                    Ok(_) => panic!("write in read-only transaction should fail"),
+                });
+            B(fut)
+        });
+
+        run(fut).unwrap();
+    }
+
     #[test]
     fn should_handle_local_infile() {
         use std::io::Write;
@@ -1162,6 +4408,112 @@ mod test {
         run(fut).unwrap();
     }
 
+    #[test]
+    fn should_handle_local_infile_from_stream() {
+        use bytes::Bytes;
+
+        use crate::{InfileStream, StreamLocalInfileHandler};
+
+        let mut opts = OptsBuilder::from_opts(get_opts());
+        opts.local_infile_handler(Some(StreamLocalInfileHandler::new(|file_name: &[u8]| {
+            assert_eq!(file_name, b"stream");
+            let chunks = vec![Bytes::from("AAA"), Bytes::from("AAA\nBBBBBB\n")];
+            Box::new(futures::stream::iter_ok(chunks)) as InfileStream
+        })));
+
+        let fut = Conn::new(opts)
+            .and_then(|conn| conn.drop_query("CREATE TEMPORARY TABLE tmp (a TEXT);"))
+            .and_then(|conn| conn.drop_query("LOAD DATA LOCAL INFILE 'stream' INTO TABLE tmp;"))
+            .and_then(|conn| conn.prep_exec("SELECT * FROM tmp;", ()))
+            .and_then(|result| result.map_and_drop(|row| from_row::<(String,)>(row).0))
+            .and_then(|(conn, result)| {
+                assert_eq!(result, vec!["AAAAAA".to_string(), "BBBBBB".to_string()]);
+                conn.disconnect()
+            })
+            .then(|result| match result {
+                Err(crate::error::Error::Server(ref err)) if err.code == 1148 => Ok(()),
+                result => result,
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_handle_local_infile_larger_than_a_packet() {
+        use bytes::Bytes;
+
+        use crate::{InfileStream, StreamLocalInfileHandler};
+
+        // a single 1MiB chunk of the stream is split into packets that the server accepts
+        let contents = (0..1024).fold(String::new(), |mut contents, i| {
+            contents.push_str(&format!("{:01023}\n", i));
+            contents
+        });
+        let mut opts = OptsBuilder::from_opts(get_opts());
+        opts.local_infile_handler(Some(StreamLocalInfileHandler::new(move |_: &[u8]| {
+            let chunks = vec![Bytes::from(contents.clone())];
+            Box::new(futures::stream::iter_ok(chunks)) as InfileStream
+        })));
+
+        let fut = Conn::new(opts)
+            .and_then(|conn| conn.drop_query("CREATE TEMPORARY TABLE tmp (a TEXT);"))
+            .and_then(|conn| conn.drop_query("LOAD DATA LOCAL INFILE 'large' INTO TABLE tmp;"))
+            .and_then(|conn| conn.first::<_, (u32, u32)>("SELECT COUNT(*), SUM(a) FROM tmp"))
+            .and_then(|(conn, row)| {
+                assert_eq!(row, Some((1024, 523_776)));
+                conn.disconnect()
+            })
+            .then(|result| match result {
+                Err(crate::error::Error::Server(ref err)) if err.code == 1148 => Ok(()),
+                result => result,
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn should_handle_local_infile_with_mmap() {
+        use crate::MmapLocalInfileHandler;
+
+        // 200 rows of 1000 bytes span several packets
+        let contents = (0..200).fold(String::new(), |mut contents, i| {
+            contents.push_str(&format!("{:0999}\n", i));
+            contents
+        });
+        ::std::fs::write("local_infile_mmap.txt", &contents).unwrap();
+
+        let mut opts = OptsBuilder::from_opts(get_opts());
+        opts.local_infile_handler(Some(MmapLocalInfileHandler::new(
+            &["local_infile_mmap.txt"][..],
+        )));
+
+        let fut = Conn::new(opts)
+            .and_then(|conn| conn.drop_query("CREATE TEMPORARY TABLE tmp (a TEXT);"))
+            .and_then(|conn| {
+                conn.drop_query("LOAD DATA LOCAL INFILE 'local_infile_mmap.txt' INTO TABLE tmp;")
+            })
+            .and_then(|conn| conn.first::<_, (u32, u32)>("SELECT COUNT(*), SUM(a) FROM tmp"))
+            .and_then(|(conn, row)| {
+                assert_eq!(row, Some((200, 19900)));
+                conn.drop_query("LOAD DATA LOCAL INFILE 'not_listed.txt' INTO TABLE tmp;")
+            })
+            .then(|result| {
+                let _ = ::std::fs::remove_file("local_infile_mmap.txt");
+                match result {
+                    Err(crate::error::Error::Server(ref err)) if err.code == 1148 => Ok(()),
+                    Err(crate::error::Error::Other(ref err)) => {
+                        assert!(err.to_string().contains("not in white list"));
+                        Ok(())
+                    }
+                    Err(err) => Err(err),
+                    Ok(_) => panic!("file that isn't white-listed was loaded"),
+                }
+            });
+
+        run(fut).unwrap();
+    }
+
     #[cfg(feature = "nightly")]
     mod bench {
         use futures::Future;
@@ -1205,6 +4557,31 @@ mod test {
             runtime.shutdown_on_idle().wait().unwrap();
         }
 
+        #[bench]
+        fn collect_large_blobs(bencher: &mut test::Bencher) {
+            let mut runtime = tokio::runtime::Runtime::new().unwrap();
+            let mut conn_opt = Some(runtime.block_on(Conn::new(get_opts())).unwrap());
+
+            bencher.iter(|| {
+                let conn = conn_opt.take().unwrap();
+                let fut = conn
+                    .query(
+                        "SELECT REPEAT('A', 65536) FROM \
+                         (SELECT 1 UNION ALL SELECT 2 UNION ALL SELECT 3 UNION ALL SELECT 4) a, \
+                         (SELECT 1 UNION ALL SELECT 2 UNION ALL SELECT 3 UNION ALL SELECT 4) b",
+                    )
+                    .and_then(|result| result.collect_and_drop::<(Vec<u8>,)>());
+                let (conn, rows) = runtime.block_on(fut).unwrap();
+                assert_eq!(rows.len(), 16);
+                conn_opt = Some(conn);
+            });
+
+            runtime
+                .block_on(conn_opt.take().unwrap().disconnect())
+                .unwrap();
+            runtime.shutdown_on_idle().wait().unwrap();
+        }
+
         #[bench]
         fn prepared_exec(bencher: &mut test::Bencher) {
             let mut runtime = tokio::runtime::Runtime::new().unwrap();
@@ -1259,5 +4636,53 @@ mod test {
                 .unwrap();
             runtime.shutdown_on_idle().wait().unwrap();
         }
+
+        /// Loads a 16MiB file using `handler` (the file is named after the handler).
+        fn load_data<H>(bencher: &mut test::Bencher, file_name: &'static str, handler: H)
+        where
+            H: crate::local_infile_handler::LocalInfileHandler + 'static,
+        {
+            let line = format!("{}\n", "A".repeat(1023));
+            ::std::fs::write(file_name, line.repeat(16 * 1024)).unwrap();
+
+            let mut opts = crate::OptsBuilder::from_opts(get_opts());
+            opts.local_infile_handler(Some(handler));
+            let mut runtime = tokio::runtime::Runtime::new().unwrap();
+            let mut conn_opt = Some(
+                runtime
+                    .block_on(Conn::new(opts).and_then(|conn| {
+                        conn.drop_query("CREATE TEMPORARY TABLE tmp (a TEXT) ENGINE=BLACKHOLE")
+                    }))
+                    .unwrap(),
+            );
+
+            let query = format!("LOAD DATA LOCAL INFILE '{}' INTO TABLE tmp", file_name);
+            bencher.bytes = 16 * 1024 * 1024;
+            bencher.iter(|| {
+                let conn = conn_opt.take().unwrap();
+                conn_opt = Some(runtime.block_on(conn.drop_query(&*query)).unwrap());
+            });
+
+            runtime
+                .block_on(conn_opt.take().unwrap().disconnect())
+                .unwrap();
+            runtime.shutdown_on_idle().wait().unwrap();
+            let _ = ::std::fs::remove_file(file_name);
+        }
+
+        #[bench]
+        fn load_data_buffered(bencher: &mut test::Bencher) {
+            let file_name = "bench_infile_buffered.txt";
+            let handler = crate::WhiteListFsLocalInfileHandler::new(&[file_name][..]);
+            load_data(bencher, file_name, handler);
+        }
+
+        #[cfg(feature = "mmap")]
+        #[bench]
+        fn load_data_mmap(bencher: &mut test::Bencher) {
+            let file_name = "bench_infile_mmap.txt";
+            let handler = crate::MmapLocalInfileHandler::new(&[file_name][..]);
+            load_data(bencher, file_name, handler);
+        }
     }
 }
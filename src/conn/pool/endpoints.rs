@@ -0,0 +1,110 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// Number of connections of a pool per endpoint (host and port).
+#[derive(Debug, Default)]
+pub struct EndpointCounts {
+    counts: Mutex<HashMap<(String, u16), usize>>,
+}
+
+impl EndpointCounts {
+    /// Returns `(host, port, connection count)` for each of `hosts` (in the same order).
+    pub fn snapshot(&self, hosts: &[(String, u16)]) -> Vec<(String, u16, usize)> {
+        let counts = self.counts.lock().unwrap();
+        hosts
+            .iter()
+            .map(|endpoint| {
+                let count = counts.get(endpoint).cloned().unwrap_or(0);
+                (endpoint.0.clone(), endpoint.1, count)
+            })
+            .collect()
+    }
+}
+
+/// Connection's share of `EndpointCounts` (released when the connection is dropped).
+#[derive(Debug)]
+pub struct EndpointGuard {
+    counts: Arc<EndpointCounts>,
+    endpoint: (String, u16),
+}
+
+impl EndpointGuard {
+    pub fn new(counts: Arc<EndpointCounts>, endpoint: (String, u16)) -> EndpointGuard {
+        *counts
+            .counts
+            .lock()
+            .unwrap()
+            .entry(endpoint.clone())
+            .or_insert(0) += 1;
+        EndpointGuard { counts, endpoint }
+    }
+
+    pub fn endpoint(&self) -> &(String, u16) {
+        &self.endpoint
+    }
+
+    /// Moves this share to another `endpoint`.
+    pub fn rebind(self, endpoint: (String, u16)) -> EndpointGuard {
+        if self.endpoint == endpoint {
+            self
+        } else {
+            EndpointGuard::new(self.counts.clone(), endpoint)
+        }
+    }
+}
+
+impl Drop for EndpointGuard {
+    fn drop(&mut self) {
+        let mut counts = self.counts.counts.lock().unwrap();
+        if let Some(count) = counts.get_mut(&self.endpoint) {
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(&self.endpoint);
+            }
+        }
+    }
+}
+
+/// Returns `true` if connection counts differ by more than `threshold`.
+pub fn is_imbalanced(counts: &[(String, u16, usize)], threshold: usize) -> bool {
+    let max = counts.iter().map(|x| x.2).max().unwrap_or(0);
+    let min = counts.iter().map(|x| x.2).min().unwrap_or(0);
+    max - min > threshold
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::{is_imbalanced, EndpointCounts, EndpointGuard};
+
+    #[test]
+    fn should_count_connections_per_endpoint() {
+        let counts = Arc::new(EndpointCounts::default());
+        let hosts = vec![("a".to_string(), 3306), ("b".to_string(), 3306)];
+
+        let guards = (0..4)
+            .map(|_| EndpointGuard::new(counts.clone(), hosts[0].clone()))
+            .collect::<Vec<_>>();
+        let snapshot = counts.snapshot(&hosts);
+        assert_eq!(
+            snapshot,
+            vec![("a".to_string(), 3306, 4), ("b".to_string(), 3306, 0)]
+        );
+        assert!(is_imbalanced(&snapshot, 2));
+        assert!(!is_imbalanced(&snapshot, 4));
+
+        drop(guards);
+        assert!(!is_imbalanced(&counts.snapshot(&hosts), 0));
+    }
+}
@@ -32,15 +32,17 @@ impl Future for DisconnectPool {
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
         self.pool.handle_futures()?;
 
-        let (new_len, dropping_len, disconnecting_len) = self.pool.with_inner(|inner| {
-            (
-                inner.new.len(),
-                inner.dropping.len(),
-                inner.disconnecting.len(),
-            )
-        });
-
-        if (new_len, dropping_len, disconnecting_len) == (0, 0, 0) {
+        let (new_len, dropping_len, disconnecting_len, pinging_len) =
+            self.pool.with_inner(|inner| {
+                (
+                    inner.new.len(),
+                    inner.dropping.len(),
+                    inner.disconnecting.len(),
+                    inner.pinging.len(),
+                )
+            });
+
+        if (new_len, dropping_len, disconnecting_len, pinging_len) == (0, 0, 0, 0) {
             Ok(Ready(()))
         } else {
             Ok(NotReady)
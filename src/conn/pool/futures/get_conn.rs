@@ -6,20 +6,69 @@
 // option. All files in the project carrying such notice may not be copied,
 // modified, or distributed except according to those terms.
 
-use futures::{Future, Poll};
+use futures::{
+    Async::{NotReady, Ready},
+    Future, Poll,
+};
+use tokio::timer::Delay;
+
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
 
 use crate::{
     conn::{pool::Pool, Conn},
     error::*,
 };
 
+/// Reason why `GetConn` waits for a connection.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum WaitReason {
+    /// Pool is at `PoolConstraints::max` and every connection is checked out,
+    /// i.e. the pool is saturated.
+    PoolAtMax,
+    /// There is no idle connection and new connections are being established.
+    Connecting,
+    /// Connections are being cleaned up after use (e.g. unread result sets are dropped
+    /// or transactions are rolled back) or closed. Connections aren't given out meanwhile.
+    CleaningUp,
+}
+
+impl fmt::Display for WaitReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WaitReason::PoolAtMax => write!(f, "pool is at max connections"),
+            WaitReason::Connecting => write!(f, "new connections are being established"),
+            WaitReason::CleaningUp => write!(f, "connections are being cleaned up"),
+        }
+    }
+}
+
 /// This future will take connection from a pool and resolve to `Conn`.
 pub struct GetConn {
     pool: Pool,
+    /// Start and the last observed reason of a wait (`None` if it didn't wait yet).
+    wait: Option<(Instant, WaitReason)>,
+    /// See `OptsBuilder::pool_checkout_timeout`.
+    deadline: Option<Delay>,
 }
 
 pub fn new(pool: &Pool) -> GetConn {
-    GetConn { pool: pool.clone() }
+    GetConn {
+        pool: pool.clone(),
+        wait: None,
+        deadline: None,
+    }
+}
+
+impl GetConn {
+    /// Reports a finished wait (see `EventHandler::checkout_wait`).
+    fn report_wait(&self, reason: WaitReason, waited: Duration) {
+        if let Some(handler) = self.pool.opts.get_event_handler() {
+            handler.checkout_wait(reason, waited);
+        }
+    }
 }
 
 impl Future for GetConn {
@@ -27,6 +76,42 @@ impl Future for GetConn {
     type Error = Error;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        self.pool.poll()
+        match self.pool.poll()? {
+            Ready(conn) => {
+                if let Some((start, reason)) = self.wait {
+                    self.report_wait(reason, start.elapsed());
+                }
+                Ok(Ready(conn))
+            }
+            NotReady => {
+                let start = self
+                    .wait
+                    .map(|(start, _)| start)
+                    .unwrap_or_else(Instant::now);
+                let reason = self.pool.wait_reason();
+                self.wait = Some((start, reason));
+
+                if self.deadline.is_none() {
+                    if let Some(timeout) = self.pool.opts.get_pool_checkout_timeout() {
+                        self.deadline = Some(Delay::new(start + timeout));
+                    }
+                }
+                let timed_out = match self.deadline {
+                    Some(ref mut deadline) => match deadline.poll() {
+                        Ok(Ready(())) | Err(_) => true,
+                        Ok(NotReady) => false,
+                    },
+                    None => false,
+                };
+
+                if timed_out {
+                    let waited = start.elapsed();
+                    self.report_wait(reason, waited);
+                    Err(DriverError::PoolCheckoutTimeout { waited, reason }.into())
+                } else {
+                    Ok(NotReady)
+                }
+            }
+        }
     }
 }
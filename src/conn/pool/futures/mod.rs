@@ -8,7 +8,7 @@
 
 pub use self::{
     disconnect_pool::{new as new_disconnect_pool, DisconnectPool},
-    get_conn::{new as new_get_conn, GetConn},
+    get_conn::{new as new_get_conn, GetConn, WaitReason},
 };
 
 mod disconnect_pool;
@@ -0,0 +1,185 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use futures::future::{Either::*, Future};
+
+use crate::{
+    conn::Conn, connection_like::ConnectionLike, queryable::Queryable, MyFuture, QueryDigest,
+};
+
+/// Summary is truncated to this number of bytes.
+const MAX_SUMMARY_LEN: usize = 4096;
+
+/// Oldest open InnoDB transactions, i.e. likely holders of the locks a statement waited for.
+const OLDEST_TRANSACTIONS: &str = "SELECT CONCAT('thread ', trx_mysql_thread_id, ' (', \
+     trx_state, ' since ', trx_started, ', ', trx_rows_locked, ' rows locked): '), trx_query \
+     FROM information_schema.innodb_trx ORDER BY trx_started LIMIT 5";
+
+/// Returns future that fetches a summary of transactions involved in a lock conflict.
+///
+/// For a deadlock it is the latest detected deadlock reported by `SHOW ENGINE INNODB STATUS`
+/// (the deadlock is already resolved at this point). For a lock wait timeout the statement
+/// is no longer waiting, so the oldest open transactions are listed instead.
+///
+/// Statements of other sessions are normalized if `OptsBuilder::redact_queries` is enabled.
+pub(crate) fn fetch(conn: Conn, deadlock: bool) -> impl MyFuture<(Conn, Option<String>)> {
+    let redact = conn.get_opts().get_redact_queries();
+    if deadlock {
+        A(conn
+            .query("SHOW ENGINE INNODB STATUS")
+            .and_then(|result| result.collect_and_drop::<(String, String, String)>())
+            .map(move |(conn, rows)| {
+                let summary = rows
+                    .first()
+                    .and_then(|(_, _, status)| deadlock_summary(status, redact));
+                (conn, summary)
+            }))
+    } else {
+        B(conn
+            .query(OLDEST_TRANSACTIONS)
+            .and_then(|result| result.collect_and_drop::<(String, Option<String>)>())
+            .map(move |(conn, rows)| {
+                let summary = if rows.is_empty() {
+                    None
+                } else {
+                    let rows = rows
+                        .into_iter()
+                        .map(|(transaction, query)| match query {
+                            Some(query) => transaction + &*statement_text(query, redact),
+                            None => transaction + "<idle>",
+                        })
+                        .collect::<Vec<_>>();
+                    Some(truncate(rows.join("; ")))
+                };
+                (conn, summary)
+            }))
+    }
+}
+
+/// Returns statement text of another session (normalized if `redact`).
+fn statement_text(query: String, redact: bool) -> String {
+    if redact {
+        QueryDigest::new(query).into_text()
+    } else {
+        query
+    }
+}
+
+/// Extracts `LATEST DETECTED DEADLOCK` section of `SHOW ENGINE INNODB STATUS` output
+/// without record dumps.
+///
+/// Statements (that follow `MySQL thread id` lines) are normalized if `redact`.
+fn deadlock_summary(status: &str, redact: bool) -> Option<String> {
+    let start = status.find("LATEST DETECTED DEADLOCK\n")?;
+    let mut lines = Vec::new();
+    let mut statement: Option<Vec<&str>> = None;
+    let section = status[start..]
+        .lines()
+        .skip(1)
+        .skip_while(|line| line.starts_with("---"))
+        .take_while(|line| !line.starts_with("---"))
+        .filter(|line| !is_record_dump(line));
+    for line in section {
+        match statement {
+            Some(ref mut statement) if !is_lock_header(line) => {
+                statement.push(line);
+                continue;
+            }
+            _ => push_redacted(&mut lines, statement.take()),
+        }
+        if redact && line.starts_with("MySQL thread id ") {
+            statement = Some(Vec::new());
+        }
+        lines.push(line.to_string());
+    }
+    push_redacted(&mut lines, statement);
+    if lines.is_empty() {
+        None
+    } else {
+        Some(truncate(lines.join("\n")))
+    }
+}
+
+/// Pushes normalized text of a (multi-line) statement of a deadlocked transaction.
+fn push_redacted(lines: &mut Vec<String>, statement: Option<Vec<&str>>) {
+    match statement {
+        Some(ref statement) if !statement.is_empty() => {
+            lines.push(QueryDigest::new(statement.join("\n")).into_text());
+        }
+        _ => (),
+    }
+}
+
+/// Returns `true` for lines that follow a statement of a deadlocked transaction.
+fn is_lock_header(line: &str) -> bool {
+    line.starts_with("*** ") || line.starts_with("RECORD LOCKS ") || line.starts_with("TABLE LOCK ")
+}
+
+/// Returns `true` for lines like ` 0: len 4; hex 80000001; asc     ;;`.
+fn is_record_dump(line: &str) -> bool {
+    let line = line.trim_start();
+    let digits = line.bytes().take_while(u8::is_ascii_digit).count();
+    digits > 0 && line[digits..].starts_with(": len ")
+}
+
+fn truncate(mut summary: String) -> String {
+    if summary.len() > MAX_SUMMARY_LEN {
+        let mut len = MAX_SUMMARY_LEN;
+        while !summary.is_char_boundary(len) {
+            len -= 1;
+        }
+        summary.truncate(len);
+        summary.push_str("...");
+    }
+    summary
+}
+
+#[cfg(test)]
+mod test {
+    use super::deadlock_summary;
+
+    #[test]
+    fn should_extract_latest_deadlock() {
+        let status = "\n=====================================\n\
+             2020-02-29 13:14:15 INNODB MONITOR OUTPUT\n\
+             ------------------------\n\
+             LATEST DETECTED DEADLOCK\n\
+             ------------------------\n\
+             2020-02-29 13:14:10 0x7f\n\
+             *** (1) TRANSACTION:\n\
+             TRANSACTION 1234, ACTIVE 5 sec starting index read\n\
+             MySQL thread id 8, OS thread handle 1, query id 20 localhost root updating\n\
+             UPDATE t SET a = 1 WHERE id = 2\n\
+             RECORD LOCKS space id 2 page no 4 n bits 72 index PRIMARY of table `test`.`t`\n\
+             Record lock, heap no 3 PHYSICAL RECORD: n_fields 3; compact format; info bits 0\n \
+             0: len 4; hex 80000002; asc     ;;\n \
+             1: len 6; hex 0000000004d2; asc       ;;\n\
+             *** WE ROLL BACK TRANSACTION (1)\n\
+             ------------\n\
+             TRANSACTIONS\n\
+             ------------\n\
+             Trx id counter 1240\n";
+
+        let summary = deadlock_summary(status, false).unwrap();
+        assert!(summary.starts_with("2020-02-29 13:14:10 0x7f\n*** (1) TRANSACTION:"));
+        assert!(summary.contains("UPDATE t SET a = 1 WHERE id = 2"));
+        assert!(summary.ends_with("*** WE ROLL BACK TRANSACTION (1)"));
+        assert!(!summary.contains("hex"));
+        assert!(!summary.contains("Trx id counter"));
+
+        let redacted = deadlock_summary(status, true).unwrap();
+        assert!(redacted.contains("root updating\nUPDATE t SET a = ? WHERE id = ?\nRECORD LOCKS"));
+        assert!(!redacted.contains("id = 2"));
+        assert_eq!(redacted.lines().count(), summary.lines().count());
+
+        assert_eq!(
+            deadlock_summary("TRANSACTIONS\n------------\n", false),
+            None
+        );
+    }
+}
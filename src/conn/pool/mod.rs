@@ -7,21 +7,38 @@
 // modified, or distributed except according to those terms.
 
 use ::futures::{
+    future::{err, loop_fn, ok, poll_fn, Either::*, IntoFuture, Loop},
     task::{self, Task},
     Async::{self, NotReady, Ready},
     Future,
 };
+use tokio::{
+    executor::{DefaultExecutor, Executor},
+    timer::{Delay, Interval},
+};
 
 use std::{
-    fmt,
+    cmp, fmt, io, mem,
     str::FromStr,
-    sync::{Arc, Mutex, MutexGuard},
+    sync::{
+        atomic::{self, AtomicBool},
+        Arc, Mutex, MutexGuard,
+    },
+    time::{Duration, Instant},
 };
 
 use crate::{
-    conn::{pool::futures::*, Conn},
+    conn::{
+        pool::{
+            endpoints::{is_imbalanced, EndpointCounts, EndpointGuard},
+            futures::*,
+            reaper::IdleReaper,
+            retry::RetryOptions,
+        },
+        Conn,
+    },
     error::*,
-    opts::{Opts, PoolConstraints},
+    opts::{HostSelection, Opts, OptsBuilder, PoolConstraints},
     queryable::{
         transaction::{Transaction, TransactionOptions},
         Queryable,
@@ -29,17 +46,38 @@ use crate::{
     BoxFuture, MyFuture,
 };
 
+pub mod endpoints;
 pub mod futures;
+mod lock_diagnostics;
+mod reaper;
+pub mod replicated;
+pub mod retry;
+#[cfg(feature = "tower")]
+pub mod service;
 
 pub struct Inner {
     closed: bool,
+    /// Pool doesn't give out connections and waits for checked-out connections
+    /// (see `Pool::disconnect_graceful`).
+    draining: bool,
     new: Vec<BoxFuture<Conn>>,
     idle: Vec<Conn>,
     disconnecting: Vec<BoxFuture<()>>,
     dropping: Vec<BoxFuture<Conn>>,
     rollback: Vec<BoxFuture<Conn>>,
+    /// Idle connections being pinged (see `OptsBuilder::pool_idle_check_interval`
+    /// and `OptsBuilder::pool_keepalive`).
+    pinging: Vec<BoxFuture<Conn>>,
     ongoing: usize,
     tasks: Vec<Task>,
+    /// Administrative connection (see `Pool::with_admin_conn`).
+    admin: Option<Conn>,
+    reaper_started: bool,
+    /// Options of new connections set by `Pool::rotate` (pool options are used if `None`).
+    conn_opts: Option<Opts>,
+    /// Token held by connections established with the current connection options
+    /// (see `Pool::rotate`).
+    generation: Arc<()>,
 }
 
 impl Inner {
@@ -49,8 +87,14 @@ impl Inner {
             + self.disconnecting.len()
             + self.dropping.len()
             + self.rollback.len()
+            + self.pinging.len()
             + self.ongoing
     }
+
+    /// Returns `true` if connections are being closed or cleaned up after use.
+    fn cleaning_up(&self) -> bool {
+        !self.disconnecting.is_empty() || !self.dropping.is_empty() || !self.rollback.is_empty()
+    }
 }
 
 #[derive(Clone)]
@@ -58,23 +102,35 @@ impl Inner {
 pub struct Pool {
     opts: Opts,
     inner: Arc<Mutex<Inner>>,
+    endpoints: Arc<EndpointCounts>,
     pool_constraints: PoolConstraints,
+    /// Server rejected a write because of read-only mode (see `Pool::is_demoted`).
+    demoted: Arc<AtomicBool>,
 }
 
 impl fmt::Debug for Pool {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let (new_len, idle_len, disconnecing_len, dropping_len, rollback_len, ongoing, tasks_len) =
-            self.with_inner(|inner| {
-                (
-                    inner.new.len(),
-                    inner.idle.len(),
-                    inner.disconnecting.len(),
-                    inner.dropping.len(),
-                    inner.rollback.len(),
-                    inner.ongoing,
-                    inner.tasks.len(),
-                )
-            });
+        let (
+            new_len,
+            idle_len,
+            disconnecing_len,
+            dropping_len,
+            rollback_len,
+            pinging_len,
+            ongoing,
+            tasks_len,
+        ) = self.with_inner(|inner| {
+            (
+                inner.new.len(),
+                inner.idle.len(),
+                inner.disconnecting.len(),
+                inner.dropping.len(),
+                inner.rollback.len(),
+                inner.pinging.len(),
+                inner.ongoing,
+                inner.tasks.len(),
+            )
+        });
         f.debug_struct("Pool")
             .field("pool_constraints", &self.pool_constraints)
             .field("new connections count", &new_len)
@@ -82,6 +138,7 @@ impl fmt::Debug for Pool {
             .field("disconnecting connections count", &disconnecing_len)
             .field("dropping connections count", &dropping_len)
             .field("rollback connections count", &rollback_len)
+            .field("pinging connections count", &pinging_len)
             .field("ongoing connections count", &ongoing)
             .field("tasks count", &tasks_len)
             .finish()
@@ -97,15 +154,23 @@ impl Pool {
             opts,
             inner: Arc::new(Mutex::new(Inner {
                 closed: false,
+                draining: false,
                 new: Vec::with_capacity(pool_constraints.min()),
                 idle: Vec::new(),
                 disconnecting: Vec::new(),
                 dropping: Vec::new(),
                 rollback: Vec::new(),
+                pinging: Vec::new(),
                 ongoing: 0,
                 tasks: Vec::new(),
+                admin: None,
+                reaper_started: false,
+                conn_opts: None,
+                generation: Arc::new(()),
             })),
+            endpoints: Arc::new(EndpointCounts::default()),
             pool_constraints,
+            demoted: Arc::new(AtomicBool::new(false)),
         };
 
         pool
@@ -122,6 +187,110 @@ impl Pool {
         new_get_conn(self)
     }
 
+    /// Returns `(host, port, connection count)` for every host of this pool.
+    ///
+    /// Connections taken from the pool are counted until they are dropped or disconnected.
+    pub fn endpoint_conn_counts(&self) -> Vec<(String, u16, usize)> {
+        self.endpoints.snapshot(&self.opts.get_hosts())
+    }
+
+    /// Whether the server of this pool rejected a write because it is in read-only mode,
+    /// i.e. a connection of this pool failed with `DriverError::ReadOnlyViolation`
+    /// (e.g. the server was demoted to a replica by a failover).
+    ///
+    /// The flag stays set until `Pool::reset_demoted` is called. `ReplicatedPool` uses it
+    /// to route reads to a demoted primary.
+    pub fn is_demoted(&self) -> bool {
+        self.demoted.load(atomic::Ordering::Relaxed)
+    }
+
+    /// Clears the flag set by a read-only violation (see `Pool::is_demoted`).
+    pub fn reset_demoted(&self) {
+        self.demoted.store(false, atomic::Ordering::Relaxed);
+    }
+
+    pub(crate) fn mark_demoted(&self) {
+        // not under the lock, because connections of the pool are polled while it is held
+        self.demoted.store(true, atomic::Ordering::Relaxed);
+    }
+
+    /// Returns future that terminates a statement currently executing on the connection with
+    /// the given `conn_id` (see `Conn::kill`).
+    ///
+    /// Useful for cancellation, because the `Conn` executing the statement is busy.
+    ///
+    /// Statement is killed using the administrative connection (see `Pool::with_admin_conn`),
+    /// so it works even if the pool is saturated.
+    pub fn kill_query(&self, conn_id: u32) -> impl MyFuture<()> {
+        self.with_admin_conn(move |conn| conn.kill(conn_id).map(|conn| (conn, ())))
+    }
+
+    /// Returns future that resolves to `()` if the server responds to `COM_PING`
+    /// on the administrative connection (see `Pool::with_admin_conn`).
+    pub fn health_check(&self) -> impl MyFuture<()> {
+        self.with_admin_conn(|conn| conn.ping().map(|conn| (conn, ())))
+    }
+
+    /// Returns future that attaches a summary of the blocking transactions to `error`
+    /// if it is `ER_LOCK_DEADLOCK` or `ER_LOCK_WAIT_TIMEOUT` (see `ServerError::lock_diagnostics`).
+    ///
+    /// For a deadlock the latest detected deadlock is taken from `SHOW ENGINE INNODB STATUS`
+    /// (requires the `PROCESS` privilege), for a lock wait timeout the oldest open
+    /// transactions are listed. Queries are executed on the administrative connection
+    /// (see `Pool::with_admin_conn`). Other errors, as well as errors of the diagnostic queries,
+    /// are ignored, i.e. the original error is returned as is.
+    pub fn diagnose_lock_error(&self, mut error: Error) -> impl MyFuture<Error> {
+        let deadlock = error.is_deadlock();
+        if !deadlock && !error.is_lock_wait_timeout() {
+            return A(ok(error));
+        }
+        let fut = self
+            .with_admin_conn(move |conn| lock_diagnostics::fetch(conn, deadlock))
+            .then(move |result| {
+                if let (Ok(summary), Some(server_error)) = (result, error.server_error_mut()) {
+                    server_error.lock_diagnostics = summary;
+                }
+                Ok(error)
+            });
+        B(fut)
+    }
+
+    /// Runs `f` on the administrative connection of this pool and resolves to its result.
+    ///
+    /// Administrative connection is created lazily, isn't counted against pool constraints
+    /// and is never given out by `Pool::get_conn`, so it should be used for short diagnostic
+    /// queries (e.g. `KILL QUERY`, health probes or variable polling) that should work
+    /// even if the pool is saturated with user work. Concurrent calls use additional
+    /// temporary connections.
+    pub fn with_admin_conn<F, U, T>(&self, f: F) -> impl MyFuture<T>
+    where
+        F: FnOnce(Conn) -> U + Send + 'static,
+        U: IntoFuture<Item = (Conn, T), Error = Error>,
+        U::Future: Send + 'static,
+        T: Send + 'static,
+    {
+        let admin = self.with_inner(|mut inner| inner.admin.take());
+        let conn = match admin {
+            Some(conn) if !conn.expired() => A(ok(conn)),
+            _ => B(Conn::new(self.conn_opts())),
+        };
+        let pool = self.clone();
+        conn.and_then(f).and_then(move |(conn, value)| {
+            let conn = pool.with_inner(|mut inner| {
+                if inner.closed || inner.admin.is_some() {
+                    Some(conn)
+                } else {
+                    inner.admin = Some(conn);
+                    None
+                }
+            });
+            match conn {
+                Some(conn) => A(conn.disconnect().map(|_| value)),
+                None => B(ok(value)),
+            }
+        })
+    }
+
     /// Shortcut for `get_conn` followed by `start_transaction`.
     pub fn start_transaction(
         &self,
@@ -131,6 +300,135 @@ impl Pool {
             .and_then(|conn| Queryable::start_transaction(conn, options))
     }
 
+    /// Runs a transaction built by `f` and commits it. Resolves to the value returned by `f`.
+    ///
+    /// Whole transaction is retried (see `RetryOptions`) if it fails with `ER_LOCK_DEADLOCK`
+    /// or `ER_LOCK_WAIT_TIMEOUT`, so `f` will be called once per attempt. Connection of
+    /// a failed attempt is rolled back by the pool before it is reused.
+    ///
+    /// ```rust,ignore
+    /// pool.run_transaction(RetryOptions::new(), |transaction| {
+    ///     transaction
+    ///         .drop_exec("UPDATE accounts SET balance = balance - ? WHERE id = ?", (10, 1))
+    ///         .and_then(|t| t.drop_exec("UPDATE accounts SET balance = balance + ? WHERE id = ?", (10, 2)))
+    ///         .map(|t| (t, ()))
+    /// })
+    /// ```
+    pub fn run_transaction<F, U, T>(&self, options: RetryOptions, f: F) -> impl MyFuture<T>
+    where
+        F: FnMut(Transaction<Conn>) -> U + Send + 'static,
+        U: IntoFuture<Item = (Transaction<Conn>, T), Error = Error>,
+        U::Future: Send + 'static,
+        T: Send + 'static,
+    {
+        let pool = self.clone();
+        loop_fn((f, 1), move |(mut f, attempt)| {
+            let options = options.clone();
+            let diagnostics_pool = pool.clone();
+            pool.start_transaction(options.transaction_options().clone())
+                .then(move |result| match result {
+                    Ok(transaction) => {
+                        let fut = f(transaction)
+                            .into_future()
+                            .and_then(|(transaction, value)| {
+                                transaction.commit().map(move |_| value)
+                            })
+                            .then(move |result| Ok::<_, Error>((result, f)));
+                        A(fut)
+                    }
+                    Err(e) => B(ok((Err(e), f))),
+                })
+                .and_then(move |(result, f)| match result {
+                    Ok(value) => A(ok(Loop::Break(value))),
+                    Err(e) => {
+                        if options.should_retry(&e, attempt) {
+                            let delay = Delay::new(Instant::now() + options.delay(attempt))
+                                .then(move |_| Ok(Loop::Continue((f, attempt + 1))));
+                            B(A(delay))
+                        } else if options.lock_diagnostics() {
+                            let fut = diagnostics_pool.diagnose_lock_error(e).and_then(err);
+                            B(B(A(fut)))
+                        } else {
+                            B(B(B(err(e))))
+                        }
+                    }
+                })
+        })
+    }
+
+    /// Gradually replaces connections of this pool with connections established using
+    /// `new_opts` (e.g. to rotate credentials or to move the pool to another endpoint).
+    ///
+    /// New connections are established using `new_opts` right away, while idle connections
+    /// established with the previous options are replaced one by one, at most `rate` connections
+    /// per second, so the server doesn't see a burst of reconnects. Checked-out connections
+    /// are replaced once they are returned to the pool. Every replacement is reported
+    /// to `EventHandler::pool_rotation`.
+    ///
+    /// Future resolves once there are no connections established with the previous options.
+    /// Connections are only replaced while it is polled. Settings of the pool itself
+    /// (e.g. `PoolConstraints` or the event handler) are not changed.
+    ///
+    /// A connection is established using `new_opts` before any connection is replaced,
+    /// so the future fails and the pool is left intact if `new_opts` don't work.
+    pub fn rotate<O: Into<Opts>>(&self, new_opts: O, rate: u32) -> impl MyFuture<()> {
+        let new_opts = new_opts.into();
+        let pool = self.clone();
+        Conn::new(self.new_conn_opts(&new_opts)).and_then(move |conn| {
+            let (outdated, admin) = pool.with_inner(|mut inner| {
+                inner.conn_opts = Some(new_opts);
+                let outdated = mem::replace(&mut inner.generation, Arc::new(()));
+                // The checked connection is added to the pool as a new one.
+                let mut conn = conn;
+                conn.inner.pool_generation = Some(inner.generation.clone());
+                inner.new.push(Box::new(ok(conn)));
+                (outdated, inner.admin.take())
+            });
+            if let Some(conn) = admin {
+                pool.with_inner(move |mut inner| inner.disconnecting.push(conn.disconnect()));
+            }
+            pool.replace_outdated_conns(outdated, rate)
+        })
+    }
+
+    /// Replaces connections holding the `outdated` token at most `rate` connections per second
+    /// (see `Pool::rotate`).
+    fn replace_outdated_conns(&self, outdated: Arc<()>, rate: u32) -> impl MyFuture<()> {
+        let mut pool = self.clone();
+        // `Interval` doesn't accept zero period.
+        let period = cmp::max(
+            Duration::from_secs(1) / cmp::max(rate, 1),
+            Duration::from_millis(1),
+        );
+        let mut interval = Interval::new_interval(period);
+        let mut replaced = 0;
+        poll_fn(move || loop {
+            if pool.with_inner(|inner| inner.closed) {
+                return Err(DriverError::PoolDisconnected.into());
+            }
+            // Connection errors don't stop the rotation, because new options were checked
+            // and failed connections are re-established by the next replacements.
+            let _ = pool.handle_futures();
+            // Every connection established with the previous options holds a clone of the token.
+            if Arc::strong_count(&outdated) == 1 {
+                return Ok(Ready(()));
+            }
+            match interval.poll() {
+                Ok(Ready(Some(_))) => {
+                    if pool.replace_outdated(&outdated) {
+                        replaced += 1;
+                        if let Some(handler) = pool.opts.get_event_handler() {
+                            handler.pool_rotation(replaced, Arc::strong_count(&outdated) - 1);
+                        }
+                    }
+                }
+                Ok(Ready(None)) => return Ok(Ready(())),
+                Ok(NotReady) => return Ok(NotReady),
+                Err(e) => return Err(io::Error::new(io::ErrorKind::Other, e).into()),
+            }
+        })
+    }
+
     /// Returns future that disconnects this pool from server and resolves to `()`.
     ///
     /// Active connections taken from this pool should be disconnected manually.
@@ -145,6 +443,9 @@ impl Pool {
             }
         });
         if become_closed {
+            if let Some(conn) = self.with_inner(|mut inner| inner.admin.take()) {
+                self.with_inner(move |mut inner| inner.disconnecting.push(conn.disconnect()));
+            }
             while let Some(conn) = self.take_conn() {
                 self.with_inner(move |mut inner| inner.disconnecting.push(conn.disconnect()));
             }
@@ -152,29 +453,103 @@ impl Pool {
         new_disconnect_pool(self)
     }
 
+    /// Returns future that gracefully disconnects this pool from server and resolves to `()`.
+    ///
+    /// Pool immediately stops giving out connections (pending and new `GetConn`'s will resolve
+    /// to error), then it waits for checked-out connections to be returned (up to `timeout`)
+    /// closing them as they come back, and then it disconnects as `Pool::disconnect` does.
+    /// Connections returned after the `timeout` are dropped.
+    pub fn disconnect_graceful(self, timeout: Duration) -> impl MyFuture<()> {
+        let mut pool = self.clone();
+        let tasks = pool.with_inner(|mut inner| {
+            inner.draining = true;
+            mem::replace(&mut inner.tasks, Vec::new())
+        });
+        // Wake pending `GetConn`'s, so that they resolve to error.
+        for task in tasks {
+            task.notify();
+        }
+
+        let drained = poll_fn(move || {
+            pool.handle_futures()?;
+            Ok(pool.with_inner(|mut inner| {
+                if inner.ongoing == 0 {
+                    Ready(())
+                } else {
+                    inner.tasks.push(task::current());
+                    NotReady
+                }
+            }))
+        });
+        let deadline = Delay::new(Instant::now() + timeout);
+        drained.select2(deadline).then(move |_| self.disconnect())
+    }
+
+    /// Replaces an idle connection holding the `outdated` token (see `Pool::rotate`).
+    ///
+    /// Returns `true` if a connection was replaced.
+    fn replace_outdated(&self, outdated: &Arc<()>) -> bool {
+        let min = self.pool_constraints.min();
+        self.with_inner(|mut inner| {
+            if inner.closed || inner.draining {
+                return false;
+            }
+            let position = inner.idle.iter().position(|conn| {
+                conn.inner
+                    .pool_generation
+                    .as_ref()
+                    .map_or(false, |generation| Arc::ptr_eq(generation, outdated))
+            });
+            match position {
+                Some(i) => {
+                    let mut conn = inner.idle.swap_remove(i);
+                    conn.inner.pool_generation = None;
+                    inner.disconnecting.push(conn.disconnect());
+                    // Pool wouldn't keep more than `min` idle connections anyway.
+                    if inner.idle.len() < min {
+                        let new_conn = self.new_conn(&inner);
+                        inner.new.push(new_conn);
+                    }
+                    true
+                }
+                None => false,
+            }
+        })
+    }
+
     /// Returns true if futures is in queue.
     fn in_queue(&self) -> bool {
         self.with_inner(|inner| {
             let count = inner.new.len()
                 + inner.disconnecting.len()
                 + inner.dropping.len()
-                + inner.rollback.len();
+                + inner.rollback.len()
+                + inner.pinging.len();
             count > 0
         })
     }
 
+    /// Returns `true` if connections shouldn't be given out until queued futures are resolved.
+    ///
+    /// Pinged idle connections don't block checkouts, so other idle connections
+    /// are given out while pings are in flight.
+    fn checkout_blocked(&self) -> bool {
+        self.with_inner(|inner| !inner.new.is_empty() || inner.cleaning_up())
+    }
+
     /// A way to take connection from a pool.
     fn take_conn(&mut self) -> Option<Conn> {
-        if self.in_queue() {
+        if self.checkout_blocked() {
             // Do not return connection until queue is empty
             return None;
         }
         self.with_inner(|mut inner| {
             while let Some(mut conn) = inner.idle.pop() {
-                if conn.expired() {
+                if conn.expired() || conn.exceeds_pool_limits() {
                     inner.disconnecting.push(conn.disconnect());
                 } else {
                     conn.inner.pool = Some(self.clone());
+                    conn.inner.uses += 1;
                     inner.ongoing += 1;
                     return Some(conn);
                 }
@@ -183,6 +558,23 @@ impl Pool {
         })
     }
 
+    /// Returns the reason why a connection couldn't be taken from this pool.
+    ///
+    /// `Pool::poll` establishes new connections whenever there is no idle connection and the pool
+    /// isn't at max, so a waiting task either waits for them, for connections being cleaned up
+    /// (see `Pool::take_conn`) or for connections to be returned.
+    fn wait_reason(&self) -> WaitReason {
+        self.with_inner(|inner| {
+            if !inner.new.is_empty() {
+                WaitReason::Connecting
+            } else if inner.cleaning_up() {
+                WaitReason::CleaningUp
+            } else {
+                WaitReason::PoolAtMax
+            }
+        })
+    }
+
     /// A way to return connection taken from a pool.
     fn return_conn(&mut self, conn: Conn) {
         let min = self.pool_constraints.min();
@@ -192,12 +584,38 @@ impl Pool {
                 return;
             }
 
-            if conn.inner.has_result.is_some() {
+            if conn.inner.pending_responses > 0 {
+                // Pipeline was interrupted, so responses to the remaining queries are still
+                // on their way and the connection will be closed.
+                inner.ongoing -= 1;
+                inner.disconnecting.push(conn.disconnect());
+            } else if conn.has_unconsumed_result_sets() {
+                // We can't reliably drain result sets of a dropped `QueryResult`
+                // so the connection will be closed.
+                if let Some(handler) = conn.inner.opts.get_event_handler() {
+                    handler.unconsumed_result_sets(conn.last_query_digest().as_ref());
+                }
+                inner.ongoing -= 1;
+                inner.disconnecting.push(conn.disconnect());
+            } else if conn.inner.has_result.is_some() {
                 inner.dropping.push(Box::new(conn.drop_result()));
             } else if conn.inner.in_transaction {
                 inner.rollback.push(Box::new(conn.rollback_transaction()));
+            } else if self.is_overloaded(&conn) {
+                // Connection is retired, so that a new one is established to a less loaded host.
+                inner.ongoing -= 1;
+                inner.disconnecting.push(conn.disconnect());
+            } else if conn.exceeds_pool_limits() {
+                // Connection is recycled (see `OptsBuilder::pool_max_lifetime`).
+                inner.ongoing -= 1;
+                inner.disconnecting.push(conn.disconnect());
+            } else if inner.draining {
+                // Pool is shutting down (see `Pool::disconnect_graceful`).
+                inner.ongoing -= 1;
+                inner.disconnecting.push(conn.disconnect());
             } else {
                 if inner.idle.len() >= min {
+                    inner.ongoing -= 1;
                     inner.disconnecting.push(conn.disconnect());
                 } else {
                     inner.ongoing -= 1;
@@ -211,6 +629,173 @@ impl Pool {
         });
     }
 
+    /// Returns `true` if connection should be retired to rebalance connections across hosts
+    /// (see `OptsBuilder::pool_rebalance`).
+    fn is_overloaded(&self, conn: &Conn) -> bool {
+        if !self.opts.get_pool_rebalance() || self.opts.get_additional_hosts().is_empty() {
+            return false;
+        }
+        let endpoint = match conn.inner.endpoint_guard {
+            Some(ref guard) => guard.endpoint(),
+            None => return false,
+        };
+        let counts = self.endpoint_conn_counts();
+        // Blacklisted hosts are unavailable, so they aren't taken into account.
+        let min = counts
+            .iter()
+            .filter(|(host, port, _)| !self.opts.is_blacklisted(host, *port))
+            .map(|x| x.2)
+            .min()
+            .unwrap_or(0);
+        let count = counts
+            .iter()
+            .find(|(host, port, _)| *host == endpoint.0 && *port == endpoint.1)
+            .map(|x| x.2)
+            .unwrap_or(0);
+        count > min + self.opts.get_pool_imbalance_threshold()
+    }
+
+    /// Spawns `IdleReaper`s once, if `OptsBuilder::pool_idle_check_interval`
+    /// or `OptsBuilder::pool_keepalive` is set.
+    fn start_idle_reaper(&self) {
+        let check_interval = self.opts.get_pool_idle_check_interval();
+        let keepalive = self.opts.get_pool_keepalive();
+        if check_interval.is_none() && keepalive.is_none() {
+            return;
+        }
+        let start = self.with_inner(|mut inner| !mem::replace(&mut inner.reaper_started, true));
+        if start {
+            // There is nothing to do if there is no executor (e.g. the pool is polled via `wait`).
+            // Zero interval is only possible if options are invalid (`Conn::new` will fail).
+            if let Some(period) = check_interval.filter(|period| *period > Duration::from_secs(0)) {
+                let reaper = IdleReaper::new(self, period, Pool::reap_idle);
+                let _ = DefaultExecutor::current().spawn(Box::new(reaper));
+            }
+            if let Some(period) = keepalive.filter(|period| *period > Duration::from_secs(0)) {
+                let reaper = IdleReaper::new(self, period, Pool::ping_idle);
+                let _ = DefaultExecutor::current().spawn(Box::new(reaper));
+            }
+        }
+    }
+
+    /// Pings connections that were idle for at least `OptsBuilder::pool_keepalive`
+    /// (the rest of idle connections could be taken from the pool meanwhile).
+    fn ping_idle(&self) {
+        let period = match self.opts.get_pool_keepalive() {
+            Some(period) => period,
+            None => return,
+        };
+        self.with_inner(|mut inner| {
+            if inner.closed || inner.draining {
+                return;
+            }
+            let idle = mem::replace(&mut inner.idle, Vec::new());
+            for conn in idle {
+                if conn.idle_for(period) {
+                    inner.pinging.push(Box::new(conn.ping()));
+                } else {
+                    inner.idle.push(conn);
+                }
+            }
+        });
+    }
+
+    /// Pings connections that were idle for at least `OptsBuilder::pool_idle_check_interval`
+    /// (closing those that are expired or exceed pool limits) and starts new connections
+    /// to keep at least `pool_min` of them.
+    fn reap_idle(&self) {
+        let period = match self.opts.get_pool_idle_check_interval() {
+            Some(period) => period,
+            None => return,
+        };
+        let min = self.pool_constraints.min();
+        self.with_inner(|mut inner| {
+            if inner.closed || inner.draining {
+                return;
+            }
+            let idle = mem::replace(&mut inner.idle, Vec::new());
+            for (i, conn) in idle.into_iter().enumerate() {
+                if i >= min || conn.expired() || conn.exceeds_pool_limits() {
+                    inner.disconnecting.push(conn.disconnect());
+                } else if conn.idle_for(period) {
+                    inner.pinging.push(Box::new(conn.ping()));
+                } else {
+                    inner.idle.push(conn);
+                }
+            }
+            let missing = min.saturating_sub(inner.conn_count());
+            for _ in 0..missing {
+                let new_conn = self.new_conn(&inner);
+                inner.new.push(new_conn);
+            }
+        });
+    }
+
+    /// Emits `EventHandler::endpoint_imbalance` if connections are unevenly distributed
+    /// across hosts.
+    fn check_endpoint_balance(&self) {
+        if self.opts.get_additional_hosts().is_empty() {
+            return;
+        }
+        if let Some(handler) = self.opts.get_event_handler() {
+            let counts = self.endpoint_conn_counts();
+            if is_imbalanced(&counts, self.opts.get_pool_imbalance_threshold()) {
+                handler.endpoint_imbalance(&counts);
+            }
+        }
+    }
+
+    /// Returns options of new connections (see `Pool::rotate`).
+    fn conn_opts(&self) -> Opts {
+        self.with_inner(|inner| inner.conn_opts.clone())
+            .unwrap_or_else(|| self.opts.clone())
+    }
+
+    /// Returns future that establishes a new connection of the current generation
+    /// (see `Pool::rotate`).
+    fn new_conn(&self, inner: &Inner) -> BoxFuture<Conn> {
+        let generation = inner.generation.clone();
+        let opts = inner.conn_opts.as_ref().unwrap_or(&self.opts);
+        let fut = Conn::new(self.new_conn_opts(opts)).map(move |mut conn| {
+            conn.inner.pool_generation = Some(generation);
+            conn
+        });
+        Box::new(fut)
+    }
+
+    /// Returns options for a new connection based on `opts`.
+    ///
+    /// If rebalancing is enabled, the least loaded available host is tried first.
+    fn new_conn_opts(&self, opts: &Opts) -> Opts {
+        if !opts.get_pool_rebalance() || opts.get_additional_hosts().is_empty() {
+            return opts.clone();
+        }
+        // `opts` are new connection options if the pool is being rotated to other hosts.
+        let counts = self.endpoints.snapshot(&opts.get_hosts());
+        let target = counts
+            .iter()
+            .filter(|(host, port, _)| !opts.is_blacklisted(host, *port))
+            .min_by_key(|x| x.2)
+            .map(|(host, port, _)| (host.clone(), *port));
+        match target {
+            Some(target) => {
+                let additional_hosts = opts
+                    .get_hosts()
+                    .into_iter()
+                    .filter(|host| *host != target)
+                    .collect();
+                let mut builder = OptsBuilder::from_opts(opts.clone());
+                builder
+                    .ip_or_hostname(target.0)
+                    .tcp_port(target.1)
+                    .additional_hosts(additional_hosts)
+                    .host_selection(HostSelection::Sequential);
+                builder.into()
+            }
+            None => opts.clone(),
+        }
+    }
+
     fn with_inner<F, T>(&self, fun: F) -> T
     where
         F: FnOnce(MutexGuard<'_, Inner>) -> T,
@@ -304,6 +889,21 @@ impl Pool {
                 Err(_) => { Ok(()) },
             });
 
+            // Handle pinged idle connections.
+            handle!(pinging {
+                Ok(Ready(conn)) => {
+                    if inner.closed {
+                        inner.disconnecting.push(conn.disconnect());
+                    } else {
+                        inner.ongoing += 1;
+                        returned_conns.push(conn);
+                    }
+                    handled = true;
+                    Ok(())
+                },
+                Err(_) => { Ok(()) },
+            });
+
             // Handle connecting connections.
             handle!(new {
                 Ok(Ready(conn)) => {
@@ -328,7 +928,14 @@ impl Pool {
             Ok(())
         })?;
 
-        for conn in returned_conns {
+        for mut conn in returned_conns {
+            if conn.inner.endpoint_guard.is_none() {
+                // This is a new connection.
+                let endpoint = conn.endpoint();
+                conn.inner.endpoint_guard =
+                    Some(EndpointGuard::new(self.endpoints.clone(), endpoint));
+                self.check_endpoint_balance();
+            }
             self.return_conn(conn);
         }
 
@@ -341,19 +948,29 @@ impl Pool {
 
     /// Will poll pool for connection.
     fn poll(&mut self) -> Result<Async<Conn>> {
-        if self.with_inner(|inner| inner.closed) {
+        if self.with_inner(|inner| inner.closed || inner.draining) {
             return Err(DriverError::PoolDisconnected.into());
         }
 
+        self.start_idle_reaper();
         self.handle_futures()?;
 
         match self.take_conn() {
             Some(conn) => Ok(Ready(conn)),
             None => {
+                let growth = self.opts.get_pool_growth();
                 let new_conn_created = self.with_inner(|mut inner| {
-                    if inner.new.len() == 0 && inner.conn_count() < self.pool_constraints.max() {
-                        let new_conn = Conn::new(self.opts.clone());
-                        inner.new.push(Box::new(new_conn));
+                    let available = self
+                        .pool_constraints
+                        .max()
+                        .saturating_sub(inner.conn_count());
+                    if inner.new.len() == 0 && available > 0 {
+                        // Current task is waiting too.
+                        let count = growth.connections_for(inner.tasks.len() + 1);
+                        for _ in 0..cmp::min(count, available) {
+                            let new_conn = self.new_conn(&inner);
+                            inner.new.push(new_conn);
+                        }
                         true
                     } else {
                         inner.tasks.push(task::current());
@@ -386,7 +1003,11 @@ mod test {
     use futures::Future;
 
     use crate::{
-        conn::pool::Pool, queryable::Queryable, test_misc::DATABASE_URL, TransactionOptions,
+        conn::pool::Pool,
+        error::{DriverError, Error},
+        queryable::Queryable,
+        test_misc::DATABASE_URL,
+        ReplicatedPool, TransactionOptions,
     };
 
     /// Same as `tokio::run`, but will panic if future panics and will return the result
@@ -414,6 +1035,44 @@ mod test {
         run(fut).unwrap();
     }
 
+    #[test]
+    fn should_split_reads_and_writes() {
+        let mut pool = ReplicatedPool::new(&**DATABASE_URL, vec![&**DATABASE_URL]);
+        pool.max_replication_lag(Some(60));
+        let fut = pool
+            .get_conn()
+            .and_then(|conn| conn.ping())
+            .and_then({
+                let pool = pool.clone();
+                move |_| pool.get_read_conn()
+            })
+            .and_then(|conn| conn.ping())
+            .and_then(|_| pool.disconnect());
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_fallback_to_primary() {
+        let mut pool = ReplicatedPool::new(&**DATABASE_URL, Vec::<String>::new());
+        pool.fallback_to_primary(false);
+        let fut = pool.get_read_conn().then({
+            let mut pool = pool.clone();
+            move |result| match result {
+                Err(Error::Driver(DriverError::NoHealthyReplica)) => {
+                    pool.fallback_to_primary(true);
+                    pool.get_read_conn()
+                }
+                _ => panic!("no healthy replica expected"),
+            }
+        });
+        let fut = fut
+            .and_then(|conn| conn.ping())
+            .and_then(|_| pool.disconnect());
+
+        run(fut).unwrap();
+    }
+
     #[test]
     fn should_start_transaction() {
         let pool = Pool::new(format!("{}?pool_min=1&pool_max=1", &**DATABASE_URL));
@@ -444,6 +1103,97 @@ mod test {
         run(fut).unwrap();
     }
 
+    #[test]
+    fn should_roll_back_dropped_transaction_without_polling_pool() {
+        use crate::Conn;
+
+        let pool = Pool::new(format!("{}?pool_min=1&pool_max=1", &**DATABASE_URL));
+        let fut = pool
+            .get_conn()
+            .and_then(|conn| {
+                conn.drop_query("CREATE TABLE IF NOT EXISTS tmp_dropped (id INT PRIMARY KEY)")
+            })
+            .and_then(|conn| conn.drop_query("INSERT IGNORE INTO tmp_dropped VALUES (1)"))
+            .and_then({
+                let pool = pool.clone();
+                move |_| pool.start_transaction(TransactionOptions::default())
+            })
+            .and_then(|transaction| {
+                transaction.drop_query("UPDATE tmp_dropped SET id = 1 WHERE id = 1")
+            })
+            .and_then(|transaction| {
+                // Row lock is released by the spawned `ROLLBACK` (the pool isn't polled here).
+                drop(transaction);
+                Conn::from_url(&**DATABASE_URL)
+            })
+            .and_then(|conn| conn.drop_query("SET SESSION innodb_lock_wait_timeout = 5"))
+            .and_then(|conn| conn.drop_query("UPDATE tmp_dropped SET id = 1 WHERE id = 1"))
+            .and_then(|conn| conn.drop_query("DROP TABLE tmp_dropped"))
+            .and_then(|conn| conn.disconnect())
+            .and_then(move |_| pool.disconnect());
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_diagnose_lock_wait_timeout() {
+        use crate::error::ServerError;
+
+        let pool = Pool::new(format!("{}?redact_queries=true", &**DATABASE_URL));
+        let fut = pool
+            .get_conn()
+            .and_then(|conn| {
+                conn.drop_query("CREATE TABLE IF NOT EXISTS tmp_lock_wait (id INT PRIMARY KEY)")
+            })
+            .and_then(|conn| conn.drop_query("INSERT IGNORE INTO tmp_lock_wait VALUES (1)"))
+            .and_then({
+                let pool = pool.clone();
+                move |_| pool.start_transaction(TransactionOptions::default())
+            })
+            .and_then(|transaction| {
+                transaction.drop_query("UPDATE tmp_lock_wait SET id = 1 WHERE id = 1")
+            })
+            .and_then({
+                let pool = pool.clone();
+                move |transaction| pool.get_conn().map(|conn| (transaction, conn))
+            })
+            .and_then(|(transaction, conn)| {
+                conn.drop_query("SET SESSION innodb_lock_wait_timeout = 1")
+                    .and_then(|conn| {
+                        conn.drop_query("UPDATE tmp_lock_wait SET id = 1 WHERE id = 1")
+                    })
+                    .then(|result| match result {
+                        Ok(_) => panic!("lock wait should time out"),
+                        Err(error) => Ok::<_, Error>((transaction, error)),
+                    })
+            })
+            .and_then({
+                let pool = pool.clone();
+                move |(transaction, error)| {
+                    assert!(error.is_lock_wait_timeout());
+                    pool.diagnose_lock_error(error)
+                        .map(|error| (transaction, error))
+                }
+            })
+            .and_then(|(transaction, error)| {
+                let diagnostics = error
+                    .server_error()
+                    .and_then(ServerError::lock_diagnostics)
+                    .expect("lock diagnostics");
+                // the transaction holding the lock is idle
+                assert!(
+                    diagnostics.contains("rows locked): <idle>"),
+                    "{}",
+                    diagnostics
+                );
+                transaction.rollback()
+            })
+            .and_then(|conn| conn.drop_query("DROP TABLE tmp_lock_wait"))
+            .and_then(move |_| pool.disconnect());
+
+        run(fut).unwrap();
+    }
+
     #[test]
     fn should_hold_bounds() {
         let pool = Pool::new(format!("{}?pool_min=1&pool_max=2", &**DATABASE_URL));
@@ -542,6 +1292,493 @@ mod test {
         run(fut).unwrap();
     }
 
+    #[test]
+    fn should_report_unconsumed_result_sets() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        use crate::{prelude::EventHandler, OptsBuilder, PoolConstraints, QueryDigest};
+
+        struct Handler(Arc<AtomicUsize>);
+
+        impl EventHandler for Handler {
+            fn unconsumed_result_sets(&self, query: Option<&QueryDigest>) {
+                assert_eq!(query.map(QueryDigest::text), Some("DO ?; SELECT ?"));
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let reported = Arc::new(AtomicUsize::new(0));
+        let mut opts = OptsBuilder::from_opts(&**DATABASE_URL);
+        opts.pool_constraints(PoolConstraints::new(1, 1))
+            .event_handler(Some(Handler(reported.clone())));
+
+        let pool = Pool::new(opts);
+        let fut = pool
+            .get_conn()
+            .and_then(|conn| conn.query("DO 1; SELECT 2"))
+            .and_then({
+                let pool = pool.clone();
+                move |result| {
+                    drop(result);
+                    pool.get_conn()
+                }
+            })
+            .and_then(|conn| conn.first::<_, (u8,)>("SELECT 3"))
+            .and_then(move |(_, row)| {
+                assert_eq!(row, Some((3,)));
+                assert_eq!(reported.load(Ordering::SeqCst), 1);
+                pool.disconnect()
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_keep_connection_after_failed_fold() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        use crate::{
+            prelude::EventHandler, Aggregation, OptsBuilder, PoolConstraints, QueryDigest,
+        };
+
+        struct Handler(Arc<AtomicUsize>);
+
+        impl EventHandler for Handler {
+            fn unconsumed_result_sets(&self, _query: Option<&QueryDigest>) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let reported = Arc::new(AtomicUsize::new(0));
+        let mut opts = OptsBuilder::from_opts(&**DATABASE_URL);
+        opts.pool_constraints(PoolConstraints::new(1, 1))
+            .event_handler(Some(Handler(reported.clone())));
+
+        let pool = Pool::new(opts);
+        let fold = |pool: &Pool, query: &'static str, column: &'static str| {
+            pool.get_conn()
+                .and_then(move |conn| conn.query(query))
+                .and_then(move |result| result.fold_numeric_and_drop(column, Aggregation::Sum))
+                .then(|result| match result {
+                    Err(Error::Driver(DriverError::FromValue { .. }))
+                    | Err(Error::Driver(DriverError::MissingColumn { .. })) => Ok(()),
+                    Err(error) => Err(error),
+                    Ok(_) => panic!("fold should fail"),
+                })
+        };
+        let fut = pool
+            .get_conn()
+            .and_then(|conn| conn.first::<_, (u32,)>("SELECT CONNECTION_ID()"))
+            .and_then({
+                let pool = pool.clone();
+                move |(conn, id)| {
+                    drop(conn);
+                    fold(&pool, "SELECT 'foo' AS x; SELECT 1", "x")
+                        .and_then(move |_| fold(&pool, "SELECT 1 AS x; SELECT 2", "y"))
+                        .and_then(move |_| pool.get_conn())
+                        .and_then(|conn| conn.first::<_, (u32,)>("SELECT CONNECTION_ID()"))
+                        .map(move |(_, same_id)| assert_eq!(same_id, id))
+                }
+            })
+            .and_then(move |_| {
+                assert_eq!(reported.load(Ordering::SeqCst), 0);
+                pool.disconnect()
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_report_endpoint_imbalance() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        use crate::{prelude::EventHandler, OptsBuilder, PoolConstraints};
+
+        struct Handler(Arc<AtomicUsize>);
+
+        impl EventHandler for Handler {
+            fn endpoint_imbalance(&self, counts: &[(String, u16, usize)]) {
+                assert_eq!(counts.len(), 2);
+                assert_eq!(counts[1], ("127.0.0.1".into(), 1, 0));
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let reported = Arc::new(AtomicUsize::new(0));
+        let mut opts = OptsBuilder::from_opts(&**DATABASE_URL);
+        // Additional host is never tried, because the primary host is available.
+        opts.additional_hosts(vec![("127.0.0.1", 1)])
+            .pool_constraints(PoolConstraints::new(1, 3))
+            .pool_imbalance_threshold(1)
+            .event_handler(Some(Handler(reported.clone())));
+
+        let pool = Pool::new(opts);
+        let fut = pool
+            .get_conn()
+            .join3(pool.get_conn(), pool.get_conn())
+            .and_then(move |conns| {
+                assert_eq!(pool.endpoint_conn_counts()[0].2, 3);
+                assert!(reported.load(Ordering::SeqCst) > 0);
+                drop(conns);
+                pool.disconnect()
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_recycle_connections_exceeding_max_uses() {
+        use crate::OptsBuilder;
+
+        let mut opts = OptsBuilder::from_opts(&**DATABASE_URL);
+        opts.pool_max_uses(Some(2));
+        let pool = Pool::new(opts);
+        let fut = pool
+            .get_conn()
+            .map(|conn| conn.id())
+            .and_then({
+                let pool = pool.clone();
+                move |first| pool.get_conn().map(move |conn| (first, conn.id()))
+            })
+            .and_then({
+                let pool = pool.clone();
+                move |(first, second)| {
+                    assert_eq!(first, second);
+                    pool.get_conn().map(move |conn| (first, conn.id()))
+                }
+            })
+            .and_then(move |(first, third)| {
+                assert_ne!(first, third);
+                pool.disconnect()
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_prewarm_idle_connections() {
+        use std::time::{Duration, Instant};
+        use tokio::timer::Delay;
+
+        use crate::{OptsBuilder, PoolConstraints};
+
+        let mut opts = OptsBuilder::from_opts(&**DATABASE_URL);
+        opts.pool_constraints(PoolConstraints::new(2, 10))
+            .pool_idle_check_interval(Some(Duration::from_millis(100)));
+        let pool = Pool::new(opts);
+        let fut = pool
+            .get_conn()
+            .and_then(|conn| {
+                drop(conn);
+                Delay::new(Instant::now() + Duration::from_millis(1000)).then(|_| Ok(()))
+            })
+            .and_then(move |_| {
+                assert_eq!(pool.with_inner(|inner| inner.idle.len()), 2);
+                pool.disconnect()
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_ping_idle_connections() {
+        use std::time::{Duration, Instant};
+        use tokio::timer::Delay;
+
+        use crate::{OptsBuilder, PoolConstraints};
+
+        let mut opts = OptsBuilder::from_opts(&**DATABASE_URL);
+        opts.pool_constraints(PoolConstraints::new(1, 10))
+            .pool_keepalive(Some(Duration::from_millis(100)));
+        let pool = Pool::new(opts);
+        let fut = pool
+            .get_conn()
+            .and_then(|conn| {
+                let bytes_sent = conn.bytes_sent();
+                drop(conn);
+                Delay::new(Instant::now() + Duration::from_millis(500))
+                    .then(move |_| Ok(bytes_sent))
+            })
+            .and_then(move |bytes_sent| {
+                pool.get_conn().and_then(move |conn| {
+                    assert!(conn.bytes_sent() > bytes_sent);
+                    drop(conn);
+                    pool.disconnect()
+                })
+            });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_disconnect_gracefully() {
+        use std::time::{Duration, Instant};
+        use tokio::timer::Delay;
+
+        let pool = Pool::new(&**DATABASE_URL);
+        let started = Instant::now();
+        let fut = pool.get_conn().and_then(move |conn| {
+            let graceful = pool.clone().disconnect_graceful(Duration::from_secs(10));
+            let get_conn = pool.get_conn().then(|result| match result {
+                Err(Error::Driver(DriverError::PoolDisconnected)) => Ok(()),
+                _ => panic!("expected PoolDisconnected"),
+            });
+            let release = Delay::new(Instant::now() + Duration::from_millis(100)).then(move |_| {
+                drop(conn);
+                Ok(())
+            });
+            graceful.join3(get_conn, release).map(move |_| {
+                assert!(started.elapsed() < Duration::from_secs(5));
+                assert_eq!(pool.with_inner(|inner| inner.ongoing), 0);
+            })
+        });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_time_out_waiting_for_saturated_pool() {
+        use std::{
+            sync::{Arc, Mutex},
+            time::Duration,
+        };
+
+        use crate::{prelude::EventHandler, OptsBuilder, PoolConstraints, WaitReason};
+
+        struct Handler(Arc<Mutex<Vec<WaitReason>>>);
+
+        impl EventHandler for Handler {
+            fn checkout_wait(&self, reason: WaitReason, waited: Duration) {
+                assert!(waited >= Duration::from_millis(100));
+                self.0.lock().unwrap().push(reason);
+            }
+        }
+
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let mut opts = OptsBuilder::from_opts(&**DATABASE_URL);
+        opts.pool_constraints(PoolConstraints::new(1, 1))
+            .pool_checkout_timeout(Some(Duration::from_millis(100)))
+            .event_handler(Some(Handler(reported.clone())));
+
+        let pool = Pool::new(opts);
+        let fut = pool.get_conn().and_then(move |conn| {
+            pool.get_conn().then(move |result| {
+                match result {
+                    Err(Error::Driver(DriverError::PoolCheckoutTimeout { reason, .. })) => {
+                        assert_eq!(reason, WaitReason::PoolAtMax)
+                    }
+                    _ => panic!("expected checkout timeout"),
+                }
+                assert_eq!(*reported.lock().unwrap(), vec![WaitReason::PoolAtMax]);
+                drop(conn);
+                pool.disconnect()
+            })
+        });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_use_admin_conn_if_pool_is_saturated() {
+        use crate::{OptsBuilder, PoolConstraints};
+
+        let mut opts = OptsBuilder::from_opts(&**DATABASE_URL);
+        opts.pool_constraints(PoolConstraints::new(1, 1));
+        let pool = Pool::new(opts);
+        let fut = pool.get_conn().and_then(move |conn| {
+            let conn_id = conn.id();
+            pool.health_check()
+                .and_then({
+                    let pool = pool.clone();
+                    move |_| pool.kill_query(conn_id)
+                })
+                .and_then({
+                    let pool = pool.clone();
+                    move |_| pool.with_admin_conn(|admin| admin.first::<_, (u32,)>("SELECT 1"))
+                })
+                .and_then(move |row| {
+                    assert_eq!(row, Some((1,)));
+                    drop(conn);
+                    pool.disconnect()
+                })
+        });
+
+        run(fut).unwrap();
+    }
+
+    #[test]
+    fn should_retry_deadlocked_transaction() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        use crate::RetryOptions;
+
+        let pool = Pool::new(&**DATABASE_URL);
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let attempts_clone = attempts.clone();
+        let fut = pool
+            .get_conn()
+            .and_then(|conn| conn.drop_query("DROP PROCEDURE IF EXISTS tmp_deadlock"))
+            .and_then(|conn| {
+                conn.drop_query(
+                    "CREATE PROCEDURE tmp_deadlock() \
+                     SIGNAL SQLSTATE '40001' SET MYSQL_ERRNO = 1213",
+                )
+            })
+            .and_then(move |conn| {
+                drop(conn);
+                let mut options = RetryOptions::new();
+                options.set_max_attempts(3);
+                pool.run_transaction(options, move |transaction| {
+                    let attempt = attempts_clone.fetch_add(1, Ordering::SeqCst) + 1;
+                    let query = if attempt < 3 {
+                        "CALL tmp_deadlock()"
+                    } else {
+                        "DO 1"
+                    };
+                    transaction
+                        .drop_query(query)
+                        .map(move |transaction| (transaction, attempt))
+                })
+                .and_then(move |attempt| {
+                    assert_eq!(attempt, 3);
+                    pool.get_conn()
+                })
+                .and_then(|conn| conn.drop_query("DROP PROCEDURE tmp_deadlock"))
+                .and_then(|conn| conn.disconnect())
+            });
+
+        run(fut).unwrap();
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn should_rotate_connections() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        use crate::{prelude::EventHandler, OptsBuilder, PoolConstraints};
+
+        struct Handler(Arc<AtomicUsize>);
+
+        impl EventHandler for Handler {
+            fn pool_rotation(&self, replaced: usize, _remaining: usize) {
+                self.0.store(replaced, Ordering::SeqCst);
+            }
+        }
+
+        let replaced = Arc::new(AtomicUsize::new(0));
+        let mut opts = OptsBuilder::from_opts(&**DATABASE_URL);
+        opts.pool_constraints(PoolConstraints::new(2, 2))
+            .event_handler(Some(Handler(replaced.clone())));
+        let mut new_opts = OptsBuilder::from_opts(&**DATABASE_URL);
+        new_opts.init(vec!["SET @rotated = 1"]);
+
+        let pool = Pool::new(opts);
+        let fut = pool
+            .get_conn()
+            .join(pool.get_conn())
+            .and_then(|(a, b)| {
+                a.first::<_, (Option<u8>,)>("SELECT @rotated")
+                    .join(b.drop_query("DO 1"))
+            })
+            .and_then(move |((conn, row), _)| {
+                assert_eq!(row, Some((None,)));
+                drop(conn);
+                pool.rotate(new_opts, 100).map(|_| pool)
+            })
+            .and_then(|pool| pool.get_conn().map(|conn| (pool, conn)))
+            .and_then(|(pool, conn)| {
+                conn.first::<_, (Option<u8>,)>("SELECT @rotated")
+                    .map(|(conn, row)| (pool, conn, row))
+            })
+            .and_then(|(pool, conn, row)| {
+                assert_eq!(row, Some((Some(1),)));
+                drop(conn);
+                pool.disconnect()
+            });
+
+        run(fut).unwrap();
+        assert_eq!(replaced.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn should_keep_pool_if_rotation_fails() {
+        use crate::OptsBuilder;
+
+        let mut new_opts = OptsBuilder::from_opts(&**DATABASE_URL);
+        new_opts.init(vec!["SELECT * FROM mysql_async_no_such_table"]);
+
+        let pool = Pool::new(&**DATABASE_URL);
+        let fut = pool
+            .get_conn()
+            .and_then(move |conn| {
+                drop(conn);
+                pool.rotate(new_opts, 100).then(move |result| match result {
+                    Err(Error::Driver(DriverError::InitCommandFailed { .. })) => Ok(pool),
+                    _ => panic!("expected InitCommandFailed"),
+                })
+            })
+            .and_then(|pool| {
+                assert!(pool.with_inner(|inner| inner.conn_opts.is_none()));
+                pool.get_conn().map(|conn| (pool, conn))
+            })
+            .and_then(|(pool, conn)| conn.drop_query("DO 1").map(|_| pool))
+            .and_then(|pool| pool.disconnect());
+
+        run(fut).unwrap();
+    }
+
+    #[cfg(feature = "tower")]
+    #[test]
+    fn should_serve_query_requests() {
+        use futures::{future::poll_fn, try_ready, Async};
+        use tower_service::Service;
+
+        use crate::{from_row, MysqlService, QueryRequest};
+
+        let pool = Pool::new(format!("{}?pool_min=1&pool_max=1", &**DATABASE_URL));
+        let service = MysqlService::new(pool.clone());
+        let other = service.clone();
+        let mut services = Some((service, other));
+        let fut = poll_fn(move || {
+            {
+                let (service, other) = services.as_mut().unwrap();
+                try_ready!(service.poll_ready());
+                // pool is at max and its only connection is reserved by `service`
+                assert_eq!(other.poll_ready()?, Async::NotReady);
+            }
+            Ok(Async::Ready(services.take().unwrap()))
+        })
+        .and_then(|(mut service, other)| {
+            service
+                .call(QueryRequest::new("SELECT ?", (42,)))
+                .map(move |response| (response, other))
+        })
+        .and_then(|(response, mut other)| {
+            assert_eq!(response.rows.len(), 1);
+            assert_eq!(from_row::<u8>(response.rows[0].clone()), 42);
+            assert_eq!(response.affected_rows, 0);
+            poll_fn(move || other.poll_ready())
+        })
+        .and_then(|_| pool.disconnect());
+
+        run(fut).unwrap();
+    }
+
     #[cfg(feature = "nightly")]
     mod bench {
         use futures::Future;
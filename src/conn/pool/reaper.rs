@@ -0,0 +1,90 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use futures::{
+    Async::{NotReady, Ready},
+    Future, Poll, Stream,
+};
+use tokio::timer::Interval;
+
+use std::{
+    sync::{atomic::AtomicBool, Arc, Mutex, Weak},
+    time::Duration,
+};
+
+use crate::{
+    conn::pool::{endpoints::EndpointCounts, Inner, Pool},
+    opts::{Opts, PoolConstraints},
+};
+
+/// Background task that periodically runs `action` on a pool, i.e. pings idle connections
+/// and pre-warms it up to `pool_min` (see `OptsBuilder::pool_idle_check_interval`) or keeps
+/// idle connections alive (see `OptsBuilder::pool_keepalive`).
+///
+/// It holds a weak reference to the pool, so it stops once the pool is dropped
+/// or disconnected.
+pub struct IdleReaper {
+    opts: Opts,
+    inner: Weak<Mutex<Inner>>,
+    endpoints: Arc<EndpointCounts>,
+    pool_constraints: PoolConstraints,
+    demoted: Arc<AtomicBool>,
+    interval: Interval,
+    action: fn(&Pool),
+}
+
+impl IdleReaper {
+    pub fn new(pool: &Pool, period: Duration, action: fn(&Pool)) -> IdleReaper {
+        IdleReaper {
+            opts: pool.opts.clone(),
+            inner: Arc::downgrade(&pool.inner),
+            endpoints: pool.endpoints.clone(),
+            pool_constraints: pool.pool_constraints.clone(),
+            demoted: pool.demoted.clone(),
+            interval: Interval::new_interval(period),
+            action,
+        }
+    }
+
+    fn pool(&self) -> Option<Pool> {
+        self.inner.upgrade().map(|inner| Pool {
+            opts: self.opts.clone(),
+            inner,
+            endpoints: self.endpoints.clone(),
+            pool_constraints: self.pool_constraints.clone(),
+            demoted: self.demoted.clone(),
+        })
+    }
+}
+
+impl Future for IdleReaper {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        let mut pool = match self.pool() {
+            Some(pool) => pool,
+            None => return Ok(Ready(())),
+        };
+
+        loop {
+            if pool.with_inner(|inner| inner.closed) {
+                return Ok(Ready(()));
+            }
+
+            // Errors of pre-warming connections will be retried on the next tick.
+            let _ = pool.handle_futures();
+
+            match self.interval.poll() {
+                Ok(Ready(Some(_))) => (self.action)(&pool),
+                Ok(Ready(None)) | Err(_) => return Ok(Ready(())),
+                Ok(NotReady) => return Ok(NotReady),
+            }
+        }
+    }
+}
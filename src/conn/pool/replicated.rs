@@ -0,0 +1,181 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use ::futures::future::{err, join_all, loop_fn, ok, Either::*, Future, Loop};
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use crate::{
+    conn::{
+        pool::{futures::GetConn, Pool},
+        Conn,
+    },
+    error::*,
+    opts::{Opts, OptsBuilder},
+    queryable::Queryable,
+    MyFuture, Row,
+};
+
+/// Pair of connection pools for a primary server and its replicas (read/write splitting).
+///
+/// `ReplicatedPool::get_conn` returns connection to the primary (use it for writes)
+/// and `ReplicatedPool::get_read_conn` returns connection to a healthy replica.
+#[derive(Debug, Clone)]
+pub struct ReplicatedPool {
+    primary: Pool,
+    replicas: Vec<Pool>,
+    max_replication_lag: Option<u32>,
+    fallback_to_primary: bool,
+    next_replica: Arc<AtomicUsize>,
+}
+
+impl ReplicatedPool {
+    /// Creates new pool with separate `primary` and `replicas` endpoints.
+    ///
+    /// `OptsBuilder::detect_read_only_server` is enabled for the primary.
+    pub fn new<P, R>(primary: P, replicas: Vec<R>) -> ReplicatedPool
+    where
+        P: Into<Opts>,
+        R: Into<Opts>,
+    {
+        let mut primary = OptsBuilder::from_opts(primary.into());
+        primary.detect_read_only_server(true);
+        ReplicatedPool {
+            primary: Pool::new(primary),
+            replicas: replicas.into_iter().map(Pool::new).collect(),
+            max_replication_lag: None,
+            fallback_to_primary: true,
+            next_replica: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Replica lagging behind the primary for more than this number of seconds (or having
+    /// replication stopped) is considered unhealthy (defaults to `None`, i.e. lag isn't checked).
+    ///
+    /// Lag is checked using `SHOW SLAVE STATUS` every time a connection is taken
+    /// by `ReplicatedPool::get_read_conn`.
+    pub fn max_replication_lag(&mut self, max_replication_lag: Option<u32>) -> &mut Self {
+        self.max_replication_lag = max_replication_lag;
+        self
+    }
+
+    /// Whether `ReplicatedPool::get_read_conn` should return connection to the primary
+    /// if there is no healthy replica (defaults to `true`).
+    pub fn fallback_to_primary(&mut self, fallback_to_primary: bool) -> &mut Self {
+        self.fallback_to_primary = fallback_to_primary;
+        self
+    }
+
+    /// Returns future that resolves to a connection to the primary.
+    ///
+    /// If the primary rejects a write because it is in read-only mode (e.g. it was demoted
+    /// by a failover), the write fails with `DriverError::ReadOnlyViolation` and the primary
+    /// is marked as demoted (see `ReplicatedPool::is_primary_demoted`).
+    pub fn get_conn(&self) -> GetConn {
+        self.primary.get_conn()
+    }
+
+    /// Whether the primary rejected a write because it is in read-only mode
+    /// (see `Pool::is_demoted`).
+    ///
+    /// Demoted primary is used by `ReplicatedPool::get_read_conn` as one of the replicas
+    /// until the flag is reset using `ReplicatedPool::reset_primary_demotion`.
+    pub fn is_primary_demoted(&self) -> bool {
+        self.primary.is_demoted()
+    }
+
+    /// Clears the demotion flag of the primary (e.g. once the application has been
+    /// reconfigured or the primary accepts writes again).
+    pub fn reset_primary_demotion(&self) {
+        self.primary.reset_demoted();
+    }
+
+    /// Returns future that resolves to a connection to a healthy replica.
+    ///
+    /// Replicas are tried in round-robin order (a demoted primary is treated as a replica,
+    /// see `ReplicatedPool::is_primary_demoted`). Future will resolve to a connection to
+    /// the primary (or to `DriverError::NoHealthyReplica` if fallback is disabled)
+    /// if there is no healthy replica.
+    pub fn get_read_conn(&self) -> impl MyFuture<Conn> {
+        let mut endpoints = self.replicas.clone();
+        if self.primary.is_demoted() {
+            endpoints.push(self.primary.clone());
+        }
+        let start = self.next_replica.fetch_add(1, Ordering::Relaxed);
+        let len = endpoints.len();
+        let replicas = (0..len)
+            .map(|i| endpoints[(start + i) % len].clone())
+            .collect::<Vec<_>>();
+        let max_replication_lag = self.max_replication_lag;
+        let primary = if self.fallback_to_primary {
+            Some(self.primary.clone())
+        } else {
+            None
+        };
+
+        loop_fn(replicas.into_iter(), move |mut replicas| {
+            match replicas.next() {
+                Some(pool) => {
+                    let fut = pool
+                        .get_conn()
+                        .and_then(move |conn| check_replication_lag(conn, max_replication_lag))
+                        .then(|result| match result {
+                            Ok((conn, true)) => Ok::<_, Error>(Loop::Break(Some(conn))),
+                            // Unhealthy connection will be returned to its pool.
+                            Ok((_, false)) | Err(_) => Ok(Loop::Continue(replicas)),
+                        });
+                    A(fut)
+                }
+                None => B(ok(Loop::Break(None))),
+            }
+        })
+        .and_then(|conn| match conn {
+            Some(conn) => A(ok(conn)),
+            None => match primary {
+                Some(primary) => B(A(primary.get_conn())),
+                None => B(B(err(DriverError::NoHealthyReplica.into()))),
+            },
+        })
+    }
+
+    /// Returns future that disconnects primary and replica pools.
+    pub fn disconnect(self) -> impl MyFuture<()> {
+        join_all(self.replicas.into_iter().map(Pool::disconnect))
+            .join(self.primary.disconnect())
+            .map(|_| ())
+    }
+}
+
+/// Returns future that resolves to `true` if replication lag of a server is acceptable.
+fn check_replication_lag(
+    conn: Conn,
+    max_replication_lag: Option<u32>,
+) -> impl MyFuture<(Conn, bool)> {
+    match max_replication_lag {
+        Some(max_replication_lag) => {
+            let fut = conn
+                .first::<_, Row>("SHOW SLAVE STATUS")
+                .map(move |(conn, row)| {
+                    let healthy = match row {
+                        // not a replica
+                        None => true,
+                        Some(row) => match row.get::<Option<u32>, _>("Seconds_Behind_Master") {
+                            Some(Some(lag)) => lag <= max_replication_lag,
+                            _ => false,
+                        },
+                    };
+                    (conn, healthy)
+                });
+            A(fut)
+        }
+        None => B(ok((conn, true))),
+    }
+}
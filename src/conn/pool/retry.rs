@@ -0,0 +1,125 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use std::time::Duration;
+
+use crate::{error::Error, queryable::transaction::TransactionOptions};
+
+/// Options for `Pool::run_transaction`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RetryOptions {
+    transaction_options: TransactionOptions,
+    max_attempts: usize,
+    backoff: Duration,
+    max_backoff: Duration,
+    lock_diagnostics: bool,
+}
+
+impl Default for RetryOptions {
+    fn default() -> Self {
+        RetryOptions {
+            transaction_options: TransactionOptions::new(),
+            max_attempts: 3,
+            backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(1),
+            lock_diagnostics: false,
+        }
+    }
+}
+
+impl RetryOptions {
+    pub fn new() -> RetryOptions {
+        RetryOptions::default()
+    }
+
+    /// Options of every transaction started by `Pool::run_transaction`.
+    pub fn set_transaction_options(&mut self, value: TransactionOptions) -> &mut Self {
+        self.transaction_options = value;
+        self
+    }
+
+    /// Maximum number of attempts including the first one (defaults to `3`).
+    pub fn set_max_attempts(&mut self, value: usize) -> &mut Self {
+        self.max_attempts = value;
+        self
+    }
+
+    /// Delay before the second attempt. Delay is doubled for every subsequent attempt
+    /// (defaults to 50ms).
+    pub fn set_backoff(&mut self, value: Duration) -> &mut Self {
+        self.backoff = value;
+        self
+    }
+
+    /// Upper bound of a delay between attempts (defaults to 1s).
+    pub fn set_max_backoff(&mut self, value: Duration) -> &mut Self {
+        self.max_backoff = value;
+        self
+    }
+
+    /// If `true`, then the error of the last attempt that failed with `ER_LOCK_DEADLOCK`
+    /// or `ER_LOCK_WAIT_TIMEOUT` is passed to `Pool::diagnose_lock_error` (defaults to `false`).
+    pub fn set_lock_diagnostics(&mut self, value: bool) -> &mut Self {
+        self.lock_diagnostics = value;
+        self
+    }
+
+    pub fn transaction_options(&self) -> &TransactionOptions {
+        &self.transaction_options
+    }
+
+    pub fn max_attempts(&self) -> usize {
+        self.max_attempts
+    }
+
+    pub fn backoff(&self) -> Duration {
+        self.backoff
+    }
+
+    pub fn max_backoff(&self) -> Duration {
+        self.max_backoff
+    }
+
+    pub fn lock_diagnostics(&self) -> bool {
+        self.lock_diagnostics
+    }
+
+    /// Returns `true` if transaction failed with `err` should be retried after `attempt`.
+    pub(crate) fn should_retry(&self, err: &Error, attempt: usize) -> bool {
+        attempt < self.max_attempts && (err.is_deadlock() || err.is_lock_wait_timeout())
+    }
+
+    /// Returns delay before the attempt following the given `attempt` (counting from 1).
+    pub(crate) fn delay(&self, attempt: usize) -> Duration {
+        let factor = 1u32 << (attempt.saturating_sub(1).min(16) as u32);
+        self.backoff
+            .checked_mul(factor)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::RetryOptions;
+
+    #[test]
+    fn should_compute_backoff() {
+        let mut options = RetryOptions::new();
+        options
+            .set_backoff(Duration::from_millis(10))
+            .set_max_backoff(Duration::from_millis(50));
+        assert_eq!(options.delay(1), Duration::from_millis(10));
+        assert_eq!(options.delay(2), Duration::from_millis(20));
+        assert_eq!(options.delay(3), Duration::from_millis(40));
+        assert_eq!(options.delay(4), Duration::from_millis(50));
+        assert_eq!(options.delay(100), Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,110 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use ::futures::{
+    future::{ok, Either::*, Future},
+    Async::{NotReady, Ready},
+    Poll,
+};
+use tower_service::Service;
+
+use std::fmt;
+
+use crate::{
+    conn::{
+        pool::{futures::GetConn, Pool},
+        request::{execute, QueryRequest, QueryResponse},
+        Conn,
+    },
+    error::*,
+    BoxFuture,
+};
+
+/// `tower::Service` that executes `QueryRequest`s using connections of a `Pool`
+/// (requires `tower` feature).
+///
+/// Readiness is tied to pool capacity: `poll_ready` takes a connection from the pool
+/// (so it waits while the pool is at max, see `Pool::get_conn`) and reserves it
+/// for the next `call`. The connection is returned to the pool once the response is ready.
+/// Errors of `poll_ready` are errors of `Pool::get_conn` (e.g. `DriverError::PoolDisconnected`
+/// or `DriverError::PoolCheckoutTimeout`).
+///
+/// Clones share the pool, but every clone reserves its own connection.
+pub struct MysqlService {
+    pool: Pool,
+    pending: Option<GetConn>,
+    reserved: Option<Conn>,
+}
+
+impl MysqlService {
+    pub fn new(pool: Pool) -> MysqlService {
+        MysqlService {
+            pool,
+            pending: None,
+            reserved: None,
+        }
+    }
+
+    /// Returns the pool of this service.
+    pub fn pool(&self) -> &Pool {
+        &self.pool
+    }
+}
+
+impl Clone for MysqlService {
+    fn clone(&self) -> MysqlService {
+        MysqlService::new(self.pool.clone())
+    }
+}
+
+impl fmt::Debug for MysqlService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MysqlService")
+            .field("pool", &self.pool)
+            .field("ready", &self.reserved.is_some())
+            .finish()
+    }
+}
+
+impl Service<QueryRequest> for MysqlService {
+    type Response = QueryResponse;
+    type Error = Error;
+    type Future = BoxFuture<QueryResponse>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        if self.reserved.is_none() {
+            let pool = &self.pool;
+            let result = self.pending.get_or_insert_with(|| pool.get_conn()).poll();
+            match result {
+                Ok(Ready(conn)) => {
+                    self.pending = None;
+                    self.reserved = Some(conn);
+                }
+                Ok(NotReady) => return Ok(NotReady),
+                Err(err) => {
+                    self.pending = None;
+                    return Err(err);
+                }
+            }
+        }
+        Ok(Ready(()))
+    }
+
+    /// Executes `request` using the reserved connection (a connection is taken from the pool
+    /// if `poll_ready` wasn't called).
+    fn call(&mut self, request: QueryRequest) -> Self::Future {
+        let conn = match self.reserved.take() {
+            Some(conn) => A(ok(conn)),
+            None => B(self.pool.get_conn()),
+        };
+        Box::new(
+            conn.and_then(|conn| execute(conn, request))
+                .map(|(_, response)| response),
+        )
+    }
+}
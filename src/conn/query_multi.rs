@@ -0,0 +1,21 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+pub use crate::queryable::query_result::{ResultSets, ResultSummary};
+
+use crate::{Conn, TextProtocol};
+
+/// Stream of per-statement results of a multi-statement query (see `Conn::query_multi`).
+pub type QueryMulti = ResultSets<Conn, TextProtocol>;
+
+impl ResultSets<Conn, TextProtocol> {
+    /// Returns the connection if the stream is exhausted.
+    pub fn into_conn(self) -> Option<Conn> {
+        self.into_inner()
+    }
+}
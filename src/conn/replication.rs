@@ -0,0 +1,97 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use mysql_common::value::convert::{from_value_opt, FromValue};
+
+use crate::{error::*, Row, Value};
+
+/// Binary log position of a server (see `Conn::master_status`).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MasterStatus {
+    pub file: String,
+    pub position: u64,
+    pub binlog_do_db: String,
+    pub binlog_ignore_db: String,
+    /// `None` if the server doesn't report GTIDs.
+    pub executed_gtid_set: Option<String>,
+}
+
+impl MasterStatus {
+    /// Parses a row of `SHOW MASTER STATUS`.
+    pub(crate) fn from_row(row: &Row) -> Result<MasterStatus> {
+        Ok(MasterStatus {
+            file: get(row, "File")?,
+            position: get(row, "Position")?,
+            binlog_do_db: get_opt(row, "Binlog_Do_DB")?.unwrap_or_default(),
+            binlog_ignore_db: get_opt(row, "Binlog_Ignore_DB")?.unwrap_or_default(),
+            executed_gtid_set: get_opt(row, "Executed_Gtid_Set")?,
+        })
+    }
+}
+
+/// Replication status of a replica (see `Conn::replica_status`).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ReplicaStatus {
+    pub master_host: String,
+    pub master_port: u16,
+    /// `true` if the I/O thread is running (`Slave_IO_Running: Yes`).
+    pub io_running: bool,
+    /// `true` if the SQL thread is running (`Slave_SQL_Running: Yes`).
+    pub sql_running: bool,
+    /// `None` if replication is stopped.
+    pub seconds_behind_master: Option<u64>,
+    pub master_log_file: String,
+    pub read_master_log_pos: u64,
+    pub relay_master_log_file: String,
+    pub exec_master_log_pos: u64,
+    pub last_errno: u32,
+    pub last_error: String,
+    /// `None` if the server doesn't report GTIDs.
+    pub retrieved_gtid_set: Option<String>,
+    /// `None` if the server doesn't report GTIDs.
+    pub executed_gtid_set: Option<String>,
+}
+
+impl ReplicaStatus {
+    /// Parses a row of `SHOW SLAVE STATUS`.
+    pub(crate) fn from_row(row: &Row) -> Result<ReplicaStatus> {
+        Ok(ReplicaStatus {
+            master_host: get(row, "Master_Host")?,
+            master_port: get(row, "Master_Port")?,
+            io_running: get::<String>(row, "Slave_IO_Running")? == "Yes",
+            sql_running: get::<String>(row, "Slave_SQL_Running")? == "Yes",
+            seconds_behind_master: get_opt(row, "Seconds_Behind_Master")?,
+            master_log_file: get(row, "Master_Log_File")?,
+            read_master_log_pos: get(row, "Read_Master_Log_Pos")?,
+            relay_master_log_file: get(row, "Relay_Master_Log_File")?,
+            exec_master_log_pos: get(row, "Exec_Master_Log_Pos")?,
+            last_errno: get(row, "Last_Errno")?,
+            last_error: get(row, "Last_Error")?,
+            retrieved_gtid_set: get_opt(row, "Retrieved_Gtid_Set")?,
+            executed_gtid_set: get_opt(row, "Executed_Gtid_Set")?,
+        })
+    }
+}
+
+/// Returns value of a column (`NULL` or missing column is an error).
+pub(crate) fn get<T: FromValue>(row: &Row, name: &str) -> Result<T> {
+    match get_opt(row, name)? {
+        Some(value) => Ok(value),
+        None => Err(DriverError::FromRow { row: row.clone() }.into()),
+    }
+}
+
+/// Returns value of a column (`None` for `NULL` or missing column).
+pub(crate) fn get_opt<T: FromValue>(row: &Row, name: &str) -> Result<Option<T>> {
+    match row.get::<Value, _>(name) {
+        None | Some(Value::NULL) => Ok(None),
+        Some(value) => from_value_opt(value)
+            .map(Some)
+            .map_err(|err| DriverError::FromValue { value: err.0 }.into()),
+    }
+}
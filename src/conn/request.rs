@@ -0,0 +1,84 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use ::futures::future::{Either::*, Future};
+
+use crate::{
+    conn::Conn,
+    queryable::{Protocol, QueryResult, Queryable},
+    MyFuture, Params, Row,
+};
+
+/// Query executed by `MysqlService` or `ConnHandle`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryRequest {
+    pub query: String,
+    /// Query is executed as a prepared statement unless params are `Params::Empty`.
+    pub params: Params,
+}
+
+impl QueryRequest {
+    pub fn new<Q, P>(query: Q, params: P) -> QueryRequest
+    where
+        Q: Into<String>,
+        P: Into<Params>,
+    {
+        QueryRequest {
+            query: query.into(),
+            params: params.into(),
+        }
+    }
+}
+
+impl<'a> From<&'a str> for QueryRequest {
+    fn from(query: &'a str) -> QueryRequest {
+        QueryRequest::new(query, ())
+    }
+}
+
+impl From<String> for QueryRequest {
+    fn from(query: String) -> QueryRequest {
+        QueryRequest::new(query, ())
+    }
+}
+
+/// Result of a `QueryRequest`.
+#[derive(Debug, Clone)]
+pub struct QueryResponse {
+    /// Rows of the first result set (other result sets are dropped).
+    pub rows: Vec<Row>,
+    pub affected_rows: u64,
+    pub last_insert_id: Option<u64>,
+}
+
+/// Returns future that executes `request` and resolves to the connection and the response.
+pub(crate) fn execute(conn: Conn, request: QueryRequest) -> impl MyFuture<(Conn, QueryResponse)> {
+    let QueryRequest { query, params } = request;
+    match params {
+        Params::Empty => A(conn.query(query).and_then(collect)),
+        params => B(conn.prep_exec(query, params).and_then(collect)),
+    }
+}
+
+fn collect<P>(result: QueryResult<Conn, P>) -> impl MyFuture<(Conn, QueryResponse)>
+where
+    P: Protocol,
+{
+    result.collect::<Row>().and_then(|(result, rows)| {
+        let affected_rows = result.affected_rows();
+        let last_insert_id = result.last_insert_id();
+        result.drop_result().map(move |conn| {
+            let response = QueryResponse {
+                rows,
+                affected_rows,
+                last_insert_id,
+            };
+            (conn, response)
+        })
+    })
+}
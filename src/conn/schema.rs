@@ -0,0 +1,171 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use crate::{
+    conn::replication::{get, get_opt},
+    connection_like::ConnectionLike,
+    error::*,
+    escape::escape_identifier,
+    queryable::stmt::Stmt,
+    Column, Row,
+};
+
+/// Columns of a table ordered by their position (see `Conn::table_columns`).
+pub(crate) const TABLE_COLUMNS: &str = "SELECT COLUMN_NAME AS name, \
+     ORDINAL_POSITION AS position, COLUMN_TYPE AS column_type, IS_NULLABLE AS nullable, \
+     COLUMN_KEY AS column_key, COLUMN_DEFAULT AS column_default, EXTRA AS extra, \
+     GENERATION_EXPRESSION AS generation_expression \
+     FROM information_schema.COLUMNS \
+     WHERE TABLE_SCHEMA = COALESCE(?, DATABASE()) AND TABLE_NAME = ? \
+     ORDER BY ORDINAL_POSITION";
+
+/// Expression of a generated column.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Generated {
+    /// `true` for a `STORED` column, `false` for a `VIRTUAL` one.
+    pub stored: bool,
+    pub expression: String,
+}
+
+/// Column definition of a table (see `Conn::table_columns`).
+///
+/// Result set metadata (`Column`) doesn't carry these properties, so they're read
+/// from `information_schema.COLUMNS`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TableColumn {
+    pub name: String,
+    /// Position of the column in the table (starting from `1`).
+    pub position: u32,
+    /// Full type of the column (e.g. `int unsigned` or `varchar(255)`).
+    pub column_type: String,
+    pub nullable: bool,
+    /// `PRI`, `UNI`, `MUL` or an empty string.
+    pub key: String,
+    /// Default value of the column (`None` if there is no default or it is `NULL`).
+    pub default: Option<String>,
+    /// `None` unless this is a generated column.
+    pub generated: Option<Generated>,
+    /// `true` if this is an `INVISIBLE` column (MySql 8.0.23+), i.e. it is omitted
+    /// by `SELECT *` and by `INSERT` without a column list.
+    pub invisible: bool,
+    pub auto_increment: bool,
+    /// Raw value of the `EXTRA` column.
+    pub extra: String,
+}
+
+impl TableColumn {
+    /// Parses a row of `TABLE_COLUMNS`.
+    pub(crate) fn from_row(row: &Row) -> Result<TableColumn> {
+        let extra: String = get_opt(row, "extra")?.unwrap_or_default();
+        let extra_upper = extra.to_ascii_uppercase();
+        // `DEFAULT_GENERATED` (MySql 8.0.13+) marks an expression default, not a generated column.
+        let stored = extra_upper.contains("STORED GENERATED");
+        let generated = if stored || extra_upper.contains("VIRTUAL GENERATED") {
+            Some(Generated {
+                stored,
+                expression: get_opt(row, "generation_expression")?.unwrap_or_default(),
+            })
+        } else {
+            None
+        };
+        Ok(TableColumn {
+            name: get(row, "name")?,
+            position: get(row, "position")?,
+            column_type: get(row, "column_type")?,
+            nullable: get::<String>(row, "nullable")? == "YES",
+            key: get_opt(row, "column_key")?.unwrap_or_default(),
+            default: get_opt(row, "column_default")?,
+            generated,
+            invisible: extra_upper.contains("INVISIBLE"),
+            auto_increment: extra_upper.contains("AUTO_INCREMENT"),
+            extra,
+        })
+    }
+}
+
+/// Parameters and result set columns of a statement (see `Conn::describe`).
+///
+/// Names, types and nullability of columns are available via `Column` methods
+/// and `ColumnExt`.
+#[derive(Debug, Clone)]
+pub struct StmtDescription {
+    /// Metadata of parameters in order of their positions.
+    ///
+    /// Note that servers don't infer types of parameters, so their types are placeholders
+    /// (usually `MYSQL_TYPE_VAR_STRING`).
+    pub params: Vec<Column>,
+    /// Names of named parameters in order of their positions (empty for a statement
+    /// with positional parameters).
+    pub named_params: Vec<String>,
+    /// Metadata of result set columns (empty if the statement doesn't return rows).
+    pub columns: Vec<Column>,
+}
+
+impl StmtDescription {
+    pub(crate) fn new<T: ConnectionLike + 'static>(stmt: &Stmt<T>) -> StmtDescription {
+        StmtDescription {
+            params: stmt.params_ref().to_vec(),
+            named_params: stmt.named_params_ref().to_vec(),
+            columns: stmt.columns_ref().to_vec(),
+        }
+    }
+}
+
+/// Splits a (possibly qualified) table name into schema and table.
+pub(crate) fn split_table_name(table: &str) -> (Option<String>, String) {
+    let unquote = |name: &str| name.trim_matches('`').replace("``", "`");
+    let dot = match table.find("`.`") {
+        Some(i) => Some(i + 1),
+        None if table.starts_with('`') => None,
+        None => table.find('.'),
+    };
+    match dot {
+        Some(i) => (Some(unquote(&table[..i])), unquote(&table[i + 1..])),
+        None => (None, unquote(table)),
+    }
+}
+
+/// Quotes a (possibly qualified) table name, so that it can be used in a query
+/// (see `split_table_name`).
+pub(crate) fn quote_table_name(table: &str) -> String {
+    match split_table_name(table) {
+        (Some(schema), table) => format!(
+            "{}.{}",
+            escape_identifier(&schema),
+            escape_identifier(&table)
+        ),
+        (None, table) => escape_identifier(&table),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{quote_table_name, split_table_name};
+
+    #[test]
+    fn should_split_table_name() {
+        assert_eq!(split_table_name("t"), (None, "t".into()));
+        assert_eq!(split_table_name("db.t"), (Some("db".into()), "t".into()));
+        assert_eq!(
+            split_table_name("`d.b`.`t``1`"),
+            (Some("d.b".into()), "t`1".into())
+        );
+        assert_eq!(split_table_name("`a.b`"), (None, "a.b".into()));
+    }
+
+    #[test]
+    fn should_quote_table_name() {
+        assert_eq!(quote_table_name("t"), "`t`");
+        assert_eq!(quote_table_name("db.t"), "`db`.`t`");
+        assert_eq!(quote_table_name("`d.b`.`t``1`"), "`d.b`.`t``1`");
+        assert_eq!(
+            quote_table_name("t (a INT); DROP TABLE u; --"),
+            "`t (a INT); DROP TABLE u; --`"
+        );
+    }
+}
@@ -53,6 +53,21 @@ impl StmtCache {
         }
     }
 
+    /// Returns cached statement by its id (without touching the order of entries).
+    pub fn get_by_id_mut(&mut self, statement_id: u32) -> Option<&mut InnerStmt> {
+        self.map
+            .values_mut()
+            .find(|inner_stmt| inner_stmt.statement_id == statement_id)
+    }
+
+    /// Returns query of a cached statement by its id.
+    pub fn query_by_id(&self, statement_id: u32) -> Option<&str> {
+        self.map
+            .iter()
+            .find(|(_, inner_stmt)| inner_stmt.statement_id == statement_id)
+            .map(|(query, _)| &**query)
+    }
+
     pub fn put(&mut self, key: String, value: InnerStmt) -> Option<InnerStmt> {
         self.map.insert(key.clone(), value);
         self.order.push_back(key);
@@ -65,6 +80,17 @@ impl StmtCache {
         }
     }
 
+    /// Removes the least recently used statement.
+    pub fn pop_lru(&mut self) -> Option<InnerStmt> {
+        self.order
+            .pop_front()
+            .and_then(|query| self.map.remove(&query))
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
     pub fn clear(&mut self) {
         self.map.clear();
         self.order.clear();
@@ -83,3 +109,31 @@ impl StmtCache {
         self.cap
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::StmtCache;
+    use crate::queryable::stmt::InnerStmt;
+
+    fn inner_stmt(statement_id: u32) -> InnerStmt {
+        let mut payload = vec![0x00];
+        payload.extend_from_slice(&statement_id.to_le_bytes());
+        payload.extend_from_slice(&[0; 7]);
+        InnerStmt::new(&payload, None).unwrap()
+    }
+
+    #[test]
+    fn should_evict_least_recently_used_statements() {
+        let mut cache = StmtCache::new(3);
+        cache.put("a".into(), inner_stmt(1));
+        cache.put("b".into(), inner_stmt(2));
+        cache.put("c".into(), inner_stmt(3));
+        assert!(cache.get("a").is_some());
+        assert_eq!(cache.query_by_id(2), Some("b"));
+        assert_eq!(cache.query_by_id(4), None);
+
+        assert_eq!(cache.pop_lru().map(|x| x.statement_id), Some(2));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.iter().collect::<Vec<_>>(), vec!["c", "a"]);
+    }
+}
@@ -0,0 +1,33 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use crate::{
+    conn::replication::{get, get_opt},
+    error::*,
+    Row,
+};
+
+/// Warning (or note) of a statement reported by `SHOW WARNINGS` (see `Conn::last_warnings`).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Warning {
+    /// `Note`, `Warning` or `Error`.
+    pub level: String,
+    pub code: u16,
+    pub message: String,
+}
+
+impl Warning {
+    /// Parses a row of `SHOW WARNINGS`.
+    pub(crate) fn from_row(row: &Row) -> Result<Warning> {
+        Ok(Warning {
+            level: get(row, "Level")?,
+            code: get(row, "Code")?,
+            message: get_opt(row, "Message")?.unwrap_or_default(),
+        })
+    }
+}
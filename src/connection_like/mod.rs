@@ -10,28 +10,40 @@ use byteorder::{ByteOrder, LittleEndian};
 use futures::future::{err, loop_fn, ok, Either::*, Future, IntoFuture, Loop};
 use mysql_common::{
     io::ReadMysqlExt,
-    packets::{column_from_payload, parse_local_infile_packet, Column, RawPacket},
+    packets::{
+        column_from_payload, parse_err_packet, parse_local_infile_packet, parse_ok_packet, Column,
+        RawPacket,
+    },
 };
 use tokio_io::io::read;
 
 use std::sync::Arc;
 
 use crate::{
-    conn::{named_params::parse_named_params, stmt_cache::StmtCache},
+    conn::{
+        columns_cache::ColumnsCache,
+        journal::{CommandOutcome, CommandRecord},
+        named_params::parse_named_params,
+        stmt_cache::StmtCache,
+        warnings::Warning,
+    },
     connection_like::{read_packet::ReadPacket, streamless::Streamless, write_packet::WritePacket},
     consts::{CapabilityFlags, Command, StatusFlags},
     error::*,
     io,
     local_infile_handler::LocalInfileHandler,
     queryable::{
+        classify,
         query_result::{self, QueryResult},
         stmt::InnerStmt,
-        Protocol,
+        Protocol, TextProtocol,
     },
-    BoxFuture, MyFuture, Opts,
+    BoxFuture, MyFuture, Opts, QueryDigest,
 };
 
 pub mod read_packet;
+#[cfg(feature = "tracing")]
+pub(crate) mod spans;
 pub mod streamless {
     use super::ConnectionLike;
     use crate::io::Stream;
@@ -57,6 +69,22 @@ pub enum StmtCacheResult {
     NotCached(u32),
 }
 
+/// Server-side cursor opened by a statement execution (see `Stmt::fetch_hint`).
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Cursor {
+    pub statement_id: u32,
+    /// Number of rows requested by every `COM_STMT_FETCH`.
+    pub fetch_size: u32,
+}
+
+/// Last statement of a connection, saved while an internal query (e.g. `SHOW WARNINGS`)
+/// is performed (see `ConnectionLike::take_last_statement`).
+#[derive(Debug, Default)]
+pub struct LastStatement {
+    pub(crate) query: Option<String>,
+    pub(crate) executed_stmt: Option<u32>,
+}
+
 pub trait ConnectionLikeWrapper {
     type ConnLike: ConnectionLike;
 
@@ -94,10 +122,18 @@ where
         self.conn_like_mut().stmt_cache_mut()
     }
 
+    fn columns_cache_mut(&mut self) -> &mut ColumnsCache {
+        self.conn_like_mut().columns_cache_mut()
+    }
+
     fn get_affected_rows(&self) -> u64 {
         self.conn_like_ref().get_affected_rows()
     }
 
+    fn get_info(&self) -> Option<&str> {
+        self.conn_like_ref().get_info()
+    }
+
     fn get_capabilities(&self) -> CapabilityFlags {
         self.conn_like_ref().get_capabilities()
     }
@@ -130,6 +166,10 @@ where
         self.conn_like_ref().get_pending_result()
     }
 
+    fn get_cursor(&self) -> Option<Cursor> {
+        self.conn_like_ref().get_cursor()
+    }
+
     fn get_server_version(&self) -> (u16, u16, u16) {
         self.conn_like_ref().get_server_version()
     }
@@ -142,10 +182,22 @@ where
         self.conn_like_ref().get_seq_id()
     }
 
+    fn get_warnings(&self) -> u16 {
+        self.conn_like_ref().get_warnings()
+    }
+
+    fn get_session_generation(&self) -> u64 {
+        self.conn_like_ref().get_session_generation()
+    }
+
     fn set_affected_rows(&mut self, affected_rows: u64) {
         self.conn_like_mut().set_affected_rows(affected_rows);
     }
 
+    fn set_info(&mut self, info: Option<String>) {
+        self.conn_like_mut().set_info(info);
+    }
+
     fn set_in_transaction(&mut self, in_transaction: bool) {
         self.conn_like_mut().set_in_transaction(in_transaction);
     }
@@ -162,6 +214,46 @@ where
         self.conn_like_mut().set_pending_result(meta);
     }
 
+    fn set_cursor(&mut self, cursor: Option<Cursor>) {
+        self.conn_like_mut().set_cursor(cursor);
+    }
+
+    fn set_last_query(&mut self, query: &str) {
+        self.conn_like_mut().set_last_query(query);
+    }
+
+    fn take_last_statement(&mut self) -> LastStatement {
+        self.conn_like_mut().take_last_statement()
+    }
+
+    fn set_last_statement(&mut self, statement: LastStatement) {
+        self.conn_like_mut().set_last_statement(statement);
+    }
+
+    fn record_command(&mut self, command: Command, data: &[u8]) {
+        self.conn_like_mut().record_command(command, data);
+    }
+
+    fn record_outcome(&mut self, outcome: CommandOutcome) {
+        self.conn_like_mut().record_outcome(outcome);
+    }
+
+    fn get_recent_commands(&self) -> Vec<CommandRecord> {
+        self.conn_like_ref().get_recent_commands()
+    }
+
+    fn read_only_violation(&mut self, error: ServerError) -> Error {
+        self.conn_like_mut().read_only_violation(error)
+    }
+
+    fn get_result_bytes(&self) -> u64 {
+        self.conn_like_ref().get_result_bytes()
+    }
+
+    fn add_result_bytes(&mut self, bytes: u64) {
+        self.conn_like_mut().add_result_bytes(bytes);
+    }
+
     fn set_status(&mut self, status: StatusFlags) -> () {
         self.conn_like_mut().set_status(status);
     }
@@ -170,6 +262,10 @@ where
         self.conn_like_mut().set_warnings(warnings);
     }
 
+    fn set_last_warnings(&mut self, warnings: Vec<Warning>) {
+        self.conn_like_mut().set_last_warnings(warnings);
+    }
+
     fn set_seq_id(&mut self, seq_id: u8) -> () {
         self.conn_like_mut().set_seq_id(seq_id);
     }
@@ -190,7 +286,9 @@ pub trait ConnectionLike: Send {
     fn return_stream(&mut self, stream: io::Stream) -> ();
     fn stmt_cache_ref(&self) -> &StmtCache;
     fn stmt_cache_mut(&mut self) -> &mut StmtCache;
+    fn columns_cache_mut(&mut self) -> &mut ColumnsCache;
     fn get_affected_rows(&self) -> u64;
+    fn get_info(&self) -> Option<&str>;
     fn get_capabilities(&self) -> CapabilityFlags;
     fn get_in_transaction(&self) -> bool;
     fn get_last_command(&self) -> Command;
@@ -199,20 +297,53 @@ pub trait ConnectionLike: Send {
     fn get_max_allowed_packet(&self) -> u64;
     fn get_opts(&self) -> &Opts;
     fn get_pending_result(&self) -> Option<&(Arc<Vec<Column>>, Option<StmtCacheResult>)>;
+    fn get_cursor(&self) -> Option<Cursor>;
     fn get_server_version(&self) -> (u16, u16, u16);
     fn get_status(&self) -> StatusFlags;
     fn get_seq_id(&self) -> u8;
+    fn get_warnings(&self) -> u16;
+    /// Changes every time server-side prepared statements are discarded
+    /// (see `Stmt` re-preparation).
+    fn get_session_generation(&self) -> u64;
     fn set_affected_rows(&mut self, affected_rows: u64);
+    fn set_info(&mut self, info: Option<String>);
     fn set_in_transaction(&mut self, in_transaction: bool);
     fn set_last_command(&mut self, last_command: Command);
     fn set_last_insert_id(&mut self, last_insert_id: u64);
     fn set_pending_result(&mut self, meta: Option<(Arc<Vec<Column>>, Option<StmtCacheResult>)>);
+    fn set_cursor(&mut self, cursor: Option<Cursor>);
+    fn set_last_query(&mut self, query: &str);
+    /// Takes the text (and the statement id) of the last statement, which is used to report
+    /// events and `DriverError::ReadOnlyViolation`.
+    fn take_last_statement(&mut self) -> LastStatement;
+    fn set_last_statement(&mut self, statement: LastStatement);
+    fn record_command(&mut self, command: Command, data: &[u8]);
+    fn record_outcome(&mut self, outcome: CommandOutcome);
+    fn get_recent_commands(&self) -> Vec<CommandRecord>;
+    /// Converts a server error of a write rejected by a read-only server
+    /// into `DriverError::ReadOnlyViolation`.
+    fn read_only_violation(&mut self, error: ServerError) -> Error;
+    fn get_result_bytes(&self) -> u64;
+    fn add_result_bytes(&mut self, bytes: u64);
     fn set_status(&mut self, status: StatusFlags);
     fn set_warnings(&mut self, warnings: u16);
+    fn set_last_warnings(&mut self, warnings: Vec<Warning>);
     fn set_seq_id(&mut self, seq_id: u8);
     fn touch(&mut self) -> ();
     fn on_disconnect(&mut self);
 
+    /// Stores the human-readable info of an OK `packet` (see `Conn::info`).
+    ///
+    /// Called only where a packet is known to be an OK packet, because the first byte
+    /// of a binary protocol row is also `0x00`.
+    fn set_info_from_ok_packet(&mut self, packet: &RawPacket) {
+        let info = parse_ok_packet(&*packet.0, self.get_capabilities())
+            .ok()
+            .and_then(|ok_packet| ok_packet.info_str().map(|info| info.into_owned()))
+            .filter(|info| !info.is_empty());
+        self.set_info(info);
+    }
+
     fn cache_stmt(mut self, query: String, stmt: &InnerStmt) -> BoxFuture<(Self, StmtCacheResult)>
     where
         Self: Sized + 'static,
@@ -271,6 +402,7 @@ pub trait ConnectionLike: Send {
         Q: AsRef<str>,
         Self: Sized + 'static,
     {
+        self.set_last_query(query.as_ref());
         match parse_named_params(query.as_ref()) {
             Ok((named_params, query)) => {
                 let query = query.into_owned();
@@ -278,13 +410,16 @@ pub trait ConnectionLike: Send {
                     inner_stmt.named_params = named_params.clone();
                     Box::new(ok((self, inner_stmt, StmtCacheResult::Cached)))
                 } else {
+                    let limit_params = classify::limit_params(&query);
                     let fut = self
-                        .write_command_data(Command::COM_STMT_PREPARE, &*query)
-                        .and_then(|this| this.read_packet())
+                        .write_stmt_prepare(query.clone(), true)
                         .and_then(|(this, packet)| {
                             InnerStmt::new(&*packet.0, named_params)
+                                .map(|mut inner_stmt| {
+                                    inner_stmt.limit_params = limit_params;
+                                    (this, inner_stmt)
+                                })
                                 .into_future()
-                                .map(|inner_stmt| (this, inner_stmt))
                         })
                         .and_then(|(this, mut inner_stmt)| {
                             this.read_packets(inner_stmt.num_params as usize)
@@ -375,6 +510,70 @@ pub trait ConnectionLike: Send {
         }
     }
 
+    /// Sends `COM_STMT_PREPARE` and resolves to the first packet of the response.
+    ///
+    /// If the server refuses to prepare a statement because `max_prepared_stmt_count`
+    /// is reached, then the least recently used half of the statement cache is closed
+    /// and the command is retried once (see `EventHandler::prepared_stmts_evicted`).
+    fn write_stmt_prepare(self, query: String, retry: bool) -> BoxFuture<(Self, RawPacket)>
+    where
+        Self: Sized + 'static,
+    {
+        #[cfg(feature = "failpoints")]
+        let response = match crate::failpoints::on_stmt_prepare() {
+            Some(packet) => A(ok((self, packet))),
+            None => B(self
+                .write_command_data(Command::COM_STMT_PREPARE, &*query)
+                .and_then(ReadPacket::lenient)),
+        };
+        #[cfg(not(feature = "failpoints"))]
+        let response = self
+            .write_command_data(Command::COM_STMT_PREPARE, &*query)
+            .and_then(ReadPacket::lenient);
+        let fut = response.and_then(move |(this, packet)| {
+            let mut error = match parse_err_packet(&*packet.0, this.get_capabilities()) {
+                Ok(err_packet) => ServerError::from(err_packet),
+                Err(_) => return A(ok((this, packet))),
+            };
+            let evict = (this.stmt_cache_ref().len() + 1) / 2;
+            if retry
+                && evict > 0
+                && error.kind() == Some(ServerErrorCode::ER_MAX_PREPARED_STMT_COUNT_REACHED)
+            {
+                if let Some(handler) = this.get_opts().get_event_handler() {
+                    handler.prepared_stmts_evicted(&*query, evict);
+                }
+                return B(A(this
+                    .evict_stmts(evict)
+                    .and_then(move |this| this.write_stmt_prepare(query, false))));
+            }
+            error.recent_commands = this.get_recent_commands();
+            B(B(err(error.into())))
+        });
+        Box::new(fut)
+    }
+
+    /// Closes `count` least recently used statements of the statement cache.
+    fn evict_stmts(mut self, count: usize) -> BoxFuture<Self>
+    where
+        Self: Sized + 'static,
+    {
+        let mut ids = Vec::with_capacity(count);
+        while let Some(inner_stmt) = self.stmt_cache_mut().pop_lru() {
+            ids.push(inner_stmt.statement_id);
+            if ids.len() == count {
+                break;
+            }
+        }
+        let fut = loop_fn((self, ids), |(this, mut ids)| match ids.pop() {
+            Some(statement_id) => A(this
+                .close_stmt(statement_id)
+                .map(|this| Loop::Continue((this, ids)))),
+            None => B(ok(Loop::Break(this))),
+        });
+        Box::new(fut)
+    }
+
     fn close_stmt(self, statement_id: u32) -> WritePacket<Self>
     where
         Self: Sized + 'static,
@@ -384,6 +583,48 @@ pub trait ConnectionLike: Send {
         self.write_command_data(Command::COM_STMT_CLOSE, &stmt_id[..])
     }
 
+    /// Returns `true` if warnings of the last statement should be read using `fetch_warnings`
+    /// (see `OptsBuilder::fetch_warnings`).
+    fn has_warnings_to_fetch(&self) -> bool {
+        self.get_opts().get_fetch_warnings() && self.get_warnings() > 0
+    }
+
+    /// Returns future that reads warnings of the last statement using `SHOW WARNINGS`
+    /// (see `OptsBuilder::fetch_warnings`).
+    ///
+    /// Affected rows, last insert id, info, the warning count and the text of the statement
+    /// are preserved.
+    fn fetch_warnings(mut self) -> BoxFuture<Self>
+    where
+        Self: Sized + 'static,
+    {
+        let affected_rows = self.get_affected_rows();
+        let last_insert_id = self.get_last_insert_id().unwrap_or(0);
+        let info = self.get_info().map(String::from);
+        let warning_count = self.get_warnings();
+        let statement = self.take_last_statement();
+        self.set_last_query("SHOW WARNINGS");
+        let fut = self
+            .write_command_data(Command::COM_QUERY, "SHOW WARNINGS")
+            .and_then(|this| this.read_packet())
+            .and_then(|(this, packet)| this.handle_result_packet::<TextProtocol>(packet, None))
+            .and_then(|result| result.read_current_rows())
+            .and_then(move |(mut this, _, rows)| {
+                let warnings = rows
+                    .iter()
+                    .map(Warning::from_row)
+                    .collect::<Result<Vec<_>>>()?;
+                this.set_affected_rows(affected_rows);
+                this.set_last_insert_id(last_insert_id);
+                this.set_info(info);
+                this.set_warnings(warning_count);
+                this.set_last_statement(statement);
+                this.set_last_warnings(warnings);
+                Ok(this)
+            });
+        Box::new(fut)
+    }
+
     /// Returns future that reads result set from a server and resolves to `QueryResult`.
     fn read_result_set<P>(self, cached: Option<StmtCacheResult>) -> BoxFuture<QueryResult<Self, P>>
     where
@@ -393,11 +634,30 @@ pub trait ConnectionLike: Send {
     {
         let fut = self
             .read_packet()
-            .and_then(|(this, packet)| match packet.0[0] {
-                0x00 => A(A(ok(query_result::new(this, None, cached)))),
-                0xFB => A(B(handle_local_infile(this, packet, cached))),
-                _ => B(handle_result_set(this, packet, cached)),
-            });
+            .and_then(|(this, packet)| this.handle_result_packet(packet, cached));
+        Box::new(fut)
+    }
+
+    /// Returns future that handles the first `packet` of a response to a query
+    /// and resolves to `QueryResult`.
+    fn handle_result_packet<P>(
+        mut self,
+        packet: RawPacket,
+        cached: Option<StmtCacheResult>,
+    ) -> BoxFuture<QueryResult<Self, P>>
+    where
+        Self: Sized + 'static,
+        P: Protocol,
+        P: Send + 'static,
+    {
+        let fut = match packet.0[0] {
+            0x00 => {
+                self.set_info_from_ok_packet(&packet);
+                A(A(ok(query_result::new(self, None, cached))))
+            }
+            0xFB => A(B(handle_local_infile(self, packet, cached))),
+            _ => B(handle_result_set(self, packet, cached)),
+        };
         Box::new(fut)
     }
 
@@ -410,20 +670,116 @@ pub trait ConnectionLike: Send {
         WritePacket::new(self, data)
     }
 
+    /// Returns future that requests next rows of an open `cursor` (`COM_STMT_FETCH`).
+    fn fetch_cursor_rows(self, cursor: Cursor) -> WritePacket<Self>
+    where
+        Self: Sized + 'static,
+    {
+        let mut data = [0u8; 8];
+        LittleEndian::write_u32(&mut data[..4], cursor.statement_id);
+        LittleEndian::write_u32(&mut data[4..], cursor.fetch_size);
+        self.write_command_data(Command::COM_STMT_FETCH, data)
+    }
+
     /// Returns future that writes command to a server end resolves to `Self`.
     fn write_command_data<T>(mut self, cmd: Command, cmd_data: T) -> WritePacket<Self>
     where
         Self: Sized + 'static,
         T: AsRef<[u8]>,
     {
-        let mut data = Vec::with_capacity(1 + cmd_data.as_ref().len());
+        let rewritten = match cmd {
+            Command::COM_QUERY | Command::COM_STMT_PREPARE => {
+                rewrite_query(self.get_opts(), cmd_data.as_ref())
+            }
+            _ => None,
+        };
+        let cmd_data = match rewritten {
+            Some(ref query) => query.as_bytes(),
+            None => cmd_data.as_ref(),
+        };
+
+        let len = 1 + cmd_data.len();
+        let max_allowed_packet = self.get_max_allowed_packet();
+        if len as u64 > max_allowed_packet {
+            let error = DriverError::PacketTooLarge {
+                len,
+                max_allowed_packet,
+            };
+            return WritePacket::failed(self, error.into());
+        }
+
+        if self.get_opts().get_enforce_read_only() {
+            if let Command::COM_QUERY | Command::COM_STMT_PREPARE = cmd {
+                let query = String::from_utf8_lossy(cmd_data);
+                let write = classify::statement_verbs(&query)
+                    .into_iter()
+                    .find(|verb| !classify::is_read_only_verb(verb));
+                if let Some(verb) = write {
+                    let error = DriverError::WriteInReadOnlyMode { verb };
+                    return WritePacket::failed(self, error.into());
+                }
+            }
+        }
+
+        let reject_server_side_files = self.get_opts().get_reject_server_side_files();
+        let handler = self
+            .get_opts()
+            .get_event_handler()
+            .filter(|_| self.get_opts().get_report_server_side_files());
+        if reject_server_side_files || handler.is_some() {
+            if let Command::COM_QUERY | Command::COM_STMT_PREPARE = cmd {
+                let query = String::from_utf8_lossy(cmd_data);
+                if let Some(clause) = classify::server_side_file_clause(&query) {
+                    if reject_server_side_files {
+                        let error = DriverError::ServerSideFile {
+                            clause: clause.into(),
+                        };
+                        return WritePacket::failed(self, error.into());
+                    }
+                    if let Some(handler) = handler {
+                        if self.get_opts().get_redact_queries() {
+                            handler.server_side_file(&QueryDigest::new(&*query).into_text());
+                        } else {
+                            handler.server_side_file(&query);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut data = Vec::with_capacity(len);
         data.push(cmd as u8);
-        data.extend_from_slice(cmd_data.as_ref());
+        data.extend_from_slice(cmd_data);
+        self.record_command(cmd, cmd_data);
         self.set_seq_id(0);
         self.write_packet(data)
     }
 }
 
+/// Applies `OptsBuilder::query_rewriter` to the text of a query (`None` if it's unchanged).
+fn rewrite_query(opts: &Opts, query: &[u8]) -> Option<String> {
+    let rewriter = opts.get_query_rewriter()?;
+    let original = String::from_utf8_lossy(query);
+    let rewritten = rewriter.rewrite(&original)?;
+    if let Some(handler) = opts.get_event_handler() {
+        if opts.get_redact_queries() {
+            handler.query_rewritten(
+                QueryDigest::new(&*original).text(),
+                QueryDigest::new(&*rewritten).text(),
+            );
+        } else {
+            handler.query_rewritten(&original, &rewritten);
+        }
+    }
+    Some(rewritten)
+}
+
+/// Size of a packet of local infile data.
+///
+/// Every chunk is sent as a separate packet, so it must fit into the server's
+/// `max_allowed_packet` (4MiB by default).
+const LOCAL_INFILE_CHUNK_LEN: usize = 64 * 1024;
+
 /// Will handle local infile packet.
 fn handle_local_infile<T, P>(
     this: T,
@@ -444,30 +800,29 @@ where
         .into_future()
         .and_then(|(local_infile, handler)| handler.handle(local_infile.file_name_ref()))
         .and_then(|reader| {
-            let mut buf = Vec::with_capacity(4096);
-            unsafe {
-                buf.set_len(4096);
-            }
-            loop_fn((this, buf, reader), |(this, buf, reader)| {
-                read(reader, buf)
-                    .map_err(Into::into)
-                    .and_then(|(reader, mut buf, count)| {
-                        unsafe {
-                            buf.set_len(count);
-                        }
-                        this.write_packet(&buf[..count])
-                            .map(move |this| (this, buf, reader, count))
-                    })
-                    .map(|(this, buf, reader, count)| {
-                        if count > 0 {
-                            Loop::Continue((this, buf, reader))
-                        } else {
-                            Loop::Break(this)
-                        }
-                    })
-            })
+            loop_fn(
+                (this, vec![0; LOCAL_INFILE_CHUNK_LEN], reader),
+                |(this, buf, reader)| {
+                    read(reader, buf)
+                        .map_err(Into::into)
+                        .and_then(|(reader, buf, count)| {
+                            this.write_packet(&buf[..count])
+                                .map(move |this| (this, buf, reader, count))
+                        })
+                        .map(|(this, buf, reader, count)| {
+                            if count > 0 {
+                                Loop::Continue((this, buf, reader))
+                            } else {
+                                Loop::Break(this)
+                            }
+                        })
+                },
+            )
             .and_then(|this| this.read_packet())
-            .map(|(this, _)| query_result::new(this, None, cached))
+            .map(|(mut this, packet)| {
+                this.set_info_from_ok_packet(&packet);
+                query_result::new(this, None, cached)
+            })
         })
 }
 
@@ -488,25 +843,20 @@ where
         .map_err(Into::into)
         .into_future()
         .and_then(|column_count| this.read_packets(column_count as usize))
-        .and_then(|(this, packets)| {
-            packets
-                .into_iter()
-                .map(|packet| column_from_payload(packet.0).map_err(Error::from))
-                .collect::<Result<Vec<Column>>>()
-                .into_future()
-                .and_then(|columns| {
-                    if this
-                        .get_capabilities()
-                        .contains(CapabilityFlags::CLIENT_DEPRECATE_EOF)
-                    {
-                        A(ok((this, columns)))
-                    } else {
-                        B(this.read_packet().map(|(this, _)| (this, columns)))
-                    }
-                })
+        .and_then(|(mut this, packets)| {
+            let columns = this.columns_cache_mut().get_or_parse(packets);
+            columns.into_future().and_then(|columns| {
+                if this
+                    .get_capabilities()
+                    .contains(CapabilityFlags::CLIENT_DEPRECATE_EOF)
+                {
+                    A(ok((this, columns)))
+                } else {
+                    B(this.read_packet().map(|(this, _)| (this, columns)))
+                }
+            })
         })
         .map(|(mut this, columns)| {
-            let columns = Arc::new(columns);
             this.set_pending_result(Some((Clone::clone(&columns), None)));
             query_result::new(this, Some(columns), cached)
         })
@@ -15,6 +15,7 @@ use futures::{
 use mysql_common::packets::{parse_err_packet, parse_ok_packet, RawPacket};
 
 use crate::{
+    conn::journal::CommandOutcome,
     connection_like::{streamless::Streamless, ConnectionLike},
     error::*,
     io,
@@ -23,6 +24,8 @@ use crate::{
 pub struct ReadPacket<T> {
     conn_like: Option<Streamless<T>>,
     fut: StreamFuture<io::Stream>,
+    /// Whether an error packet should be returned as is instead of failing the future.
+    lenient: bool,
 }
 
 impl<T: ConnectionLike> ReadPacket<T> {
@@ -31,6 +34,16 @@ impl<T: ConnectionLike> ReadPacket<T> {
         ReadPacket {
             conn_like: Some(incomplete_conn),
             fut: stream.into_future(),
+            lenient: false,
+        }
+    }
+
+    /// Returns future that resolves to an error packet instead of failing,
+    /// so that the connection survives a server error.
+    pub fn lenient(conn_like: T) -> Self {
+        ReadPacket {
+            lenient: true,
+            ..ReadPacket::new(conn_like)
         }
     }
 }
@@ -44,15 +57,32 @@ impl<T: ConnectionLike> Future for ReadPacket<T> {
         let mut conn_like = self.conn_like.take().unwrap().return_stream(stream);
         match packet_opt {
             Some((packet, seq_id)) => {
+                #[cfg(feature = "failpoints")]
+                let packet = {
+                    let mut packet = packet;
+                    crate::failpoints::on_packet_read(&mut packet)?;
+                    packet
+                };
                 if let Ok(ok_packet) = parse_ok_packet(&*packet.0, conn_like.get_capabilities()) {
                     conn_like.set_affected_rows(ok_packet.affected_rows());
                     conn_like.set_last_insert_id(ok_packet.last_insert_id().unwrap_or(0));
                     conn_like.set_status(ok_packet.status_flags());
                     conn_like.set_warnings(ok_packet.warnings());
+                    conn_like.record_outcome(CommandOutcome::Ok);
                 } else if let Ok(err_packet) =
                     parse_err_packet(&*packet.0, conn_like.get_capabilities())
                 {
-                    return Err(err_packet.into());
+                    let mut error = ServerError::from(err_packet);
+                    conn_like.record_outcome(CommandOutcome::Error(error.code));
+                    if !self.lenient {
+                        error.recent_commands = conn_like.get_recent_commands();
+                        if error.is_read_only()
+                            && conn_like.get_opts().get_detect_read_only_server()
+                        {
+                            return Err(conn_like.read_only_violation(error));
+                        }
+                        return Err(error.into());
+                    }
                 }
 
                 conn_like.touch();
@@ -0,0 +1,91 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! `tracing` spans of commands and transactions (requires `tracing` feature).
+//!
+//! Command span is created when `COM_QUERY`, `COM_STMT_PREPARE` or `COM_STMT_EXECUTE`
+//! is written and is closed once its response is read. Spans of commands executed within
+//! a transaction are children of the transaction span.
+
+use tracing::{field, span, Level, Span};
+
+use std::time::Duration;
+
+use crate::{consts::Command, QueryDigest};
+
+macro_rules! command_span {
+    ($name:expr, $conn_id:expr, $transaction:expr) => {
+        match $transaction {
+            Some(transaction) => span!(
+                parent: transaction,
+                Level::INFO,
+                $name,
+                conn_id = $conn_id,
+                digest = field::Empty,
+                rows = field::Empty,
+                affected_rows = field::Empty,
+                duration_ms = field::Empty,
+                error_code = field::Empty,
+            ),
+            None => span!(
+                Level::INFO,
+                $name,
+                conn_id = $conn_id,
+                digest = field::Empty,
+                rows = field::Empty,
+                affected_rows = field::Empty,
+                duration_ms = field::Empty,
+                error_code = field::Empty,
+            ),
+        }
+    };
+}
+
+/// Returns span of a `command` (`None` if the command isn't traced).
+///
+/// `digest` is only called if the span is enabled.
+pub(crate) fn command_span<F>(
+    conn_id: u32,
+    command: Command,
+    transaction: Option<&Span>,
+    digest: F,
+) -> Option<Span>
+where
+    F: FnOnce() -> Option<QueryDigest>,
+{
+    let span = match command {
+        Command::COM_QUERY => command_span!("mysql.query", conn_id, transaction),
+        Command::COM_STMT_PREPARE => command_span!("mysql.prepare", conn_id, transaction),
+        Command::COM_STMT_EXECUTE => command_span!("mysql.execute", conn_id, transaction),
+        _ => return None,
+    };
+    if !span.is_disabled() {
+        if let Some(digest) = digest() {
+            span.record("digest", &digest.text());
+        }
+    }
+    Some(span)
+}
+
+/// Returns span of a transaction started on the connection.
+pub(crate) fn transaction_span(conn_id: u32) -> Span {
+    span!(Level::INFO, "mysql.transaction", conn_id = conn_id)
+}
+
+/// Records results of a finished query (or statement execution).
+pub(crate) fn finish(span: &Span, rows: u64, affected_rows: u64, duration: Duration) {
+    let duration_ms = duration.as_secs() * 1000 + u64::from(duration.subsec_millis());
+    span.record("rows", &rows);
+    span.record("affected_rows", &affected_rows);
+    span.record("duration_ms", &duration_ms);
+}
+
+/// Records error code of a failed command.
+pub(crate) fn fail(span: &Span, error_code: u16) {
+    span.record("error_code", &error_code);
+}
@@ -16,7 +16,10 @@ use crate::{
 
 pub struct WritePacket<T> {
     conn_like: Option<Streamless<T>>,
-    fut: io::futures::WritePacket,
+    fut: Option<io::futures::WritePacket>,
+    error: Option<Error>,
+    #[cfg(feature = "failpoints")]
+    delay: Option<tokio::timer::Delay>,
 }
 
 impl<T: ConnectionLike> WritePacket<T> {
@@ -25,7 +28,24 @@ impl<T: ConnectionLike> WritePacket<T> {
         let (incomplete_conn, stream) = conn_like.take_stream();
         WritePacket {
             conn_like: Some(incomplete_conn),
-            fut: stream.write_packet(data.into(), seq_id),
+            fut: Some(stream.write_packet(data.into(), seq_id)),
+            error: None,
+            #[cfg(feature = "failpoints")]
+            delay: crate::failpoints::write_delay()
+                .map(|delay| tokio::timer::Delay::new(std::time::Instant::now() + delay)),
+        }
+    }
+
+    /// Returns future that fails with the `error` without writing anything
+    /// (`conn_like` is dropped).
+    pub fn failed(conn_like: T, error: Error) -> WritePacket<T> {
+        drop(conn_like);
+        WritePacket {
+            conn_like: None,
+            fut: None,
+            error: Some(error),
+            #[cfg(feature = "failpoints")]
+            delay: None,
         }
     }
 }
@@ -35,7 +55,19 @@ impl<T: ConnectionLike> Future for WritePacket<T> {
     type Error = Error;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        let (stream, seq_id) = try_ready!(self.fut.poll());
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        #[cfg(feature = "failpoints")]
+        {
+            if let Some(ref mut delay) = self.delay {
+                try_ready!(delay.poll().map_err(|err| {
+                    std::io::Error::new(std::io::ErrorKind::Other, err.to_string())
+                }));
+            }
+            self.delay = None;
+        }
+        let (stream, seq_id) = try_ready!(self.fut.as_mut().unwrap().poll());
         let mut conn_like = self.conn_like.take().unwrap().return_stream(stream);
         conn_like.set_seq_id(seq_id);
         conn_like.touch();
@@ -0,0 +1,340 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Conversions of `BIT`, `ENUM` and `SET` values.
+
+use mysql_common::value::convert::{ConvIr, FromValue, FromValueError};
+
+use std::{collections::BTreeSet, fmt, str::FromStr};
+
+use crate::Value;
+
+/// Value of a `BIT(n)` column.
+///
+/// The server sends `BIT` values as big-endian byte strings (for both protocols), which can't
+/// be told apart from textual numbers without the column type. Use `ColumnDecode::Bit`
+/// to convert raw bytes of a `BIT` column into a number, or `Vec<u8>` to get the raw bytes.
+///
+/// Numbers (and their textual representations) are converted as is.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+pub struct Bit(pub u64);
+
+impl From<Bit> for Value {
+    fn from(bit: Bit) -> Value {
+        Value::UInt(bit.0)
+    }
+}
+
+/// Intermediate result of a `Value` to `Bit` conversion.
+#[derive(Debug)]
+pub struct BitIr {
+    bit: Bit,
+    value: Value,
+}
+
+impl ConvIr<Bit> for BitIr {
+    fn new(value: Value) -> Result<BitIr, FromValueError> {
+        let bit = match value {
+            Value::Bytes(ref bytes) => std::str::from_utf8(bytes)
+                .ok()
+                .and_then(|text| u64::from_str(text).ok()),
+            Value::Int(x) if x >= 0 => Some(x as u64),
+            Value::UInt(x) => Some(x),
+            _ => None,
+        };
+        match bit {
+            Some(bit) => Ok(BitIr {
+                bit: Bit(bit),
+                value,
+            }),
+            None => Err(FromValueError(value)),
+        }
+    }
+
+    fn commit(self) -> Bit {
+        self.bit
+    }
+
+    fn rollback(self) -> Value {
+        self.value
+    }
+}
+
+impl FromValue for Bit {
+    type Intermediate = BitIr;
+}
+
+/// Rust enum that represents members of an `ENUM` (or `SET`) column.
+///
+/// `FromValue` can't be implemented for every such enum by this crate, but it only takes
+/// a single line using `EnumIr`:
+///
+/// ```rust,ignore
+/// #[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+/// enum Size { Small, Large }
+///
+/// impl EnumValue for Size {
+///     fn from_member(member: &str) -> Option<Size> {
+///         match member {
+///             "small" => Some(Size::Small),
+///             "large" => Some(Size::Large),
+///             _ => None,
+///         }
+///     }
+///
+///     fn member(&self) -> &'static str {
+///         match self {
+///             Size::Small => "small",
+///             Size::Large => "large",
+///         }
+///     }
+/// }
+///
+/// impl FromValue for Size {
+///     type Intermediate = EnumIr<Size>;
+/// }
+///
+/// // `SET('small','large')` column
+/// let sizes: SetValue<Size> = from_value(Value::Bytes(b"small,large".to_vec()));
+/// ```
+pub trait EnumValue: Sized {
+    /// Returns the variant that represents a `member` (`None` if it's unknown).
+    fn from_member(member: &str) -> Option<Self>;
+
+    /// Returns the member represented by this variant.
+    fn member(&self) -> &'static str;
+
+    /// Converts this variant into a value suitable for a statement parameter.
+    fn to_value(&self) -> Value {
+        Value::Bytes(self.member().as_bytes().to_vec())
+    }
+}
+
+/// Intermediate result of a `Value` to `T: EnumValue` conversion.
+#[derive(Debug)]
+pub struct EnumIr<T> {
+    member: T,
+    value: Value,
+}
+
+impl<T: EnumValue> ConvIr<T> for EnumIr<T> {
+    fn new(value: Value) -> Result<EnumIr<T>, FromValueError> {
+        let member = match value {
+            Value::Bytes(ref bytes) => std::str::from_utf8(bytes).ok().and_then(T::from_member),
+            _ => None,
+        };
+        match member {
+            Some(member) => Ok(EnumIr { member, value }),
+            None => Err(FromValueError(value)),
+        }
+    }
+
+    fn commit(self) -> T {
+        self.member
+    }
+
+    fn rollback(self) -> Value {
+        self.value
+    }
+}
+
+/// Value of a `SET` column, i.e. a set of `T` members.
+///
+/// Members are ordered by `T::cmp`, so deriving `Ord` for `T` orders them
+/// like the server does if variants are declared in the order of the column definition.
+#[derive(Clone, Eq, PartialEq)]
+pub struct SetValue<T> {
+    members: BTreeSet<T>,
+}
+
+impl<T: EnumValue + Ord> SetValue<T> {
+    pub fn new() -> SetValue<T> {
+        SetValue {
+            members: BTreeSet::new(),
+        }
+    }
+
+    /// Returns `true` if this set contains `member`.
+    pub fn contains(&self, member: &T) -> bool {
+        self.members.contains(member)
+    }
+
+    /// Adds `member` to this set. Returns `false` if it's already present.
+    pub fn insert(&mut self, member: T) -> bool {
+        self.members.insert(member)
+    }
+
+    /// Removes `member` from this set. Returns `false` if it's not present.
+    pub fn remove(&mut self, member: &T) -> bool {
+        self.members.remove(member)
+    }
+
+    /// Returns members of this set.
+    pub fn members(&self) -> &BTreeSet<T> {
+        &self.members
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+}
+
+impl<T: EnumValue + Ord> Default for SetValue<T> {
+    fn default() -> SetValue<T> {
+        SetValue::new()
+    }
+}
+
+impl<T: EnumValue + Ord> std::iter::FromIterator<T> for SetValue<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> SetValue<T> {
+        SetValue {
+            members: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<T: EnumValue + Ord> fmt::Debug for SetValue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set()
+            .entries(self.members.iter().map(EnumValue::member))
+            .finish()
+    }
+}
+
+impl<T: EnumValue + Ord> From<SetValue<T>> for Value {
+    fn from(set: SetValue<T>) -> Value {
+        let members = set
+            .members
+            .iter()
+            .map(EnumValue::member)
+            .collect::<Vec<_>>();
+        Value::Bytes(members.join(",").into_bytes())
+    }
+}
+
+/// Intermediate result of a `Value` to `SetValue<T>` conversion.
+#[derive(Debug)]
+pub struct SetValueIr<T> {
+    members: BTreeSet<T>,
+    value: Value,
+}
+
+impl<T: EnumValue + Ord> ConvIr<SetValue<T>> for SetValueIr<T> {
+    fn new(value: Value) -> Result<SetValueIr<T>, FromValueError> {
+        let members = match value {
+            Value::Bytes(ref bytes) => std::str::from_utf8(bytes).ok().and_then(|text| {
+                text.split(',')
+                    .filter(|member| !member.is_empty())
+                    .map(T::from_member)
+                    .collect::<Option<BTreeSet<T>>>()
+            }),
+            _ => None,
+        };
+        match members {
+            Some(members) => Ok(SetValueIr { members, value }),
+            None => Err(FromValueError(value)),
+        }
+    }
+
+    fn commit(self) -> SetValue<T> {
+        SetValue {
+            members: self.members,
+        }
+    }
+
+    fn rollback(self) -> Value {
+        self.value
+    }
+}
+
+impl<T: EnumValue + Ord> FromValue for SetValue<T> {
+    type Intermediate = SetValueIr<T>;
+}
+
+#[cfg(test)]
+mod test {
+    use mysql_common::value::convert::{from_value, from_value_opt, FromValue};
+
+    use super::{Bit, EnumIr, EnumValue, SetValue};
+    use crate::Value;
+
+    #[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+    enum Size {
+        Small,
+        Large,
+    }
+
+    impl EnumValue for Size {
+        fn from_member(member: &str) -> Option<Size> {
+            match member {
+                "small" => Some(Size::Small),
+                "large" => Some(Size::Large),
+                _ => None,
+            }
+        }
+
+        fn member(&self) -> &'static str {
+            match self {
+                Size::Small => "small",
+                Size::Large => "large",
+            }
+        }
+    }
+
+    impl FromValue for Size {
+        type Intermediate = EnumIr<Size>;
+    }
+
+    #[test]
+    fn should_convert_bit_values() {
+        // text protocol
+        assert_eq!(from_value::<Bit>(Value::Bytes(b"5".to_vec())), Bit(5));
+        assert_eq!(
+            from_value::<Bit>(Value::Bytes(b"18446744073709551615".to_vec())),
+            Bit(u64::max_value())
+        );
+        assert_eq!(from_value::<Bit>(Value::UInt(5)), Bit(5));
+        assert_eq!(from_value::<Bit>(Value::Int(5)), Bit(5));
+        assert!(from_value_opt::<Bit>(Value::Int(-1)).is_err());
+        // raw bytes are converted by `ColumnDecode::Bit`
+        assert!(from_value_opt::<Bit>(Value::Bytes(vec![0x01, 0x02])).is_err());
+        assert_eq!(
+            from_value::<Vec<u8>>(Value::Bytes(vec![0x00, 0x05])),
+            vec![0x00, 0x05]
+        );
+        assert_eq!(Value::from(Bit(7)), Value::UInt(7));
+    }
+
+    #[test]
+    fn should_convert_enum_and_set_values() {
+        assert_eq!(
+            from_value::<Size>(Value::Bytes(b"large".to_vec())),
+            Size::Large
+        );
+        assert!(from_value_opt::<Size>(Value::Bytes(b"medium".to_vec())).is_err());
+        assert_eq!(Size::Small.to_value(), Value::Bytes(b"small".to_vec()));
+
+        let set = from_value::<SetValue<Size>>(Value::Bytes(b"large,small".to_vec()));
+        assert!(set.contains(&Size::Small) && set.contains(&Size::Large));
+        assert_eq!(set.len(), 2);
+        assert_eq!(Value::from(set), Value::Bytes(b"small,large".to_vec()));
+        assert!(from_value::<SetValue<Size>>(Value::Bytes(Vec::new())).is_empty());
+        assert!(from_value_opt::<SetValue<Size>>(Value::Bytes(b"small,x".to_vec())).is_err());
+
+        let mut set = SetValue::new();
+        assert!(set.insert(Size::Large));
+        assert!(!set.insert(Size::Large));
+        assert!(set.insert(Size::Small));
+        assert!(set.remove(&Size::Large));
+        assert_eq!(Value::from(set), Value::Bytes(b"small".to_vec()));
+    }
+}
@@ -0,0 +1,224 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use twox_hash::XxHash;
+
+use std::{fmt, hash::Hasher, iter::Peekable, str::Chars};
+
+/// Normalized form of a query text that is safe to log.
+///
+/// String, numeric, hex and bit literals are replaced with `?`, lists of literals
+/// (e.g. `IN (1, 2, 3)`) are collapsed into `(...)`, comments are removed
+/// and whitespaces are collapsed, so that queries differing only in literals
+/// have the same digest:
+///
+/// ```rust
+/// # use mysql_async::QueryDigest;
+/// let digest = QueryDigest::new("SELECT * FROM users WHERE email = 'foo@example.com'  AND id IN (1, 2)");
+/// assert_eq!(digest.text(), "SELECT * FROM users WHERE email = ? AND id IN (...)");
+/// assert_eq!(digest.hash(), QueryDigest::new("SELECT * FROM users WHERE email = 'bar' AND id IN (3)").hash());
+/// ```
+///
+/// If `OptsBuilder::redact_queries` is enabled, query text reported to `EventHandler`
+/// is normalized this way. Command journal (see `OptsBuilder::command_journal_size`) always
+/// stores digest hashes.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct QueryDigest {
+    text: String,
+    hash: u64,
+}
+
+impl QueryDigest {
+    pub fn new<T: AsRef<str>>(query: T) -> QueryDigest {
+        let text = normalize(query.as_ref());
+        let mut hasher = XxHash::default();
+        hasher.write(text.as_bytes());
+        QueryDigest {
+            hash: hasher.finish(),
+            text,
+        }
+    }
+
+    /// Normalized query text.
+    pub fn text(&self) -> &str {
+        &*self.text
+    }
+
+    /// Hash of the normalized query text.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    pub fn into_text(self) -> String {
+        self.text
+    }
+}
+
+impl fmt::Display for QueryDigest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&*self.text)
+    }
+}
+
+/// Returns `true` if `c` could be a part of an unquoted identifier.
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Skips a quoted literal (opening `quote` is already consumed).
+fn skip_quoted(chars: &mut Peekable<Chars<'_>>, quote: char) {
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if c == quote {
+            if chars.peek() == Some(&quote) {
+                chars.next();
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+/// Appends a token to the normalized text (prepending a pending whitespace, if any).
+fn push_token(out: &mut String, space: &mut bool, token: &str) {
+    if *space && !out.is_empty() {
+        out.push(' ');
+    }
+    *space = false;
+    out.push_str(token);
+}
+
+fn normalize(query: &str) -> String {
+    let mut out = String::with_capacity(query.len());
+    let mut space = false;
+    let mut chars = query.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let prev_is_ident = !space && out.chars().next_back().map_or(false, is_ident_char);
+        match c {
+            c if c.is_whitespace() => space = true,
+            '\'' | '"' => {
+                skip_quoted(&mut chars, c);
+                push_token(&mut out, &mut space, "?");
+            }
+            '`' => {
+                let mut ident = String::from("`");
+                while let Some(c) = chars.next() {
+                    ident.push(c);
+                    if c == '`' {
+                        if chars.peek() == Some(&'`') {
+                            ident.push(chars.next().unwrap());
+                        } else {
+                            break;
+                        }
+                    }
+                }
+                push_token(&mut out, &mut space, &ident);
+            }
+            '#' => {
+                chars.by_ref().take_while(|c| *c != '\n').for_each(drop);
+                space = true;
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                chars.by_ref().take_while(|c| *c != '\n').for_each(drop);
+                space = true;
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = '\0';
+                while let Some(c) = chars.next() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+                space = true;
+            }
+            'x' | 'X' | 'b' | 'B' if !prev_is_ident && chars.peek() == Some(&'\'') => {
+                chars.next();
+                skip_quoted(&mut chars, '\'');
+                push_token(&mut out, &mut space, "?");
+            }
+            c if c.is_ascii_digit() && !prev_is_ident => {
+                while let Some(&c) = chars.peek() {
+                    if c.is_ascii_alphanumeric() || c == '.' {
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                push_token(&mut out, &mut space, "?");
+            }
+            ')' => {
+                push_token(&mut out, &mut space, ")");
+                collapse_list(&mut out);
+            }
+            c => {
+                let mut buf = [0; 4];
+                push_token(&mut out, &mut space, c.encode_utf8(&mut buf));
+            }
+        }
+    }
+
+    out
+}
+
+/// Collapses a trailing list of placeholders (e.g. `(?, ?, ?)`) into `(...)`.
+fn collapse_list(out: &mut String) {
+    if let Some(open) = out.rfind('(') {
+        let items = &out[open + 1..out.len() - 1];
+        let is_placeholder = |item: &str| {
+            item.trim()
+                .trim_start_matches(|c: char| c == '-' || c == '+')
+                == "?"
+        };
+        if items.split(',').all(is_placeholder) {
+            out.truncate(open + 1);
+            out.push_str("...)");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::QueryDigest;
+
+    #[test]
+    fn should_normalize_queries() {
+        let cases = [
+            ("SELECT 1", "SELECT ?"),
+            ("  SELECT\n\t a1,  b_2 FROM t3  ", "SELECT a1, b_2 FROM t3"),
+            (
+                r#"INSERT INTO t VALUES ('it''s', "a\"b", -1.5e3, 0xFF, X'0A', b'01')"#,
+                "INSERT INTO t VALUES (...)",
+            ),
+            (
+                "SELECT * FROM t WHERE id IN (1,2 , 3) AND f(a, 1)",
+                "SELECT * FROM t WHERE id IN (...) AND f(a, ?)",
+            ),
+            (
+                "SELECT `odd``name 1` -- comment\nFROM t /* block */ WHERE x = ? # tail",
+                "SELECT `odd``name 1` FROM t WHERE x = ?",
+            ),
+            ("SELECT f()", "SELECT f()"),
+        ];
+        for (query, expected) in cases.iter() {
+            assert_eq!(QueryDigest::new(query).text(), *expected);
+        }
+
+        assert_eq!(
+            QueryDigest::new("SELECT 'a'").hash(),
+            QueryDigest::new("SELECT   'b'").hash()
+        );
+        assert_ne!(
+            QueryDigest::new("SELECT a").hash(),
+            QueryDigest::new("SELECT b").hash()
+        );
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Schema-less representation of rows for ad-hoc queries (admin UIs, REPLs, etc.):
+//!
+//! ```rust,ignore
+//! use mysql_async::{prelude::*, DynamicRow, Value};
+//!
+//! let (conn, rows) = conn
+//!     .query("SELECT 1 AS a, 'foo' AS b")
+//!     .and_then(|result| result.collect_and_drop::<DynamicRow>())
+//!     .wait()?;
+//! assert_eq!(rows[0].get("b"), Some(&Value::from("foo")));
+//! ```
+
+use mysql_common::row::convert::{FromRow, FromRowError};
+
+use std::{collections::HashMap, slice, vec};
+
+use crate::{Row, Value};
+
+/// Conversions of a `Row` that don't require its schema to be known ahead of time.
+pub trait RowExt {
+    /// Converts this row into a map from column names to values.
+    ///
+    /// If several columns have the same name, the value of the last one is kept
+    /// (use `RowExt::into_dynamic` to keep all of them).
+    ///
+    /// # Panics
+    ///
+    /// Panics if a value of this row was taken using `Row::take`.
+    fn into_map(self) -> HashMap<String, Value>;
+
+    /// Converts this row into a `DynamicRow`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a value of this row was taken using `Row::take`.
+    fn into_dynamic(self) -> DynamicRow;
+}
+
+impl RowExt for Row {
+    fn into_map(self) -> HashMap<String, Value> {
+        self.into_dynamic().into_map()
+    }
+
+    fn into_dynamic(self) -> DynamicRow {
+        let names = self
+            .columns_ref()
+            .iter()
+            .map(|column| column.name_str().into_owned())
+            .collect::<Vec<_>>();
+        DynamicRow {
+            entries: names.into_iter().zip(self.unwrap()).collect(),
+        }
+    }
+}
+
+/// Column names and values of a row in the order of the result set columns.
+///
+/// Unlike a map it keeps duplicate column names (e.g. `SELECT a.id, b.id FROM a JOIN b`).
+/// It implements `FromRow`, so it could be used with `QueryResult::collect` and friends.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DynamicRow {
+    entries: Vec<(String, Value)>,
+}
+
+impl DynamicRow {
+    /// Returns the number of columns.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the row has no columns.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the value of the first column with the given `name`.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.entries
+            .iter()
+            .find(|(column, _)| column == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Returns values of all columns with the given `name`.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Value> + 'a {
+        self.entries
+            .iter()
+            .filter(move |(column, _)| column == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Returns the name and the value of the column at `index`.
+    pub fn get_index(&self, index: usize) -> Option<(&str, &Value)> {
+        self.entries
+            .get(index)
+            .map(|(name, value)| (&**name, value))
+    }
+
+    /// Returns an iterator over column names and values.
+    pub fn iter(&self) -> slice::Iter<'_, (String, Value)> {
+        self.entries.iter()
+    }
+
+    /// Returns column names.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(name, _)| &**name)
+    }
+
+    /// Converts this row into a map from column names to values (see `RowExt::into_map`).
+    pub fn into_map(self) -> HashMap<String, Value> {
+        self.entries.into_iter().collect()
+    }
+
+    /// Returns column names and values.
+    pub fn into_entries(self) -> Vec<(String, Value)> {
+        self.entries
+    }
+}
+
+impl IntoIterator for DynamicRow {
+    type Item = (String, Value);
+    type IntoIter = vec::IntoIter<(String, Value)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a DynamicRow {
+    type Item = &'a (String, Value);
+    type IntoIter = slice::Iter<'a, (String, Value)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
+
+impl From<Row> for DynamicRow {
+    fn from(row: Row) -> DynamicRow {
+        row.into_dynamic()
+    }
+}
+
+impl FromRow for DynamicRow {
+    fn from_row(row: Row) -> DynamicRow {
+        row.into_dynamic()
+    }
+
+    fn from_row_opt(row: Row) -> Result<DynamicRow, FromRowError> {
+        Ok(row.into_dynamic())
+    }
+}
@@ -14,7 +14,12 @@ use mysql_common::{
     row::Row, value::Value,
 };
 
-use std::{io, result};
+use std::{fmt, io, result, time::Duration};
+
+use crate::{
+    conn::{journal::CommandRecord, pool::futures::WaitReason},
+    QueryDigest,
+};
 
 /// Result type alias for this library.
 pub type Result<T> = result::Result<T, Error>;
@@ -42,6 +47,69 @@ pub enum Error {
     Url(#[cause] UrlError),
 }
 
+impl Error {
+    /// Returns server error if this is an `Error::Server`.
+    ///
+    /// Server error of a failed init command (see `DriverError::InitCommandFailed`)
+    /// and of a write rejected by a read-only server (see `DriverError::ReadOnlyViolation`)
+    /// is also returned.
+    pub fn server_error(&self) -> Option<&ServerError> {
+        match self {
+            Error::Server(err) => Some(err),
+            Error::Driver(DriverError::InitCommandFailed { error, .. }) => error.server_error(),
+            Error::Driver(DriverError::ReadOnlyViolation { error, .. }) => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Mutable version of `Error::server_error`.
+    pub(crate) fn server_error_mut(&mut self) -> Option<&mut ServerError> {
+        match self {
+            Error::Server(err) => Some(err),
+            Error::Driver(DriverError::InitCommandFailed { error, .. }) => error.server_error_mut(),
+            Error::Driver(DriverError::ReadOnlyViolation { error, .. }) => Some(error),
+            _ => None,
+        }
+    }
+
+    /// See `ServerError::is_deadlock`.
+    pub fn is_deadlock(&self) -> bool {
+        self.server_error()
+            .map(ServerError::is_deadlock)
+            .unwrap_or(false)
+    }
+
+    /// See `ServerError::is_lock_wait_timeout`.
+    pub fn is_lock_wait_timeout(&self) -> bool {
+        self.server_error()
+            .map(ServerError::is_lock_wait_timeout)
+            .unwrap_or(false)
+    }
+
+    /// See `ServerError::is_duplicate_key`.
+    pub fn is_duplicate_key(&self) -> bool {
+        self.server_error()
+            .map(ServerError::is_duplicate_key)
+            .unwrap_or(false)
+    }
+
+    /// See `ServerError::is_access_denied`.
+    pub fn is_access_denied(&self) -> bool {
+        self.server_error()
+            .map(ServerError::is_access_denied)
+            .unwrap_or(false)
+    }
+
+    /// Returns `true` if authentication failed or the server requested an authentication plugin
+    /// that is unknown to the driver (see `OptsBuilder::failover_on_auth_error`).
+    pub fn is_auth_error(&self) -> bool {
+        match self {
+            Error::Driver(DriverError::UnknownAuthPlugin { .. }) => true,
+            _ => self.is_access_denied(),
+        }
+    }
+}
+
 /// This type represents MySql server error.
 #[derive(Debug, Fail)]
 #[fail(display = "ERROR {} ({}): {}", state, code, message)]
@@ -49,6 +117,165 @@ pub struct ServerError {
     pub code: u16,
     pub message: String,
     pub state: String,
+    pub(crate) recent_commands: Vec<CommandRecord>,
+    pub(crate) lock_diagnostics: Option<String>,
+}
+
+impl ServerError {
+    /// Last commands executed on the connection (see `OptsBuilder::command_journal_size`).
+    pub fn recent_commands(&self) -> &[CommandRecord] {
+        &*self.recent_commands
+    }
+
+    /// Summary of transactions involved in a lock conflict (see `Pool::diagnose_lock_error`).
+    pub fn lock_diagnostics(&self) -> Option<&str> {
+        self.lock_diagnostics.as_ref().map(AsRef::as_ref)
+    }
+
+    /// Returns known error code of this error (`None` if the code isn't listed
+    /// in `ServerErrorCode`).
+    pub fn kind(&self) -> Option<ServerErrorCode> {
+        ServerErrorCode::from_u16(self.code)
+    }
+
+    /// `ER_LOCK_DEADLOCK` – transaction was rolled back and could be retried.
+    pub fn is_deadlock(&self) -> bool {
+        self.kind() == Some(ServerErrorCode::ER_LOCK_DEADLOCK)
+    }
+
+    /// `ER_LOCK_WAIT_TIMEOUT` – statement (not transaction) was rolled back
+    /// unless `innodb_rollback_on_timeout` is enabled.
+    pub fn is_lock_wait_timeout(&self) -> bool {
+        self.kind() == Some(ServerErrorCode::ER_LOCK_WAIT_TIMEOUT)
+    }
+
+    /// `ER_DUP_ENTRY`, `ER_DUP_ENTRY_WITH_KEY_NAME` or `ER_DUP_KEY`.
+    pub fn is_duplicate_key(&self) -> bool {
+        match self.kind() {
+            Some(ServerErrorCode::ER_DUP_ENTRY)
+            | Some(ServerErrorCode::ER_DUP_ENTRY_WITH_KEY_NAME)
+            | Some(ServerErrorCode::ER_DUP_KEY) => true,
+            _ => false,
+        }
+    }
+
+    /// `ER_ACCESS_DENIED_ERROR`, `ER_DBACCESS_DENIED_ERROR`, `ER_TABLEACCESS_DENIED_ERROR`,
+    /// `ER_COLUMNACCESS_DENIED_ERROR` or `ER_SPECIFIC_ACCESS_DENIED_ERROR`.
+    pub fn is_access_denied(&self) -> bool {
+        match self.kind() {
+            Some(ServerErrorCode::ER_ACCESS_DENIED_ERROR)
+            | Some(ServerErrorCode::ER_DBACCESS_DENIED_ERROR)
+            | Some(ServerErrorCode::ER_TABLEACCESS_DENIED_ERROR)
+            | Some(ServerErrorCode::ER_COLUMNACCESS_DENIED_ERROR)
+            | Some(ServerErrorCode::ER_SPECIFIC_ACCESS_DENIED_ERROR) => true,
+            _ => false,
+        }
+    }
+
+    /// `ER_OPTION_PREVENTS_STATEMENT` caused by `--read-only` or `--super-read-only`,
+    /// or `ER_READ_ONLY_MODE` – the server doesn't accept writes (e.g. it was demoted
+    /// to a replica).
+    ///
+    /// `ER_OPTION_PREVENTS_STATEMENT` is shared by other server options (e.g.
+    /// `--secure-file-priv`), so the option is looked up in the message. Its name is inserted
+    /// into the message as is, i.e. it doesn't depend on the language of server messages.
+    pub fn is_read_only(&self) -> bool {
+        match self.kind() {
+            Some(ServerErrorCode::ER_OPTION_PREVENTS_STATEMENT) => self
+                .message
+                .split(|c: char| c.is_whitespace() || c == '\'' || c == '"')
+                .any(|word| word == "--read-only" || word == "--super-read-only"),
+            Some(ServerErrorCode::ER_READ_ONLY_MODE) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Server error codes (see `ServerError::kind`).
+///
+/// Only commonly handled codes are listed here. Refer to the MySql Server Error
+/// Message Reference for the full list.
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash)]
+#[repr(u16)]
+pub enum ServerErrorCode {
+    ER_DUP_KEY = 1022,
+    ER_CON_COUNT_ERROR = 1040,
+    ER_DBACCESS_DENIED_ERROR = 1044,
+    ER_ACCESS_DENIED_ERROR = 1045,
+    ER_NO_DB_ERROR = 1046,
+    ER_BAD_DB_ERROR = 1049,
+    ER_TABLE_EXISTS_ERROR = 1050,
+    ER_BAD_TABLE_ERROR = 1051,
+    ER_SERVER_SHUTDOWN = 1053,
+    ER_BAD_FIELD_ERROR = 1054,
+    ER_DUP_ENTRY = 1062,
+    ER_PARSE_ERROR = 1064,
+    ER_TABLEACCESS_DENIED_ERROR = 1142,
+    ER_COLUMNACCESS_DENIED_ERROR = 1143,
+    ER_NO_SUCH_TABLE = 1146,
+    ER_NET_PACKET_TOO_LARGE = 1153,
+    ER_NET_READ_INTERRUPTED = 1159,
+    ER_LOCK_WAIT_TIMEOUT = 1205,
+    ER_LOCK_DEADLOCK = 1213,
+    ER_NO_REFERENCED_ROW = 1216,
+    ER_ROW_IS_REFERENCED = 1217,
+    ER_SPECIFIC_ACCESS_DENIED_ERROR = 1227,
+    ER_OPTION_PREVENTS_STATEMENT = 1290,
+    ER_QUERY_INTERRUPTED = 1317,
+    ER_ROW_IS_REFERENCED_2 = 1451,
+    ER_NO_REFERENCED_ROW_2 = 1452,
+    ER_MAX_PREPARED_STMT_COUNT_REACHED = 1461,
+    ER_DUP_ENTRY_WITH_KEY_NAME = 1586,
+    ER_READ_ONLY_MODE = 1836,
+    ER_QUERY_TIMEOUT = 3024,
+}
+
+impl ServerErrorCode {
+    /// Returns known error code for the given numeric `code`.
+    pub fn from_u16(code: u16) -> Option<ServerErrorCode> {
+        use self::ServerErrorCode::*;
+        let kind = match code {
+            1022 => ER_DUP_KEY,
+            1040 => ER_CON_COUNT_ERROR,
+            1044 => ER_DBACCESS_DENIED_ERROR,
+            1045 => ER_ACCESS_DENIED_ERROR,
+            1046 => ER_NO_DB_ERROR,
+            1049 => ER_BAD_DB_ERROR,
+            1050 => ER_TABLE_EXISTS_ERROR,
+            1051 => ER_BAD_TABLE_ERROR,
+            1053 => ER_SERVER_SHUTDOWN,
+            1054 => ER_BAD_FIELD_ERROR,
+            1062 => ER_DUP_ENTRY,
+            1064 => ER_PARSE_ERROR,
+            1142 => ER_TABLEACCESS_DENIED_ERROR,
+            1143 => ER_COLUMNACCESS_DENIED_ERROR,
+            1146 => ER_NO_SUCH_TABLE,
+            1153 => ER_NET_PACKET_TOO_LARGE,
+            1159 => ER_NET_READ_INTERRUPTED,
+            1205 => ER_LOCK_WAIT_TIMEOUT,
+            1213 => ER_LOCK_DEADLOCK,
+            1216 => ER_NO_REFERENCED_ROW,
+            1217 => ER_ROW_IS_REFERENCED,
+            1227 => ER_SPECIFIC_ACCESS_DENIED_ERROR,
+            1290 => ER_OPTION_PREVENTS_STATEMENT,
+            1317 => ER_QUERY_INTERRUPTED,
+            1451 => ER_ROW_IS_REFERENCED_2,
+            1452 => ER_NO_REFERENCED_ROW_2,
+            1461 => ER_MAX_PREPARED_STMT_COUNT_REACHED,
+            1586 => ER_DUP_ENTRY_WITH_KEY_NAME,
+            1836 => ER_READ_ONLY_MODE,
+            3024 => ER_QUERY_TIMEOUT,
+            _ => return None,
+        };
+        Some(kind)
+    }
+}
+
+impl From<ServerErrorCode> for u16 {
+    fn from(code: ServerErrorCode) -> u16 {
+        code as u16
+    }
 }
 
 /// This type enumerates connection URL errors.
@@ -88,21 +315,66 @@ pub enum UrlError {
 /// This type enumerates driver errors.
 #[derive(Debug, Fail)]
 pub enum DriverError {
+    #[fail(display = "Can't connect to any host: {}.", errors)]
+    AllHostsFailed { errors: HostErrors },
+
     #[fail(
         display = "Can't parse server version from string `{}'.",
         version_string
     )]
     CantParseServerVersion { version_string: String },
 
+    #[fail(display = "Query was cancelled.")]
+    Cancelled,
+
+    #[fail(
+        display = "Session variable `{}' is `{}', but `{}' was requested.",
+        variable, actual, requested
+    )]
+    CharsetMismatch {
+        variable: String,
+        requested: String,
+        actual: String,
+    },
+
+    #[fail(display = "Can't decode value of column `{}': {}.", column, reason)]
+    ColumnDecode { column: String, reason: String },
+
     #[fail(display = "Connection to the server is closed.")]
     ConnectionClosed,
 
+    #[fail(display = "List bound to named parameter `{}' is empty.", name)]
+    EmptyListParam { name: String },
+
     #[fail(display = "Error converting from mysql value.")]
     FromValue { value: Value },
 
     #[fail(display = "Error converting from mysql row.")]
     FromRow { row: Row },
 
+    #[fail(display = "Init command #{} `{}' failed: {}", index, query, error)]
+    InitCommandFailed {
+        index: usize,
+        query: String,
+        error: Box<Error>,
+    },
+
+    #[fail(display = "Invalid init command #{} `{}': {}.", index, query, reason)]
+    InvalidInitQuery {
+        index: usize,
+        query: String,
+        reason: String,
+    },
+
+    #[fail(display = "Invalid value of option `{}': {}.", option, reason)]
+    InvalidOption { option: String, reason: String },
+
+    #[fail(display = "Column `{}' is missing in the result set.", column)]
+    MissingColumn { column: String },
+
+    #[fail(display = "Key column `{}' is missing in the result set.", column)]
+    MissingKeyColumn { column: String },
+
     #[fail(display = "Missing named parameter `{}'.", name)]
     MissingNamedParam { name: String },
 
@@ -118,9 +390,33 @@ pub enum DriverError {
     #[fail(display = "Can't handle local infile request. Handler not specified.")]
     NoLocalInfileHandler,
 
+    #[fail(display = "There is no healthy replica.")]
+    NoHealthyReplica,
+
+    #[fail(
+        display = "Table `{}' doesn't have a single-column primary key.",
+        table
+    )]
+    NoSingleColumnKey { table: String },
+
     #[fail(display = "Packet out of order.")]
     PacketOutOfOrder,
 
+    #[fail(
+        display = "Packet of {} bytes exceeds max_allowed_packet ({} bytes).",
+        len, max_allowed_packet
+    )]
+    PacketTooLarge { len: usize, max_allowed_packet: u64 },
+
+    #[fail(
+        display = "Timed out waiting for a pool connection after {:?}: {}.",
+        waited, reason
+    )]
+    PoolCheckoutTimeout {
+        waited: Duration,
+        reason: WaitReason,
+    },
+
     #[fail(display = "Pool was disconnected.")]
     PoolDisconnected,
 
@@ -129,17 +425,85 @@ pub enum DriverError {
     )]
     ReadOnlyTransNotSupported,
 
+    #[fail(display = "Write was rejected by a read-only server: {}", error)]
+    ReadOnlyViolation {
+        digest: Option<QueryDigest>,
+        error: ServerError,
+    },
+
+    #[fail(
+        display = "Result set columns ({}) can't be converted into the requested row type.",
+        columns
+    )]
+    ResultTypeMismatch { columns: String },
+
+    #[fail(display = "Rows aren't ordered by column `{}': {}.", column, reason)]
+    RowOrder { column: String, reason: String },
+
+    #[fail(
+        display = "`{}' is rejected because the file would be written on the server host.",
+        clause
+    )]
+    ServerSideFile { clause: String },
+
     #[fail(
         display = "Statement takes {} parameters but {} was supplied.",
         required, supplied
     )]
     StmtParamsMismatch { required: u16, supplied: u16 },
 
+    #[fail(
+        display = "`{}' statement is rejected because the driver enforces read-only mode.",
+        verb
+    )]
+    WriteInReadOnlyMode { verb: String },
+
+    #[fail(
+        display = "Statement expected to return no rows returned a result set with {} columns.",
+        columns
+    )]
+    UnexpectedResultSet { columns: usize },
+
     #[fail(display = "Unexpected packet.")]
     UnexpectedPacket { payload: Vec<u8> },
 
     #[fail(display = "Unknown authentication plugin `{}'.", name)]
     UnknownAuthPlugin { name: String },
+
+    #[fail(display = "Capabilities {:?} couldn't be toggled.", capabilities)]
+    UnsupportedCapabilities {
+        capabilities: crate::consts::CapabilityFlags,
+    },
+}
+
+/// Errors of every host tried by `Conn::new` (see `DriverError::AllHostsFailed`).
+#[derive(Debug)]
+pub struct HostErrors(pub Vec<(String, u16, Error)>);
+
+impl fmt::Display for HostErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (host, port, error)) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{}:{}: {}", host, port, error)?;
+        }
+        Ok(())
+    }
+}
+
+impl DriverError {
+    /// Returns a hint on how to avoid this error (if any).
+    pub fn help(&self) -> Option<&'static str> {
+        match self {
+            DriverError::ServerSideFile { .. } => Some(
+                "`SELECT ... INTO OUTFILE' is executed by the server process, so the file is \
+                 created on the server host. Use `Conn::dump_table` to write rows into \
+                 a client-side writer.",
+            ),
+            _ => None,
+        }
+    }
 }
 
 impl From<DriverError> for Error {
@@ -179,6 +543,8 @@ impl From<ErrPacket<'_>> for ServerError {
             code: packet.error_code(),
             message: packet.message_str().into(),
             state: packet.sql_state_str().into(),
+            recent_commands: Vec::new(),
+            lock_diagnostics: None,
         }
     }
 }
@@ -243,3 +609,68 @@ impl From<ParseError> for Error {
         Error::Url(err.into())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{DriverError, Error, ServerError, ServerErrorCode};
+    use crate::QueryDigest;
+
+    fn server_error(code: u16) -> Error {
+        Error::Server(server_error_with_message(code, ""))
+    }
+
+    fn server_error_with_message(code: u16, message: &str) -> ServerError {
+        ServerError {
+            code,
+            message: message.into(),
+            state: String::from("HY000"),
+            recent_commands: Vec::new(),
+            lock_diagnostics: None,
+        }
+    }
+
+    #[test]
+    fn should_classify_server_errors() {
+        assert!(server_error(1213).is_deadlock());
+        assert!(server_error(1205).is_lock_wait_timeout());
+        assert!(server_error(1062).is_duplicate_key());
+        assert!(server_error(1586).is_duplicate_key());
+        assert!(server_error(1045).is_access_denied());
+        assert!(!server_error(1064).is_deadlock());
+        assert!(!Error::from("foo").is_duplicate_key());
+        assert_eq!(
+            server_error(1064)
+                .server_error()
+                .and_then(ServerError::kind),
+            Some(ServerErrorCode::ER_PARSE_ERROR)
+        );
+        assert_eq!(ServerErrorCode::from_u16(9999), None);
+
+        let read_only = server_error_with_message(
+            1290,
+            "The MySQL server is running with the --super-read-only option \
+             so it cannot execute this statement",
+        );
+        assert!(read_only.is_read_only());
+        assert!(server_error_with_message(1836, "Running in read-only mode").is_read_only());
+        assert!(server_error_with_message(
+            1290,
+            "Der MySQL-Server läuft mit der Option --read-only und kann diese Anweisung \
+             deshalb nicht ausführen",
+        )
+        .is_read_only());
+        assert!(!server_error_with_message(1290, "read-only").is_read_only());
+        assert!(!server_error_with_message(
+            1290,
+            "The MySQL server is running with the --secure-file-priv option \
+             so it cannot execute this statement",
+        )
+        .is_read_only());
+        let error = Error::Driver(DriverError::ReadOnlyViolation {
+            digest: Some(QueryDigest::new("UPDATE t SET a = 1")),
+            error: read_only,
+        });
+        assert_eq!(error.server_error().map(|err| err.code), Some(1290));
+        assert_eq!(u16::from(ServerErrorCode::ER_LOCK_DEADLOCK), 1213);
+    }
+}
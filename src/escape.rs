@@ -0,0 +1,121 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Escaping of identifiers and literals for dynamically constructed SQL.
+
+use std::fmt::Write;
+
+use crate::{queryable::query_result::value_to_text, Value};
+
+/// Multi-byte character sets that allow `\` or `'` as a trailing byte of a character.
+const UNSAFE_CHARSETS: &[&str] = &["big5", "cp932", "gb18030", "gbk", "sjis"];
+
+/// Quotes `name` as a single identifier, i.e. wraps it into backticks and doubles backticks
+/// inside of it.
+///
+/// Dots aren't treated specially, so a qualified name should be escaped part by part:
+///
+/// ```rust,ignore
+/// let table = format!("{}.{}", escape_identifier(db), escape_identifier(table));
+/// let query = format!("SELECT * FROM {}", table);
+/// ```
+pub fn escape_identifier(name: &str) -> String {
+    format!("`{}`", name.replace('`', "``"))
+}
+
+/// Renders `value` as an SQL literal for a connection that uses `charset`
+/// (e.g. `"utf8mb4"`, see `character_set_client`).
+///
+/// Statement parameters should be preferred whenever possible. This function is meant for
+/// places where parameters aren't allowed (e.g. some `SHOW` or DDL statements).
+///
+/// Literals don't depend on the `NO_BACKSLASH_ESCAPES` SQL mode: a string that contains
+/// backslashes (or non-ASCII bytes for `big5`, `cp932`, `gb18030`, `gbk` and `sjis` whose
+/// characters may contain `\` or `'` bytes) is rendered as a hex literal with
+/// a character set introducer (e.g. `_utf8mb4 X'5C'`), other strings are quoted
+/// with doubled single quotes.
+pub fn escape_literal(value: &Value, charset: &str) -> String {
+    match value {
+        Value::NULL => String::from("NULL"),
+        Value::Int(_) | Value::UInt(_) => value_to_text(value.clone()),
+        Value::Float(x) if x.is_finite() => value_to_text(value.clone()),
+        Value::Bytes(bytes) => quote_bytes(bytes, charset),
+        value => quote_bytes(value_to_text(value.clone()).as_bytes(), charset),
+    }
+}
+
+fn quote_bytes(bytes: &[u8], charset: &str) -> String {
+    let charset = charset.to_ascii_lowercase();
+    let text = match std::str::from_utf8(bytes) {
+        Ok(text) if !text.contains('\\') => Some(text),
+        _ => None,
+    };
+    match text {
+        Some(text) if text.is_ascii() || !UNSAFE_CHARSETS.contains(&&*charset) => {
+            format!("'{}'", text.replace('\'', "''"))
+        }
+        _ => {
+            let mut literal = String::with_capacity(bytes.len() * 2 + charset.len() + 5);
+            // introducer is omitted if charset name is malformed
+            if !charset.is_empty()
+                && charset
+                    .bytes()
+                    .all(|x| x.is_ascii_alphanumeric() || x == b'_')
+            {
+                literal.push('_');
+                literal.push_str(&charset);
+                literal.push(' ');
+            }
+            literal.push_str("X'");
+            for byte in bytes {
+                write!(literal, "{:02X}", byte).unwrap();
+            }
+            literal.push('\'');
+            literal
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{escape_identifier, escape_literal};
+    use crate::Value;
+
+    #[test]
+    fn should_escape_identifiers() {
+        assert_eq!(escape_identifier("table"), "`table`");
+        assert_eq!(escape_identifier("ta`ble"), "`ta``ble`");
+        assert_eq!(escape_identifier("db.table"), "`db.table`");
+    }
+
+    #[test]
+    fn should_escape_literals() {
+        let bytes = |text: &str| Value::Bytes(text.as_bytes().to_vec());
+
+        assert_eq!(escape_literal(&Value::NULL, "utf8mb4"), "NULL");
+        assert_eq!(escape_literal(&Value::Int(-1), "utf8mb4"), "-1");
+        assert_eq!(escape_literal(&Value::Float(0.5), "utf8mb4"), "0.5");
+        assert_eq!(
+            escape_literal(&Value::Date(2019, 1, 2, 3, 4, 5, 0), "utf8mb4"),
+            "'2019-01-02 03:04:05'"
+        );
+        assert_eq!(escape_literal(&bytes("it's"), "utf8mb4"), "'it''s'");
+        assert_eq!(escape_literal(&bytes("ok\n"), "utf8mb4"), "'ok\n'");
+        assert_eq!(
+            escape_literal(&bytes("a\\'"), "utf8mb4"),
+            "_utf8mb4 X'615C27'"
+        );
+        assert_eq!(escape_literal(&bytes("ü"), "utf8mb4"), "'ü'");
+        assert_eq!(escape_literal(&bytes("ü"), "GBK"), "_gbk X'C3BC'");
+        assert_eq!(
+            escape_literal(&Value::Bytes(vec![0xff]), "binary"),
+            "_binary X'FF'"
+        );
+        assert_eq!(escape_literal(&Value::Bytes(vec![0xff]), "x'; --"), "X'FF'");
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use std::{fmt, sync::Arc, time::Duration};
+
+use crate::{conn::pool::futures::WaitReason, error::ServerError, QueryDigest};
+
+/// Trait used to handle driver events.
+///
+/// Every method has an empty default implementation, so one should only implement
+/// events of interest.
+pub trait EventHandler: Sync + Send {
+    /// Called when connection is returned to a pool while its query result still has unread
+    /// result sets (i.e. `QueryResult` of a multi-statement query or a `CALL` was dropped before
+    /// all of its result sets were consumed). Such a connection is closed by the pool.
+    ///
+    /// `query` is the digest of the query that produced the result (if known), so literals
+    /// of the query aren't exposed.
+    fn unconsumed_result_sets(&self, _query: Option<&QueryDigest>) {}
+
+    /// Called when a multi-host pool establishes a connection while its connections are unevenly
+    /// distributed across hosts (see `OptsBuilder::pool_imbalance_threshold`).
+    ///
+    /// `counts` contains `(host, port, connection count)` for every host of the pool.
+    fn endpoint_imbalance(&self, _counts: &[(String, u16, usize)]) {}
+
+    /// Called when a result set is finished (or dropped) with an approximate number of bytes
+    /// occupied by its decoded rows (see `QueryResult::memory_usage`).
+    ///
+    /// `query` is the digest of the query that produced the result (if known).
+    fn result_set_memory(&self, _query: Option<&QueryDigest>, _bytes: u64) {}
+
+    /// Called when a text query or a statement execution takes longer than
+    /// `OptsBuilder::slow_query_threshold`.
+    ///
+    /// `query` is the digest of the query (or of the executed statement) if known.
+    fn slow_query(&self, _query: Option<&QueryDigest>, _duration: Duration) {}
+
+    /// Called when a text query or a statement execution is finished with the number of bytes
+    /// sent to and received from the server while it was executed (see `Conn::bytes_sent`).
+    ///
+    /// `query` is the digest of the query (or of the executed statement) if known.
+    fn query_io(&self, _query: Option<&QueryDigest>, _bytes_sent: u64, _bytes_received: u64) {}
+
+    /// Called when a best-effort init command (see `InitCommand::best_effort`) fails.
+    /// Connection is established regardless of the failure.
+    fn init_command_failed(&self, _query: &str, _error: &ServerError) {}
+
+    /// Called when the server refuses to prepare `query` because `max_prepared_stmt_count`
+    /// is reached. `evicted` least recently used statements of the connection's statement
+    /// cache are closed before the statement is prepared again.
+    fn prepared_stmts_evicted(&self, _query: &str, _evicted: usize) {}
+
+    /// Called when a text query or a statement being prepared writes its result into a file
+    /// using `SELECT ... INTO OUTFILE` (or `INTO DUMPFILE`). Note that such a file is created
+    /// on the server host, not on the client (see `Conn::dump_table` for a client-side dump).
+    ///
+    /// Only called if `OptsBuilder::report_server_side_files` is enabled (and
+    /// `OptsBuilder::reject_server_side_files` is not).
+    fn server_side_file(&self, _query: &str) {}
+
+    /// Called when `Pool::get_conn` had to wait for a connection, once it resolves
+    /// (or fails with `DriverError::PoolCheckoutTimeout`).
+    ///
+    /// `reason` is the reason of the wait observed last (see `WaitReason`).
+    fn checkout_wait(&self, _reason: WaitReason, _waited: Duration) {}
+
+    /// Called when a session character set `variable` (e.g. `character_set_client`) is `actual`
+    /// rather than `requested` by the driver (see `OptsBuilder::charset_check`).
+    fn charset_mismatch(&self, _variable: &str, _requested: &str, _actual: &str) {}
+
+    /// Called when a statement is transformed by `OptsBuilder::query_rewriter`
+    /// before it is sent.
+    ///
+    /// Both texts are normalized if `OptsBuilder::redact_queries` is enabled.
+    fn query_rewritten(&self, _original: &str, _rewritten: &str) {}
+
+    /// Called by `Pool::rotate` every time an idle connection established with the previous
+    /// options is replaced.
+    ///
+    /// `replaced` is the number of connections replaced so far and `remaining` is the number
+    /// of connections (including checked-out ones) that are still to be replaced.
+    fn pool_rotation(&self, _replaced: usize, _remaining: usize) {}
+}
+
+/// Object used to wrap `T: EventHandler` inside of Opts.
+#[derive(Clone)]
+pub struct EventHandlerObject(Arc<dyn EventHandler>);
+
+impl EventHandlerObject {
+    pub fn new<T: EventHandler + 'static>(handler: T) -> Self {
+        EventHandlerObject(Arc::new(handler))
+    }
+
+    pub fn clone_inner(&self) -> Arc<dyn EventHandler> {
+        self.0.clone()
+    }
+}
+
+impl PartialEq for EventHandlerObject {
+    fn eq(&self, other: &EventHandlerObject) -> bool {
+        self.0.as_ref() as *const dyn EventHandler == other.0.as_ref() as *const dyn EventHandler
+    }
+}
+
+impl Eq for EventHandlerObject {}
+
+impl fmt::Debug for EventHandlerObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Event handler object")
+    }
+}
@@ -0,0 +1,259 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Fault injection for resilience testing (requires `failpoints` feature).
+//!
+//! Faults are global, i.e. they affect every connection of the process, and are meant
+//! to be used in tests of retry and timeout handling:
+//!
+//! ```rust,ignore
+//! mysql_async::failpoints::drop_connection_after(Some(10));
+//! // ... connection fails with `ConnectionReset` on the 10th received packet
+//! mysql_async::failpoints::reset();
+//! ```
+
+use mysql_common::packets::RawPacket;
+
+use std::{
+    io,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use crate::error::*;
+
+/// Number of packets to receive till the connection drop (`0` if disabled).
+static DROP_AFTER_PACKETS: AtomicUsize = AtomicUsize::new(0);
+/// Write delay in milliseconds (`0` if disabled).
+static WRITE_DELAY_MS: AtomicUsize = AtomicUsize::new(0);
+static CORRUPT_NEXT_PACKET: AtomicBool = AtomicBool::new(false);
+static FAIL_NEXT_AUTH: AtomicBool = AtomicBool::new(false);
+static REJECT_NEXT_PREPARE: AtomicBool = AtomicBool::new(false);
+
+/// Makes the `packets`-th received packet (counting from now) fail with `ConnectionReset`
+/// as if the connection was dropped by the server (`None` to disable).
+pub fn drop_connection_after(packets: Option<usize>) {
+    let value = packets.map(|packets| packets.max(1)).unwrap_or(0);
+    DROP_AFTER_PACKETS.store(value, Ordering::SeqCst);
+}
+
+/// Delays every packet write by `delay` (`None` to disable).
+pub fn delay_writes(delay: Option<Duration>) {
+    let millis = delay
+        .map(|delay| delay.as_secs() as usize * 1000 + delay.subsec_millis() as usize)
+        .unwrap_or(0);
+    WRITE_DELAY_MS.store(millis, Ordering::SeqCst);
+}
+
+/// Corrupts the next received packet (its first byte is inverted).
+pub fn corrupt_next_packet() {
+    CORRUPT_NEXT_PACKET.store(true, Ordering::SeqCst);
+}
+
+/// Makes the next connection attempt fail with an "Access denied" server error (`1045`)
+/// after the handshake response is sent.
+pub fn fail_next_auth() {
+    FAIL_NEXT_AUTH.store(true, Ordering::SeqCst);
+}
+
+/// Makes the next `COM_STMT_PREPARE` fail with `ER_MAX_PREPARED_STMT_COUNT_REACHED` (`1461`)
+/// without sending it to the server.
+pub fn reject_next_prepare() {
+    REJECT_NEXT_PREPARE.store(true, Ordering::SeqCst);
+}
+
+/// Disables all faults.
+pub fn reset() {
+    drop_connection_after(None);
+    delay_writes(None);
+    CORRUPT_NEXT_PACKET.store(false, Ordering::SeqCst);
+    FAIL_NEXT_AUTH.store(false, Ordering::SeqCst);
+    REJECT_NEXT_PREPARE.store(false, Ordering::SeqCst);
+}
+
+/// Applies faults to a received packet.
+pub(crate) fn on_packet_read(packet: &mut RawPacket) -> Result<()> {
+    loop {
+        let remaining = DROP_AFTER_PACKETS.load(Ordering::SeqCst);
+        if remaining == 0 {
+            break;
+        }
+        if DROP_AFTER_PACKETS
+            .compare_exchange(remaining, remaining - 1, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+        {
+            if remaining == 1 {
+                let error = io::Error::new(
+                    io::ErrorKind::ConnectionReset,
+                    "failpoint: connection dropped",
+                );
+                return Err(error.into());
+            }
+            break;
+        }
+    }
+    if CORRUPT_NEXT_PACKET.swap(false, Ordering::SeqCst) {
+        if let Some(byte) = packet.0.first_mut() {
+            *byte = !*byte;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the delay of a packet write (if any).
+pub(crate) fn write_delay() -> Option<Duration> {
+    match WRITE_DELAY_MS.load(Ordering::SeqCst) {
+        0 => None,
+        millis => Some(Duration::from_millis(millis as u64)),
+    }
+}
+
+/// Returns an error packet in response to `COM_STMT_PREPARE` if it should be rejected.
+pub(crate) fn on_stmt_prepare() -> Option<RawPacket> {
+    if REJECT_NEXT_PREPARE.swap(false, Ordering::SeqCst) {
+        let mut payload = vec![0xff];
+        payload.extend_from_slice(&1461u16.to_le_bytes());
+        payload
+            .extend_from_slice(b"#42000Can't create more than max_prepared_stmt_count (failpoint)");
+        Some(RawPacket(payload))
+    } else {
+        None
+    }
+}
+
+/// Fails an authentication attempt if requested.
+pub(crate) fn on_auth() -> Result<()> {
+    if FAIL_NEXT_AUTH.swap(false, Ordering::SeqCst) {
+        let error = ServerError {
+            code: 1045,
+            message: "Access denied (failpoint)".into(),
+            state: "28000".into(),
+            recent_commands: Vec::new(),
+            lock_diagnostics: None,
+        };
+        return Err(error.into());
+    }
+    Ok(())
+}
+
+/// Faults are global, so tests of this module should be run separately from the rest
+/// of the test suite, e.g. `cargo test --features failpoints failpoints::`.
+#[cfg(test)]
+mod test {
+    use futures::Future;
+    use lazy_static::lazy_static;
+    use mysql_common::packets::RawPacket;
+
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+    use crate::{
+        connection_like::ConnectionLike, prelude::*, test_misc::DATABASE_URL, Conn, Opts,
+        OptsBuilder,
+    };
+
+    lazy_static! {
+        /// Serializes tests of this module.
+        static ref FAULTS: Mutex<()> = Mutex::new(());
+    }
+
+    #[test]
+    fn should_drop_connection_and_corrupt_packets() {
+        let _guard = FAULTS.lock().unwrap();
+
+        drop_connection_after(Some(2));
+        corrupt_next_packet();
+        let mut packet = RawPacket(vec![0x00, 0x01]);
+        on_packet_read(&mut packet).unwrap();
+        assert_eq!(packet.0, vec![0xff, 0x01]);
+        match on_packet_read(&mut packet) {
+            Err(Error::Io(ref error)) if error.kind() == io::ErrorKind::ConnectionReset => (),
+            result => panic!("expected ConnectionReset, got {:?}", result),
+        }
+        on_packet_read(&mut packet).unwrap();
+        assert_eq!(packet.0, vec![0xff, 0x01]);
+        reset();
+    }
+
+    #[test]
+    fn should_fail_next_auth() {
+        let _guard = FAULTS.lock().unwrap();
+
+        fail_next_auth();
+        let fut = Conn::from_url(&**DATABASE_URL)
+            .then(|result| match result {
+                Err(ref error) if error.is_access_denied() => Ok(()),
+                Err(error) => Err(error),
+                Ok(_) => panic!("authentication should fail"),
+            })
+            .and_then(|_| Conn::from_url(&**DATABASE_URL))
+            .and_then(|conn| conn.ping())
+            .and_then(|conn| conn.disconnect());
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let result = runtime.block_on(fut);
+        runtime.shutdown_on_idle().wait().unwrap();
+        reset();
+        result.unwrap();
+    }
+
+    #[test]
+    fn should_evict_cached_statements_and_retry_rejected_prepare() {
+        let _guard = FAULTS.lock().unwrap();
+
+        struct Handler(Arc<AtomicUsize>);
+
+        impl EventHandler for Handler {
+            fn prepared_stmts_evicted(&self, query: &str, evicted: usize) {
+                assert_eq!(query, "SELECT 3");
+                self.0.fetch_add(evicted, Ordering::SeqCst);
+            }
+        }
+
+        let evicted = Arc::new(AtomicUsize::new(0));
+        let mut opts = OptsBuilder::from_opts(Opts::from_url(&**DATABASE_URL).unwrap());
+        opts.stmt_cache_size(4)
+            .event_handler(Some(Handler(evicted.clone())));
+        let fut = Conn::new(opts)
+            .and_then(|conn| conn.drop_exec("SELECT 1", ()))
+            .and_then(|conn| conn.drop_exec("SELECT 2", ()))
+            .and_then(|conn| {
+                reject_next_prepare();
+                conn.drop_exec("SELECT 3", ())
+            })
+            .and_then(|conn| {
+                let order = conn
+                    .stmt_cache_ref()
+                    .iter()
+                    .map(Clone::clone)
+                    .collect::<Vec<String>>();
+                assert_eq!(order, &["SELECT 2", "SELECT 3"]);
+                conn.disconnect()
+            })
+            // nothing to evict, so the error is returned
+            .and_then(|_| Conn::from_url(&**DATABASE_URL))
+            .and_then(|conn| {
+                reject_next_prepare();
+                conn.drop_exec("SELECT 4", ()).then(|result| match result {
+                    Err(Error::Server(ref error)) if error.code == 1461 => Ok(()),
+                    Err(error) => Err(error),
+                    Ok(_) => panic!("prepare should be rejected"),
+                })
+            });
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let result = runtime.block_on(fut);
+        runtime.shutdown_on_idle().wait().unwrap();
+        reset();
+        result.unwrap();
+        assert_eq!(evicted.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,339 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Spatial values.
+
+use byteorder::{BigEndian as BE, ByteOrder, LittleEndian as LE, WriteBytesExt};
+use mysql_common::value::convert::{ConvIr, FromValue, FromValueError};
+
+use crate::Value;
+
+const WKB_POINT: u32 = 1;
+const WKB_LINESTRING: u32 = 2;
+const WKB_POLYGON: u32 = 3;
+
+/// Point of a `Shape`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Point {
+    pub fn new(x: f64, y: f64) -> Point {
+        Point { x, y }
+    }
+}
+
+/// Decoded geometry (see `Geometry::shape`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Point(Point),
+    LineString(Vec<Point>),
+    /// Rings of a polygon, the exterior ring goes first.
+    Polygon(Vec<Vec<Point>>),
+}
+
+/// Value of a spatial column.
+///
+/// The server sends spatial values in its internal format, i.e. 4-byte SRID followed by
+/// the WKB representation of the geometry. Points, linestrings and polygons could be decoded
+/// using `Geometry::shape`, other geometries are only available as raw WKB.
+///
+/// `Geometry` could be used as a statement parameter, the internal format is accepted
+/// by spatial columns as is. With `geo` feature `Shape` converts into `geo_types::Geometry`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Geometry {
+    srid: u32,
+    wkb: Vec<u8>,
+}
+
+impl Geometry {
+    /// Creates geometry from a `srid` and a WKB payload.
+    pub fn from_wkb(srid: u32, wkb: Vec<u8>) -> Geometry {
+        Geometry { srid, wkb }
+    }
+
+    /// Creates geometry from a `shape` (encoded as little-endian WKB).
+    pub fn new(srid: u32, shape: &Shape) -> Geometry {
+        let mut wkb = Vec::new();
+        wkb.push(1);
+        match shape {
+            Shape::Point(point) => {
+                wkb.write_u32::<LE>(WKB_POINT).unwrap();
+                write_points(&mut wkb, &[*point], false);
+            }
+            Shape::LineString(points) => {
+                wkb.write_u32::<LE>(WKB_LINESTRING).unwrap();
+                write_points(&mut wkb, points, true);
+            }
+            Shape::Polygon(rings) => {
+                wkb.write_u32::<LE>(WKB_POLYGON).unwrap();
+                wkb.write_u32::<LE>(rings.len() as u32).unwrap();
+                for ring in rings {
+                    write_points(&mut wkb, ring, true);
+                }
+            }
+        }
+        Geometry { srid, wkb }
+    }
+
+    /// Spatial reference system identifier.
+    pub fn srid(&self) -> u32 {
+        self.srid
+    }
+
+    /// WKB representation of this geometry.
+    pub fn wkb(&self) -> &[u8] {
+        &*self.wkb
+    }
+
+    /// Returns WKB geometry type (`1` for a point, `2` for a linestring, etc.).
+    pub fn wkb_type(&self) -> Option<u32> {
+        let mut reader = WkbReader::new(&*self.wkb)?;
+        reader.u32()
+    }
+
+    /// Decodes this geometry. Returns `None` if it isn't a point, a linestring or a polygon
+    /// (or if its WKB is malformed).
+    pub fn shape(&self) -> Option<Shape> {
+        let mut reader = WkbReader::new(&*self.wkb)?;
+        let shape = match reader.u32()? {
+            WKB_POINT => Shape::Point(reader.point()?),
+            WKB_LINESTRING => Shape::LineString(reader.points()?),
+            WKB_POLYGON => {
+                let count = reader.u32()?;
+                let mut rings = Vec::new();
+                for _ in 0..count {
+                    rings.push(reader.points()?);
+                }
+                Shape::Polygon(rings)
+            }
+            _ => return None,
+        };
+        if reader.input.is_empty() {
+            Some(shape)
+        } else {
+            None
+        }
+    }
+}
+
+fn write_points(wkb: &mut Vec<u8>, points: &[Point], with_count: bool) {
+    if with_count {
+        wkb.write_u32::<LE>(points.len() as u32).unwrap();
+    }
+    for point in points {
+        wkb.write_f64::<LE>(point.x).unwrap();
+        wkb.write_f64::<LE>(point.y).unwrap();
+    }
+}
+
+/// Reader of a WKB payload that respects its byte order.
+struct WkbReader<'a> {
+    input: &'a [u8],
+    little_endian: bool,
+}
+
+impl<'a> WkbReader<'a> {
+    fn new(wkb: &'a [u8]) -> Option<WkbReader<'a>> {
+        let (&order, input) = wkb.split_first()?;
+        match order {
+            0 | 1 => Some(WkbReader {
+                input,
+                little_endian: order == 1,
+            }),
+            _ => None,
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.input.len() < len {
+            return None;
+        }
+        let (bytes, rest) = self.input.split_at(len);
+        self.input = rest;
+        Some(bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let little_endian = self.little_endian;
+        self.take(4).map(|bytes| {
+            if little_endian {
+                LE::read_u32(bytes)
+            } else {
+                BE::read_u32(bytes)
+            }
+        })
+    }
+
+    fn f64(&mut self) -> Option<f64> {
+        let little_endian = self.little_endian;
+        self.take(8).map(|bytes| {
+            if little_endian {
+                LE::read_f64(bytes)
+            } else {
+                BE::read_f64(bytes)
+            }
+        })
+    }
+
+    fn point(&mut self) -> Option<Point> {
+        Some(Point::new(self.f64()?, self.f64()?))
+    }
+
+    fn points(&mut self) -> Option<Vec<Point>> {
+        let count = self.u32()? as usize;
+        // every point takes 16 bytes
+        if self.input.len() < count.saturating_mul(16) {
+            return None;
+        }
+        (0..count).map(|_| self.point()).collect()
+    }
+}
+
+impl From<Geometry> for Value {
+    fn from(geometry: Geometry) -> Value {
+        let mut bytes = Vec::with_capacity(4 + geometry.wkb.len());
+        bytes.write_u32::<LE>(geometry.srid).unwrap();
+        bytes.extend_from_slice(&*geometry.wkb);
+        Value::Bytes(bytes)
+    }
+}
+
+/// Intermediate result of a `Value` to `Geometry` conversion.
+#[derive(Debug)]
+pub struct GeometryIr {
+    geometry: Geometry,
+    value: Value,
+}
+
+impl ConvIr<Geometry> for GeometryIr {
+    fn new(value: Value) -> Result<GeometryIr, FromValueError> {
+        let geometry = match value {
+            // SRID, byte order and geometry type
+            Value::Bytes(ref bytes) if bytes.len() >= 9 && bytes[4] <= 1 => {
+                Geometry::from_wkb(LE::read_u32(&bytes[..4]), bytes[4..].to_vec())
+            }
+            value => return Err(FromValueError(value)),
+        };
+        Ok(GeometryIr { geometry, value })
+    }
+
+    fn commit(self) -> Geometry {
+        self.geometry
+    }
+
+    fn rollback(self) -> Value {
+        self.value
+    }
+}
+
+impl FromValue for Geometry {
+    type Intermediate = GeometryIr;
+}
+
+#[cfg(feature = "geo")]
+impl From<Shape> for geo_types::Geometry<f64> {
+    fn from(shape: Shape) -> geo_types::Geometry<f64> {
+        use geo_types::{Coordinate, LineString, Polygon};
+
+        fn line_string(points: Vec<Point>) -> LineString<f64> {
+            LineString(
+                points
+                    .into_iter()
+                    .map(|point| Coordinate {
+                        x: point.x,
+                        y: point.y,
+                    })
+                    .collect(),
+            )
+        }
+
+        match shape {
+            Shape::Point(point) => geo_types::Point::new(point.x, point.y).into(),
+            Shape::LineString(points) => line_string(points).into(),
+            Shape::Polygon(rings) => {
+                let mut rings = rings.into_iter().map(line_string);
+                let exterior = rings.next().unwrap_or_else(|| LineString(Vec::new()));
+                Polygon::new(exterior, rings.collect()).into()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mysql_common::value::convert::{from_value, from_value_opt};
+
+    use super::{Geometry, Point, Shape};
+    use crate::Value;
+
+    #[test]
+    fn should_convert_geometry_values() {
+        // SRID 0, POINT(1 2)
+        let mut bytes = vec![0, 0, 0, 0, 1, 1, 0, 0, 0];
+        bytes.extend_from_slice(&1f64.to_le_bytes());
+        bytes.extend_from_slice(&2f64.to_le_bytes());
+
+        let geometry = from_value::<Geometry>(Value::Bytes(bytes.clone()));
+        assert_eq!(geometry.srid(), 0);
+        assert_eq!(geometry.wkb_type(), Some(1));
+        assert_eq!(geometry.shape(), Some(Shape::Point(Point::new(1.0, 2.0))));
+        assert_eq!(Value::from(geometry), Value::Bytes(bytes));
+
+        assert!(from_value_opt::<Geometry>(Value::Bytes(vec![0; 4])).is_err());
+        assert!(from_value_opt::<Geometry>(Value::Int(1)).is_err());
+    }
+
+    #[test]
+    fn should_encode_and_decode_shapes() {
+        let shapes = vec![
+            Shape::Point(Point::new(-1.5, 0.25)),
+            Shape::LineString(vec![Point::new(0.0, 0.0), Point::new(1.0, 1.0)]),
+            Shape::Polygon(vec![
+                vec![
+                    Point::new(0.0, 0.0),
+                    Point::new(4.0, 0.0),
+                    Point::new(0.0, 4.0),
+                    Point::new(0.0, 0.0),
+                ],
+                vec![
+                    Point::new(1.0, 1.0),
+                    Point::new(2.0, 1.0),
+                    Point::new(1.0, 2.0),
+                    Point::new(1.0, 1.0),
+                ],
+            ]),
+        ];
+        for shape in shapes {
+            let geometry = Geometry::new(4326, &shape);
+            let value = Value::from(geometry.clone());
+            assert_eq!(from_value::<Geometry>(value), geometry);
+            assert_eq!(geometry.shape(), Some(shape));
+        }
+
+        // big-endian POINT(1 2)
+        let mut wkb = vec![0, 0, 0, 0, 1];
+        wkb.extend_from_slice(&1f64.to_be_bytes());
+        wkb.extend_from_slice(&2f64.to_be_bytes());
+        assert_eq!(
+            Geometry::from_wkb(0, wkb).shape(),
+            Some(Shape::Point(Point::new(1.0, 2.0)))
+        );
+
+        // MULTIPOINT is only available as WKB
+        let multi_point = Geometry::from_wkb(0, vec![1, 4, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(multi_point.wkb_type(), Some(4));
+        assert_eq!(multi_point.shape(), None);
+
+        // truncated linestring
+        let truncated = Geometry::from_wkb(0, vec![1, 2, 0, 0, 0, 2, 0, 0, 0]);
+        assert_eq!(truncated.shape(), None);
+    }
+}
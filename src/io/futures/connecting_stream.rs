@@ -76,6 +76,9 @@ impl Future for ConnectingStream {
             Out::WaitForStream((stream, _)) => Ok(Ready(Stream {
                 closed: false,
                 codec: Box::new(Framed::new(stream.into(), PacketCodec::new())).into(),
+                bytes_sent: 0,
+                bytes_received: 0,
+                unread: None,
             })),
             Out::Fail(_) => unreachable!(),
         }
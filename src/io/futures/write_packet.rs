@@ -6,18 +6,32 @@
 // option. All files in the project carrying such notice may not be copied,
 // modified, or distributed except according to those terms.
 
-use futures::{try_ready, Async, AsyncSink, Future, Poll, Sink};
-use mysql_common::packets::RawPacket;
+use bytes::Buf;
+use futures::{try_ready, Async, Future, Poll, Sink};
+use tokio_io::AsyncWrite;
+
+use std::io::{self, Cursor};
 
 use crate::{consts::MAX_PAYLOAD_LEN, error::*, io::Stream};
 
 /// Future that writes packet to a `Stream` and resolves to a pair of `Stream` and MySql's sequence
 /// id.
+///
+/// Packet is split into protocol chunks lazily and every chunk is written directly from the
+/// packet buffer together with its header (using vectored write if endpoint supports it),
+/// so payload is never copied into the write buffer of the codec.
 pub struct WritePacket {
-    data: Option<RawPacket>,
+    data: Vec<u8>,
     stream: Option<Stream>,
+    /// Sequence id of the current chunk.
     seq_id: u8,
     resulting_seq_id: u8,
+    /// Offset of the current chunk in `data`.
+    chunk_start: usize,
+    /// Number of bytes of the current chunk (including header) already written.
+    written: usize,
+    codec_flushed: bool,
+    all_written: bool,
 }
 
 pub fn new(stream: Stream, data: Vec<u8>, seq_id: u8) -> WritePacket {
@@ -34,10 +48,52 @@ pub fn new(stream: Stream, data: Vec<u8>, seq_id: u8) -> WritePacket {
     }
 
     WritePacket {
-        data: Some(RawPacket(data)),
+        data,
         stream: Some(stream),
         seq_id,
         resulting_seq_id,
+        chunk_start: 0,
+        written: 0,
+        codec_flushed: false,
+        all_written: false,
+    }
+}
+
+impl WritePacket {
+    /// Writes remaining chunks to the endpoint.
+    fn poll_write_chunks(&mut self) -> Poll<(), io::Error> {
+        let endpoint = self
+            .stream
+            .as_mut()
+            .unwrap()
+            .codec
+            .as_mut()
+            .unwrap()
+            .get_mut();
+
+        loop {
+            let chunk_len = MAX_PAYLOAD_LEN.min(self.data.len() - self.chunk_start);
+            let header = (chunk_len as u32 | ((self.seq_id as u32) << 24)).to_le_bytes();
+            let chunk = &self.data[self.chunk_start..self.chunk_start + chunk_len];
+
+            let mut buf = Cursor::new(header).chain(Cursor::new(chunk));
+            buf.advance(self.written);
+            while buf.has_remaining() {
+                let count = try_ready!(endpoint.write_buf(&mut buf));
+                if count == 0 {
+                    return Err(io::ErrorKind::WriteZero.into());
+                }
+                self.written += count;
+            }
+
+            self.seq_id = self.seq_id.wrapping_add(1);
+            self.written = 0;
+            if chunk_len < MAX_PAYLOAD_LEN {
+                // last chunk (possibly empty) is written
+                return Ok(Async::Ready(()));
+            }
+            self.chunk_start += chunk_len;
+        }
     }
 }
 
@@ -46,23 +102,23 @@ impl Future for WritePacket {
     type Error = Error;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        match self.data.take() {
-            Some(data) => match self
+        if !self.codec_flushed {
+            // data buffered by the codec must go first
+            try_ready!(self
                 .stream
                 .as_mut()
                 .unwrap()
                 .codec
                 .as_mut()
                 .unwrap()
-                .start_send((data, self.seq_id))?
-            {
-                AsyncSink::Ready => (),
-                AsyncSink::NotReady(data) => {
-                    self.data = Some(data.0);
-                    return Ok(Async::NotReady);
-                }
-            },
-            None => (),
+                .poll_complete()
+                .map_err(Error::from));
+            self.codec_flushed = true;
+        }
+
+        if !self.all_written {
+            try_ready!(self.poll_write_chunks().map_err(Error::from));
+            self.all_written = true;
         }
 
         try_ready!(self
@@ -72,7 +128,8 @@ impl Future for WritePacket {
             .codec
             .as_mut()
             .unwrap()
-            .poll_complete()
+            .get_mut()
+            .poll_flush()
             .map_err(Error::from));
         Ok(Async::Ready((
             self.stream.take().unwrap(),
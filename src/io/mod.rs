@@ -11,7 +11,8 @@ use ::futures::future::ok;
 use ::futures::{stream, Async, Poll};
 #[cfg(feature = "ssl")]
 use ::futures::{Future, IntoFuture};
-use mysql_common::packets::RawPacket;
+use bytes::Buf;
+use mysql_common::{constants::MAX_PAYLOAD_LEN, packets::RawPacket};
 #[cfg(feature = "ssl")]
 use native_tls::{Certificate, Identity, TlsConnector};
 use tokio::net::TcpStream;
@@ -34,7 +35,7 @@ use crate::{
 #[cfg(feature = "ssl")]
 mod async_tls;
 pub mod futures;
-mod packet_codec;
+pub(crate) mod packet_codec;
 
 #[derive(Debug)]
 pub enum Endpoint {
@@ -77,6 +78,21 @@ impl Endpoint {
         Ok(())
     }
 
+    pub fn set_buffer_sizes(&self, send: Option<usize>, recv: Option<usize>) -> Result<()> {
+        let stream = match *self {
+            Endpoint::Plain(ref stream) => stream,
+            #[cfg(feature = "ssl")]
+            Endpoint::Secure(ref stream) => stream.get_ref().get_ref(),
+        };
+        if let Some(size) = send {
+            stream.set_send_buffer_size(size)?;
+        }
+        if let Some(size) = recv {
+            stream.set_recv_buffer_size(size)?;
+        }
+        Ok(())
+    }
+
     #[cfg(feature = "ssl")]
     pub fn make_secure(self, domain: String, ssl_opts: SslOpts) -> impl MyFuture<Self> {
         (|| {
@@ -165,6 +181,14 @@ impl AsyncRead for Endpoint {
 }
 
 impl AsyncWrite for Endpoint {
+    fn write_buf<B: Buf>(&mut self, buf: &mut B) -> Poll<usize, io::Error> {
+        match *self {
+            Endpoint::Plain(ref mut stream) => stream.write_buf(buf),
+            #[cfg(feature = "ssl")]
+            Endpoint::Secure(ref mut stream) => stream.write_buf(buf),
+        }
+    }
+
     fn shutdown(&mut self) -> Poll<(), io::Error> {
         match *self {
             Endpoint::Plain(ref mut stream) => stream.shutdown(),
@@ -175,9 +199,25 @@ impl AsyncWrite for Endpoint {
 }
 
 /// Stream connected to MySql server.
+///
+/// Dropping a stream just closes the socket without writing anything to it, so it never
+/// blocks the event loop. `COM_QUIT` is only sent by `Queryable::disconnect`.
 pub struct Stream {
     closed: bool,
     codec: Option<Box<Framed<Endpoint, packet_codec::PacketCodec>>>,
+    /// Number of bytes of packets written to this stream (including packet headers).
+    bytes_sent: u64,
+    /// Number of bytes of packets read from this stream (including packet headers).
+    bytes_received: u64,
+    /// Packet returned to this stream by `Stream::unread`.
+    unread: Option<(RawPacket, u8)>,
+}
+
+/// Returns the number of bytes a packet with the given payload length occupies on the wire.
+fn wire_len(payload_len: usize) -> u64 {
+    // every chunk has a 4-byte header and a payload of `MAX_PAYLOAD_LEN` is followed
+    // by an empty chunk
+    (payload_len + 4 * (payload_len / MAX_PAYLOAD_LEN + 1)) as u64
 }
 
 impl fmt::Debug for Stream {
@@ -198,10 +238,28 @@ impl Stream {
         new_connecting_stream(addr)
     }
 
-    pub fn write_packet(self, data: Vec<u8>, seq_id: u8) -> WritePacket {
+    pub fn write_packet(mut self, data: Vec<u8>, seq_id: u8) -> WritePacket {
+        self.bytes_sent += wire_len(data.len());
         new_write_packet(self, data, seq_id)
     }
 
+    /// Number of bytes of packets written to this stream (including packet headers).
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent
+    }
+
+    /// Number of bytes of packets read from this stream (including packet headers).
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received
+    }
+
+    /// Returns a `packet` that was read from this stream back to it,
+    /// so that it will be the next packet read.
+    pub fn unread(&mut self, packet: RawPacket, seq_id: u8) {
+        debug_assert!(self.unread.is_none());
+        self.unread = Some((packet, seq_id));
+    }
+
     pub fn set_keepalive_ms(&self, ms: Option<u32>) -> Result<()> {
         self.codec.as_ref().unwrap().get_ref().set_keepalive_ms(ms)
     }
@@ -210,6 +268,14 @@ impl Stream {
         self.codec.as_ref().unwrap().get_ref().set_tcp_nodelay(val)
     }
 
+    pub fn set_buffer_sizes(&self, send: Option<usize>, recv: Option<usize>) -> Result<()> {
+        self.codec
+            .as_ref()
+            .unwrap()
+            .get_ref()
+            .set_buffer_sizes(send, recv)
+    }
+
     #[cfg(not(feature = "ssl"))]
     #[allow(unused)]
     pub fn make_secure(self, domain: String, ssl_opts: SslOpts) -> impl MyFuture<Self> {
@@ -237,8 +303,15 @@ impl stream::Stream for Stream {
     type Error = Error;
 
     fn poll(&mut self) -> Poll<Option<(RawPacket, u8)>, Error> {
+        if let Some(item) = self.unread.take() {
+            return Ok(Async::Ready(Some(item)));
+        }
         if !self.closed {
-            self.codec.as_mut().unwrap().poll().map_err(Error::from)
+            let result = self.codec.as_mut().unwrap().poll().map_err(Error::from);
+            if let Ok(Async::Ready(Some((ref packet, _)))) = result {
+                self.bytes_received += wire_len(packet.0.len());
+            }
+            result
         } else {
             Ok(Async::Ready(None))
         }
@@ -13,6 +13,10 @@ use tokio_codec::{Decoder, Encoder};
 
 use std::io;
 
+/// Codec of MySql packets.
+///
+/// Decodes (and encodes) `(packet, sequence_id)` pairs splitting payloads larger
+/// than `MAX_PAYLOAD_LEN` into several chunks.
 #[derive(Debug)]
 pub struct PacketCodec {
     chunk_len: isize,
@@ -28,6 +32,11 @@ impl PacketCodec {
             seq_id: 0,
         }
     }
+
+    /// Returns `true` if the codec holds the beginning of a packet that is not yet decoded.
+    pub(crate) fn has_pending_packet(&self) -> bool {
+        self.chunk_len >= 0 || !self.packet_data.is_empty()
+    }
 }
 
 impl Decoder for PacketCodec {
@@ -41,11 +50,17 @@ impl Decoder for PacketCodec {
                 let chunk = buf.split_to(chunk_len);
 
                 self.chunk_len = -1;
-                self.packet_data.extend_from_slice(&chunk[..]);
 
                 if chunk_len == MAX_PAYLOAD_LEN {
-                    Ok(None)
+                    self.packet_data.extend_from_slice(&chunk[..]);
+                    // Next chunk of this packet could already be in the buffer.
+                    self.decode(buf)
+                } else if self.packet_data.is_empty() {
+                    // Packet consisting of a single chunk is copied out of the read buffer
+                    // directly, without intermediate buffering.
+                    Ok(Some((RawPacket(chunk.to_vec()), self.seq_id)))
                 } else {
+                    self.packet_data.extend_from_slice(&chunk[..]);
                     let packet_data = self.packet_data.as_ref().into();
                     self.packet_data.clear();
                     Ok(Some((RawPacket(packet_data), self.seq_id)))
@@ -99,3 +114,64 @@ impl Encoder for PacketCodec {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use bytes::BytesMut;
+    use mysql_common::{constants::MAX_PAYLOAD_LEN, packets::RawPacket};
+    use tokio_codec::{Decoder, Encoder};
+
+    use super::PacketCodec;
+
+    /// Encodes `packets` and decodes them back, feeding the decoder with `chunk_size` bytes
+    /// at a time.
+    fn roundtrip(packets: &[Vec<u8>], chunk_size: usize) -> Vec<(Vec<u8>, u8)> {
+        let mut encoded = BytesMut::new();
+        let mut codec = PacketCodec::new();
+        for (i, packet) in packets.iter().enumerate() {
+            codec
+                .encode((RawPacket(packet.clone()), i as u8), &mut encoded)
+                .unwrap();
+        }
+
+        let mut codec = PacketCodec::new();
+        let mut buf = BytesMut::new();
+        let mut output = Vec::new();
+        for chunk in encoded.chunks(chunk_size) {
+            buf.extend_from_slice(chunk);
+            while let Some((RawPacket(packet), seq_id)) = codec.decode(&mut buf).unwrap() {
+                output.push((packet, seq_id));
+            }
+        }
+        assert!(buf.is_empty());
+        output
+    }
+
+    #[test]
+    fn should_decode_packets_split_at_any_point() {
+        let packets = vec![vec![], vec![0x01], vec![0x02; 300]];
+        for chunk_size in 1..20 {
+            let output = roundtrip(&packets, chunk_size);
+            assert_eq!(
+                output,
+                vec![(vec![], 0), (vec![0x01], 1), (vec![0x02; 300], 2)]
+            );
+        }
+    }
+
+    #[test]
+    fn should_decode_max_size_packets() {
+        let packets = vec![
+            vec![0x01; MAX_PAYLOAD_LEN],
+            vec![0x02; MAX_PAYLOAD_LEN + 1],
+            vec![0x03],
+        ];
+        for &chunk_size in &[4093, 1024 * 1024 + 1, 64 * 1024 * 1024] {
+            let output = roundtrip(&packets, chunk_size);
+            assert_eq!(output.len(), 3);
+            assert_eq!(output[0].0, packets[0]);
+            assert_eq!(output[1].0, packets[1]);
+            assert_eq!(output[2], (vec![0x03], 2));
+        }
+    }
+}
@@ -6,7 +6,11 @@
 // option. All files in the project carrying such notice may not be copied,
 // modified, or distributed except according to those terms.
 
-use futures::IntoFuture;
+use bytes::Bytes;
+use futures::{
+    Async::{NotReady, Ready as AsyncReady},
+    IntoFuture, Stream,
+};
 use mio::{Evented, Poll, PollOpt, Ready, Registration, Token};
 use tokio::reactor::PollEvented2;
 use tokio_io::AsyncRead;
@@ -21,7 +25,10 @@ use std::{
     thread,
 };
 
-use crate::{local_infile_handler::LocalInfileHandler, BoxFuture};
+use crate::{error::Error, local_infile_handler::LocalInfileHandler, BoxFuture};
+
+/// Stream of local infile contents (see `StreamLocalInfileHandler`).
+pub type InfileStream = Box<dyn Stream<Item = Bytes, Error = Error> + Send + 'static>;
 
 #[derive(Debug)]
 enum Message {
@@ -198,3 +205,158 @@ impl LocalInfileHandler for WhiteListFsLocalInfileHandler {
         }
     }
 }
+
+/// `AsyncRead` wrapper for `InfileStream`.
+pub struct StreamReader {
+    stream: InfileStream,
+    buf: Bytes,
+}
+
+impl StreamReader {
+    pub fn new(stream: InfileStream) -> StreamReader {
+        StreamReader {
+            stream,
+            buf: Bytes::new(),
+        }
+    }
+}
+
+impl Read for StreamReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if !self.buf.is_empty() {
+                let count = ::std::cmp::min(buf.len(), self.buf.len());
+                buf[..count].copy_from_slice(&self.buf.split_to(count));
+                return Ok(count);
+            }
+            match self.stream.poll() {
+                Ok(AsyncReady(Some(chunk))) => self.buf = chunk,
+                Ok(AsyncReady(None)) => return Ok(0),
+                Ok(NotReady) => return Err(io::ErrorKind::WouldBlock.into()),
+                Err(Error::Io(err)) => return Err(err),
+                Err(err) => return Err(io::Error::new(io::ErrorKind::Other, err.to_string())),
+            }
+        }
+    }
+}
+
+impl AsyncRead for StreamReader {}
+
+/// Handles local infile requests using a user-supplied stream of bytes, so that data
+/// could be piped into `LOAD DATA LOCAL INFILE` from any asynchronous source.
+///
+/// ```rust,ignore
+/// opts.local_infile_handler(Some(StreamLocalInfileHandler::new(|file_name: &[u8]| {
+///     let chunks = vec![Bytes::from("foo\n"), Bytes::from("bar\n")];
+///     Box::new(futures::stream::iter_ok(chunks)) as InfileStream
+/// })));
+/// ```
+pub struct StreamLocalInfileHandler<F> {
+    make_stream: F,
+}
+
+impl<F> StreamLocalInfileHandler<F>
+where
+    F: Fn(&[u8]) -> InfileStream + Send + Sync,
+{
+    /// `make_stream` will be called with the file name of every local infile request.
+    pub fn new(make_stream: F) -> StreamLocalInfileHandler<F> {
+        StreamLocalInfileHandler { make_stream }
+    }
+}
+
+impl<F> LocalInfileHandler for StreamLocalInfileHandler<F>
+where
+    F: Fn(&[u8]) -> InfileStream + Send + Sync,
+{
+    fn handle(&self, file_name: &[u8]) -> BoxFuture<Box<dyn AsyncRead + Send + 'static>> {
+        let reader = StreamReader::new((self.make_stream)(file_name));
+        Box::new(Ok(Box::new(reader) as Box<dyn AsyncRead + Send + 'static>).into_future())
+    }
+}
+
+/// `AsyncRead` over a memory-mapped file (see `MmapLocalInfileHandler`).
+#[cfg(feature = "mmap")]
+pub struct MmapReader {
+    /// `None` for an empty file (it can't be mapped).
+    mmap: Option<memmap::Mmap>,
+    pos: usize,
+}
+
+#[cfg(feature = "mmap")]
+impl MmapReader {
+    /// Maps the file at `path` into memory.
+    pub fn open<T: AsRef<std::path::Path>>(path: T) -> io::Result<MmapReader> {
+        let file = fs::File::open(path)?;
+        let mmap = if file.metadata()?.len() == 0 {
+            None
+        } else {
+            // the mapping is read-only and the file is expected to stay intact while it's sent
+            Some(unsafe { memmap::Mmap::map(&file)? })
+        };
+        Ok(MmapReader { mmap, pos: 0 })
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl Read for MmapReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let rest = match self.mmap {
+            Some(ref mmap) => &mmap[self.pos..],
+            None => &[][..],
+        };
+        let count = ::std::cmp::min(buf.len(), rest.len());
+        buf[..count].copy_from_slice(&rest[..count]);
+        self.pos += count;
+        Ok(count)
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl AsyncRead for MmapReader {}
+
+/// Handles local infile requests by memory-mapping white-listed files (requires `mmap` feature).
+///
+/// Unlike `WhiteListFsLocalInfileHandler` it doesn't read the file on a separate thread,
+/// the mapped contents are copied straight into packets of up to `max_allowed_packet` bytes.
+/// It is meant for ingestion of large files from a local disk: page faults block
+/// the executor thread, so files on slow (e.g. network) filesystems should be loaded
+/// using `WhiteListFsLocalInfileHandler`.
+///
+/// The file must not be truncated while it is being sent.
+#[cfg(feature = "mmap")]
+#[derive(Clone, Debug)]
+pub struct MmapLocalInfileHandler {
+    white_list: HashSet<PathBuf>,
+}
+
+#[cfg(feature = "mmap")]
+impl MmapLocalInfileHandler {
+    pub fn new<A, B>(white_list: B) -> MmapLocalInfileHandler
+    where
+        A: Into<PathBuf>,
+        B: IntoIterator<Item = A>,
+    {
+        MmapLocalInfileHandler {
+            white_list: white_list.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl LocalInfileHandler for MmapLocalInfileHandler {
+    fn handle(&self, file_name: &[u8]) -> BoxFuture<Box<dyn AsyncRead + Send + 'static>> {
+        let path: PathBuf = match from_utf8(file_name) {
+            Ok(path_str) => path_str.into(),
+            Err(_) => return Box::new(Err("Invalid file name".into()).into_future()),
+        };
+        if !self.white_list.contains(&path) {
+            let err_msg = format!("Path `{}' is not in white list", path.display());
+            return Box::new(Err(err_msg.into()).into_future());
+        }
+        let reader = MmapReader::open(path)
+            .map(|reader| Box::new(reader) as Box<dyn AsyncRead + Send + 'static>)
+            .map_err(Error::from);
+        Box::new(reader.into_future())
+    }
+}
@@ -0,0 +1,37 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Low-level MySql protocol API (requires `proto` feature).
+//!
+//! This is the packet parsing and framing code used by this crate internally. It is meant
+//! for building MySql proxies and test servers:
+//!
+//! ```rust,ignore
+//! use mysql_async::proto::{parse_ok_packet, Packet, PacketCodec};
+//! use tokio_codec::Framed;
+//!
+//! let framed = Framed::new(tcp_stream, PacketCodec::new());
+//! // framed is a `Stream + Sink` of `(Packet, sequence_id)` pairs
+//! ```
+//!
+//! Golden packets captured from different server versions live in the [`test_vectors`] module
+//! along with helpers to check external captures against the parser. Length-encoded integers
+//! and strings can be read and written using the [`util`] module.
+
+pub use mysql_common::packets::{
+    column_from_payload, parse_auth_switch_request, parse_err_packet, parse_handshake_packet,
+    parse_local_infile_packet, parse_ok_packet, AuthPlugin, AuthSwitchRequest, Column, ErrPacket,
+    HandshakePacket, HandshakeResponse, OkPacket, RawPacket as Packet, SslRequest,
+};
+pub use mysql_common::value::read_text_values;
+
+pub use crate::io::packet_codec::PacketCodec;
+pub use crate::queryable::read_bin_values;
+
+pub mod test_vectors;
+pub mod util;
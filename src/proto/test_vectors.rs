@@ -0,0 +1,524 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Golden packets of the MySql protocol.
+//!
+//! Vectors are packet payloads (without the 4-byte header) as sent by MySql 5.6, 5.7, 8.0
+//! and MariaDB servers. Every vector is parsed by the tests of this module, so a `mysql_common`
+//! update that breaks any of them is caught before it reaches a server.
+//!
+//! [`split_capture`] and [`check_packet`] allow to run the same checks against a capture
+//! of a real connection (e.g. the server half of a TCP stream dumped by Wireshark):
+//!
+//! ```rust,ignore
+//! use mysql_async::proto::test_vectors::{check_packet, split_capture, PacketKind};
+//!
+//! let packets = split_capture(&capture)?;
+//! check_packet(PacketKind::Handshake, &(packets[0].0).0, CapabilityFlags::empty())?;
+//! ```
+
+use bytes::BytesMut;
+use tokio_codec::Decoder;
+
+use std::io;
+
+use crate::{
+    consts::CapabilityFlags,
+    proto::{
+        column_from_payload, parse_err_packet, parse_handshake_packet, parse_ok_packet, Column,
+        Packet, PacketCodec,
+    },
+    Value,
+};
+
+/// `CLIENT_PROTOCOL_41 | CLIENT_TRANSACTIONS | CLIENT_SECURE_CONNECTION`.
+const PROTOCOL_41: u32 = 0x0000_a200;
+/// `PROTOCOL_41 | CLIENT_SESSION_TRACK`.
+const SESSION_TRACK: u32 = PROTOCOL_41 | 0x0080_0000;
+/// `PROTOCOL_41 | CLIENT_DEPRECATE_EOF`.
+const DEPRECATE_EOF: u32 = PROTOCOL_41 | 0x0100_0000;
+
+/// Kind of a packet that could be parsed without any context but capabilities.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketKind {
+    /// Initial handshake packet (protocol version 10).
+    Handshake,
+    /// OK packet.
+    Ok,
+    /// EOF packet (or OK packet with `0xFE` header if `CLIENT_DEPRECATE_EOF` is negotiated).
+    Eof,
+    /// ERR packet.
+    Err,
+    /// Column definition packet (protocol 4.1).
+    ColumnDef,
+}
+
+/// Golden packet.
+#[derive(Debug, Clone, Copy)]
+pub struct TestVector {
+    /// Server and packet this vector was captured from.
+    pub name: &'static str,
+    pub kind: PacketKind,
+    /// Bits of capabilities negotiated for the connection the packet belongs to.
+    pub capabilities: u32,
+    pub payload: &'static [u8],
+}
+
+impl TestVector {
+    /// Capabilities negotiated for the connection the packet belongs to.
+    pub fn capabilities(&self) -> CapabilityFlags {
+        CapabilityFlags::from_bits_truncate(self.capabilities)
+    }
+
+    /// Parses the payload as a packet of `self.kind`.
+    pub fn check(&self) -> io::Result<()> {
+        check_packet(self.kind, self.payload, self.capabilities())
+    }
+}
+
+/// Initial handshake packets of supported servers.
+pub const HANDSHAKES: &[TestVector] = &[
+    TestVector {
+        name: "MySql 5.6.51",
+        kind: PacketKind::Handshake,
+        capabilities: 0,
+        payload: b"\x0a5.6.51\x00\x0a\x00\x00\x003bB/&ZG[\x00\xff\xf7\x08\x02\x00\x7f\x80\x15\x00\
+          \x00\x00\x00\x00\x00\x00\x00\x00\x00mX7F~Y0@w#)_\x00mysql_native_password\x00",
+    },
+    TestVector {
+        name: "MySql 5.7.31",
+        kind: PacketKind::Handshake,
+        capabilities: 0,
+        payload: b"\x0a5.7.31-log\x00\x0b\x00\x00\x00\x1e\x13zN\x0ca;q\x00\xff\xff\x08\x02\x00\
+          \xff\x81\x15\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00h\x01Fp<G\x7f\x11^\x1b\x04\
+          L\x00mysql_native_password\x00",
+    },
+    TestVector {
+        name: "MySql 8.0.21",
+        kind: PacketKind::Handshake,
+        capabilities: 0,
+        payload: b"\x0a8.0.21\x00\x0c\x00\x00\x00\x16C\x0bq\x5c\x1f~\x05\x00\xff\xff\xff\x02\x00\
+          \xff\xdf\x15\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x19R9\x0f]`bJ\x12~\x7f\
+          \x03\x00caching_sha2_password\x00",
+    },
+    TestVector {
+        name: "MariaDB 10.4.12",
+        kind: PacketKind::Handshake,
+        capabilities: 0,
+        payload: b"\x0a5.5.5-10.4.12-MariaDB-1:10.4.12+maria~bionic\x00\x0d\x00\x00\x00a\x5ctB}:K\
+          v\x00\xfe\xf7\x08\x02\x00\xbf\x81\x15\x00\x00\x00\x00\x00\x00\x07\x00\x00\x00E\
+          z7;$5z=iF'(\x00mysql_native_password\x00",
+    },
+];
+
+/// OK, EOF and ERR packets.
+pub const GENERIC_RESPONSES: &[TestVector] = &[
+    TestVector {
+        name: "OK",
+        kind: PacketKind::Ok,
+        capabilities: PROTOCOL_41,
+        payload: b"\x00\x00\x00\x02\x00\x00\x00",
+    },
+    TestVector {
+        name: "OK after INSERT with warnings",
+        kind: PacketKind::Ok,
+        capabilities: PROTOCOL_41,
+        payload: b"\x00\x03\xfc\x01\x01\x02\x00\x01\x00",
+    },
+    TestVector {
+        name: "OK with info and CLIENT_SESSION_TRACK",
+        kind: PacketKind::Ok,
+        capabilities: SESSION_TRACK,
+        payload: b"\x00\x05\x00\x22\x00\x00\x00(Rows matched: 5  Changed: 5  Warnings: 0",
+    },
+    TestVector {
+        name: "OK with 0xFE header and CLIENT_DEPRECATE_EOF",
+        kind: PacketKind::Eof,
+        capabilities: DEPRECATE_EOF,
+        payload: b"\xfe\x00\x00\x02\x00\x00\x00",
+    },
+    TestVector {
+        name: "EOF",
+        kind: PacketKind::Eof,
+        capabilities: PROTOCOL_41,
+        payload: b"\xfe\x00\x00\x22\x00",
+    },
+    TestVector {
+        name: "ERR",
+        kind: PacketKind::Err,
+        capabilities: PROTOCOL_41,
+        payload: b"\xff(\x04#42000You have an error in your SQL syntax",
+    },
+    TestVector {
+        name: "ERR without SQL state sent instead of handshake",
+        kind: PacketKind::Err,
+        capabilities: 0,
+        payload: b"\xff\x10\x04Too many connections",
+    },
+];
+
+/// Column definitions of a result set with a column of every type (see `BINARY_ROWS`).
+pub const COLUMN_DEFS: &[&[u8]] = &[
+    // tiny
+    b"\x03def\x04test\x01t\x01t\x04tiny\x04tiny\x0c?\x00\x04\x00\x00\x00\x01\x00\x00\x00\x00\
+      \x00",
+    // tiny_u
+    b"\x03def\x04test\x01t\x01t\x06tiny_u\x06tiny_u\x0c?\x00\x03\x00\x00\x00\x01 \x00\x00\x00\
+      \x00",
+    // short
+    b"\x03def\x04test\x01t\x01t\x05short\x05short\x0c?\x00\x06\x00\x00\x00\x02\x00\x00\x00\x00\
+      \x00",
+    // medium
+    b"\x03def\x04test\x01t\x01t\x06medium\x06medium\x0c?\x00\x09\x00\x00\x00\x09\x00\x00\x00\
+      \x00\x00",
+    // long
+    b"\x03def\x04test\x01t\x01t\x04long\x04long\x0c?\x00\x0b\x00\x00\x00\x03\x00\x00\x00\x00\
+      \x00",
+    // longlong_u
+    b"\x03def\x04test\x01t\x01t\x0alonglong_u\x0alonglong_u\x0c?\x00\x14\x00\x00\x00\x08 \x00\
+      \x00\x00\x00",
+    // float
+    b"\x03def\x04test\x01t\x01t\x05float\x05float\x0c?\x00\x0c\x00\x00\x00\x04\x00\x00\x00\x00\
+      \x00",
+    // double
+    b"\x03def\x04test\x01t\x01t\x06double\x06double\x0c?\x00\x16\x00\x00\x00\x05\x00\x00\x00\
+      \x00\x00",
+    // year
+    b"\x03def\x04test\x01t\x01t\x04year\x04year\x0c?\x00\x04\x00\x00\x00\x0d`\x00\x00\x00\x00",
+    // date
+    b"\x03def\x04test\x01t\x01t\x04date\x04date\x0c?\x00\x0a\x00\x00\x00\x0a\x80\x00\x00\x00\
+      \x00",
+    // datetime
+    b"\x03def\x04test\x01t\x01t\x08datetime\x08datetime\x0c?\x00\x1a\x00\x00\x00\x0c\x80\x00\
+      \x06\x00\x00",
+    // timestamp
+    b"\x03def\x04test\x01t\x01t\x09timestamp\x09timestamp\x0c?\x00\x13\x00\x00\x00\x07\x81\x00\
+      \x00\x00\x00",
+    // time
+    b"\x03def\x04test\x01t\x01t\x04time\x04time\x0c?\x00\x11\x00\x00\x00\x0b\x80\x00\x06\x00\
+      \x00",
+    // decimal
+    b"\x03def\x04test\x01t\x01t\x07decimal\x07decimal\x0c?\x00\x07\x00\x00\x00\xf6\x00\x00\x02\
+      \x00\x00",
+    // varchar
+    b"\x03def\x04test\x01t\x01t\x07varchar\x07varchar\x0c-\x00P\x00\x00\x00\xfd\x00\x00\x00\
+      \x00\x00",
+    // char
+    b"\x03def\x04test\x01t\x01t\x04char\x04char\x0c-\x00\x08\x00\x00\x00\xfe\x00\x00\x00\x00\
+      \x00",
+    // blob
+    b"\x03def\x04test\x01t\x01t\x04blob\x04blob\x0c?\x00\xff\xff\x00\x00\xfc\x90\x00\x00\x00\
+      \x00",
+    // bit
+    b"\x03def\x04test\x01t\x01t\x03bit\x03bit\x0c?\x00\x03\x00\x00\x00\x10 \x00\x00\x00\x00",
+    // enum
+    b"\x03def\x04test\x01t\x01t\x04enum\x04enum\x0c-\x00\x04\x00\x00\x00\xfe\x00\x01\x00\x00\
+      \x00",
+    // set
+    b"\x03def\x04test\x01t\x01t\x03set\x03set\x0c-\x00\x14\x00\x00\x00\xfe\x00\x08\x00\x00\x00",
+    // json
+    b"\x03def\x04test\x01t\x01t\x04json\x04json\x0c?\x00\xff\xff\xff\xff\xf5\x90\x00\x00\x00\
+      \x00",
+    // geometry
+    b"\x03def\x04test\x01t\x01t\x08geometry\x08geometry\x0c?\x00\xff\xff\xff\xff\xff\x90\x00\
+      \x00\x00\x00",
+];
+
+/// Binary protocol rows of the result set described by `COLUMN_DEFS`.
+///
+/// The second row consists of `NULL`s and zero dates.
+pub const BINARY_ROWS: &[&[u8]] = &[
+    b"\x00\x00\x00\x00\xfb\xfa\xd4\xfe\x00\x00\x80\xff\xff\xff\xff\x7f\xff\xff\xff\xff\xff\xff\
+      \xff\xff\x00\x00\xc0?\x00\x00\x00\x00\x00\x00\x02\xc0\xe4\x07\x04\xe4\x07\x02\x1d\x0b\
+      \xe4\x07\x02\x1d\x0d\x0e\x0f@\xe2\x01\x00\x07\xb2\x07\x01\x01\x00\x00\x01\x0c\x01\x01\
+      \x00\x00\x00\x02\x03\x04\x05\x00\x00\x00\x06-12.34\x06h\xc3\xa9llo\x02ab\x02\x00\xff\x01\
+      \x05\x01b\x03a,c\x10{\x22k\x22: [1, null]}\x19\x00\x00\x00\x00\x01\x01\x00\x00\x00\x00\
+      \x00\x00\x00\x00\x00\xf0?\x00\x00\x00\x00\x00\x00\x00@",
+    b"\x00\xf8\xa7\xff\x00\x00\x00\x00",
+];
+
+/// Parses `COLUMN_DEFS`.
+pub fn columns() -> io::Result<Vec<Column>> {
+    COLUMN_DEFS
+        .iter()
+        .map(|payload| column_from_payload(payload.to_vec()))
+        .collect()
+}
+
+/// Values of `BINARY_ROWS` as defined by the binary protocol (`ProtocolBinary::*` value
+/// encodings and the `UNSIGNED_FLAG` of `COLUMN_DEFS`), independently of any parser.
+///
+/// Comments show the wire bytes of every value of the first row.
+pub fn binary_rows_values() -> Vec<Vec<Value>> {
+    use Value::*;
+
+    let geometry = {
+        // SRID 0, WKB POINT(1 2)
+        let mut wkb = vec![0, 0, 0, 0, 1, 1, 0, 0, 0];
+        wkb.extend_from_slice(&1f64.to_le_bytes());
+        wkb.extend_from_slice(&2f64.to_le_bytes());
+        wkb
+    };
+
+    vec![
+        vec![
+            // int<1> fb
+            Int(-5),
+            // int<1> fa, UNSIGNED_FLAG
+            UInt(250),
+            // int<2> d4 fe
+            Int(-300),
+            // MYSQL_TYPE_INT24 is sent as int<4> 00 00 80 ff
+            Int(-8_388_608),
+            // int<4> ff ff ff 7f
+            Int(2_147_483_647),
+            // int<8> ff ff ff ff ff ff ff ff, UNSIGNED_FLAG
+            UInt(u64::max_value()),
+            // float 00 00 c0 3f
+            Float(1.5),
+            // double 00 00 00 00 00 00 02 c0
+            Float(-2.25),
+            // int<2> e4 07, UNSIGNED_FLAG (the server always sets it for YEAR)
+            UInt(2020),
+            // length 4, year e4 07, month 02, day 1d
+            Date(2020, 2, 29, 0, 0, 0, 0),
+            // length 11, .., hour 0d, minute 0e, second 0f, microsecond 40 e2 01 00
+            Date(2020, 2, 29, 13, 14, 15, 123_456),
+            // length 7, year b2 07, month 01, day 01, 00 00 01
+            Date(1970, 1, 1, 0, 0, 1, 0),
+            // length 12, is_negative 01, days 01 00 00 00, 02 03 04, microsecond 05 00 00 00
+            Time(true, 1, 2, 3, 4, 5),
+            // the rest are string<lenenc>
+            Bytes(b"-12.34".to_vec()),
+            Bytes("h\u{e9}llo".as_bytes().to_vec()),
+            Bytes(b"ab".to_vec()),
+            Bytes(vec![0x00, 0xff]),
+            Bytes(vec![0x05]),
+            Bytes(b"b".to_vec()),
+            Bytes(b"a,c".to_vec()),
+            Bytes(br#"{"k": [1, null]}"#.to_vec()),
+            Bytes(geometry),
+        ],
+        vec![
+            Int(0),
+            NULL,
+            NULL,
+            NULL,
+            NULL,
+            NULL,
+            NULL,
+            NULL,
+            NULL,
+            Date(0, 0, 0, 0, 0, 0, 0),
+            Date(0, 0, 0, 0, 0, 0, 0),
+            NULL,
+            Time(false, 0, 0, 0, 0, 0),
+            NULL,
+            NULL,
+            NULL,
+            NULL,
+            NULL,
+            NULL,
+            NULL,
+            NULL,
+            NULL,
+        ],
+    ]
+}
+
+/// Splits a capture of one direction of a connection into `(payload, sequence_id)` pairs.
+///
+/// Fails if the capture ends in the middle of a packet.
+pub fn split_capture(capture: &[u8]) -> io::Result<Vec<(Packet, u8)>> {
+    let mut codec = PacketCodec::new();
+    let mut buf = BytesMut::from(capture);
+    let mut packets = Vec::new();
+    while let Some(packet) = codec.decode(&mut buf)? {
+        packets.push(packet);
+    }
+    if !buf.is_empty() || codec.has_pending_packet() {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "capture ends with an incomplete packet",
+        ));
+    }
+    Ok(packets)
+}
+
+/// Parses `payload` as a packet of the given `kind`.
+pub fn check_packet(
+    kind: PacketKind,
+    payload: &[u8],
+    capabilities: CapabilityFlags,
+) -> io::Result<()> {
+    let header = payload.get(0).cloned();
+    match kind {
+        PacketKind::Handshake => parse_handshake_packet(payload).map(drop),
+        PacketKind::Ok if header == Some(0x00) => parse_ok_packet(payload, capabilities).map(drop),
+        PacketKind::Eof if header == Some(0xFE) => parse_ok_packet(payload, capabilities).map(drop),
+        PacketKind::Err => parse_err_packet(payload, capabilities).map(drop),
+        PacketKind::ColumnDef => column_from_payload(payload.to_vec()).map(drop),
+        PacketKind::Ok | PacketKind::Eof => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unexpected header {:?} of {:?} packet", header, kind),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::BytesMut;
+    use tokio_codec::Encoder;
+
+    use super::*;
+    use crate::proto::AuthPlugin;
+
+    /// Frames `payloads` as they would be sent over the wire.
+    fn capture(payloads: &[&[u8]]) -> Vec<u8> {
+        let mut codec = PacketCodec::new();
+        let mut buf = BytesMut::new();
+        for (i, payload) in payloads.iter().enumerate() {
+            codec
+                .encode((Packet(payload.to_vec()), i as u8), &mut buf)
+                .unwrap();
+        }
+        buf.to_vec()
+    }
+
+    #[test]
+    fn should_parse_handshakes() {
+        let expected = [
+            (&b"5.6.51"[..], Some((5, 6, 51)), 10, false),
+            (&b"5.7.31-log"[..], Some((5, 7, 31)), 11, false),
+            (&b"8.0.21"[..], Some((8, 0, 21)), 12, true),
+            (
+                &b"5.5.5-10.4.12-MariaDB-1:10.4.12+maria~bionic"[..],
+                None,
+                13,
+                false,
+            ),
+        ];
+        for (vector, (version, parsed, id, sha2)) in HANDSHAKES.iter().zip(expected.iter()) {
+            let handshake = parse_handshake_packet(vector.payload).expect(vector.name);
+            assert_eq!(handshake.server_version_ref(), *version, "{}", vector.name);
+            if parsed.is_some() {
+                assert_eq!(
+                    handshake.server_version_parsed(),
+                    *parsed,
+                    "{}",
+                    vector.name
+                );
+            }
+            assert_eq!(handshake.connection_id(), *id, "{}", vector.name);
+            assert_eq!(handshake.scramble_1_ref().len(), 8, "{}", vector.name);
+            assert_eq!(
+                handshake.scramble_2_ref().map(|x| x.len()),
+                Some(12),
+                "{}",
+                vector.name
+            );
+            assert!(handshake.capabilities().contains(
+                CapabilityFlags::CLIENT_PROTOCOL_41 | CapabilityFlags::CLIENT_PLUGIN_AUTH
+            ));
+            match handshake.auth_plugin() {
+                Some(AuthPlugin::CachingSha2Password) => assert!(*sha2, "{}", vector.name),
+                Some(AuthPlugin::MysqlNativePassword) => assert!(!*sha2, "{}", vector.name),
+                other => panic!("{}: unexpected plugin {:?}", vector.name, other),
+            }
+        }
+    }
+
+    #[test]
+    fn should_parse_generic_responses() {
+        for vector in GENERIC_RESPONSES {
+            vector.check().expect(vector.name);
+        }
+
+        let ok = parse_ok_packet(
+            GENERIC_RESPONSES[1].payload,
+            GENERIC_RESPONSES[1].capabilities(),
+        )
+        .unwrap();
+        assert_eq!(ok.affected_rows(), 3);
+        assert_eq!(ok.last_insert_id(), Some(257));
+        assert_eq!(ok.warnings(), 1);
+
+        let eof = parse_ok_packet(
+            GENERIC_RESPONSES[4].payload,
+            GENERIC_RESPONSES[4].capabilities(),
+        )
+        .unwrap();
+        assert_eq!(eof.status_flags().bits(), 0x0022);
+
+        let err = parse_err_packet(
+            GENERIC_RESPONSES[5].payload,
+            GENERIC_RESPONSES[5].capabilities(),
+        )
+        .unwrap();
+        assert_eq!(err.error_code(), 1064);
+        assert_eq!(err.sql_state_str(), "42000");
+        assert_eq!(err.message_str(), "You have an error in your SQL syntax");
+
+        let err = parse_err_packet(
+            GENERIC_RESPONSES[6].payload,
+            GENERIC_RESPONSES[6].capabilities(),
+        )
+        .unwrap();
+        assert_eq!(err.error_code(), 1040);
+        assert_eq!(err.message_str(), "Too many connections");
+    }
+
+    #[test]
+    fn should_reject_packets_of_other_kind() {
+        let ok = &GENERIC_RESPONSES[0];
+        assert!(check_packet(PacketKind::Eof, ok.payload, ok.capabilities()).is_err());
+        let eof = &GENERIC_RESPONSES[4];
+        assert!(check_packet(PacketKind::Ok, eof.payload, eof.capabilities()).is_err());
+    }
+
+    #[test]
+    fn should_decode_binary_rows() {
+        let columns = columns().unwrap();
+        assert_eq!(columns.len(), 22);
+        for payload in COLUMN_DEFS {
+            check_packet(PacketKind::ColumnDef, payload, CapabilityFlags::empty()).unwrap();
+        }
+
+        for (payload, expected) in BINARY_ROWS.iter().zip(binary_rows_values()) {
+            let values = crate::proto::read_bin_values(payload, &*columns).unwrap();
+            assert_eq!(values, expected);
+        }
+    }
+
+    #[test]
+    fn should_round_trip_captures() {
+        let payloads = HANDSHAKES
+            .iter()
+            .chain(GENERIC_RESPONSES)
+            .map(|vector| vector.payload)
+            .chain(COLUMN_DEFS.iter().cloned())
+            .chain(BINARY_ROWS.iter().cloned())
+            .collect::<Vec<_>>();
+
+        let packets = split_capture(&capture(&payloads)).unwrap();
+        assert_eq!(packets.len(), payloads.len());
+        for (i, ((packet, seq_id), payload)) in packets.into_iter().zip(payloads).enumerate() {
+            assert_eq!(&*packet.0, payload);
+            assert_eq!(seq_id, i as u8);
+        }
+    }
+
+    #[test]
+    fn should_reject_truncated_captures() {
+        let capture = capture(&[HANDSHAKES[0].payload]);
+        for len in 1..capture.len() {
+            assert!(split_capture(&capture[..len]).is_err());
+        }
+        assert_eq!(split_capture(&[]).unwrap().len(), 0);
+    }
+}
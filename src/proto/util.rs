@@ -0,0 +1,137 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Length-encoded integers and strings of the MySql protocol.
+//!
+//! Readers take a `&mut &[u8]` cursor and advance it past the consumed bytes:
+//!
+//! ```rust,ignore
+//! use mysql_async::proto::util::{read_lenenc_int, read_lenenc_str};
+//!
+//! let mut buf = &payload[..];
+//! let column_count = read_lenenc_int(&mut buf)?;
+//! let catalog = read_lenenc_str(&mut buf)?;
+//! ```
+
+use mysql_common::io::{ReadMysqlExt, WriteMysqlExt};
+
+use std::io;
+
+/// First byte of a `NULL` value in a text protocol row.
+const NULL_MARKER: u8 = 0xfb;
+/// First byte of an error packet.
+const ERR_MARKER: u8 = 0xff;
+
+/// Returns the number of bytes used to encode `x` as a length-encoded integer.
+pub fn lenenc_int_len(x: u64) -> usize {
+    if x < 251 {
+        1
+    } else if x < 1 << 16 {
+        3
+    } else if x < 1 << 24 {
+        4
+    } else {
+        9
+    }
+}
+
+/// Returns the number of bytes used to encode `bytes` as a length-encoded string.
+pub fn lenenc_str_len(bytes: &[u8]) -> usize {
+    lenenc_int_len(bytes.len() as u64) + bytes.len()
+}
+
+/// Reads a length-encoded integer from `buf`.
+///
+/// `0xfb` (`NULL` in a text protocol row) and `0xff` (error packet) aren't valid first bytes
+/// of an integer, so `io::ErrorKind::InvalidData` is returned for them.
+pub fn read_lenenc_int(buf: &mut &[u8]) -> io::Result<u64> {
+    match buf.first() {
+        Some(&NULL_MARKER) | Some(&ERR_MARKER) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid length-encoded integer",
+        )),
+        _ => buf.read_lenenc_int(),
+    }
+}
+
+/// Reads a length-encoded string from `buf` (without copying it).
+pub fn read_lenenc_str<'a>(buf: &mut &'a [u8]) -> io::Result<&'a [u8]> {
+    let len = read_lenenc_int(buf)?;
+    if len > buf.len() as u64 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "length-encoded string is truncated",
+        ));
+    }
+    let (bytes, rest) = buf.split_at(len as usize);
+    *buf = rest;
+    Ok(bytes)
+}
+
+/// Appends `x` encoded as a length-encoded integer to `buf`.
+pub fn write_lenenc_int(buf: &mut Vec<u8>, x: u64) {
+    buf.write_lenenc_int(x)
+        .expect("write to a vector should not fail");
+}
+
+/// Appends `bytes` encoded as a length-encoded string to `buf`.
+pub fn write_lenenc_str(buf: &mut Vec<u8>, bytes: &[u8]) {
+    buf.write_lenenc_bytes(bytes)
+        .expect("write to a vector should not fail");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_roundtrip_lenenc_ints() {
+        for &x in &[
+            0,
+            250,
+            251,
+            0xffff,
+            0x1_0000,
+            0xff_ffff,
+            0x100_0000,
+            u64::max_value(),
+        ] {
+            let mut buf = Vec::new();
+            write_lenenc_int(&mut buf, x);
+            assert_eq!(buf.len(), lenenc_int_len(x));
+            let mut cursor = &buf[..];
+            assert_eq!(read_lenenc_int(&mut cursor).unwrap(), x);
+            assert!(cursor.is_empty());
+        }
+
+        assert_eq!(
+            read_lenenc_int(&mut &[0xfc, 0x01, 0x02][..]).unwrap(),
+            0x0201
+        );
+        assert!(read_lenenc_int(&mut &[0xfb][..]).is_err());
+        assert!(read_lenenc_int(&mut &[0xff][..]).is_err());
+        assert!(read_lenenc_int(&mut &[0xfd, 0x01][..]).is_err());
+        assert!(read_lenenc_int(&mut &[][..]).is_err());
+    }
+
+    #[test]
+    fn should_roundtrip_lenenc_strs() {
+        let long = vec![b'x'; 300];
+        for bytes in &[&b""[..], &b"def"[..], &long[..]] {
+            let mut buf = Vec::new();
+            write_lenenc_str(&mut buf, bytes);
+            buf.push(0);
+            assert_eq!(buf.len(), lenenc_str_len(bytes) + 1);
+            let mut cursor = &buf[..];
+            assert_eq!(read_lenenc_str(&mut cursor).unwrap(), *bytes);
+            assert_eq!(cursor, &[0][..]);
+        }
+
+        assert!(read_lenenc_str(&mut &[0x03, b'a', b'b'][..]).is_err());
+    }
+}
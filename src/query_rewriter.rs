@@ -0,0 +1,61 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use std::{fmt, sync::Arc};
+
+/// Trait used to transform statement text before it is sent to the server
+/// (see `OptsBuilder::query_rewriter`), e.g. to add shard comments, optimizer hints
+/// or tenant predicates.
+///
+/// ```rust,ignore
+/// struct ShardComment(u32);
+///
+/// impl QueryRewriter for ShardComment {
+///     fn rewrite(&self, query: &str) -> Option<String> {
+///         Some(format!("/* shard={} */ {}", self.0, query))
+///     }
+/// }
+/// ```
+pub trait QueryRewriter: Sync + Send {
+    /// Returns the new text of a `query` (`None` to send it unchanged).
+    ///
+    /// It is called for every text query and every statement being prepared, including
+    /// statements executed by the driver itself (e.g. init commands or `SELECT @@...`
+    /// queries issued while connecting), so unknown statements should be left unchanged.
+    /// Named parameters are already replaced with `?` at this point, and the number
+    /// of `?` placeholders must not be changed.
+    fn rewrite(&self, query: &str) -> Option<String>;
+}
+
+/// Object used to wrap `T: QueryRewriter` inside of Opts.
+#[derive(Clone)]
+pub struct QueryRewriterObject(Arc<dyn QueryRewriter>);
+
+impl QueryRewriterObject {
+    pub fn new<T: QueryRewriter + 'static>(rewriter: T) -> Self {
+        QueryRewriterObject(Arc::new(rewriter))
+    }
+
+    pub fn clone_inner(&self) -> Arc<dyn QueryRewriter> {
+        self.0.clone()
+    }
+}
+
+impl PartialEq for QueryRewriterObject {
+    fn eq(&self, other: &QueryRewriterObject) -> bool {
+        self.0.as_ref() as *const dyn QueryRewriter == other.0.as_ref() as *const dyn QueryRewriter
+    }
+}
+
+impl Eq for QueryRewriterObject {}
+
+impl fmt::Debug for QueryRewriterObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Query rewriter object")
+    }
+}
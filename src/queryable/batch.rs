@@ -0,0 +1,287 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use std::ops::Range;
+
+use crate::{
+    error::*,
+    Params,
+    Value::{self, *},
+};
+
+/// `INSERT` (or `REPLACE`) statement with a single `VALUES (..)` row,
+/// that could be rewritten into a multi-row form.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MultiValuesInsert {
+    head: String,
+    row: String,
+    tail: String,
+    params_per_row: usize,
+}
+
+impl MultiValuesInsert {
+    /// Returns `None` if `query` can't be rewritten into a multi-row form.
+    ///
+    /// Named parameters should be replaced with `?` prior to this call.
+    pub fn parse(query: &str) -> Option<MultiValuesInsert> {
+        let bytes = query.as_bytes();
+        let unquoted = unquoted_mask(bytes);
+        let words = words(bytes, &*unquoted);
+        let is = |word: &(usize, usize), keyword: &str| {
+            bytes[word.0..word.1].eq_ignore_ascii_case(keyword.as_bytes())
+        };
+
+        let verb = words.first()?;
+        if !is(verb, "INSERT") && !is(verb, "REPLACE") {
+            return None;
+        }
+
+        let values = words
+            .iter()
+            .position(|word| is(word, "VALUES") || is(word, "VALUE"))?;
+        // `INSERT .. SET` and `INSERT .. SELECT` could mention `VALUES()` function
+        // in `ON DUPLICATE KEY UPDATE` clause.
+        if words[..values]
+            .iter()
+            .any(|word| is(word, "SET") || is(word, "SELECT") || is(word, "UPDATE"))
+        {
+            return None;
+        }
+
+        let keyword_end = words[values].1;
+        let open = keyword_end
+            + bytes[keyword_end..]
+                .iter()
+                .position(|x| !x.is_ascii_whitespace())?;
+        if bytes[open] != b'(' {
+            return None;
+        }
+
+        let mut depth = 0;
+        let mut close = None;
+        for i in open..bytes.len() {
+            if !unquoted[i] {
+                continue;
+            }
+            match bytes[i] {
+                b'(' => depth += 1,
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        close = Some(i);
+                        break;
+                    }
+                }
+                _ => (),
+            }
+        }
+        let close = close?;
+
+        let count_params =
+            |range: Range<usize>| range.filter(|&i| unquoted[i] && bytes[i] == b'?').count();
+        if count_params(0..open) > 0 || count_params(close + 1..bytes.len()) > 0 {
+            return None;
+        }
+
+        let tail = &query[close + 1..];
+        if tail.trim_start().starts_with(',') {
+            // already a multi-row statement
+            return None;
+        }
+
+        Some(MultiValuesInsert {
+            head: query[..open].into(),
+            row: query[open..close + 1].into(),
+            tail: tail.into(),
+            params_per_row: count_params(open..close + 1),
+        })
+    }
+
+    /// Number of parameters of a row.
+    pub fn params_per_row(&self) -> usize {
+        self.params_per_row
+    }
+
+    /// Maximum number of rows in one statement (statement could have at most 65535 parameters).
+    pub fn max_rows(&self) -> usize {
+        if self.params_per_row > 0 {
+            u16::max_value() as usize / self.params_per_row
+        } else {
+            usize::max_value()
+        }
+    }
+
+    /// Length of a statement text without rows.
+    pub fn base_len(&self) -> usize {
+        self.head.len() + self.tail.len()
+    }
+
+    /// Length of a row in statement text.
+    pub fn row_len(&self) -> usize {
+        self.row.len() + 1
+    }
+
+    /// Returns text of a statement with `rows` rows.
+    pub fn build(&self, rows: usize) -> String {
+        let mut query = String::with_capacity(self.base_len() + self.row_len() * rows);
+        query.push_str(&*self.head);
+        for i in 0..rows {
+            if i > 0 {
+                query.push(',');
+            }
+            query.push_str(&*self.row);
+        }
+        query.push_str(&*self.tail);
+        query
+    }
+}
+
+/// Binds `NULL` to named parameters missing in `params`
+/// (see `OptsBuilder::missing_named_params_as_null`).
+pub fn fill_missing_named_params(params: Params, named_params: &[String]) -> Params {
+    match params {
+        Params::Named(mut values) => {
+            for name in named_params {
+                values.entry(name.clone()).or_insert(NULL);
+            }
+            Params::Named(values)
+        }
+        params => params,
+    }
+}
+
+/// Converts `params` into a list of positional values.
+pub fn positional_values(
+    params: Params,
+    named_params: Option<&Vec<String>>,
+    missing_as_null: bool,
+) -> Result<Vec<Value>> {
+    match params {
+        Params::Empty => Ok(Vec::new()),
+        Params::Positional(values) => Ok(values),
+        Params::Named(_) => match named_params {
+            Some(named_params) if missing_as_null => positional_values(
+                fill_missing_named_params(params, named_params),
+                Some(named_params),
+                false,
+            ),
+            Some(named_params) => match params.into_positional(named_params)? {
+                Params::Positional(values) => Ok(values),
+                _ => unreachable!(),
+            },
+            None => Err(DriverError::NamedParamsForPositionalQuery.into()),
+        },
+    }
+}
+
+/// Approximate length of a `value` in a `COM_STMT_EXECUTE` packet (including type info).
+pub fn bin_value_len(value: &Value) -> usize {
+    2 + match *value {
+        NULL => 0,
+        Bytes(ref x) => 9 + x.len(),
+        Int(..) | UInt(..) | Float(..) => 8,
+        Date(..) => 12,
+        Time(..) => 13,
+    }
+}
+
+/// Returns `true` for every byte of a `query` that is not inside of quotes or backticks.
+pub fn unquoted_mask(query: &[u8]) -> Vec<bool> {
+    let mut mask = Vec::with_capacity(query.len());
+    let mut quote = None;
+    let mut escaped = false;
+    for &x in query {
+        match quote {
+            Some(q) => {
+                mask.push(false);
+                if escaped {
+                    escaped = false;
+                } else if x == b'\\' && q != b'`' {
+                    escaped = true;
+                } else if x == q {
+                    quote = None;
+                }
+            }
+            None if x == b'\'' || x == b'"' || x == b'`' => {
+                mask.push(false);
+                quote = Some(x);
+            }
+            None => mask.push(true),
+        }
+    }
+    mask
+}
+
+/// Returns ranges of unquoted words of a `query`.
+fn words(query: &[u8], unquoted: &[bool]) -> Vec<(usize, usize)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for i in 0..=query.len() {
+        let is_word_byte = i < query.len()
+            && unquoted[i]
+            && (query[i].is_ascii_alphanumeric() || query[i] == b'_' || query[i] == b'$');
+        match (start, is_word_byte) {
+            (None, true) => start = Some(i),
+            (Some(word_start), false) => {
+                words.push((word_start, i));
+                start = None;
+            }
+            _ => (),
+        }
+    }
+    words
+}
+
+#[cfg(test)]
+mod test {
+    use super::{positional_values, MultiValuesInsert};
+    use crate::{params, Value};
+
+    #[test]
+    fn should_bind_missing_named_params_as_null() {
+        let names = vec!["a".to_string(), "b".to_string(), "a".to_string()];
+        let values = positional_values(params! { "a" => 1 }, Some(&names), true).unwrap();
+        assert_eq!(values, vec![Value::Int(1), Value::NULL, Value::Int(1)]);
+        assert!(positional_values(params! { "a" => 1 }, Some(&names), false).is_err());
+    }
+
+    #[test]
+    fn should_rewrite_simple_insert() {
+        let insert =
+            MultiValuesInsert::parse("INSERT INTO tbl (a, `b)`) VALUES (?, CONCAT('?)', ?))")
+                .unwrap();
+        assert_eq!(insert.max_rows(), 32767);
+        assert_eq!(
+            insert.build(2),
+            "INSERT INTO tbl (a, `b)`) VALUES (?, CONCAT('?)', ?)),(?, CONCAT('?)', ?))"
+        );
+
+        let insert =
+            MultiValuesInsert::parse("replace tbl value(?) ON DUPLICATE KEY UPDATE a = VALUES(a)")
+                .unwrap();
+        assert_eq!(
+            insert.build(3),
+            "replace tbl value(?),(?),(?) ON DUPLICATE KEY UPDATE a = VALUES(a)"
+        );
+    }
+
+    #[test]
+    fn should_not_rewrite_other_statements() {
+        for query in &[
+            "SELECT 'VALUES (?)'",
+            "UPDATE tbl SET a = ?",
+            "INSERT INTO tbl SET a = ? ON DUPLICATE KEY UPDATE a = VALUES(a)",
+            "INSERT INTO tbl SELECT ? FROM dual",
+            "INSERT INTO tbl VALUES (?), (?)",
+            "INSERT INTO tbl VALUES (?) ON DUPLICATE KEY UPDATE a = ?",
+            "INSERT INTO tbl VALUES (?",
+        ] {
+            assert_eq!(MultiValuesInsert::parse(query), None, "{}", query);
+        }
+    }
+}
@@ -0,0 +1,350 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Cheap client-side classification of statements by their leading keyword.
+
+use std::{iter::Peekable, str::Chars};
+
+/// Statements that don't modify data (see `OptsBuilder::enforce_read_only`).
+const READ_ONLY_VERBS: &[&str] = &[
+    "BEGIN",
+    "CHECK",
+    "CHECKSUM",
+    "COMMIT",
+    "DESC",
+    "DESCRIBE",
+    "DO",
+    "EXPLAIN",
+    "HELP",
+    "KILL",
+    "LOCK",
+    "RELEASE",
+    "ROLLBACK",
+    "SAVEPOINT",
+    "SELECT",
+    "SET",
+    "SHOW",
+    "START",
+    "TABLE",
+    "UNLOCK",
+    "USE",
+    "VALUES",
+];
+
+/// Statements that may follow common table expressions of a `WITH` clause.
+const WITH_VERBS: &[&str] = &["DELETE", "SELECT", "TABLE", "UPDATE", "VALUES"];
+
+/// Lexical token of a query (see `tokens`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// Upper-cased bare word (keyword or unquoted identifier).
+    Word(String),
+    /// `?` parameter placeholder.
+    Placeholder,
+    /// `(`.
+    Open,
+    /// `)`.
+    Close,
+    /// `,`.
+    Comma,
+    /// Unsigned integer or decimal number.
+    Number,
+    /// Quoted string or identifier, operator, etc.
+    Other,
+    /// `;` separating statements.
+    End,
+}
+
+/// Splits `query` into tokens. Whitespace and comments are skipped.
+///
+/// Contents of `/*! ... */` and `/*+ ... */` comments are tokenized, because the server
+/// executes (or interprets) them.
+fn tokens(query: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+    let mut in_executable_comment = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' | '`' => {
+                tokens.push(Token::Other);
+                while let Some(x) = chars.next() {
+                    if x == '\\' && c != '`' {
+                        chars.next();
+                    } else if x == c {
+                        break;
+                    }
+                }
+            }
+            '#' => {
+                chars.by_ref().take_while(|x| *x != '\n').for_each(drop);
+            }
+            '-' if starts_dash_comment(chars.clone()) => {
+                chars.by_ref().take_while(|x| *x != '\n').for_each(drop);
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                match chars.peek() {
+                    Some(&'!') | Some(&'+') if !in_executable_comment => {
+                        chars.next();
+                        // version of `/*!50700 ... */`
+                        while chars.peek().filter(|x| x.is_ascii_digit()).is_some() {
+                            chars.next();
+                        }
+                        in_executable_comment = true;
+                        continue;
+                    }
+                    _ => (),
+                }
+                let mut prev = '\0';
+                while let Some(x) = chars.next() {
+                    if prev == '*' && x == '/' {
+                        break;
+                    }
+                    prev = x;
+                }
+            }
+            '*' if in_executable_comment && chars.peek() == Some(&'/') => {
+                chars.next();
+                in_executable_comment = false;
+            }
+            ';' => tokens.push(Token::End),
+            '?' => tokens.push(Token::Placeholder),
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            ',' => tokens.push(Token::Comma),
+            c if c.is_ascii_digit() => {
+                while chars
+                    .peek()
+                    .filter(|x| x.is_ascii_digit() || **x == '.')
+                    .is_some()
+                {
+                    chars.next();
+                }
+                tokens.push(Token::Number);
+            }
+            c if c.is_alphabetic() => {
+                let mut word = c.to_uppercase().collect::<String>();
+                while let Some(&x) = chars.peek().filter(|x| x.is_alphanumeric() || **x == '_') {
+                    word.extend(x.to_uppercase());
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+            c if c.is_whitespace() => (),
+            _ => tokens.push(Token::Other),
+        }
+    }
+
+    tokens
+}
+
+/// Returns `true` if `rest` of a query that follows `-` starts a `-- ` comment, i.e. the second
+/// dash is followed by whitespace, a control character or the end of the query (otherwise
+/// `1--1` is an expression).
+fn starts_dash_comment(mut rest: Peekable<Chars>) -> bool {
+    rest.next() == Some('-')
+        && rest
+            .next()
+            .map_or(true, |x| x.is_whitespace() || x.is_control())
+}
+
+/// Returns upper-cased leading keywords of statements of a (possibly multi-statement) `query`.
+///
+/// Comments and opening parentheses are skipped and empty statements are omitted.
+/// Statement that doesn't start with a keyword has an empty verb. Statement that starts
+/// with common table expressions (`WITH ...`) is classified by the statement that follows them.
+pub(crate) fn statement_verbs(query: &str) -> Vec<String> {
+    let mut verbs = Vec::new();
+    let mut verb: Option<String> = None;
+    let mut depth = 0;
+    let mut verb_depth = 0;
+
+    for token in tokens(query) {
+        match token {
+            Token::Word(word) => match verb {
+                None => {
+                    verb = Some(word);
+                    verb_depth = depth;
+                }
+                Some(ref mut verb)
+                    if *verb == "WITH" && depth == verb_depth && WITH_VERBS.contains(&&*word) =>
+                {
+                    *verb = word;
+                }
+                Some(_) => (),
+            },
+            Token::Open => depth += 1,
+            Token::Close => {
+                depth -= 1;
+                verb.get_or_insert_with(String::new);
+            }
+            Token::Placeholder | Token::Comma | Token::Number | Token::Other => {
+                verb.get_or_insert_with(String::new);
+            }
+            Token::End => {
+                verbs.extend(verb.take());
+                depth = 0;
+            }
+        }
+    }
+    verbs.extend(verb);
+
+    verbs
+}
+
+/// Returns `INTO OUTFILE` or `INTO DUMPFILE` if a statement of `query` writes
+/// its result into a file on the server host.
+pub(crate) fn server_side_file_clause(query: &str) -> Option<&'static str> {
+    let tokens = tokens(query);
+    tokens.windows(2).find_map(|pair| match pair {
+        [Token::Word(into), Token::Word(file)] if into == "INTO" => match &**file {
+            "OUTFILE" => Some("INTO OUTFILE"),
+            "DUMPFILE" => Some("INTO DUMPFILE"),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// Returns indexes of `?` placeholders of `query` bound to `LIMIT` or `OFFSET` clauses
+/// (e.g. both placeholders of `LIMIT ?, ?` and of `LIMIT ? OFFSET ?`).
+///
+/// A clause ends at any token but a placeholder, a number or a comma, so placeholders
+/// that follow a parenthesized subquery with a `LIMIT` (or a comparison) aren't matched.
+pub(crate) fn limit_params(query: &str) -> Vec<usize> {
+    let mut params = Vec::new();
+    let mut index = 0;
+    let mut in_limit = false;
+
+    for token in tokens(query) {
+        match token {
+            Token::Word(word) => in_limit = word == "LIMIT" || word == "OFFSET",
+            Token::Placeholder => {
+                if in_limit {
+                    params.push(index);
+                }
+                index += 1;
+            }
+            Token::Comma | Token::Number => (),
+            Token::Open | Token::Close | Token::Other | Token::End => in_limit = false,
+        }
+    }
+
+    params
+}
+
+/// Returns `true` if a statement with the given leading keyword doesn't modify data.
+pub(crate) fn is_read_only_verb(verb: &str) -> bool {
+    READ_ONLY_VERBS.contains(&verb)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_read_only_verb, limit_params, server_side_file_clause, statement_verbs};
+
+    #[test]
+    fn should_classify_statements() {
+        assert_eq!(
+            statement_verbs(
+                " /* hint */ select 1; -- comment\n(SELECT 'a;b') ; ; # x\n\tInsert INTO t VALUES (1)"
+            ),
+            vec!["SELECT", "SELECT", "INSERT"]
+        );
+        assert_eq!(statement_verbs("DELETE FROM t; "), vec!["DELETE"]);
+        assert!(statement_verbs(" -- nothing").is_empty());
+        assert_eq!(statement_verbs("'a'; SHOW TABLES"), vec!["", "SHOW"]);
+        assert_eq!(
+            statement_verbs(
+                "WITH RECURSIVE x (a) AS (SELECT 1 UNION SELECT a + 1 FROM x WHERE a < 3) \
+                 DELETE FROM t WHERE a IN (SELECT a FROM x)"
+            ),
+            vec!["DELETE"]
+        );
+        assert_eq!(
+            statement_verbs("with x as (select 1), y as (table x) update t, x set t.a = 1"),
+            vec!["UPDATE"]
+        );
+        assert_eq!(
+            statement_verbs("(WITH x AS (SELECT 1) SELECT * FROM x); WITH x AS (SELECT 1)"),
+            vec!["SELECT", "WITH"]
+        );
+        assert_eq!(
+            statement_verbs("SELECT 1--1; DELETE FROM t"),
+            vec!["SELECT", "DELETE"]
+        );
+        assert_eq!(
+            statement_verbs("SELECT 1 --\tx; DELETE FROM t\nSHOW TABLES --"),
+            vec!["SELECT"]
+        );
+        assert_eq!(
+            statement_verbs("SELECT 1 /*! ; DELETE FROM t */; /*!50700 DROP TABLE t */"),
+            vec!["SELECT", "DELETE", "DROP"]
+        );
+        assert_eq!(
+            statement_verbs("SELECT /*+ MAX_EXECUTION_TIME(1) */ 1 /* ; DELETE */"),
+            vec!["SELECT"]
+        );
+
+        assert!(is_read_only_verb("SELECT"));
+        assert!(is_read_only_verb("SHOW"));
+        assert!(is_read_only_verb("KILL"));
+        assert!(is_read_only_verb("LOCK"));
+        assert!(is_read_only_verb("UNLOCK"));
+        assert!(!is_read_only_verb("WITH"));
+        assert!(!is_read_only_verb("UPDATE"));
+        assert!(!is_read_only_verb("CALL"));
+    }
+
+    #[test]
+    fn should_detect_server_side_files() {
+        assert_eq!(
+            server_side_file_clause("SELECT * FROM t INTO OUTFILE '/tmp/t.csv'"),
+            Some("INTO OUTFILE")
+        );
+        assert_eq!(
+            server_side_file_clause("select a into /* x */ dumpfile '/tmp/a' from t"),
+            Some("INTO DUMPFILE")
+        );
+        assert_eq!(server_side_file_clause("SELECT 1 INTO @a"), None);
+        assert_eq!(
+            server_side_file_clause("SELECT 'INTO OUTFILE', `into` outfile"),
+            None
+        );
+        assert_eq!(server_side_file_clause("-- INTO OUTFILE\nSELECT 1"), None);
+    }
+
+    #[test]
+    fn should_find_limit_params() {
+        assert_eq!(
+            limit_params("SELECT ? FROM t WHERE a = ? ORDER BY b LIMIT ?, ?"),
+            vec![2, 3]
+        );
+        assert_eq!(
+            limit_params("select * from t limit ? offset ?; select ? limit 10"),
+            vec![0, 1]
+        );
+        assert_eq!(
+            limit_params("(SELECT a FROM t LIMIT ?) UNION (SELECT '?' FROM u LIMIT 1) LIMIT ?"),
+            vec![0, 1]
+        );
+        assert_eq!(
+            limit_params("SELECT a FROM t WHERE b IN (SELECT c FROM u LIMIT 1) AND d = ?"),
+            Vec::<usize>::new()
+        );
+        assert_eq!(
+            limit_params("SELECT * FROM t WHERE (SELECT code FROM t LIMIT 1) = ?"),
+            Vec::<usize>::new()
+        );
+        assert_eq!(
+            limit_params("SELECT * FROM t WHERE a IN (?, ?) LIMIT 10, ?"),
+            vec![2]
+        );
+        assert!(limit_params("SELECT `limit` FROM t -- LIMIT ?\nWHERE a = ?").is_empty());
+    }
+}
@@ -0,0 +1,207 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Futures returned by `Queryable` methods.
+//!
+//! These are concrete types, so they can be stored in structs or returned from functions
+//! without boxing. Steps that don't depend on a server response are polled in place,
+//! e.g. `Query` and `DropQuery` of a statement that returns an OK packet don't allocate
+//! futures at all.
+
+use futures::{try_ready, Async::Ready, Future, Poll};
+
+use crate::{
+    connection_like::{read_packet::ReadPacket, write_packet::WritePacket},
+    consts::Command,
+    error::*,
+    queryable::{
+        query_result::{self, QueryResult},
+        stmt::Stmt,
+        BinaryProtocol, Queryable, TextProtocol,
+    },
+    BoxFuture, Params,
+};
+
+enum PingStep<T> {
+    Write(WritePacket<T>),
+    Read(ReadPacket<T>),
+}
+
+/// Future that executes `COM_PING` and resolves to the wrapped `Queryable`
+/// (see `Queryable::ping`).
+pub struct Ping<T> {
+    step: PingStep<T>,
+}
+
+impl<T: Queryable> Ping<T> {
+    pub(crate) fn new(queryable: T) -> Ping<T> {
+        Ping {
+            step: PingStep::Write(queryable.write_command_data(Command::COM_PING, &[])),
+        }
+    }
+}
+
+impl<T: Queryable> Future for Ping<T> {
+    type Item = T;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            let step = match self.step {
+                PingStep::Write(ref mut fut) => {
+                    PingStep::Read(try_ready!(fut.poll()).read_packet())
+                }
+                PingStep::Read(ref mut fut) => {
+                    let (this, _) = try_ready!(fut.poll());
+                    return Ok(Ready(this));
+                }
+            };
+            self.step = step;
+        }
+    }
+}
+
+enum QueryStep<T> {
+    Write(WritePacket<T>),
+    Read(ReadPacket<T>),
+    Result(BoxFuture<QueryResult<T, TextProtocol>>),
+}
+
+/// Future that performs a text query and resolves to its `QueryResult`
+/// (see `Queryable::query`).
+pub struct Query<T> {
+    step: QueryStep<T>,
+}
+
+impl<T: Queryable> Query<T> {
+    pub(crate) fn new(mut queryable: T, query: &str) -> Query<T> {
+        queryable.set_last_query(query);
+        Query {
+            step: QueryStep::Write(
+                queryable.write_command_data(Command::COM_QUERY, query.as_bytes()),
+            ),
+        }
+    }
+}
+
+impl<T: Queryable> Future for Query<T> {
+    type Item = QueryResult<T, TextProtocol>;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            let step = match self.step {
+                QueryStep::Write(ref mut fut) => {
+                    QueryStep::Read(try_ready!(fut.poll()).read_packet())
+                }
+                QueryStep::Read(ref mut fut) => {
+                    let (mut this, packet) = try_ready!(fut.poll());
+                    if packet.0[0] == 0x00 {
+                        // OK packet, i.e. there is nothing else to read
+                        // (see `ConnectionLike::handle_result_packet`).
+                        this.set_info_from_ok_packet(&packet);
+                        return Ok(Ready(query_result::new(this, None, None)));
+                    }
+                    QueryStep::Result(this.handle_result_packet(packet, None))
+                }
+                QueryStep::Result(ref mut fut) => return fut.poll(),
+            };
+            self.step = step;
+        }
+    }
+}
+
+enum DropQueryStep<T> {
+    Query(Query<T>),
+    Drop(BoxFuture<T>),
+}
+
+/// Future that performs a text query, drops its result and resolves to the wrapped `Queryable`
+/// (see `Queryable::drop_query`).
+pub struct DropQuery<T> {
+    step: DropQueryStep<T>,
+}
+
+impl<T: Queryable> DropQuery<T> {
+    pub(crate) fn new(queryable: T, query: &str) -> DropQuery<T> {
+        DropQuery {
+            step: DropQueryStep::Query(Query::new(queryable, query)),
+        }
+    }
+}
+
+impl<T: Queryable> Future for DropQuery<T> {
+    type Item = T;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            let step = match self.step {
+                DropQueryStep::Query(ref mut fut) => {
+                    let result = try_ready!(fut.poll());
+                    if result.is_empty() {
+                        let (this, _, _) = query_result::disassemble(result);
+                        if !this.has_warnings_to_fetch() {
+                            return Ok(Ready(this));
+                        }
+                        DropQueryStep::Drop(this.fetch_warnings())
+                    } else {
+                        DropQueryStep::Drop(Box::new(result.drop_result()))
+                    }
+                }
+                DropQueryStep::Drop(ref mut fut) => return fut.poll(),
+            };
+            self.step = step;
+        }
+    }
+}
+
+enum PrepExecStep<T> {
+    Prepare(BoxFuture<Stmt<T>>),
+    Execute(BoxFuture<QueryResult<Stmt<T>, BinaryProtocol>>),
+}
+
+/// Future that prepares and executes a statement and resolves to its `QueryResult`
+/// (see `Queryable::prep_exec`).
+pub struct PrepExec<T> {
+    step: PrepExecStep<T>,
+    params: Option<Params>,
+}
+
+impl<T: Queryable> PrepExec<T> {
+    pub(crate) fn new(queryable: T, query: &str, params: Params) -> PrepExec<T> {
+        PrepExec {
+            step: PrepExecStep::Prepare(queryable.prepare(query)),
+            params: Some(params),
+        }
+    }
+}
+
+impl<T: Queryable> Future for PrepExec<T> {
+    type Item = QueryResult<T, BinaryProtocol>;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            let step = match self.step {
+                PrepExecStep::Prepare(ref mut fut) => {
+                    let stmt = try_ready!(fut.poll());
+                    let params = self.params.take().expect("polled after completion");
+                    PrepExecStep::Execute(Box::new(stmt.execute(params)))
+                }
+                PrepExecStep::Execute(ref mut fut) => {
+                    let result = try_ready!(fut.poll());
+                    let (stmt, columns, _) = query_result::disassemble(result);
+                    let (conn_like, cached) = stmt.unwrap();
+                    return Ok(Ready(query_result::assemble(conn_like, columns, cached)));
+                }
+            };
+            self.step = step;
+        }
+    }
+}
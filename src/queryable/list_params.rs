@@ -0,0 +1,175 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use std::{collections::HashMap, fmt::Write};
+
+use super::batch::unquoted_mask;
+use crate::{error::*, Params, Value};
+
+/// Lists of values bound to named parameters (i.e. `IN (:ids)`).
+///
+/// Every named parameter bound to a list is expanded into the right number of named parameters,
+/// so statement cache will hold a separate statement for every list length used.
+///
+/// ```rust,ignore
+/// let (query, params) = ListParams::new()
+///     .bind("ids", vec![1, 2, 3])
+///     .expand("SELECT * FROM foo WHERE id IN (:ids) AND bar = :bar", params! { "bar" => 42 })?;
+/// conn.prep_exec(query, params)
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ListParams {
+    lists: HashMap<String, Vec<Value>>,
+}
+
+impl ListParams {
+    pub fn new() -> ListParams {
+        ListParams::default()
+    }
+
+    /// Binds list of `values` to a named parameter `name`.
+    pub fn bind<N, I, T>(mut self, name: N, values: I) -> ListParams
+    where
+        N: Into<String>,
+        I: IntoIterator<Item = T>,
+        T: Into<Value>,
+    {
+        self.lists
+            .insert(name.into(), values.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Expands bound lists in the `query` and merges them into named `params`.
+    ///
+    /// Fails with `DriverError::EmptyListParam` if an empty list is used in the `query`, because
+    /// `IN ()` is not valid SQL and there is no expansion that suits both `IN` and `NOT IN`.
+    pub fn expand<Q, P>(self, query: Q, params: P) -> Result<(String, Params)>
+    where
+        Q: AsRef<str>,
+        P: Into<Params>,
+    {
+        let mut named = match params.into() {
+            Params::Empty => HashMap::new(),
+            Params::Named(named) => named,
+            Params::Positional(_) => return Err(DriverError::MixedParams.into()),
+        };
+
+        let query = query.as_ref();
+        let bytes = query.as_bytes();
+        let unquoted = unquoted_mask(bytes);
+        let mut output = String::with_capacity(query.len());
+        let mut last = 0;
+        let mut i = 0;
+        while i < bytes.len() {
+            let is_param = unquoted[i]
+                && bytes[i] == b':'
+                && bytes.get(i + 1).map_or(false, |&x| is_name_start(x));
+            if !is_param {
+                i += 1;
+                continue;
+            }
+
+            let end = i
+                + 1
+                + bytes[i + 1..]
+                    .iter()
+                    .take_while(|&&x| is_name_byte(x))
+                    .count();
+            let name = &query[i + 1..end];
+            if let Some(values) = self.lists.get(name) {
+                if values.is_empty() {
+                    return Err(DriverError::EmptyListParam {
+                        name: name.to_string(),
+                    }
+                    .into());
+                }
+                output.push_str(&query[last..i]);
+                for n in 0..values.len() {
+                    if n > 0 {
+                        output.push_str(", ");
+                    }
+                    write!(output, ":{}", expanded_name(name, n)).unwrap();
+                }
+                last = end;
+            }
+            i = end;
+        }
+        output.push_str(&query[last..]);
+
+        for (name, values) in self.lists {
+            for (n, value) in values.into_iter().enumerate() {
+                named.insert(expanded_name(&*name, n), value);
+            }
+        }
+
+        if named.is_empty() {
+            Ok((output, Params::Empty))
+        } else {
+            Ok((output, Params::Named(named)))
+        }
+    }
+}
+
+fn expanded_name(name: &str, n: usize) -> String {
+    format!("{}__{}", name, n)
+}
+
+fn is_name_start(x: u8) -> bool {
+    x.is_ascii_lowercase() || x == b'_'
+}
+
+fn is_name_byte(x: u8) -> bool {
+    is_name_start(x) || x.is_ascii_digit()
+}
+
+#[cfg(test)]
+mod test {
+    use super::ListParams;
+    use crate::{
+        error::{DriverError, Error},
+        Params, Value,
+    };
+
+    #[test]
+    fn should_expand_list_params() {
+        let (query, params) = ListParams::new()
+            .bind("ids", vec![1, 2])
+            .bind("empty", Vec::<u8>::new())
+            .expand(
+                "SELECT ':ids' FROM t WHERE id IN (:ids) AND a = :a",
+                params! { "a" => 3 },
+            )
+            .unwrap();
+        assert_eq!(
+            query,
+            "SELECT ':ids' FROM t WHERE id IN (:ids__0, :ids__1) AND a = :a"
+        );
+        match params {
+            Params::Named(params) => {
+                assert_eq!(params.len(), 3);
+                assert_eq!(params["ids__0"], Value::Int(1));
+                assert_eq!(params["ids__1"], Value::Int(2));
+                assert_eq!(params["a"], Value::Int(3));
+            }
+            _ => panic!("named params expected"),
+        }
+
+        assert!(ListParams::new()
+            .bind("ids", vec![1])
+            .expand("SELECT :ids, ?", (1,))
+            .is_err());
+
+        match ListParams::new()
+            .bind("empty", Vec::<u8>::new())
+            .expand("SELECT * FROM t WHERE b NOT IN (:empty)", ())
+        {
+            Err(Error::Driver(DriverError::EmptyListParam { ref name })) if name == "empty" => (),
+            _ => panic!("expected EmptyListParam error"),
+        }
+    }
+}
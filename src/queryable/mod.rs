@@ -6,25 +6,37 @@
 // option. All files in the project carrying such notice may not be copied,
 // modified, or distributed except according to those terms.
 
-use futures::future::Future;
+use ::futures::future::{err, loop_fn, ok, Either::*, Future, Loop};
 use mysql_common::{
     packets::{parse_ok_packet, RawPacket},
-    row::new_row,
-    value::{read_bin_values, read_text_values},
+    row::{convert::FromRowError, new_row},
+    value::{self, read_text_values},
 };
 
-use std::sync::Arc;
+use std::{cmp, io, sync::Arc};
 
 use self::{
-    query_result::QueryResult,
+    batch::{bin_value_len, positional_values, MultiValuesInsert},
+    futures::{DropQuery, Ping, PrepExec, Query},
+    paginate::Paginate,
     stmt::Stmt,
     transaction::{Transaction, TransactionOptions},
 };
 use crate::{
-    connection_like::ConnectionLike, consts::Command, error::*, prelude::FromRow, BoxFuture,
-    Column, Conn, Params, Row,
+    conn::named_params::parse_named_params,
+    connection_like::ConnectionLike,
+    consts::Command,
+    error::*,
+    prelude::{ColumnExt, FromRow},
+    BoxFuture, Column, Conn, Params, ResultSummary, Row, Value,
 };
 
+mod batch;
+pub(crate) mod classify;
+pub mod futures;
+pub mod list_params;
+pub mod paginate;
+mod procedure;
 pub mod query_result;
 pub mod stmt;
 pub mod transaction;
@@ -67,18 +79,66 @@ impl Protocol for BinaryProtocol {
     }
 }
 
+/// Reads values of a binary protocol row.
+///
+/// Values of unsigned integer columns are decoded as `Value::UInt` (the server sends
+/// `TINYINT UNSIGNED` .. `INT UNSIGNED` zero-extended, so they would otherwise be decoded
+/// as `Value::Int` unlike `BIGINT UNSIGNED`).
+pub fn read_bin_values(payload: &[u8], columns: &[Column]) -> io::Result<Vec<Value>> {
+    let mut values = value::read_bin_values(payload, columns)?;
+    for (value, column) in values.iter_mut().zip(columns) {
+        if let Value::Int(x) = *value {
+            if x >= 0 && column.is_unsigned() {
+                *value = Value::UInt(x as u64);
+            }
+        }
+    }
+    Ok(values)
+}
+
+/// Converts a `row` into `R` (`DriverError::FromRow` if it can't be converted).
+pub(crate) fn try_from_row<R: FromRow>(row: Row) -> Result<R> {
+    R::from_row_opt(row).map_err(|FromRowError(row)| DriverError::FromRow { row }.into())
+}
+
+/// Converts a `row` into `R`. Conversion failure panics unless conversions are `strict`
+/// (see `OptsBuilder::strict_conversions`).
+pub(crate) fn convert_row<R: FromRow>(row: Row, strict: bool) -> Result<R> {
+    if strict {
+        try_from_row(row)
+    } else {
+        Ok(R::from_row(row))
+    }
+}
+
+/// Converts the first of `rows` into `R` (see `Queryable::first`).
+pub(crate) fn first_row<T, R>(this: T, mut rows: Vec<Row>) -> Result<(T, Option<R>)>
+where
+    T: ConnectionLike,
+    R: FromRow,
+{
+    let row = if rows.len() > 1 {
+        Some(rows.swap_remove(0))
+    } else {
+        rows.pop()
+    };
+    match row {
+        Some(row) => {
+            let strict = this.get_opts().get_strict_conversions();
+            convert_row(row, strict).map(|row| (this, Some(row)))
+        }
+        None => Ok((this, None)),
+    }
+}
+
 /// Represents something queryable like connection or transaction.
 pub trait Queryable: ConnectionLike
 where
     Self: Sized + 'static,
 {
     /// Returns future that resolves to `Conn` if `COM_PING` executed successfully.
-    fn ping(self) -> BoxFuture<Self> {
-        let fut = self
-            .write_command_data(Command::COM_PING, &[])
-            .and_then(|this| this.read_packet())
-            .map(|(this, _)| this);
-        Box::new(fut)
+    fn ping(self) -> Ping<Self> {
+        Ping::new(self)
     }
 
     /// Returns future that disconnects this connection from a server.
@@ -89,16 +149,14 @@ where
     }
 
     /// Returns future that performs `query`.
-    fn query<Q: AsRef<str>>(self, query: Q) -> BoxFuture<QueryResult<Self, TextProtocol>> {
-        let fut = self
-            .write_command_data(Command::COM_QUERY, query.as_ref().as_bytes())
-            .and_then(|conn_like| conn_like.read_result_set(None));
-        Box::new(fut)
+    fn query<Q: AsRef<str>>(self, query: Q) -> Query<Self> {
+        Query::new(self, query.as_ref())
     }
 
     /// Returns future that resolves to a first row of result of a `query` execution (if any).
     ///
-    /// Returned future will call `R::from_row(row)` internally.
+    /// Returned future will call `R::from_row(row)` internally
+    /// (see `OptsBuilder::strict_conversions`).
     fn first<Q, R>(self, query: Q) -> BoxFuture<(Self, Option<R>)>
     where
         Q: AsRef<str>,
@@ -107,53 +165,39 @@ where
         let fut = self
             .query(query)
             .and_then(|result| result.collect_and_drop::<Row>())
-            .map(|(this, mut rows)| {
-                if rows.len() > 1 {
-                    (this, Some(FromRow::from_row(rows.swap_remove(0))))
-                } else {
-                    (this, rows.pop().map(FromRow::from_row))
-                }
-            });
+            .and_then(|(this, rows)| first_row(this, rows));
         Box::new(fut)
     }
 
     /// Returns future that performs query. Result will be dropped.
-    fn drop_query<Q: AsRef<str>>(self, query: Q) -> BoxFuture<Self> {
-        let fut = self.query(query).and_then(|result| result.drop_result());
-        Box::new(fut)
+    fn drop_query<Q: AsRef<str>>(self, query: Q) -> DropQuery<Self> {
+        DropQuery::new(self, query.as_ref())
     }
 
     /// Returns future that prepares statement.
     fn prepare<Q: AsRef<str>>(self, query: Q) -> BoxFuture<Stmt<Self>> {
+        let query = query.as_ref().to_string();
         let fut = self
-            .prepare_stmt(query)
+            .prepare_stmt(query.clone())
             .map(|(this, inner_stmt, stmt_cache_result)| {
-                stmt::new(this, inner_stmt, stmt_cache_result)
+                stmt::new(this, inner_stmt, stmt_cache_result, query)
             });
         Box::new(fut)
     }
 
     /// Returns future that prepares and executes statement in one pass.
-    fn prep_exec<Q, P>(self, query: Q, params: P) -> BoxFuture<QueryResult<Self, BinaryProtocol>>
+    fn prep_exec<Q, P>(self, query: Q, params: P) -> PrepExec<Self>
     where
         Q: AsRef<str>,
         P: Into<Params>,
     {
-        let params: Params = params.into();
-        let fut = self
-            .prepare(query)
-            .and_then(|stmt| stmt.execute(params))
-            .map(|result| {
-                let (stmt, columns, _) = query_result::disassemble(result);
-                let (conn_like, cached) = stmt.unwrap();
-                query_result::assemble(conn_like, columns, cached)
-            });
-        Box::new(fut)
+        PrepExec::new(self, query.as_ref(), params.into())
     }
 
     /// Returns future that resolves to a first row of result of a statement execution (if any).
     ///
-    /// Returned future will call `R::from_row(row)` internally.
+    /// Returned future will call `R::from_row(row)` internally
+    /// (see `OptsBuilder::strict_conversions`).
     fn first_exec<Q, P, R>(self, query: Q, params: P) -> BoxFuture<(Self, Option<R>)>
     where
         Q: AsRef<str>,
@@ -163,16 +207,34 @@ where
         let fut = self
             .prep_exec(query, params)
             .and_then(|result| result.collect_and_drop::<Row>())
-            .map(|(this, mut rows)| {
-                if rows.len() > 1 {
-                    (this, Some(FromRow::from_row(rows.swap_remove(0))))
-                } else {
-                    (this, rows.pop().map(FromRow::from_row))
-                }
-            });
+            .and_then(|(this, rows)| first_row(this, rows));
         Box::new(fut)
     }
 
+    /// Returns future that calls a stored procedure and resolves to all its result sets
+    /// (including the trailing OK packet) and its OUT parameters.
+    ///
+    /// OUT parameters are taken from the result set marked with `SERVER_PS_OUT_PARAMS`
+    /// (if they are bound to `?` placeholders) or selected afterwards if the `query` mentions
+    /// user variables (e.g. `CALL proc(?, @out)`). Returned future will call `O::from_row(row)`
+    /// internally and resolves to `None` if there are no OUT parameters.
+    ///
+    /// ```rust,ignore
+    /// conn.call_procedure::<_, _, (i64, String)>("CALL proc(?, @count, @name)", (42,))
+    /// ```
+    fn call_procedure<Q, P, O>(
+        self,
+        query: Q,
+        params: P,
+    ) -> BoxFuture<(Self, Vec<ResultSummary>, Option<O>)>
+    where
+        Q: AsRef<str>,
+        P: Into<Params>,
+        O: FromRow + Send + 'static,
+    {
+        procedure::call_procedure(self, query, params)
+    }
+
     /// Returns future that prepares and executes statement. Result will be dropped.
     fn drop_exec<Q, P>(self, query: Q, params: P) -> BoxFuture<Self>
     where
@@ -202,6 +264,122 @@ where
         Box::new(fut)
     }
 
+    /// Returns future that performs batch execution of a simple `INSERT` (or `REPLACE`)
+    /// statement by rewriting it into a multi-row `VALUES (..), (..)` form, so that up to
+    /// `rows_per_stmt` params sets are sent in one statement execution. Results will be dropped.
+    ///
+    /// A batch is split into smaller statements if statement execution would exceed
+    /// `max_allowed_packet`. Statements that can't be rewritten are executed using `batch_exec`.
+    fn batch_exec_bulk<Q, I, P>(
+        self,
+        query: Q,
+        params_iter: I,
+        rows_per_stmt: usize,
+    ) -> BoxFuture<Self>
+    where
+        Q: AsRef<str>,
+        I: IntoIterator<Item = P> + Send + 'static,
+        I::IntoIter: Send + 'static,
+        Params: From<P>,
+        P: Send + 'static,
+    {
+        let parsed = parse_named_params(query.as_ref())
+            .map(|(named_params, query)| (named_params, MultiValuesInsert::parse(&*query)));
+        let (named_params, insert) = match parsed {
+            Ok((named_params, Some(insert))) => (named_params, insert),
+            Ok((_, None)) => return self.batch_exec(query, params_iter),
+            Err(error) => return Box::new(err(error.into())),
+        };
+
+        let rows_per_stmt = cmp::max(1, cmp::min(rows_per_stmt, insert.max_rows()));
+        let max_packet_len = self.get_max_allowed_packet() as usize;
+        let missing_as_null = self.get_opts().get_missing_named_params_as_null();
+        let params_iter = params_iter.into_iter().map(Params::from);
+
+        let fut = loop_fn(
+            (self, params_iter, None),
+            move |(this, mut params_iter, mut pending)| {
+                let mut values = Vec::new();
+                let mut rows = 0;
+                let mut packet_len = insert.base_len();
+                while rows < rows_per_stmt {
+                    let row = match pending.take() {
+                        Some(row) => row,
+                        None => match params_iter.next() {
+                            Some(params) => {
+                                match positional_values(
+                                    params,
+                                    named_params.as_ref(),
+                                    missing_as_null,
+                                ) {
+                                    Ok(row) => row,
+                                    Err(error) => return A(err(error)),
+                                }
+                            }
+                            None => break,
+                        },
+                    };
+                    // otherwise values would be shifted into columns of neighbouring rows
+                    if row.len() != insert.params_per_row() {
+                        let error = DriverError::StmtParamsMismatch {
+                            required: insert.params_per_row() as u16,
+                            supplied: row.len() as u16,
+                        };
+                        return A(err(error.into()));
+                    }
+                    let row_len = insert.row_len() + row.iter().map(bin_value_len).sum::<usize>();
+                    if rows > 0 && packet_len + row_len > max_packet_len {
+                        pending = Some(row);
+                        break;
+                    }
+                    packet_len += row_len;
+                    values.extend(row);
+                    rows += 1;
+                }
+
+                if rows == 0 {
+                    A(ok(Loop::Break(this)))
+                } else {
+                    B(this
+                        .drop_exec(insert.build(rows), Params::Positional(values))
+                        .map(move |this| Loop::Continue((this, params_iter, pending))))
+                }
+            },
+        );
+        Box::new(fut)
+    }
+
+    /// Returns stream of row batches of a `query_template` result ordered by `key_column`.
+    ///
+    /// Every batch of at most `batch_size` rows is fetched by a separate query filtering rows
+    /// by `key_column > last_key` (no `OFFSET` is used and no result set is kept open between
+    /// batches). `key_column` must be a unique column of the `query_template` result
+    /// (a table qualifier, if any, is ignored, e.g. `t.id` refers to the `id` column,
+    /// and names containing dots must be quoted, e.g. `` `a.id` ``).
+    /// Use `Paginate::into_inner` to get `self` back after the stream is exhausted.
+    ///
+    /// ```rust,ignore
+    /// conn.paginate_by_key("SELECT id, name FROM users WHERE active", "id", 1000)
+    ///     .for_each(|rows| /* process batch */)
+    /// ```
+    fn paginate_by_key<Q, K>(
+        self,
+        query_template: Q,
+        key_column: K,
+        batch_size: usize,
+    ) -> Paginate<Self>
+    where
+        Q: AsRef<str>,
+        K: AsRef<str>,
+    {
+        Paginate::new(
+            self,
+            query_template.as_ref(),
+            key_column.as_ref(),
+            batch_size,
+        )
+    }
+
     /// Returns future that starts transaction.
     fn start_transaction(self, options: TransactionOptions) -> BoxFuture<Transaction<Self>> {
         Box::new(transaction::new(self, options))
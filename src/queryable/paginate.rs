@@ -0,0 +1,117 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use futures::{
+    Async::{NotReady, Ready},
+    Future, Poll, Stream,
+};
+
+use std::fmt;
+
+use crate::{
+    conn::schema::split_table_name, error::*, escape::escape_identifier, queryable::Queryable,
+    BoxFuture, Row, Value,
+};
+
+/// Stream of row batches of a query paginated by a key column
+/// (see `Queryable::paginate_by_key`).
+pub struct Paginate<T> {
+    fut: Option<BoxFuture<(T, Vec<Row>)>>,
+    queryable: Option<T>,
+    next_page: String,
+    key_column: String,
+    batch_size: usize,
+}
+
+impl<T: Queryable> Paginate<T> {
+    pub(crate) fn new(
+        queryable: T,
+        query_template: &str,
+        key_column: &str,
+        batch_size: usize,
+    ) -> Paginate<T> {
+        let batch_size = batch_size.max(1);
+        // result of the `page` derived table has unqualified column names
+        let (_, key_column) = split_table_name(key_column);
+        let key = escape_identifier(&key_column);
+        let first_page = format!(
+            "SELECT * FROM ({}) AS page ORDER BY page.{} LIMIT {}",
+            query_template, key, batch_size
+        );
+        let next_page = format!(
+            "SELECT * FROM ({}) AS page WHERE page.{key} > ? ORDER BY page.{key} LIMIT {}",
+            query_template,
+            batch_size,
+            key = key
+        );
+        Paginate {
+            fut: Some(fetch(queryable, first_page, ())),
+            queryable: None,
+            next_page,
+            key_column,
+            batch_size,
+        }
+    }
+
+    /// Returns the wrapped queryable if the stream is exhausted.
+    pub fn into_inner(self) -> Option<T> {
+        self.queryable
+    }
+}
+
+fn fetch<T, P>(queryable: T, query: String, params: P) -> BoxFuture<(T, Vec<Row>)>
+where
+    T: Queryable,
+    P: Into<crate::Params>,
+{
+    Box::new(
+        queryable
+            .prep_exec(query, params)
+            .and_then(|result| result.collect_and_drop::<Row>()),
+    )
+}
+
+impl<T> fmt::Debug for Paginate<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Paginate")
+            .field("exhausted", &self.fut.is_none())
+            .field("key_column", &self.key_column)
+            .field("batch_size", &self.batch_size)
+            .finish()
+    }
+}
+
+impl<T: Queryable> Stream for Paginate<T> {
+    type Item = Vec<Row>;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let (queryable, rows) = match self.fut.as_mut() {
+            Some(fut) => match fut.poll()? {
+                Ready(output) => output,
+                NotReady => return Ok(NotReady),
+            },
+            None => return Ok(Ready(None)),
+        };
+        self.fut = None;
+
+        if rows.len() < self.batch_size {
+            self.queryable = Some(queryable);
+            return Ok(Ready(if rows.is_empty() { None } else { Some(rows) }));
+        }
+
+        let last_key = rows
+            .last()
+            .and_then(|row| row.get::<Value, _>(&*self.key_column))
+            .ok_or_else(|| DriverError::MissingKeyColumn {
+                column: self.key_column.clone(),
+            })?;
+        self.fut = Some(fetch(queryable, self.next_page.clone(), (last_key,)));
+        Ok(Ready(Some(rows)))
+    }
+}
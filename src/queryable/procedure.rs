@@ -0,0 +1,113 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use futures::future::{ok, Either::*, Future, IntoFuture};
+
+use crate::{
+    prelude::FromRow,
+    queryable::{convert_row, Queryable},
+    BoxFuture, Params, ResultSummary,
+};
+
+/// Returns future that executes a `CALL` statement and resolves to all its result sets
+/// and OUT parameters (see `Queryable::call_procedure`).
+pub(crate) fn call_procedure<T, Q, P, O>(
+    queryable: T,
+    query: Q,
+    params: P,
+) -> BoxFuture<(T, Vec<ResultSummary>, Option<O>)>
+where
+    T: Queryable,
+    Q: AsRef<str>,
+    P: Into<Params>,
+    O: FromRow + Send + 'static,
+{
+    let variables = session_variables(query.as_ref());
+    let fut = queryable
+        .prep_exec(query, params)
+        .and_then(|result| result.result_sets().collect_all())
+        .and_then(move |(queryable, mut summaries)| {
+            let position = summaries.iter().position(|summary| match summary {
+                ResultSummary::OutParams { .. } => true,
+                _ => false,
+            });
+            match position.map(|i| summaries.remove(i)) {
+                Some(ResultSummary::OutParams { row, .. }) => {
+                    let strict = queryable.get_opts().get_strict_conversions();
+                    A(convert_row(row, strict)
+                        .map(|out| (queryable, summaries, Some(out)))
+                        .into_future())
+                }
+                _ if !variables.is_empty() => {
+                    let query = format!("SELECT {}", variables.join(", "));
+                    B(queryable
+                        .first(query)
+                        .map(move |(queryable, out)| (queryable, summaries, out)))
+                }
+                _ => A(ok((queryable, summaries, None))),
+            }
+        });
+    Box::new(fut)
+}
+
+/// Returns distinct user variables (e.g. `@out`) mentioned in the `query`
+/// outside of quoted literals and identifiers.
+fn session_variables(query: &str) -> Vec<String> {
+    let mut variables: Vec<String> = Vec::new();
+    let mut chars = query.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' | '`' => {
+                while let Some(x) = chars.next() {
+                    if x == '\\' && c != '`' {
+                        chars.next();
+                    } else if x == c {
+                        break;
+                    }
+                }
+            }
+            '@' if chars.peek() == Some(&'@') => {
+                // system variable
+                chars.next();
+                while chars.peek().map_or(false, |x| is_var_char(*x)) {
+                    chars.next();
+                }
+            }
+            '@' => {
+                let mut name = String::from("@");
+                while let Some(&x) = chars.peek().filter(|x| is_var_char(**x)) {
+                    name.push(x);
+                    chars.next();
+                }
+                if name.len() > 1 && !variables.contains(&name) {
+                    variables.push(name);
+                }
+            }
+            _ => (),
+        }
+    }
+    variables
+}
+
+fn is_var_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$' || c == '.'
+}
+
+#[cfg(test)]
+mod test {
+    use super::session_variables;
+
+    #[test]
+    fn should_find_session_variables() {
+        assert_eq!(
+            session_variables("CALL proc(?, @out, @total, '@not_var', @out, @@session.x)"),
+            vec!["@out".to_string(), "@total".to_string()]
+        );
+        assert!(session_variables("CALL proc(?)").is_empty());
+    }
+}
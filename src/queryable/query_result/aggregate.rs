@@ -0,0 +1,94 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use crate::{error::*, Column, Row, Value};
+
+/// Aggregation computed by `QueryResult::fold_numeric`.
+///
+/// `NULL` values are skipped, as they are by the corresponding SQL aggregate functions.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Aggregation {
+    /// Number of non-`NULL` values.
+    Count,
+    Sum,
+    Min,
+    Max,
+}
+
+/// Accumulates values of a column of a result set (see `QueryResult::fold_numeric`).
+#[derive(Debug, Clone)]
+pub struct Aggregator {
+    aggregation: Aggregation,
+    /// Index of the aggregated column.
+    index: usize,
+    count: u64,
+    acc: Option<f64>,
+}
+
+impl Aggregator {
+    /// Returns aggregator of the `column` of a result set with the given `columns`
+    /// (`DriverError::MissingColumn` if there is no such column).
+    pub fn new(columns: &[Column], column: String, aggregation: Aggregation) -> Result<Aggregator> {
+        let index = columns
+            .iter()
+            .position(|x| x.name_str() == column.as_str())
+            .ok_or_else(|| DriverError::MissingColumn { column })?;
+        Ok(Aggregator {
+            aggregation,
+            index,
+            count: 0,
+            acc: None,
+        })
+    }
+
+    pub fn push(&mut self, row: &Row) -> Result<()> {
+        let value = match row.as_ref(self.index) {
+            Some(Value::NULL) | None => return Ok(()),
+            Some(value) => value,
+        };
+        self.count += 1;
+        if self.aggregation == Aggregation::Count {
+            return Ok(());
+        }
+        let x = to_f64(value)?;
+        self.acc = Some(match (self.aggregation, self.acc) {
+            (_, None) | (Aggregation::Count, _) => x,
+            (Aggregation::Sum, Some(acc)) => acc + x,
+            (Aggregation::Min, Some(acc)) => acc.min(x),
+            (Aggregation::Max, Some(acc)) => acc.max(x),
+        });
+        Ok(())
+    }
+
+    pub fn finish(self) -> Option<f64> {
+        match self.aggregation {
+            Aggregation::Count => Some(self.count as f64),
+            _ => self.acc,
+        }
+    }
+}
+
+/// Converts a numeric value (numbers are sent as strings by the text protocol) to `f64`.
+fn to_f64(value: &Value) -> Result<f64> {
+    match *value {
+        Value::Int(x) => Ok(x as f64),
+        Value::UInt(x) => Ok(x as f64),
+        Value::Float(x) => Ok(x),
+        Value::Bytes(ref bytes) => String::from_utf8_lossy(bytes)
+            .trim()
+            .parse()
+            .map_err(|_| DriverError::FromValue {
+                value: value.clone(),
+            })
+            .map_err(Error::from),
+        _ => Err(DriverError::FromValue {
+            value: value.clone(),
+        }
+        .into()),
+    }
+}
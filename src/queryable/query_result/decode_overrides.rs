@@ -0,0 +1,232 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use mysql_common::row::new_row;
+
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{consts::ColumnType, error::*, Column, Row, Value};
+
+/// Decode strategy for a column (see `DecodeOverrides`).
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ColumnDecode {
+    /// Value is converted into `Value::Bytes` holding its textual representation
+    /// (useful for binary protocol results).
+    Bytes,
+    /// Bytes that aren't valid UTF-8 are replaced with `U+FFFD`.
+    StringLossy,
+    /// Value is parsed as a JSON document and converted into `Value::Bytes` holding its compact
+    /// form, so that invalid documents are reported with the name of the column.
+    Json,
+    /// Raw bytes of a `BIT(n)` column are converted into `Value::UInt`, so that the value
+    /// could be converted into `Bit` or `u64` (values of other columns are rejected).
+    Bit,
+}
+
+/// Per-call overrides of how values of particular columns are decoded.
+///
+/// Overrides are keyed by column name or by column index (index has priority).
+/// `NULL` values are never affected.
+///
+/// ```rust,ignore
+/// let overrides = DecodeOverrides::new()
+///     .column("legacy_name", ColumnDecode::StringLossy)
+///     .index(2, ColumnDecode::Json);
+/// conn.query("SELECT * FROM legacy_table")
+///     .and_then(move |result| result.decode_overrides(overrides).collect::<Row>())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DecodeOverrides {
+    by_name: HashMap<String, ColumnDecode>,
+    by_index: HashMap<usize, ColumnDecode>,
+}
+
+impl DecodeOverrides {
+    pub fn new() -> DecodeOverrides {
+        DecodeOverrides::default()
+    }
+
+    /// Overrides decode strategy of columns named `name`.
+    pub fn column<N: Into<String>>(mut self, name: N, decode: ColumnDecode) -> DecodeOverrides {
+        self.by_name.insert(name.into(), decode);
+        self
+    }
+
+    /// Overrides decode strategy of a column at `index`.
+    pub fn index(mut self, index: usize, decode: ColumnDecode) -> DecodeOverrides {
+        self.by_index.insert(index, decode);
+        self
+    }
+
+    fn get(&self, index: usize, column: &Column) -> Option<ColumnDecode> {
+        self.by_index
+            .get(&index)
+            .or_else(|| self.by_name.get(&*column.name_str()))
+            .cloned()
+    }
+
+    /// Applies overrides to a `row` of a result set with `columns`.
+    pub(crate) fn apply(&self, row: Row, columns: Arc<Vec<Column>>) -> Result<Row> {
+        let mut values = row.unwrap();
+        for (index, (value, column)) in values.iter_mut().zip(columns.iter()).enumerate() {
+            if let Some(decode) = self.get(index, column) {
+                let decoded = decode_value(value.clone(), decode, column.column_type());
+                let decoded = decoded.map_err(|reason| DriverError::ColumnDecode {
+                    column: column.name_str().into_owned(),
+                    reason,
+                })?;
+                *value = decoded;
+            }
+        }
+        Ok(new_row(values, columns))
+    }
+}
+
+fn decode_value(
+    value: Value,
+    decode: ColumnDecode,
+    column_type: ColumnType,
+) -> ::std::result::Result<Value, String> {
+    match (value, decode) {
+        (Value::NULL, _) => Ok(Value::NULL),
+        (Value::Bytes(bytes), ColumnDecode::Bytes) => Ok(Value::Bytes(bytes)),
+        (value, ColumnDecode::Bytes) => Ok(Value::Bytes(value_to_text(value).into_bytes())),
+        (Value::Bytes(bytes), ColumnDecode::StringLossy) => match String::from_utf8(bytes) {
+            Ok(string) => Ok(Value::Bytes(string.into_bytes())),
+            Err(error) => Ok(Value::Bytes(
+                String::from_utf8_lossy(error.as_bytes())
+                    .into_owned()
+                    .into_bytes(),
+            )),
+        },
+        (value, ColumnDecode::StringLossy) => Ok(value),
+        (value, ColumnDecode::Json) => {
+            let text = match value {
+                Value::Bytes(bytes) => bytes,
+                value => value_to_text(value).into_bytes(),
+            };
+            serde_json::from_slice::<serde_json::Value>(&*text)
+                .map(|json| Value::Bytes(json.to_string().into_bytes()))
+                .map_err(|error| error.to_string())
+        }
+        (_, ColumnDecode::Bit) if column_type != ColumnType::MYSQL_TYPE_BIT => {
+            Err(format!("{:?} isn't a BIT column", column_type))
+        }
+        (Value::Bytes(ref bytes), ColumnDecode::Bit) if bytes.len() <= 8 => Ok(Value::UInt(
+            bytes
+                .iter()
+                .fold(0, |acc, byte| acc << 8 | u64::from(*byte)),
+        )),
+        (value, ColumnDecode::Bit) => Err(format!("{:?} isn't a BIT(n) value", value)),
+    }
+}
+
+/// Returns textual representation of a value (as if it was received using text protocol).
+pub(crate) fn value_to_text(value: Value) -> String {
+    match value {
+        Value::NULL => String::from("NULL"),
+        Value::Bytes(bytes) => String::from_utf8_lossy(&*bytes).into_owned(),
+        Value::Int(x) => x.to_string(),
+        Value::UInt(x) => x.to_string(),
+        Value::Float(x) => x.to_string(),
+        Value::Date(year, month, day, hour, minute, second, micros) => {
+            let mut text = format!(
+                "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+                year, month, day, hour, minute, second
+            );
+            if micros > 0 {
+                text.push_str(&format!(".{:06}", micros));
+            }
+            text
+        }
+        Value::Time(is_neg, days, hours, minutes, seconds, micros) => {
+            let mut text = format!(
+                "{}{:02}:{:02}:{:02}",
+                if is_neg { "-" } else { "" },
+                days * 24 + u32::from(hours),
+                minutes,
+                seconds
+            );
+            if micros > 0 {
+                text.push_str(&format!(".{:06}", micros));
+            }
+            text
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode_value, ColumnDecode};
+    use crate::{consts::ColumnType::*, Value};
+
+    #[test]
+    fn should_decode_values() {
+        assert_eq!(
+            decode_value(Value::Int(-42), ColumnDecode::Bytes, MYSQL_TYPE_LONGLONG),
+            Ok(Value::Bytes(b"-42".to_vec()))
+        );
+        assert_eq!(
+            decode_value(
+                Value::Date(2019, 1, 2, 3, 4, 5, 6),
+                ColumnDecode::Bytes,
+                MYSQL_TYPE_DATETIME
+            ),
+            Ok(Value::Bytes(b"2019-01-02 03:04:05.000006".to_vec()))
+        );
+        assert_eq!(
+            decode_value(
+                Value::Time(true, 1, 2, 3, 4, 0),
+                ColumnDecode::Bytes,
+                MYSQL_TYPE_TIME
+            ),
+            Ok(Value::Bytes(b"-26:03:04".to_vec()))
+        );
+        assert_eq!(
+            decode_value(
+                Value::Bytes(b"a\xFFb".to_vec()),
+                ColumnDecode::StringLossy,
+                MYSQL_TYPE_VAR_STRING
+            ),
+            Ok(Value::Bytes("a\u{FFFD}b".as_bytes().to_vec()))
+        );
+        assert_eq!(
+            decode_value(
+                Value::Bytes(b"{ \"a\": [1, 2] }".to_vec()),
+                ColumnDecode::Json,
+                MYSQL_TYPE_JSON
+            ),
+            Ok(Value::Bytes(b"{\"a\":[1,2]}".to_vec()))
+        );
+        assert!(decode_value(
+            Value::Bytes(b"{".to_vec()),
+            ColumnDecode::Json,
+            MYSQL_TYPE_JSON
+        )
+        .is_err());
+        assert_eq!(
+            decode_value(Value::NULL, ColumnDecode::Json, MYSQL_TYPE_JSON),
+            Ok(Value::NULL)
+        );
+        assert_eq!(
+            decode_value(
+                Value::Bytes(vec![0x01, 0x02]),
+                ColumnDecode::Bit,
+                MYSQL_TYPE_BIT
+            ),
+            Ok(Value::UInt(258))
+        );
+        assert!(decode_value(Value::Bytes(vec![0; 9]), ColumnDecode::Bit, MYSQL_TYPE_BIT).is_err());
+        assert!(decode_value(
+            Value::Bytes(b"5".to_vec()),
+            ColumnDecode::Bit,
+            MYSQL_TYPE_VAR_STRING
+        )
+        .is_err());
+    }
+}
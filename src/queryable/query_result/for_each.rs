@@ -11,12 +11,15 @@ use futures::{try_ready, Async::Ready, Future, Poll};
 use crate::{
     connection_like::ConnectionLike,
     error::*,
-    queryable::{query_result::QueryResult, Protocol},
-    BoxFuture, Row,
+    queryable::{
+        query_result::{row_reader::RowReader, QueryResult},
+        Protocol,
+    },
+    Row,
 };
 
 pub struct ForEach<T, P, F> {
-    fut: BoxFuture<(QueryResult<T, P>, Option<Row>)>,
+    reader: RowReader<T, P>,
     fun: F,
 }
 
@@ -28,7 +31,7 @@ where
 {
     pub fn new(query_result: QueryResult<T, P>, fun: F) -> ForEach<T, P, F> {
         ForEach {
-            fut: Box::new(query_result.get_row()),
+            reader: RowReader::new(query_result),
             fun,
         }
     }
@@ -45,17 +48,9 @@ where
     type Error = Error;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        loop {
-            let (query_result, row_opt) = try_ready!(self.fut.poll());
-            match row_opt {
-                Some(row) => {
-                    (self.fun)(row);
-                }
-                None => {
-                    return Ok(Ready(query_result));
-                }
-            }
-            self.fut = Box::new(query_result.get_row());
+        while let Some(row) = try_ready!(self.reader.poll_row()) {
+            (self.fun)(row);
         }
+        Ok(Ready(self.reader.take_query_result()))
     }
 }
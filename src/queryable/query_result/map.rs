@@ -13,12 +13,15 @@ use std::mem;
 use crate::{
     connection_like::ConnectionLike,
     error::*,
-    queryable::{query_result::QueryResult, Protocol},
-    BoxFuture, Row,
+    queryable::{
+        query_result::{row_reader::RowReader, QueryResult},
+        Protocol,
+    },
+    Row,
 };
 
 pub struct Map<T, P, F, U> {
-    fut: BoxFuture<(QueryResult<T, P>, Option<Row>)>,
+    reader: RowReader<T, P>,
     acc: Vec<U>,
     fun: F,
 }
@@ -32,7 +35,7 @@ where
 {
     pub fn new(query_result: QueryResult<T, P>, fun: F) -> Map<T, P, F, U> {
         Map {
-            fut: Box::new(query_result.get_row()),
+            reader: RowReader::new(query_result),
             acc: Vec::new(),
             fun,
         }
@@ -50,21 +53,13 @@ where
     type Error = Error;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        loop {
-            let (query_result, row_opt) = try_ready!(self.fut.poll());
-            match row_opt {
-                Some(row) => {
-                    let val = (self.fun)(row);
-                    self.acc.push(val);
-                }
-                None => {
-                    return Ok(Ready((
-                        query_result,
-                        mem::replace(&mut self.acc, Vec::new()),
-                    )));
-                }
-            }
-            self.fut = Box::new(query_result.get_row());
+        while let Some(row) = try_ready!(self.reader.poll_row()) {
+            let val = (self.fun)(row);
+            self.acc.push(val);
         }
+        Ok(Ready((
+            self.reader.take_query_result(),
+            mem::replace(&mut self.acc, Vec::new()),
+        )))
     }
 }
@@ -6,33 +6,54 @@
 // option. All files in the project carrying such notice may not be copied,
 // modified, or distributed except according to those terms.
 
-pub use self::{for_each::ForEach, map::Map, reduce::Reduce};
+pub(crate) use self::decode_overrides::value_to_text;
+pub use self::{
+    aggregate::Aggregation,
+    decode_overrides::{ColumnDecode, DecodeOverrides},
+    for_each::ForEach,
+    map::Map,
+    reduce::Reduce,
+    result_sets::{ResultSets, ResultSummary},
+    spool::SpooledRows,
+};
 
-use futures::future::{
-    loop_fn, ok, AndThen,
-    Either::{self, *},
-    Future, FutureResult, Loop,
+use byteorder::{ByteOrder, LittleEndian};
+use futures::{
+    future::{
+        err, loop_fn, ok, AndThen,
+        Either::{self, *},
+        Future, FutureResult, Loop,
+    },
+    Async::{NotReady, Ready},
 };
 use mysql_common::packets::RawPacket;
+use tokio_threadpool::blocking;
+use twox_hash::XxHash;
 
-use std::{marker::PhantomData, mem, sync::Arc};
+use std::{hash::Hasher, marker::PhantomData, mem, sync::Arc};
 
-use self::QueryResultInner::*;
+use self::{aggregate::Aggregator, order_check::OrderCheck, spool::Spooler, QueryResultInner::*};
 use crate::{
     connection_like::{
-        streamless::Streamless, ConnectionLike, ConnectionLikeWrapper, StmtCacheResult,
+        streamless::Streamless, ConnectionLike, ConnectionLikeWrapper, Cursor, StmtCacheResult,
     },
-    consts::StatusFlags,
+    consts::{Command, StatusFlags},
     error::*,
     io,
     prelude::FromRow,
-    queryable::Protocol,
-    BoxFuture, Column, MyFuture, Row,
+    queryable::{convert_row, try_from_row, Protocol},
+    BoxFuture, Column, MyFuture, Row, Value,
 };
 
+mod aggregate;
+mod decode_overrides;
 mod for_each;
 mod map;
+mod order_check;
 mod reduce;
+mod result_sets;
+mod row_reader;
+mod spool;
 
 pub type ForEachAndDrop<S, T, P, F> =
     AndThen<Either<FutureResult<S, Error>, ForEach<T, P, F>>, BoxFuture<T>, fn(S) -> BoxFuture<T>>;
@@ -66,8 +87,8 @@ pub fn disassemble<T, P>(
     query_result: QueryResult<T, P>,
 ) -> (T, Option<Arc<Vec<Column>>>, Option<StmtCacheResult>) {
     match query_result {
-        QueryResult(Empty(Some(A(conn_like)), cached, _)) => (conn_like, None, cached),
-        QueryResult(WithRows(Some(A(conn_like)), columns, cached, _)) => {
+        QueryResult(Empty(Some(A(conn_like)), cached, ..)) => (conn_like, None, cached),
+        QueryResult(WithRows(Some(A(conn_like)), columns, cached, ..)) => {
             (conn_like, Some(columns), cached)
         }
         _ => unreachable!(),
@@ -84,8 +105,15 @@ where
     P: Protocol + 'static,
 {
     match columns {
-        Some(columns) => QueryResult(WithRows(Some(A(conn_like)), columns, cached, PhantomData)),
-        None => QueryResult(Empty(Some(A(conn_like)), cached, PhantomData)),
+        Some(columns) => QueryResult(WithRows(
+            Some(A(conn_like)),
+            columns,
+            cached,
+            None,
+            None,
+            PhantomData,
+        )),
+        None => QueryResult(Empty(Some(A(conn_like)), cached, None, PhantomData)),
     }
 }
 
@@ -93,12 +121,15 @@ enum QueryResultInner<T, P> {
     Empty(
         Option<Either<T, Streamless<T>>>,
         Option<StmtCacheResult>,
+        Option<Arc<DecodeOverrides>>,
         PhantomData<P>,
     ),
     WithRows(
         Option<Either<T, Streamless<T>>>,
         Arc<Vec<Column>>,
         Option<StmtCacheResult>,
+        Option<Arc<DecodeOverrides>>,
+        Option<OrderCheck>,
         PhantomData<P>,
     ),
 }
@@ -116,8 +147,8 @@ where
     fn into_empty(mut self) -> Self {
         self.set_pending_result(None);
         match self {
-            QueryResult(WithRows(conn_like, _, cached, _)) => {
-                QueryResult(Empty(conn_like, cached, PhantomData))
+            QueryResult(WithRows(conn_like, _, cached, overrides, ..)) => {
+                QueryResult(Empty(conn_like, cached, overrides, PhantomData))
             }
             x => x,
         }
@@ -125,8 +156,8 @@ where
 
     fn into_inner(self) -> (T, Option<StmtCacheResult>) {
         match self {
-            QueryResult(Empty(conn_like, cached, _))
-            | QueryResult(WithRows(conn_like, _, cached, _)) => match conn_like {
+            QueryResult(Empty(conn_like, cached, ..))
+            | QueryResult(WithRows(conn_like, _, cached, ..)) => match conn_like {
                 Some(A(conn_like)) => (conn_like, cached),
                 _ => unreachable!(),
             },
@@ -137,36 +168,176 @@ where
         if self.is_empty() {
             return A(ok((self, None)));
         }
-        let fut = self.read_packet().and_then(|(this, packet)| {
-            if P::is_last_result_set_packet(&this, &packet) {
-                if this
-                    .get_status()
-                    .contains(StatusFlags::SERVER_MORE_RESULTS_EXISTS)
-                {
-                    let (inner, cached) = this.into_inner();
-                    A(A(inner
-                        .read_result_set(cached)
-                        .map(|new_this| (new_this, None))))
+        let fut = self
+            .read_row_packet()
+            .and_then(|(this, packet_opt)| match packet_opt {
+                Some(packet) => A(ok((this, Some(packet)))),
+                None => B(this.finish_result_set().map(|this| (this, None))),
+            });
+        B(fut)
+    }
+
+    /// Returns future that reads the next row packet of the current result set (`None` if the end
+    /// of the result set is reached, see `QueryResult::finish_result_set`).
+    fn read_row_packet(self) -> impl MyFuture<(Self, Option<RawPacket>)> {
+        loop_fn(self, |this| {
+            this.read_packet().and_then(|(mut this, packet)| {
+                if P::is_last_result_set_packet(&this, &packet) {
+                    if let Some(cursor) = this.get_cursor() {
+                        let status = this.get_status();
+                        if status.contains(StatusFlags::SERVER_STATUS_CURSOR_EXISTS)
+                            && !status.contains(StatusFlags::SERVER_STATUS_LAST_ROW_SENT)
+                        {
+                            // the end of fetched rows, but not of the cursor
+                            return A(this.fetch_cursor_rows(cursor).map(Loop::Continue));
+                        }
+                        this.set_cursor(None);
+                    }
+                    B(ok(Loop::Break((this, None))))
                 } else {
-                    A(B(ok((this.into_empty(), None))))
+                    B(ok(Loop::Break((this, Some(packet)))))
+                }
+            })
+        })
+    }
+
+    /// Returns future that moves to the next result set (if any) once the last packet
+    /// of the current one is read.
+    fn finish_result_set(self) -> impl MyFuture<Self> {
+        if self.more_results_exists() {
+            let overrides = self.overrides();
+            let (inner, cached) = self.into_inner();
+            A(inner
+                .read_result_set(cached)
+                .map(|new_this| new_this.with_overrides(overrides)))
+        } else {
+            B(ok(self.into_empty()))
+        }
+    }
+
+    /// Returns `true` if rows of the current result set should be decoded off the event loop
+    /// (see `Opts::get_decode_offload_threshold`).
+    fn offloads_decoding(&self) -> bool {
+        match *self {
+            QueryResult(WithRows(_, ref columns, _, _, None, _)) => {
+                match self.get_opts().get_decode_offload_threshold() {
+                    Some(threshold) => columns.len() >= threshold,
+                    None => false,
                 }
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns future that reads up to `DECODE_BATCH_LEN` rows of the current result set
+    /// and decodes them off the event loop (see `decode_rows`).
+    ///
+    /// Resolves to `true` if the end of the current result set is reached.
+    fn get_row_batch(self) -> impl MyFuture<(Self, Vec<Row>, bool)> {
+        let columns = self.columns().expect("result set with rows");
+        let overrides = self.overrides();
+        loop_fn((self, Vec::new()), |(this, mut packets)| {
+            this.read_row_packet()
+                .map(move |(this, packet_opt)| match packet_opt {
+                    Some(packet) => {
+                        packets.push(packet);
+                        if packets.len() < DECODE_BATCH_LEN {
+                            Loop::Continue((this, packets))
+                        } else {
+                            Loop::Break((this, packets, false))
+                        }
+                    }
+                    None => Loop::Break((this, packets, true)),
+                })
+        })
+        .and_then(move |(mut this, packets, end)| {
+            let rows = decode_rows::<P>(&packets, &columns, overrides.as_ref().map(|x| &**x))?;
+            for row in &rows {
+                this.add_result_bytes(row_size(row));
+            }
+            Ok((this, rows, end))
+        })
+        .and_then(|(this, rows, end)| {
+            if end {
+                A(this.finish_result_set().map(|this| (this, rows, true)))
             } else {
-                B(ok((this, Some(packet))))
+                B(ok((this, rows, false)))
             }
+        })
+    }
+
+    /// Returns future that reads rows of the current result set and resolves to the wrapped
+    /// `ConnectionLike` (subsequent result sets aren't read).
+    pub(crate) fn read_current_rows(self) -> impl MyFuture<(T, Option<StmtCacheResult>, Vec<Row>)> {
+        let columns = match self.columns() {
+            Some(columns) => columns,
+            None => {
+                let (conn_like, cached) = self.into_inner();
+                return A(ok((conn_like, cached, Vec::new())));
+            }
+        };
+        let fut = loop_fn((self, Vec::new()), move |(this, mut rows)| {
+            let columns = columns.clone();
+            this.read_packet().and_then(move |(mut this, packet)| {
+                if P::is_last_result_set_packet(&this, &packet) {
+                    Ok(Loop::Break((this, rows)))
+                } else {
+                    let row = P::read_result_set_row(&packet, columns)?;
+                    this.add_result_bytes(row_size(&row));
+                    rows.push(row);
+                    Ok(Loop::Continue((this, rows)))
+                }
+            })
+        })
+        .map(|(this, rows)| {
+            let (conn_like, cached) = this.into_empty().into_inner();
+            (conn_like, cached, rows)
         });
         B(fut)
     }
 
+    /// Returns future that skips rows of an open `cursor` that are already requested,
+    /// and closes the cursor (`COM_STMT_RESET`) without fetching remaining rows.
+    fn close_cursor(mut self, cursor: Cursor) -> impl MyFuture<Self> {
+        self.set_cursor(None);
+        loop_fn(self, |this| {
+            this.read_packet().map(|(this, packet)| {
+                if P::is_last_result_set_packet(&this, &packet) {
+                    Loop::Break(this)
+                } else {
+                    Loop::Continue(this)
+                }
+            })
+        })
+        .and_then(move |this| {
+            let mut data = [0u8; 4];
+            LittleEndian::write_u32(&mut data, cursor.statement_id);
+            this.write_command_data(Command::COM_STMT_RESET, data)
+        })
+        .and_then(|this| this.read_packet())
+        .map(|(this, _)| this.into_empty())
+    }
+
     fn get_row(self) -> impl MyFuture<(Self, Option<Row>)> {
         self.get_row_raw()
             .and_then(|(this, packet_opt)| match packet_opt {
                 Some(packet) => match this {
-                    QueryResult(WithRows(_, ref columns, ..)) => {
-                        P::read_result_set_row(&packet, columns.clone())
+                    QueryResult(WithRows(_, ref columns, _, ref overrides, ..)) => {
+                        P::read_result_set_row(&packet, columns.clone()).and_then(|row| {
+                            match *overrides {
+                                Some(ref overrides) => overrides.apply(row, columns.clone()),
+                                None => Ok(row),
+                            }
+                        })
                     }
                     _ => unreachable!(),
                 }
-                .map(|row| (this, Some(row))),
+                .and_then(|row| {
+                    let mut this = this;
+                    this.add_result_bytes(row_size(&row));
+                    this.check_order(&row)?;
+                    Ok((this, Some(row)))
+                }),
                 None => Ok((this, None)),
             })
     }
@@ -177,10 +348,85 @@ where
         cached: Option<StmtCacheResult>,
     ) -> QueryResult<T, P> {
         match columns {
-            Some(columns) => {
-                QueryResult(WithRows(Some(A(conn_like)), columns, cached, PhantomData))
+            Some(columns) => QueryResult(WithRows(
+                Some(A(conn_like)),
+                columns,
+                cached,
+                None,
+                None,
+                PhantomData,
+            )),
+            None => QueryResult(Empty(Some(A(conn_like)), cached, None, PhantomData)),
+        }
+    }
+
+    /// Sets decode overrides for rows of this query result (see `DecodeOverrides`).
+    ///
+    /// Overrides also apply to subsequent result sets of this query result
+    /// (even if the current one has no rows).
+    pub fn decode_overrides(self, overrides: DecodeOverrides) -> Self {
+        self.with_overrides(Some(Arc::new(overrides)))
+    }
+
+    fn with_overrides(self, overrides: Option<Arc<DecodeOverrides>>) -> Self {
+        match self {
+            QueryResult(Empty(conn_like, cached, _, _)) => {
+                QueryResult(Empty(conn_like, cached, overrides, PhantomData))
+            }
+            QueryResult(WithRows(conn_like, columns, cached, _, order_check, _)) => {
+                QueryResult(WithRows(
+                    conn_like,
+                    columns,
+                    cached,
+                    overrides,
+                    order_check,
+                    PhantomData,
+                ))
+            }
+        }
+    }
+
+    /// Makes this query result verify that rows of the current result set are ordered
+    /// by the `column` (in descending order if `descending` is `true`).
+    ///
+    /// Row that breaks the order (or a missing column) results in `DriverError::RowOrder`.
+    /// Strings are compared bytewise (i.e. as if they were ordered using a binary collation),
+    /// numbers are compared numerically, and `NULL` is the smallest value.
+    ///
+    /// This is a development aid, so it does nothing if debug assertions are disabled.
+    pub fn verify_order<N: Into<String>>(self, column: N, descending: bool) -> Self {
+        if !cfg!(debug_assertions) {
+            return self;
+        }
+        match self {
+            QueryResult(WithRows(conn_like, columns, cached, overrides, _, _)) => {
+                let order_check = OrderCheck::new(column.into(), descending);
+                QueryResult(WithRows(
+                    conn_like,
+                    columns,
+                    cached,
+                    overrides,
+                    Some(order_check),
+                    PhantomData,
+                ))
             }
-            None => QueryResult(Empty(Some(A(conn_like)), cached, PhantomData)),
+            x => x,
+        }
+    }
+
+    fn check_order(&mut self, row: &Row) -> Result<()> {
+        match *self {
+            QueryResult(WithRows(_, ref columns, _, _, Some(ref mut order_check), _)) => {
+                order_check.check(row, &*columns)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn overrides(&self) -> Option<Arc<DecodeOverrides>> {
+        match *self {
+            QueryResult(Empty(_, _, ref overrides, _))
+            | QueryResult(WithRows(_, _, _, ref overrides, ..)) => overrides.clone(),
         }
     }
 
@@ -194,6 +440,15 @@ where
         self.get_affected_rows()
     }
 
+    /// Approximate number of bytes occupied by decoded rows of the current (or the last finished)
+    /// result set.
+    ///
+    /// Only rows read so far are accounted. The estimate includes the size of every `Value`
+    /// and the length of its bytes (if any).
+    pub fn memory_usage(&self) -> u64 {
+        self.get_result_bytes()
+    }
+
     /// `true` if there is no more rows nor result sets in this query.
     ///
     /// One could use it to check if there is more than one result set in this query result.
@@ -223,19 +478,63 @@ where
 
     /// Returns future that collects result set of this query result.
     ///
-    /// It is parametrized by `R` and internally calls `R::from_row(Row)` on each row.
+    /// It is parametrized by `R` and internally calls `R::from_row(Row)` on each row
+    /// (see `OptsBuilder::strict_conversions`).
     ///
     /// It will stop collecting on result set boundary. This means that you should call `collect`
     /// as many times as result sets in your query result. For example query
     /// `SELECT 'foo'; SELECT 'foo', 'bar';` will produce `QueryResult` with two result sets in it.
     /// One can use `QueryResult::is_empty` to make sure that there is no more result sets.
     pub fn collect<R>(self) -> impl MyFuture<(Self, Vec<R>)>
+    where
+        R: FromRow,
+        R: Send + 'static,
+    {
+        let strict = self.get_opts().get_strict_conversions();
+        self.reduce(Ok(Vec::new()), move |acc: Result<Vec<R>>, row| {
+            acc.and_then(|mut acc| {
+                acc.push(convert_row(row, strict)?);
+                Ok(acc)
+            })
+        })
+        .and_then(|(this, acc)| acc.map(|acc| (this, acc)))
+    }
+
+    /// Returns future that collects result set of this query result without panicking
+    /// if a row can't be converted to `R`.
+    ///
+    /// Every row is converted using `R::from_row_opt`, and a row that can't be converted
+    /// results in `DriverError::FromRow` in its place.
+    ///
+    /// It will stop on result set boundary (see `QueryResult::collect` docs).
+    pub fn collect_opt<R>(self) -> impl MyFuture<(Self, Vec<Result<R>>)>
     where
         R: FromRow,
         R: Send + 'static,
     {
         self.reduce(Vec::new(), |mut acc, row| {
-            acc.push(FromRow::from_row(row));
+            acc.push(try_from_row(row));
+            acc
+        })
+    }
+
+    /// Returns future that converts every row of current result set to `R` and maps it
+    /// to `U` using `fun` without panicking if a row can't be converted.
+    ///
+    /// A row that can't be converted results in `DriverError::FromRow` in its place
+    /// (`fun` isn't called for it).
+    ///
+    /// It will stop on result set boundary (see `QueryResult::collect` docs).
+    pub fn map_opt<R, F, U>(
+        self,
+        mut fun: F,
+    ) -> impl Future<Item = (Self, Vec<Result<U>>), Error = Error>
+    where
+        R: FromRow,
+        F: FnMut(R) -> U,
+    {
+        self.reduce(Vec::new(), move |mut acc, row| {
+            acc.push(try_from_row(row).map(&mut fun));
             acc
         })
     }
@@ -251,6 +550,103 @@ where
             .and_then(|(this, output)| (this.drop_result(), ok(output)))
     }
 
+    /// Returns future that collects result set of this query result keeping at most
+    /// `memory_threshold` bytes of rows in memory (see `QueryResult::memory_usage`).
+    ///
+    /// Rows beyond the threshold are written to a temporary file (in `std::env::temp_dir()`)
+    /// using the binary protocol encoding and are read back one by one as `SpooledRows`
+    /// is iterated. The file is written in batches using `tokio_threadpool::blocking`
+    /// (or in place outside of a thread pool), while `SpooledRows` reads it using blocking IO.
+    ///
+    /// It will stop on result set boundary (see `QueryResult::collect` docs).
+    pub fn collect_spooled(self, memory_threshold: u64) -> impl MyFuture<(Self, SpooledRows)> {
+        let spooler = Spooler::new(self.columns(), memory_threshold);
+        self.reduce(spooler, |mut spooler, row| {
+            spooler.push(row);
+            spooler
+        })
+        .and_then(|(this, spooler)| spooler.finish().map(|rows| (this, rows)))
+    }
+
+    /// Returns future that collects result set of a query result (see
+    /// `QueryResult::collect_spooled`) and drops everything else. It will resolve to a pair
+    /// of wrapped `Queryable` and collected rows.
+    pub fn collect_spooled_and_drop(
+        self,
+        memory_threshold: u64,
+    ) -> impl MyFuture<(T, SpooledRows)> {
+        self.collect_spooled(memory_threshold)
+            .and_then(|(this, rows)| (this.drop_result(), ok(rows)))
+    }
+
+    /// Returns future that computes a digest of current result set without storing its rows.
+    ///
+    /// Every row is fed to the hasher using a length-prefixed encoding of its values, so equal
+    /// result sets (including row order) produce equal digests. Values are hashed in the form
+    /// they came from the server, so digests should only be compared between results obtained
+    /// using the same protocol.
+    ///
+    /// It will stop on result set boundary (see `QueryResult::collect` docs).
+    pub fn digest(self) -> impl MyFuture<(Self, u64)> {
+        self.reduce(XxHash::default(), |mut hasher, row| {
+            hash_row(&mut hasher, row);
+            hasher
+        })
+        .map(|(this, hasher)| (this, hasher.finish()))
+    }
+
+    /// Returns future that computes a digest of current result set and drops everything else.
+    /// It will resolve to a pair of wrapped `Queryable` and computed digest.
+    pub fn digest_and_drop(self) -> impl MyFuture<(T, u64)> {
+        self.digest()
+            .and_then(|(this, digest)| (this.drop_result(), ok(digest)))
+    }
+
+    /// Returns future that computes `aggregation` over values of the `column` of current
+    /// result set without storing its rows.
+    ///
+    /// `NULL` values are skipped. Future resolves to `None` if there are no other values
+    /// (except for `Aggregation::Count`, that resolves to `Some(0.0)`). Values are converted
+    /// to `f64` (`Aggregation::Count` counts values of any type), so sums of large integers
+    /// may be imprecise. Missing column results in `DriverError::MissingColumn` (even if
+    /// the result set is empty), non-numeric value results in `DriverError::FromValue`.
+    /// Everything is dropped on error, so that the wrapped `Queryable` could be reused.
+    ///
+    /// It will stop on result set boundary (see `QueryResult::collect` docs).
+    pub fn fold_numeric<N: Into<String>>(
+        self,
+        column: N,
+        aggregation: Aggregation,
+    ) -> impl MyFuture<(Self, Option<f64>)> {
+        let aggregator = match Aggregator::new(self.columns_ref(), column.into(), aggregation) {
+            Ok(aggregator) => aggregator,
+            Err(error) => return A(self.drop_result().then(|_| Err(error))),
+        };
+        let fut = self
+            .reduce(Ok(aggregator), |acc: Result<Aggregator>, row| {
+                acc.and_then(|mut acc| {
+                    acc.push(&row)?;
+                    Ok(acc)
+                })
+            })
+            .and_then(|(this, acc)| match acc {
+                Ok(acc) => A(ok((this, acc.finish()))),
+                Err(error) => B(this.drop_result().then(|_| Err(error))),
+            });
+        B(fut)
+    }
+
+    /// Returns future that computes `aggregation` over values of the `column` of current
+    /// result set and drops everything else (see `QueryResult::fold_numeric`).
+    pub fn fold_numeric_and_drop<N: Into<String>>(
+        self,
+        column: N,
+        aggregation: Aggregation,
+    ) -> impl MyFuture<(T, Option<f64>)> {
+        self.fold_numeric(column, aggregation)
+            .and_then(|(this, output)| (this.drop_result(), ok(output)))
+    }
+
     /// Returns future that will execute `fun` on every row of current result set.
     ///
     /// It will stop on result set boundary (see `QueryResult::collect` docs).
@@ -357,12 +753,23 @@ where
                 } else {
                     A(B(ok(Loop::Break(this.into_inner()))))
                 }
+            } else if let Some(cursor) = this.get_cursor() {
+                B(A(this.close_cursor(cursor).map(Loop::Continue)))
             } else {
-                B(this.get_row_raw().map(|(this, _)| Loop::Continue(this)))
+                B(B(this.get_row_raw().map(|(this, _)| Loop::Continue(this))))
             }
         });
 
         fut.and_then(|(conn_like, cached)| {
+            if conn_like.has_warnings_to_fetch() {
+                A(conn_like
+                    .fetch_warnings()
+                    .map(|conn_like| (conn_like, cached)))
+            } else {
+                B(ok((conn_like, cached)))
+            }
+        })
+        .and_then(|(conn_like, cached)| {
             if let Some(StmtCacheResult::NotCached(statement_id)) = cached {
                 A(conn_like.close_stmt(statement_id))
             } else {
@@ -371,6 +778,51 @@ where
         })
     }
 
+    /// Returns future that skips remaining rows of the current result set and moves
+    /// to the next one. Resolves to `true` if there was the next result set (its rows, if any,
+    /// are available via `collect`, `for_each`, etc.) and to `false` otherwise.
+    ///
+    /// Like `collect`, it consumes exactly one result set, so to read the second result set
+    /// of `CALL proc()` call `next_result_set` once and then `collect`.
+    pub fn next_result_set(self) -> impl MyFuture<(Self, bool)> {
+        let fut = if !self.has_rows() {
+            A(ok(self))
+        } else if let Some(cursor) = self.get_cursor() {
+            B(A(self.close_cursor(cursor)))
+        } else {
+            let fut = loop_fn(self, |this| {
+                this.read_packet().map(|(this, packet)| {
+                    if P::is_last_result_set_packet(&this, &packet) {
+                        Loop::Break(this)
+                    } else {
+                        Loop::Continue(this)
+                    }
+                })
+            });
+            B(B(fut))
+        };
+
+        fut.and_then(|this| {
+            if this.more_results_exists() {
+                let overrides = this.overrides();
+                let (inner, cached) = this.into_inner();
+                A(inner
+                    .read_result_set(cached)
+                    .map(|new_this| (new_this.with_overrides(overrides), true)))
+            } else {
+                B(ok((this.into_empty(), false)))
+            }
+        })
+    }
+
+    /// Returns stream of remaining result sets of this query result (see `ResultSets`).
+    ///
+    /// Wrapped `Queryable` is available via `ResultSets::into_inner` once the stream
+    /// is exhausted.
+    pub fn result_sets(self) -> ResultSets<T, P> {
+        ResultSets::new(Box::new(ok(self)))
+    }
+
     /// Returns reference to columns in this query result.
     pub fn columns_ref(&self) -> &[Column] {
         match self.0 {
@@ -391,6 +843,94 @@ where
     }
 }
 
+/// Number of rows decoded at once if decoding is offloaded
+/// (see `Opts::get_decode_offload_threshold`).
+const DECODE_BATCH_LEN: usize = 256;
+
+/// Decodes row `packets` using `tokio_threadpool::blocking`, so that other tasks
+/// of the current worker are moved to another thread meanwhile.
+///
+/// Packets are decoded in place if called outside of a thread pool or if the pool
+/// has no spare blocking capacity (waiting for it would stall the connection).
+fn decode_rows<P: Protocol>(
+    packets: &[RawPacket],
+    columns: &Arc<Vec<Column>>,
+    overrides: Option<&DecodeOverrides>,
+) -> Result<Vec<Row>> {
+    let decode = || {
+        packets
+            .iter()
+            .map(|packet| {
+                let row = P::read_result_set_row(packet, columns.clone())?;
+                match overrides {
+                    Some(overrides) => overrides.apply(row, columns.clone()),
+                    None => Ok(row),
+                }
+            })
+            .collect::<Result<Vec<_>>>()
+    };
+    match blocking(|| decode()) {
+        Ok(Ready(rows)) => rows,
+        Ok(NotReady) | Err(_) => decode(),
+    }
+}
+
+/// Returns approximate number of bytes occupied by a decoded `row`.
+fn row_size(row: &Row) -> u64 {
+    let size = (0..row.len())
+        .filter_map(|index| row.as_ref(index))
+        .map(|value| {
+            mem::size_of::<Value>()
+                + match *value {
+                    Value::Bytes(ref bytes) => bytes.len(),
+                    _ => 0,
+                }
+        })
+        .sum::<usize>();
+    size as u64
+}
+
+/// Feeds canonical representation of a `row` to the `hasher`.
+fn hash_row<H: Hasher>(hasher: &mut H, row: Row) {
+    let values = row.unwrap();
+    hasher.write_u64(values.len() as u64);
+    for value in values {
+        match value {
+            Value::NULL => hasher.write_u8(0),
+            Value::Bytes(bytes) => {
+                hasher.write_u8(1);
+                hasher.write_u64(bytes.len() as u64);
+                hasher.write(&*bytes);
+            }
+            Value::Int(x) => {
+                hasher.write_u8(2);
+                hasher.write_i64(x);
+            }
+            Value::UInt(x) => {
+                hasher.write_u8(3);
+                hasher.write_u64(x);
+            }
+            Value::Float(x) => {
+                hasher.write_u8(4);
+                hasher.write_u64(x.to_bits());
+            }
+            Value::Date(year, month, day, hour, minute, second, micros) => {
+                hasher.write_u8(5);
+                hasher.write_u16(year);
+                hasher.write(&[month, day, hour, minute, second]);
+                hasher.write_u32(micros);
+            }
+            Value::Time(is_neg, days, hours, minutes, seconds, micros) => {
+                hasher.write_u8(6);
+                hasher.write_u8(is_neg as u8);
+                hasher.write_u32(days);
+                hasher.write(&[hours, minutes, seconds]);
+                hasher.write_u32(micros);
+            }
+        }
+    }
+}
+
 impl<T: ConnectionLike + 'static, P: Protocol> ConnectionLikeWrapper for QueryResult<T, P> {
     type ConnLike = T;
 
@@ -399,12 +939,13 @@ impl<T: ConnectionLike + 'static, P: Protocol> ConnectionLikeWrapper for QueryRe
         Self: Sized,
     {
         match self {
-            QueryResult(Empty(conn_like, cached, _)) => match conn_like {
+            QueryResult(Empty(conn_like, cached, overrides, _)) => match conn_like {
                 Some(A(conn_like)) => {
                     let (streamless, stream) = conn_like.take_stream();
                     let self_streamless = Streamless::new(QueryResult(Empty(
                         Some(B(streamless)),
                         cached,
+                        overrides,
                         PhantomData,
                     )));
                     (self_streamless, stream)
@@ -412,20 +953,24 @@ impl<T: ConnectionLike + 'static, P: Protocol> ConnectionLikeWrapper for QueryRe
                 Some(B(..)) => panic!("Logic error: stream taken"),
                 None => unreachable!(),
             },
-            QueryResult(WithRows(conn_like, columns, cached, _)) => match conn_like {
-                Some(A(conn_like)) => {
-                    let (streamless, stream) = conn_like.take_stream();
-                    let self_streamless = Streamless::new(QueryResult(WithRows(
-                        Some(B(streamless)),
-                        columns,
-                        cached,
-                        PhantomData,
-                    )));
-                    (self_streamless, stream)
+            QueryResult(WithRows(conn_like, columns, cached, overrides, order_check, _)) => {
+                match conn_like {
+                    Some(A(conn_like)) => {
+                        let (streamless, stream) = conn_like.take_stream();
+                        let self_streamless = Streamless::new(QueryResult(WithRows(
+                            Some(B(streamless)),
+                            columns,
+                            cached,
+                            overrides,
+                            order_check,
+                            PhantomData,
+                        )));
+                        (self_streamless, stream)
+                    }
+                    Some(B(..)) => panic!("Logic error: stream taken"),
+                    None => unreachable!(),
                 }
-                Some(B(..)) => panic!("Logic error: stream taken"),
-                None => unreachable!(),
-            },
+            }
         }
     }
 
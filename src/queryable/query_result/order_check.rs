@@ -0,0 +1,131 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use std::cmp::Ordering;
+
+use super::decode_overrides::value_to_text;
+use crate::{consts::ColumnType, error::*, Column, Row, Value};
+
+/// Comparable representation of a value of the sort column.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+enum SortKey {
+    // `NULL` sorts first in ascending order.
+    Null,
+    Number(f64),
+    Bytes(Vec<u8>),
+}
+
+impl SortKey {
+    fn new(value: &Value, numeric: bool) -> SortKey {
+        match *value {
+            Value::NULL => SortKey::Null,
+            Value::Int(x) => SortKey::Number(x as f64),
+            Value::UInt(x) => SortKey::Number(x as f64),
+            Value::Float(x) => SortKey::Number(x),
+            Value::Bytes(ref bytes) if numeric => String::from_utf8_lossy(bytes)
+                .parse()
+                .map(SortKey::Number)
+                .unwrap_or_else(|_| SortKey::Bytes(bytes.clone())),
+            Value::Bytes(ref bytes) => SortKey::Bytes(bytes.clone()),
+            ref value => SortKey::Bytes(value_to_text(value.clone()).into_bytes()),
+        }
+    }
+}
+
+/// Verifies that rows of a result set are ordered by a column (see `QueryResult::verify_order`).
+#[derive(Debug, Clone)]
+pub struct OrderCheck {
+    column: String,
+    descending: bool,
+    rows: usize,
+    last: Option<SortKey>,
+}
+
+impl OrderCheck {
+    pub fn new(column: String, descending: bool) -> OrderCheck {
+        OrderCheck {
+            column,
+            descending,
+            rows: 0,
+            last: None,
+        }
+    }
+
+    pub fn check(&mut self, row: &Row, columns: &[Column]) -> Result<()> {
+        let index = columns
+            .iter()
+            .position(|column| column.name_str() == self.column.as_str())
+            .ok_or_else(|| self.error("column not found".into()))?;
+        let key = SortKey::new(
+            row.as_ref(index).unwrap_or(&Value::NULL),
+            is_numeric(columns[index].column_type()),
+        );
+        self.rows += 1;
+        if let Some(ref last) = self.last {
+            let expected = if self.descending {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            };
+            match key.partial_cmp(last) {
+                Some(Ordering::Equal) => (),
+                Some(ordering) if ordering == expected => (),
+                _ => return Err(self.error(format!("row {} is out of order", self.rows))),
+            }
+        }
+        self.last = Some(key);
+        Ok(())
+    }
+
+    fn error(&self, reason: String) -> Error {
+        DriverError::RowOrder {
+            column: self.column.clone(),
+            reason,
+        }
+        .into()
+    }
+}
+
+fn is_numeric(column_type: ColumnType) -> bool {
+    use crate::consts::ColumnType::*;
+    match column_type {
+        MYSQL_TYPE_TINY
+        | MYSQL_TYPE_SHORT
+        | MYSQL_TYPE_LONG
+        | MYSQL_TYPE_INT24
+        | MYSQL_TYPE_LONGLONG
+        | MYSQL_TYPE_FLOAT
+        | MYSQL_TYPE_DOUBLE
+        | MYSQL_TYPE_DECIMAL
+        | MYSQL_TYPE_NEWDECIMAL
+        | MYSQL_TYPE_YEAR => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SortKey, Value};
+
+    #[test]
+    fn should_compare_sort_keys() {
+        assert!(SortKey::new(&Value::NULL, true) < SortKey::new(&Value::Int(-1), true));
+        assert!(
+            SortKey::new(&Value::Bytes(b"9".to_vec()), true)
+                < SortKey::new(&Value::Bytes(b"10".to_vec()), true)
+        );
+        assert!(
+            SortKey::new(&Value::Bytes(b"9".to_vec()), false)
+                > SortKey::new(&Value::Bytes(b"10".to_vec()), false)
+        );
+        assert!(
+            SortKey::new(&Value::Bytes(b"B".to_vec()), false)
+                < SortKey::new(&Value::Bytes(b"a".to_vec()), false)
+        );
+    }
+}
@@ -11,12 +11,15 @@ use futures::{try_ready, Async::Ready, Future, Poll};
 use crate::{
     connection_like::ConnectionLike,
     error::*,
-    queryable::{query_result::QueryResult, Protocol},
-    BoxFuture, Row,
+    queryable::{
+        query_result::{row_reader::RowReader, QueryResult},
+        Protocol,
+    },
+    Row,
 };
 
 pub struct Reduce<T, P, F, U> {
-    fut: BoxFuture<(QueryResult<T, P>, Option<Row>)>,
+    reader: RowReader<T, P>,
     acc: Option<U>,
     fun: F,
 }
@@ -29,7 +32,7 @@ where
 {
     pub fn new(query_result: QueryResult<T, P>, init: U, fun: F) -> Reduce<T, P, F, U> {
         Reduce {
-            fut: Box::new(query_result.get_row()),
+            reader: RowReader::new(query_result),
             acc: Some(init),
             fun,
         }
@@ -46,19 +49,14 @@ where
     type Error = Error;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        loop {
-            let (query_result, row_opt) = try_ready!(self.fut.poll());
-            match row_opt {
-                Some(row) => {
-                    let prev_acc_val = self.acc.take().unwrap();
-                    let new_acc_value = (self.fun)(prev_acc_val, row);
-                    self.acc = Some(new_acc_value);
-                }
-                None => {
-                    return Ok(Ready((query_result, self.acc.take().unwrap())));
-                }
-            }
-            self.fut = Box::new(query_result.get_row());
+        while let Some(row) = try_ready!(self.reader.poll_row()) {
+            let prev_acc_val = self.acc.take().unwrap();
+            let new_acc_value = (self.fun)(prev_acc_val, row);
+            self.acc = Some(new_acc_value);
         }
+        Ok(Ready((
+            self.reader.take_query_result(),
+            self.acc.take().unwrap(),
+        )))
     }
 }
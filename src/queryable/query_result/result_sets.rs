@@ -0,0 +1,190 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use futures::{
+    future::{loop_fn, Loop},
+    Async::{NotReady, Ready},
+    Future, Poll, Stream,
+};
+
+use std::{fmt, sync::Arc};
+
+use crate::{
+    connection_like::{ConnectionLike, StmtCacheResult},
+    consts::StatusFlags,
+    error::*,
+    queryable::Protocol,
+    BoxFuture, Column, MyFuture, QueryResult, Row,
+};
+
+/// Single result set of a query result (see `ResultSets`).
+#[derive(Debug, Clone)]
+pub enum ResultSummary {
+    /// Statement produced a result set.
+    Rows {
+        columns: Arc<Vec<Column>>,
+        rows: Vec<Row>,
+    },
+    /// Values of OUT and INOUT parameters of a stored procedure called via prepared statement
+    /// (result set marked with the `SERVER_PS_OUT_PARAMS` flag).
+    OutParams { columns: Arc<Vec<Column>>, row: Row },
+    /// Statement produced an OK packet (e.g. the trailing OK packet of a `CALL`).
+    Ok {
+        affected_rows: u64,
+        last_insert_id: Option<u64>,
+        warnings: u16,
+    },
+}
+
+enum Step<T, P> {
+    Result(BoxFuture<QueryResult<T, P>>),
+    Rows(
+        BoxFuture<(T, Option<StmtCacheResult>, Vec<Row>)>,
+        Arc<Vec<Column>>,
+    ),
+    Close(BoxFuture<T>),
+}
+
+/// Stream of result sets of a query result (see `QueryResult::result_sets`).
+///
+/// Every result set is yielded in order with its own columns (or affected rows info),
+/// so it is suitable for procedures returning several result sets followed by an OK packet.
+pub struct ResultSets<T, P> {
+    step: Option<Step<T, P>>,
+    inner: Option<T>,
+}
+
+impl<T, P> ResultSets<T, P>
+where
+    P: Protocol,
+    P: Send + 'static,
+    T: ConnectionLike,
+    T: Sized + Send + 'static,
+{
+    pub(crate) fn new(fut: BoxFuture<QueryResult<T, P>>) -> ResultSets<T, P> {
+        ResultSets {
+            step: Some(Step::Result(fut)),
+            inner: None,
+        }
+    }
+
+    /// Returns the wrapped `Queryable` if the stream is exhausted.
+    pub fn into_inner(self) -> Option<T> {
+        self.inner
+    }
+
+    /// Returns future that collects all remaining result sets and resolves to the wrapped
+    /// `Queryable` and collected summaries.
+    pub(crate) fn collect_all(self) -> impl MyFuture<(T, Vec<ResultSummary>)> {
+        loop_fn((self, Vec::new()), |(this, mut summaries)| {
+            this.into_future()
+                .map_err(|(err, _)| err)
+                .map(|(summary, this)| match summary {
+                    Some(summary) => {
+                        summaries.push(summary);
+                        Loop::Continue((this, summaries))
+                    }
+                    None => {
+                        let inner = this.into_inner().expect("stream is exhausted");
+                        Loop::Break((inner, summaries))
+                    }
+                })
+        })
+    }
+
+    /// Requests the next result set (if any) or closes the statement that isn't cached.
+    fn advance(&mut self, conn_like: T, cached: Option<StmtCacheResult>) {
+        if conn_like
+            .get_status()
+            .contains(StatusFlags::SERVER_MORE_RESULTS_EXISTS)
+        {
+            self.step = Some(Step::Result(conn_like.read_result_set(cached)));
+        } else if let Some(StmtCacheResult::NotCached(statement_id)) = cached {
+            self.step = Some(Step::Close(Box::new(conn_like.close_stmt(statement_id))));
+        } else {
+            self.inner = Some(conn_like);
+        }
+    }
+}
+
+impl<T, P> fmt::Debug for ResultSets<T, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResultSets")
+            .field("exhausted", &self.step.is_none())
+            .finish()
+    }
+}
+
+impl<T, P> Stream for ResultSets<T, P>
+where
+    P: Protocol,
+    P: Send + 'static,
+    T: ConnectionLike,
+    T: Sized + Send + 'static,
+{
+    type Item = ResultSummary;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            match self.step.take() {
+                Some(Step::Result(mut fut)) => match fut.poll()? {
+                    Ready(result) => match result.columns() {
+                        Some(columns) => {
+                            let fut = Box::new(result.read_current_rows());
+                            self.step = Some(Step::Rows(fut, columns));
+                        }
+                        None => {
+                            let (conn_like, _, cached) = super::disassemble(result);
+                            let summary = ResultSummary::Ok {
+                                affected_rows: conn_like.get_affected_rows(),
+                                last_insert_id: conn_like.get_last_insert_id(),
+                                warnings: conn_like.get_warnings(),
+                            };
+                            self.advance(conn_like, cached);
+                            return Ok(Ready(Some(summary)));
+                        }
+                    },
+                    NotReady => {
+                        self.step = Some(Step::Result(fut));
+                        return Ok(NotReady);
+                    }
+                },
+                Some(Step::Rows(mut fut, columns)) => match fut.poll()? {
+                    Ready((conn_like, cached, mut rows)) => {
+                        let out_params = conn_like
+                            .get_status()
+                            .contains(StatusFlags::SERVER_PS_OUT_PARAMS);
+                        let summary = if out_params && rows.len() == 1 {
+                            ResultSummary::OutParams {
+                                columns,
+                                row: rows.pop().unwrap(),
+                            }
+                        } else {
+                            ResultSummary::Rows { columns, rows }
+                        };
+                        self.advance(conn_like, cached);
+                        return Ok(Ready(Some(summary)));
+                    }
+                    NotReady => {
+                        self.step = Some(Step::Rows(fut, columns));
+                        return Ok(NotReady);
+                    }
+                },
+                Some(Step::Close(mut fut)) => match fut.poll()? {
+                    Ready(conn_like) => self.inner = Some(conn_like),
+                    NotReady => {
+                        self.step = Some(Step::Close(fut));
+                        return Ok(NotReady);
+                    }
+                },
+                None => return Ok(Ready(None)),
+            }
+        }
+    }
+}
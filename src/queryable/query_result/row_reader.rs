@@ -0,0 +1,95 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use futures::{try_ready, Async::Ready, Future, Poll};
+
+use std::{mem, vec};
+
+use crate::{
+    connection_like::ConnectionLike,
+    error::*,
+    queryable::{query_result::QueryResult, Protocol},
+    BoxFuture, Row,
+};
+
+enum Step<T, P> {
+    Row(BoxFuture<(QueryResult<T, P>, Option<Row>)>),
+    Batch(BoxFuture<(QueryResult<T, P>, Vec<Row>, bool)>),
+    Done(Option<QueryResult<T, P>>),
+}
+
+impl<T, P> Step<T, P>
+where
+    P: Protocol + 'static,
+    T: ConnectionLike + Sized + 'static,
+{
+    fn next(query_result: QueryResult<T, P>) -> Step<T, P> {
+        if query_result.offloads_decoding() {
+            Step::Batch(Box::new(query_result.get_row_batch()))
+        } else {
+            Step::Row(Box::new(query_result.get_row()))
+        }
+    }
+}
+
+/// Reads rows of the current result set one by one or in batches decoded off the event loop
+/// (see `Opts::get_decode_offload_threshold`).
+pub(crate) struct RowReader<T, P> {
+    step: Step<T, P>,
+    rows: vec::IntoIter<Row>,
+}
+
+impl<T, P> RowReader<T, P>
+where
+    P: Protocol + 'static,
+    T: ConnectionLike + Sized + 'static,
+{
+    pub fn new(query_result: QueryResult<T, P>) -> RowReader<T, P> {
+        RowReader {
+            step: Step::next(query_result),
+            rows: Vec::new().into_iter(),
+        }
+    }
+
+    /// Polls the next row (`None` if the end of the current result set is reached).
+    pub fn poll_row(&mut self) -> Poll<Option<Row>, Error> {
+        loop {
+            if let Some(row) = self.rows.next() {
+                return Ok(Ready(Some(row)));
+            }
+            let (step, row_opt) = match self.step {
+                Step::Row(ref mut fut) => match try_ready!(fut.poll()) {
+                    (query_result, Some(row)) => (Step::next(query_result), Some(row)),
+                    (query_result, None) => (Step::Done(Some(query_result)), None),
+                },
+                Step::Batch(ref mut fut) => {
+                    let (query_result, rows, end) = try_ready!(fut.poll());
+                    self.rows = rows.into_iter();
+                    if end {
+                        (Step::Done(Some(query_result)), None)
+                    } else {
+                        (Step::next(query_result), None)
+                    }
+                }
+                Step::Done(_) => return Ok(Ready(None)),
+            };
+            self.step = step;
+            if let Some(row) = row_opt {
+                return Ok(Ready(Some(row)));
+            }
+        }
+    }
+
+    /// Returns the query result once `poll_row` resolved to `None`.
+    pub fn take_query_result(&mut self) -> QueryResult<T, P> {
+        match mem::replace(&mut self.step, Step::Done(None)) {
+            Step::Done(Some(query_result)) => query_result,
+            _ => panic!("polled after completion"),
+        }
+    }
+}
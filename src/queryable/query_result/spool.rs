@@ -0,0 +1,498 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use byteorder::{LittleEndian as LE, ReadBytesExt, WriteBytesExt};
+use futures::Async::{NotReady, Ready};
+use mysql_common::{io::ReadMysqlExt, row::new_row};
+use tokio_threadpool::blocking;
+
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::{
+    collections::{hash_map::RandomState, VecDeque},
+    env, fmt,
+    fs::{self, File, OpenOptions},
+    hash::{BuildHasher, Hasher},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use super::row_size;
+use crate::{error::*, Column, Row, Value};
+
+static SPOOL_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of names `SpoolFile::create` tries before giving up.
+const SPOOL_FILE_ATTEMPTS: usize = 16;
+
+/// Encoded rows are written to the spool file once this number of bytes is accumulated.
+const SPOOL_WRITE_LEN: usize = 64 * 1024;
+
+/// Temporary file that is removed on drop.
+struct SpoolFile {
+    path: PathBuf,
+    writer: Option<BufWriter<File>>,
+    reader: Option<BufReader<File>>,
+}
+
+impl SpoolFile {
+    /// Creates a new file in `env::temp_dir()` readable only by the current user.
+    ///
+    /// The name carries a random suffix and the file is opened with `create_new`, so an
+    /// existing file (or a symlink planted by another user of a shared temp dir) is never
+    /// opened; such names are skipped.
+    fn create() -> io::Result<SpoolFile> {
+        let mut last_err = None;
+        for _ in 0..SPOOL_FILE_ATTEMPTS {
+            let path = env::temp_dir().join(format!(
+                "mysql_async-spool-{}-{}-{:016x}.tmp",
+                process::id(),
+                SPOOL_FILE_COUNTER.fetch_add(1, Ordering::Relaxed),
+                random_suffix(),
+            ));
+            let mut options = OpenOptions::new();
+            options.read(true).write(true).create_new(true);
+            #[cfg(unix)]
+            options.mode(0o600);
+            match options.open(&path) {
+                Ok(file) => {
+                    return Ok(SpoolFile {
+                        path,
+                        writer: Some(BufWriter::new(file)),
+                        reader: None,
+                    });
+                }
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => last_err = Some(err),
+                Err(err) => return Err(err),
+            }
+        }
+        Err(last_err.expect("at least one attempt"))
+    }
+
+    fn writer(&mut self) -> &mut BufWriter<File> {
+        self.writer.as_mut().expect("spool file is finished")
+    }
+
+    /// Flushes written rows and rewinds the file for reading.
+    fn finish(&mut self) -> io::Result<()> {
+        if let Some(writer) = self.writer.take() {
+            let mut file = writer.into_inner().map_err(io::Error::from)?;
+            file.seek(SeekFrom::Start(0))?;
+            self.reader = Some(BufReader::new(file));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SpoolFile {
+    fn drop(&mut self) {
+        self.writer.take();
+        self.reader.take();
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Returns a hard to guess value.
+///
+/// Keys of `RandomState` are seeded from the OS once per thread and incremented for every
+/// instance, so the value isn't cryptographically random. Name collisions are handled
+/// by `SpoolFile::create` anyway.
+fn random_suffix() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_usize(SPOOL_FILE_COUNTER.load(Ordering::Relaxed));
+    hasher.finish()
+}
+
+/// Accumulator of `QueryResult::collect_spooled`.
+pub(super) struct Spooler {
+    columns: Arc<Vec<Column>>,
+    memory_threshold: u64,
+    memory_usage: u64,
+    memory: VecDeque<Row>,
+    spooled: usize,
+    /// Encoded rows that are not yet written to the `file`.
+    pending: Vec<u8>,
+    file: Option<SpoolFile>,
+    error: Option<io::Error>,
+}
+
+impl Spooler {
+    pub(super) fn new(columns: Option<Arc<Vec<Column>>>, memory_threshold: u64) -> Spooler {
+        Spooler {
+            columns: columns.unwrap_or_default(),
+            memory_threshold,
+            memory_usage: 0,
+            memory: VecDeque::new(),
+            spooled: 0,
+            pending: Vec::new(),
+            file: None,
+            error: None,
+        }
+    }
+
+    pub(super) fn push(&mut self, row: Row) {
+        if self.error.is_some() {
+            return;
+        }
+
+        if self.spooled == 0 {
+            let size = row_size(&row);
+            if self.memory_usage + size <= self.memory_threshold {
+                self.memory_usage += size;
+                self.memory.push_back(row);
+                return;
+            }
+        }
+
+        if let Err(err) = self.spool(row) {
+            self.error = Some(err);
+        }
+    }
+
+    fn spool(&mut self, row: Row) -> io::Result<()> {
+        let mut buf = Vec::new();
+        for value in row.unwrap() {
+            buf.push(value_tag(&value));
+            write_bin_value(&mut buf, &value);
+        }
+        self.pending.write_u32::<LE>(buf.len() as u32)?;
+        self.pending.extend_from_slice(&buf);
+        self.spooled += 1;
+        if self.pending.len() >= SPOOL_WRITE_LEN {
+            self.write_pending(false)?;
+        }
+        Ok(())
+    }
+
+    /// Writes pending rows to the spool file (creating it if needed) and rewinds the file
+    /// for reading if `finish` is `true`.
+    fn write_pending(&mut self, finish: bool) -> io::Result<()> {
+        let file = &mut self.file;
+        let pending = &mut self.pending;
+        run_blocking(|| {
+            if file.is_none() {
+                *file = Some(SpoolFile::create()?);
+            }
+            let file = file.as_mut().unwrap();
+            file.writer().write_all(pending)?;
+            pending.clear();
+            if finish {
+                file.finish()?;
+            }
+            Ok(())
+        })
+    }
+
+    pub(super) fn finish(mut self) -> Result<SpooledRows> {
+        if let Some(err) = self.error.take() {
+            return Err(err.into());
+        }
+        if self.spooled > 0 {
+            self.write_pending(true)?;
+        }
+        let file = self.file;
+        Ok(SpooledRows {
+            columns: self.columns,
+            memory: self.memory,
+            spooled: self.spooled,
+            file,
+        })
+    }
+}
+
+/// Rows of a result set collected by `QueryResult::collect_spooled`.
+///
+/// Rows that didn't fit into the memory threshold are stored in a temporary file
+/// and read back one by one as the iterator advances. The file is removed on drop.
+pub struct SpooledRows {
+    columns: Arc<Vec<Column>>,
+    memory: VecDeque<Row>,
+    spooled: usize,
+    file: Option<SpoolFile>,
+}
+
+impl SpooledRows {
+    /// Number of rows that are not yet yielded.
+    pub fn len(&self) -> usize {
+        self.memory.len() + self.spooled
+    }
+
+    /// `true` if all rows are yielded.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of not yet yielded rows stored in the temporary file.
+    pub fn spooled(&self) -> usize {
+        self.spooled
+    }
+
+    fn read_spooled(&mut self) -> io::Result<Row> {
+        let reader = self
+            .file
+            .as_mut()
+            .and_then(|file| file.reader.as_mut())
+            .expect("spool file is finished");
+        let len = reader.read_u32::<LE>()? as usize;
+        let mut buf = vec![0; len];
+        reader.read_exact(&mut buf)?;
+
+        let mut buf = &buf[..];
+        let mut values = Vec::with_capacity(self.columns.len());
+        while !buf.is_empty() {
+            let tag = buf.read_u8()?;
+            values.push(read_bin_value(&mut buf, tag)?);
+        }
+        Ok(new_row(values, self.columns.clone()))
+    }
+}
+
+impl fmt::Debug for SpooledRows {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpooledRows")
+            .field("in memory", &self.memory.len())
+            .field("spooled", &self.spooled)
+            .field("path", &self.file.as_ref().map(|file| &file.path))
+            .finish()
+    }
+}
+
+impl Iterator for SpooledRows {
+    type Item = Result<Row>;
+
+    fn next(&mut self) -> Option<Result<Row>> {
+        if let Some(row) = self.memory.pop_front() {
+            return Some(Ok(row));
+        }
+        if self.spooled == 0 {
+            return None;
+        }
+        self.spooled -= 1;
+        Some(self.read_spooled().map_err(Error::from))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len(), Some(self.len()))
+    }
+}
+
+/// Runs blocking file io using `tokio_threadpool::blocking`, so that other tasks
+/// of the current worker are moved to another thread meanwhile.
+///
+/// `fun` is called in place if called outside of a thread pool or if the pool
+/// has no spare blocking capacity (see `decode_rows`).
+fn run_blocking<F>(mut fun: F) -> io::Result<()>
+where
+    F: FnMut() -> io::Result<()>,
+{
+    match blocking(|| fun()) {
+        Ok(Ready(result)) => result,
+        Ok(NotReady) | Err(_) => fun(),
+    }
+}
+
+/// Tag of a spooled value (followed by the value in the binary protocol encoding).
+fn value_tag(value: &Value) -> u8 {
+    match *value {
+        Value::NULL => 0,
+        Value::Bytes(..) => 1,
+        Value::Int(..) => 2,
+        Value::UInt(..) => 3,
+        Value::Float(..) => 4,
+        Value::Date(..) => 5,
+        Value::Time(..) => 6,
+    }
+}
+
+/// Writes a `value` using the binary protocol encoding (`Bytes` of any length are supported).
+fn write_bin_value(writer: &mut Vec<u8>, value: &Value) {
+    use crate::Value::*;
+
+    match *value {
+        NULL => (),
+        Bytes(ref x) => {
+            let len = x.len() as u64;
+            if len < 251 {
+                writer.write_u8(len as u8).unwrap();
+            } else if len < 1 << 16 {
+                writer.write_u8(0xFC).unwrap();
+                writer.write_u16::<LE>(len as u16).unwrap();
+            } else if len < 1 << 24 {
+                writer.write_u8(0xFD).unwrap();
+                writer.write_uint::<LE>(len, 3).unwrap();
+            } else {
+                writer.write_u8(0xFE).unwrap();
+                writer.write_u64::<LE>(len).unwrap();
+            }
+            writer.write_all(x).unwrap();
+        }
+        Int(x) => writer.write_i64::<LE>(x).unwrap(),
+        UInt(x) => writer.write_u64::<LE>(x).unwrap(),
+        Float(x) => writer.write_f64::<LE>(x).unwrap(),
+        Date(0, 0, 0, 0, 0, 0, 0) => writer.write_u8(0).unwrap(),
+        Date(y, m, d, 0, 0, 0, 0) => {
+            writer.write_u8(4).unwrap();
+            writer.write_u16::<LE>(y).unwrap();
+            writer.write_all(&[m, d]).unwrap();
+        }
+        Date(y, m, d, h, i, s, 0) => {
+            writer.write_u8(7).unwrap();
+            writer.write_u16::<LE>(y).unwrap();
+            writer.write_all(&[m, d, h, i, s]).unwrap();
+        }
+        Date(y, m, d, h, i, s, u) => {
+            writer.write_u8(11).unwrap();
+            writer.write_u16::<LE>(y).unwrap();
+            writer.write_all(&[m, d, h, i, s]).unwrap();
+            writer.write_u32::<LE>(u).unwrap();
+        }
+        Time(_, 0, 0, 0, 0, 0) => writer.write_u8(0).unwrap(),
+        Time(neg, d, h, m, s, 0) => {
+            writer.write_u8(8).unwrap();
+            writer.write_u8(neg as u8).unwrap();
+            writer.write_u32::<LE>(d).unwrap();
+            writer.write_all(&[h, m, s]).unwrap();
+        }
+        Time(neg, d, h, m, s, u) => {
+            writer.write_u8(12).unwrap();
+            writer.write_u8(neg as u8).unwrap();
+            writer.write_u32::<LE>(d).unwrap();
+            writer.write_all(&[h, m, s]).unwrap();
+            writer.write_u32::<LE>(u).unwrap();
+        }
+    }
+}
+
+/// Reads a value written by `write_bin_value` and tagged with `value_tag`.
+fn read_bin_value(buf: &mut &[u8], tag: u8) -> io::Result<Value> {
+    let value = match tag {
+        0 => Value::NULL,
+        1 => {
+            let len = buf.read_lenenc_int()? as usize;
+            let mut bytes = vec![0; len];
+            buf.read_exact(&mut bytes)?;
+            Value::Bytes(bytes)
+        }
+        2 => Value::Int(buf.read_i64::<LE>()?),
+        3 => Value::UInt(buf.read_u64::<LE>()?),
+        4 => Value::Float(buf.read_f64::<LE>()?),
+        5 => {
+            let len = buf.read_u8()?;
+            let mut date = (0, 0, 0, 0, 0, 0, 0);
+            if len >= 4 {
+                date.0 = buf.read_u16::<LE>()?;
+                date.1 = buf.read_u8()?;
+                date.2 = buf.read_u8()?;
+            }
+            if len >= 7 {
+                date.3 = buf.read_u8()?;
+                date.4 = buf.read_u8()?;
+                date.5 = buf.read_u8()?;
+            }
+            if len >= 11 {
+                date.6 = buf.read_u32::<LE>()?;
+            }
+            let (y, m, d, h, i, s, u) = date;
+            Value::Date(y, m, d, h, i, s, u)
+        }
+        6 => {
+            let len = buf.read_u8()?;
+            let mut time = (false, 0, 0, 0, 0, 0);
+            if len >= 8 {
+                time.0 = buf.read_u8()? == 1;
+                time.1 = buf.read_u32::<LE>()?;
+                time.2 = buf.read_u8()?;
+                time.3 = buf.read_u8()?;
+                time.4 = buf.read_u8()?;
+            }
+            if len >= 12 {
+                time.5 = buf.read_u32::<LE>()?;
+            }
+            let (neg, d, h, m, s, u) = time;
+            Value::Time(neg, d, h, m, s, u)
+        }
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid tag of a spooled value",
+            ))
+        }
+    };
+    Ok(value)
+}
+
+#[cfg(test)]
+mod test {
+    use mysql_common::row::new_row;
+
+    use std::sync::Arc;
+
+    use super::Spooler;
+    use crate::Value::{self, *};
+
+    #[test]
+    fn should_spool_rows_beyond_threshold() {
+        let values = vec![
+            NULL,
+            Bytes(vec![b'a'; 300]),
+            Int(-1),
+            UInt(u64::max_value()),
+            Float(1.5),
+            Date(0, 0, 0, 0, 0, 0, 0),
+            Date(2020, 2, 29, 0, 0, 0, 0),
+            Date(2020, 2, 29, 13, 14, 15, 0),
+            Date(2020, 2, 29, 13, 14, 15, 16),
+            Time(false, 0, 0, 0, 0, 0),
+            Time(true, 1, 2, 3, 4, 0),
+            Time(true, 1, 2, 3, 4, 5),
+        ];
+        let row = |i: u64| {
+            let mut values = values.clone();
+            values.push(UInt(i));
+            new_row(values, Arc::new(Vec::new()))
+        };
+
+        let mut spooler = Spooler::new(None, 2 * super::row_size(&row(0)));
+        for i in 0..10 {
+            spooler.push(row(i));
+        }
+        let rows = spooler.finish().unwrap();
+        assert_eq!(rows.len(), 10);
+        assert_eq!(rows.spooled(), 8);
+
+        let path = rows.file.as_ref().unwrap().path.clone();
+        assert!(path.exists());
+        let collected = rows
+            .map(|row| row.unwrap().unwrap())
+            .collect::<Vec<Vec<Value>>>();
+        assert_eq!(
+            collected,
+            (0..10).map(|i| row(i).unwrap()).collect::<Vec<_>>()
+        );
+        assert!(!path.exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn should_create_private_spool_files() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let first = super::SpoolFile::create().unwrap();
+        let second = super::SpoolFile::create().unwrap();
+        assert_ne!(first.path, second.path);
+        for file in &[&first, &second] {
+            let mode = file.path.metadata().unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+}
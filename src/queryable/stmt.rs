@@ -8,28 +8,67 @@
 
 use bit_vec::BitVec;
 use byteorder::{LittleEndian as LE, ReadBytesExt, WriteBytesExt};
-use futures::future::{
-    err, loop_fn, ok,
-    Either::{self, *},
-    Future, IntoFuture, Loop,
+use futures::{
+    future::{
+        err, loop_fn, ok,
+        Either::{self, *},
+        Future, IntoFuture, Loop,
+    },
+    Stream,
 };
-use mysql_common::value::serialize_bin_many;
+use mysql_common::{packets::RawPacket, row::new_row, value::serialize_bin_many};
 
-use std::io::Write;
+use std::{borrow::Cow, io::Write, slice, sync::Arc};
 
 use crate::{
     connection_like::{
-        streamless::Streamless, ConnectionLike, ConnectionLikeWrapper, StmtCacheResult,
+        streamless::Streamless, ConnectionLike, ConnectionLikeWrapper, Cursor, StmtCacheResult,
     },
-    consts::{ColumnType, Command},
+    consts::{CapabilityFlags, ColumnType, Command, StatusFlags},
     error::*,
     io,
-    prelude::FromRow,
-    queryable::{query_result::QueryResult, BinaryProtocol},
-    Column, MyFuture, Params, Row,
+    prelude::{ColumnExt, FromRow},
+    queryable::{
+        batch::fill_missing_named_params,
+        first_row,
+        query_result::{self, QueryResult},
+        BinaryProtocol,
+    },
+    Column, Conn, MyFuture, Params, Row,
     Value::{self, *},
 };
 
+/// Parameter of `Stmt::execute_shared`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SharedParam {
+    /// Regular parameter value.
+    Value(Value),
+    /// Binary payload sent to the server chunk by chunk from the shared buffer
+    /// (using `COM_STMT_SEND_LONG_DATA`), i.e. without being copied into a `Value`.
+    Blob(bytes::Bytes),
+}
+
+impl From<Value> for SharedParam {
+    fn from(value: Value) -> Self {
+        SharedParam::Value(value)
+    }
+}
+
+impl From<bytes::Bytes> for SharedParam {
+    fn from(bytes: bytes::Bytes) -> Self {
+        SharedParam::Blob(bytes)
+    }
+}
+
+impl From<Cow<'static, [u8]>> for SharedParam {
+    fn from(bytes: Cow<'static, [u8]>) -> Self {
+        match bytes {
+            Cow::Borrowed(bytes) => SharedParam::Blob(bytes::Bytes::from_static(bytes)),
+            Cow::Owned(bytes) => SharedParam::Blob(bytes.into()),
+        }
+    }
+}
+
 /// Inner statement representation.
 #[derive(Eq, PartialEq, Clone, Debug)]
 pub struct InnerStmt {
@@ -41,6 +80,8 @@ pub struct InnerStmt {
     pub num_columns: u16,
     pub num_params: u16,
     pub warning_count: u16,
+    /// Indexes of parameters bound to `LIMIT` and `OFFSET` clauses.
+    pub limit_params: Vec<usize>,
 }
 
 impl InnerStmt {
@@ -59,11 +100,16 @@ impl InnerStmt {
             warning_count: warning_count,
             params: None,
             columns: None,
+            limit_params: Vec::new(),
         })
     }
 }
 
 /// Prepared statement
+///
+/// Statement is transparently re-prepared (using its original SQL text) on the first use after
+/// server-side statements of its connection were discarded (i.e. the session was reset or
+/// re-established using `Stmt::reset_connection`).
 pub struct Stmt<T> {
     conn_like: Option<Either<T, Streamless<T>>>,
     inner: InnerStmt,
@@ -71,27 +117,56 @@ pub struct Stmt<T> {
     /// Some(Cached) => Should not be closed
     /// Some(NotCached(_)) => Should be closed
     cached: Option<StmtCacheResult>,
+    /// Number of rows to fetch at a time using a server-side cursor.
+    fetch_hint: Option<u32>,
+    /// Original SQL text of this statement.
+    query: String,
+    /// Session generation of the connection at the time this statement was prepared.
+    generation: u64,
 }
 
-pub fn new<T>(conn_like: T, inner: InnerStmt, cached: StmtCacheResult) -> Stmt<T>
+/// `CURSOR_TYPE_READ_ONLY` flag of `COM_STMT_EXECUTE`.
+const CURSOR_TYPE_READ_ONLY: u8 = 0x01;
+
+pub fn new<T>(conn_like: T, inner: InnerStmt, cached: StmtCacheResult, query: String) -> Stmt<T>
 where
     T: ConnectionLike + Sized + 'static,
 {
-    Stmt::new(conn_like, inner, cached)
+    Stmt::new(conn_like, inner, cached, query)
 }
 
 impl<T> Stmt<T>
 where
     T: ConnectionLike + Sized + 'static,
 {
-    fn new(conn_like: T, inner: InnerStmt, cached: StmtCacheResult) -> Stmt<T> {
+    fn new(conn_like: T, inner: InnerStmt, cached: StmtCacheResult, query: String) -> Stmt<T> {
         Stmt {
+            generation: conn_like.get_session_generation(),
             conn_like: Some(A(conn_like)),
             inner,
             cached: Some(cached),
+            fetch_hint: None,
+            query,
         }
     }
 
+    /// Prepares this statement again if server-side statements of the connection
+    /// were discarded since it was prepared.
+    fn ensure_prepared(self) -> impl MyFuture<Self> {
+        if self.generation == self.get_session_generation() {
+            return A(ok(self));
+        }
+        let query = self.query.clone();
+        let fut = self.prepare_stmt(query).map(|(mut this, inner, cached)| {
+            // Statement of the previous session is gone, so there is nothing to close.
+            this.generation = this.get_session_generation();
+            this.inner = inner;
+            this.cached = Some(cached);
+            this
+        });
+        B(fut)
+    }
+
     fn send_long_data_for_index(
         self,
         params: Vec<Value>,
@@ -154,12 +229,10 @@ where
         )
     }
 
-    fn execute_positional<U>(self, params: U) -> impl MyFuture<QueryResult<Self, BinaryProtocol>>
-    where
-        U: ::std::ops::Deref<Target = [Value]>,
-        U: IntoIterator<Item = Value>,
-        U: Send + 'static,
-    {
+    fn execute_positional(
+        self,
+        mut params: Vec<Value>,
+    ) -> impl MyFuture<QueryResult<Self, BinaryProtocol>> {
         if self.inner.num_params as usize != params.len() {
             let error = DriverError::StmtParamsMismatch {
                 required: self.inner.num_params,
@@ -169,30 +242,35 @@ where
             return A(err(error));
         }
 
-        let fut = self
-            .inner
-            .params
-            .as_ref()
-            .ok_or_else(|| unreachable!())
-            .and_then(|params_def| serialize_bin_many(&*params_def, &*params).map_err(Error::from))
-            .into_future()
-            .and_then(|bin_payload| match bin_payload {
-                (row_data, null_bitmap, large_bitmap) => self
-                    .send_long_data(params.into_iter().collect(), large_bitmap.clone())
-                    .and_then(|(this, params)| {
-                        let mut data = Vec::new();
-                        write_data(
-                            &mut data,
-                            this.inner.statement_id,
-                            row_data,
-                            params,
-                            this.inner.params.as_ref().unwrap(),
-                            null_bitmap,
-                        );
-                        this.write_command_data(Command::COM_STMT_EXECUTE, data)
-                    }),
+        coerce_limit_params(&mut params, &self.inner.limit_params);
+        let (row_data, null_bitmap, large_bitmap) =
+            match serialize_bin_many(self.params_ref(), &params) {
+                Ok(serialized) => serialized,
+                Err(error) => return A(err(error.into())),
+            };
+        // Long data accumulates on the server side until the statement is executed,
+        // so we should discard leftovers of a previous (possibly failed) execution.
+        let fut = if large_bitmap.any() {
+            A(self.reset())
+        } else {
+            B(ok(self))
+        };
+        let fut = fut
+            .and_then(move |this| this.send_long_data(params, large_bitmap))
+            .and_then(|(this, params)| {
+                let mut data = Vec::new();
+                write_data(
+                    &mut data,
+                    this.inner.statement_id,
+                    this.execute_flags(),
+                    row_data,
+                    &params,
+                    this.params_ref(),
+                    null_bitmap,
+                );
+                this.write_command_data(Command::COM_STMT_EXECUTE, data)
             })
-            .and_then(|this| this.read_result_set(None));
+            .and_then(|this| this.read_execute_result());
         B(fut)
     }
 
@@ -202,13 +280,18 @@ where
             return A(err(error));
         }
 
-        let positional_params =
-            match params.into_positional(self.inner.named_params.as_ref().unwrap()) {
-                Ok(positional_params) => positional_params,
-                Err(error) => {
-                    return A(err(error.into()));
-                }
-            };
+        let named_params = self.inner.named_params.as_ref().unwrap();
+        let params = if self.get_opts().get_missing_named_params_as_null() {
+            fill_missing_named_params(params, named_params)
+        } else {
+            params
+        };
+        let positional_params = match params.into_positional(named_params) {
+            Ok(positional_params) => positional_params,
+            Err(error) => {
+                return A(err(error.into()));
+            }
+        };
 
         match positional_params {
             Params::Positional(params) => B(self.execute_positional(params)),
@@ -228,12 +311,141 @@ where
 
         let mut data = Vec::with_capacity(4 + 1 + 4);
         data.write_u32::<LE>(self.inner.statement_id).unwrap();
-        data.write_u8(0u8).unwrap();
+        data.write_u8(self.execute_flags()).unwrap();
         data.write_u32::<LE>(1u32).unwrap();
 
         B(self
             .write_command_data(Command::COM_STMT_EXECUTE, data)
-            .and_then(|this| this.read_result_set(None)))
+            .and_then(|this| this.read_execute_result()))
+    }
+
+    /// Returns flags of `COM_STMT_EXECUTE` for this statement.
+    fn execute_flags(&self) -> u8 {
+        match self.fetch_hint {
+            Some(_) => CURSOR_TYPE_READ_ONLY,
+            None => 0,
+        }
+    }
+
+    /// Reads result of `COM_STMT_EXECUTE` and requests the first rows if a cursor was opened.
+    fn read_execute_result(self) -> impl MyFuture<QueryResult<Self, BinaryProtocol>> {
+        let fetch_size = match self.fetch_hint {
+            Some(fetch_size) => fetch_size,
+            None => return A(self.read_result_set(None)),
+        };
+        let statement_id = self.inner.statement_id;
+
+        let fut = self.read_result_set(None).and_then(move |result| {
+            let (this, columns, cached) = query_result::disassemble(result);
+            let fut = if columns.is_none() {
+                A(ok(this))
+            } else if this
+                .get_capabilities()
+                .contains(CapabilityFlags::CLIENT_DEPRECATE_EOF)
+            {
+                B(this.read_cursor_eof())
+            } else {
+                A(ok(this))
+            };
+            fut.and_then(move |mut this| {
+                if columns.is_some()
+                    && this
+                        .get_status()
+                        .contains(StatusFlags::SERVER_STATUS_CURSOR_EXISTS)
+                {
+                    let cursor = Cursor {
+                        statement_id,
+                        fetch_size,
+                    };
+                    this.set_cursor(Some(cursor));
+                    A(this.fetch_cursor_rows(cursor))
+                } else {
+                    B(ok(this))
+                }
+            })
+            .map(|this| query_result::assemble(this, columns, cached))
+        });
+        B(fut)
+    }
+
+    /// Reads EOF packet that follows column definitions if the server opened a cursor.
+    ///
+    /// This EOF is sent even if it's deprecated, because it carries `SERVER_STATUS_CURSOR_EXISTS`
+    /// flag. Otherwise the next packet is the first row or the end of the result set,
+    /// so it's returned to the stream to be read as usual.
+    fn read_cursor_eof(self) -> impl MyFuture<Self> {
+        let (streamless, stream) = self.take_stream();
+        stream
+            .into_future()
+            .map_err(|(error, _)| error)
+            .and_then(|(packet_opt, mut stream)| match packet_opt {
+                Some((packet, seq_id)) => match eof_status(&packet) {
+                    Some(status) if status.contains(StatusFlags::SERVER_STATUS_CURSOR_EXISTS) => {
+                        let mut this = streamless.return_stream(stream);
+                        this.set_status(status);
+                        this.set_seq_id(seq_id.wrapping_add(1));
+                        this.touch();
+                        Ok(this)
+                    }
+                    _ => {
+                        stream.unread(packet, seq_id);
+                        Ok(streamless.return_stream(stream))
+                    }
+                },
+                None => Err(DriverError::ConnectionClosed.into()),
+            })
+    }
+
+    /// Returns the SQL text this statement was prepared from.
+    pub fn query(&self) -> &str {
+        &*self.query
+    }
+
+    /// Returns identifier of this statement (assigned by the server).
+    pub fn id(&self) -> u32 {
+        self.inner.statement_id
+    }
+
+    /// Returns number of parameters of this statement.
+    pub fn num_params(&self) -> u16 {
+        self.inner.num_params
+    }
+
+    /// Returns number of columns in a result of this statement.
+    pub fn num_columns(&self) -> u16 {
+        self.inner.num_columns
+    }
+
+    /// Returns reference to parameters metadata of this statement.
+    pub fn params_ref(&self) -> &[Column] {
+        self.inner.params.as_ref().map(|x| &**x).unwrap_or(&[])
+    }
+
+    /// Returns reference to columns metadata of this statement.
+    pub fn columns_ref(&self) -> &[Column] {
+        self.inner.columns.as_ref().map(|x| &**x).unwrap_or(&[])
+    }
+
+    /// Returns names of named parameters of this statement in order of their positions
+    /// (empty for a statement with positional parameters).
+    pub fn named_params_ref(&self) -> &[String] {
+        self.inner
+            .named_params
+            .as_ref()
+            .map(|x| &**x)
+            .unwrap_or(&[])
+    }
+
+    /// Makes subsequent executions of this statement open a read-only server-side cursor,
+    /// so that rows are fetched from the server by `fetch_hint` rows at a time
+    /// (using `COM_STMT_FETCH`) instead of being sent all at once (defaults to `None`).
+    ///
+    /// It allows to tune memory usage and latency of large scans. Cursor is only applicable to
+    /// statements producing a single result set (i.e. `SELECT`). Dropping a result of
+    /// such execution will close the cursor without fetching remaining rows.
+    pub fn fetch_hint(mut self, fetch_hint: Option<u32>) -> Self {
+        self.fetch_hint = fetch_hint.filter(|&rows| rows > 0);
+        self
     }
 
     /// See `Queriable::execute`
@@ -242,10 +454,257 @@ where
         P: Into<Params>,
     {
         let params = params.into();
-        match params {
-            Params::Positional(params) => return A(self.execute_positional(params)),
-            Params::Named(_) => return B(A(self.execute_named(params))),
-            Params::Empty => return B(B(self.execute_empty())),
+        let fut = self.ensure_prepared().and_then(|this| match params {
+            Params::Positional(params) => A(this.execute_positional(params)),
+            Params::Named(_) => B(A(this.execute_named(params))),
+            Params::Empty => B(B(this.execute_empty())),
+        });
+        fut.map(|result| {
+            let (mut stmt, columns, cached) = query_result::disassemble(result);
+            if let Some(ref columns) = columns {
+                stmt.refresh_columns(&**columns);
+            }
+            query_result::assemble(stmt, columns, cached)
+        })
+    }
+
+    /// Same as `Stmt::execute` with positional parameters, but `SharedParam::Blob` parameters
+    /// are sent as long data chunk by chunk, so that binding large blobs (e.g. `&'static [u8]`
+    /// or `bytes::Bytes` shared with other parts of a program) doesn't copy them as a whole:
+    /// only the chunk being sent is copied into a packet buffer.
+    pub fn execute_shared(
+        self,
+        params: Vec<SharedParam>,
+    ) -> impl MyFuture<QueryResult<Self, BinaryProtocol>> {
+        self.ensure_prepared()
+            .and_then(|this| this.execute_shared_prepared(params))
+    }
+
+    fn execute_shared_prepared(
+        self,
+        params: Vec<SharedParam>,
+    ) -> impl MyFuture<QueryResult<Self, BinaryProtocol>> {
+        if self.inner.num_params as usize != params.len() {
+            let error = DriverError::StmtParamsMismatch {
+                required: self.inner.num_params,
+                supplied: params.len() as u16,
+            }
+            .into();
+            return A(err(error));
+        }
+
+        let mut values = Vec::with_capacity(params.len());
+        let mut blobs = Vec::new();
+        for (index, param) in params.into_iter().enumerate() {
+            match param {
+                SharedParam::Value(value) => values.push(value),
+                SharedParam::Blob(blob) => {
+                    // placeholder that isn't serialized into the row data
+                    values.push(NULL);
+                    blobs.push((index, blob));
+                }
+            }
+        }
+
+        coerce_limit_params(&mut values, &self.inner.limit_params);
+        let (row_data, mut null_bitmap, large_bitmap) =
+            match serialize_bin_many(self.params_ref(), &values) {
+                Ok(serialized) => serialized,
+                Err(error) => return A(err(error.into())),
+            };
+        for &(index, _) in &blobs {
+            null_bitmap.set(index, false);
+            values[index] = Bytes(Vec::new());
+        }
+        let fut = if large_bitmap.any() || !blobs.is_empty() {
+            A(self.reset())
+        } else {
+            B(ok(self))
+        };
+        let fut = fut
+            .and_then(move |this| this.send_long_data(values, large_bitmap))
+            .and_then(move |(this, values)| {
+                loop_fn((this, blobs.into_iter()), |(this, mut blobs)| {
+                    match blobs.next() {
+                        Some((index, blob)) => A(this
+                            .send_shared_long_data(index, blob)
+                            .map(|this| Loop::Continue((this, blobs)))),
+                        None => B(ok(Loop::Break(this))),
+                    }
+                })
+                .map(move |this| (this, values))
+            })
+            .and_then(move |(this, values)| {
+                let mut data = Vec::new();
+                write_data(
+                    &mut data,
+                    this.inner.statement_id,
+                    this.execute_flags(),
+                    row_data,
+                    &values,
+                    this.params_ref(),
+                    null_bitmap,
+                );
+                this.write_command_data(Command::COM_STMT_EXECUTE, data)
+            })
+            .and_then(|this| this.read_execute_result())
+            .map(|result| {
+                let (mut stmt, columns, cached) = query_result::disassemble(result);
+                if let Some(ref columns) = columns {
+                    stmt.refresh_columns(&**columns);
+                }
+                query_result::assemble(stmt, columns, cached)
+            });
+        B(fut)
+    }
+
+    /// Same as `Stmt::execute` with positional parameters, but parameters are serialized
+    /// into the execute packet straight from references, so that executing a statement
+    /// in a loop doesn't require cloning parameter values.
+    ///
+    /// Values that don't fit into a single packet are copied once to be sent as long data.
+    pub fn execute_ref(
+        self,
+        params: &[&Value],
+    ) -> impl MyFuture<QueryResult<Self, BinaryProtocol>> {
+        // The number of parameters doesn't change if the statement is re-prepared,
+        // so it is checked before `params_ref` is used to serialize them.
+        if self.inner.num_params as usize != params.len() {
+            let error = DriverError::StmtParamsMismatch {
+                required: self.inner.num_params,
+                supplied: params.len() as u16,
+            }
+            .into();
+            return A(err(error));
+        }
+
+        let coerced = self
+            .inner
+            .limit_params
+            .iter()
+            .filter_map(|&index| {
+                let value = coerce_limit_param(params.get(index)?)?;
+                Some((index, value))
+            })
+            .collect::<Vec<_>>();
+        let params: Cow<[&Value]> = if coerced.is_empty() {
+            Cow::Borrowed(params)
+        } else {
+            let mut params = params.to_vec();
+            for &(index, ref value) in &coerced {
+                params[index] = value;
+            }
+            Cow::Owned(params)
+        };
+        let (row_data, null_bitmap, large_bitmap) =
+            match serialize_params_ref(self.params_ref(), &params) {
+                Ok(serialized) => serialized,
+                Err(error) => return A(err(error)),
+            };
+        let long_data = large_bitmap
+            .iter()
+            .enumerate()
+            .filter(|&(_, large)| large)
+            .map(|(index, _)| match *params[index] {
+                Bytes(ref x) => (index, bytes::Bytes::from(&x[..])),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        // Types of parameters are written once the statement is (re-)prepared, because
+        // types of `NULL`s are taken from its current metadata. Bytes are already serialized.
+        let types = params
+            .iter()
+            .map(|&value| match *value {
+                Bytes(..) => Bytes(Vec::new()),
+                ref value => value.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        let fut =
+            self.ensure_prepared()
+                .and_then(move |this| {
+                    let fut = if long_data.is_empty() {
+                        A(ok(this))
+                    } else {
+                        B(this.reset())
+                    };
+                    fut.and_then(|this| {
+                        loop_fn((this, long_data.into_iter()), |(this, mut long_data)| {
+                            match long_data.next() {
+                                Some((index, blob)) => A(this
+                                    .send_shared_long_data(index, blob)
+                                    .map(|this| Loop::Continue((this, long_data)))),
+                                None => B(ok(Loop::Break(this))),
+                            }
+                        })
+                    })
+                })
+                .and_then(move |this| {
+                    let mut data = Vec::new();
+                    write_data(
+                        &mut data,
+                        this.inner.statement_id,
+                        this.execute_flags(),
+                        row_data,
+                        &types,
+                        this.params_ref(),
+                        null_bitmap,
+                    );
+                    this.write_command_data(Command::COM_STMT_EXECUTE, data)
+                })
+                .and_then(|this| this.read_execute_result())
+                .map(|result| {
+                    let (mut stmt, columns, cached) = query_result::disassemble(result);
+                    if let Some(ref columns) = columns {
+                        stmt.refresh_columns(&**columns);
+                    }
+                    query_result::assemble(stmt, columns, cached)
+                });
+        B(fut)
+    }
+
+    /// Sends a shared `blob` as long data of a parameter at `index`.
+    ///
+    /// Every chunk is copied into its packet, so at most one chunk of the blob is duplicated
+    /// at a time.
+    fn send_shared_long_data(self, index: usize, blob: bytes::Bytes) -> impl MyFuture<Self> {
+        let data_cap = crate::consts::MAX_PAYLOAD_LEN - 10;
+        loop_fn((self, blob, 0), move |(this, blob, offset)| {
+            let end = ::std::cmp::min(offset + data_cap, blob.len());
+            let mut buf = Vec::with_capacity(end - offset + 6);
+            buf.write_u32::<LE>(this.inner.statement_id).unwrap();
+            buf.write_u16::<LE>(index as u16).unwrap();
+            buf.write_all(&blob[offset..end]).unwrap();
+            this.write_command_data(Command::COM_STMT_SEND_LONG_DATA, buf)
+                .map(move |this| {
+                    // empty blob is sent as a single empty chunk
+                    if end < blob.len() {
+                        Loop::Continue((this, blob, end))
+                    } else {
+                        Loop::Break(this)
+                    }
+                })
+        })
+    }
+
+    /// Updates column metadata of this statement (and of its cached counterpart) if it doesn't
+    /// match metadata of a result set (i.e. table was altered since the statement was prepared).
+    fn refresh_columns(&mut self, columns: &[Column]) {
+        if self.inner.num_columns == 0 || self.inner.columns.as_ref().map(|x| &**x) == Some(columns)
+        {
+            return;
+        }
+
+        self.inner.columns = Some(columns.to_vec());
+        self.inner.num_columns = columns.len() as u16;
+
+        if let Some(StmtCacheResult::Cached) = self.cached {
+            let statement_id = self.inner.statement_id;
+            let refreshed_columns = self.inner.columns.clone();
+            if let Some(inner_stmt) = self.stmt_cache_mut().get_by_id_mut(statement_id) {
+                inner_stmt.columns = refreshed_columns;
+                inner_stmt.num_columns = columns.len() as u16;
+            }
         }
     }
 
@@ -257,13 +716,7 @@ where
     {
         self.execute(params)
             .and_then(|result| result.collect_and_drop::<Row>())
-            .map(|(this, mut rows)| {
-                if rows.len() > 1 {
-                    (this, Some(FromRow::from_row(rows.swap_remove(0))))
-                } else {
-                    (this, rows.pop().map(FromRow::from_row))
-                }
-            })
+            .and_then(|(this, rows)| first_row(this, rows))
     }
 
     /// See `Queriable::batch`
@@ -288,6 +741,43 @@ where
         )
     }
 
+    /// Returns future that resets this statement on the server side (`COM_STMT_RESET`).
+    ///
+    /// It discards data sent using `COM_STMT_SEND_LONG_DATA` and closes the statement's cursor.
+    /// Note that `Stmt::execute` will reset the statement before sending long data.
+    pub fn reset(self) -> impl MyFuture<Self> {
+        let mut data = Vec::with_capacity(4);
+        data.write_u32::<LE>(self.inner.statement_id).unwrap();
+        self.write_command_data(Command::COM_STMT_RESET, data)
+            .and_then(|this| this.read_packet())
+            .map(|(this, _)| this)
+    }
+
+    /// Checks (using column metadata) that rows of this statement's result could be converted
+    /// into `R`, so that mismatch is reported before the statement is executed.
+    ///
+    /// It's a best-effort check: every column is assumed to be non-`NULL`, and a conversion is
+    /// checked against a sample value of the column type. String columns are sampled as `"0"`,
+    /// so they pass for string, byte, numeric, boolean and JSON types.
+    pub fn assert_result_type<R: FromRow>(&self) -> Result<()> {
+        let columns = self.inner.columns.clone().unwrap_or_default();
+        let values = columns.iter().map(sample_value).collect();
+        let row = new_row(values, Arc::new(columns));
+        match R::from_row_opt(row) {
+            Ok(_) => Ok(()),
+            Err(error) => {
+                let columns = error
+                    .0
+                    .columns_ref()
+                    .iter()
+                    .map(|column| format!("`{}` {:?}", column.name_str(), column.column_type()))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                Err(DriverError::ResultTypeMismatch { columns }.into())
+            }
+        }
+    }
+
     /// This will close statement (if it's not in the cache) and resolve to a wrapped queryable.
     pub fn close(mut self) -> impl MyFuture<T> {
         let cached = self.cached.take();
@@ -311,6 +801,88 @@ where
     }
 }
 
+impl Stmt<Conn> {
+    /// Returns future that resets the session of the connection (see `Conn::reset`)
+    /// and resolves to this statement.
+    ///
+    /// Reset discards server-side statements, so the statement is prepared again on its next use.
+    pub fn reset_connection(mut self) -> impl MyFuture<Self> {
+        let conn = match self.conn_like.take() {
+            Some(A(conn)) => conn,
+            _ => unreachable!(),
+        };
+        conn.reset().map(move |conn| {
+            self.conn_like = Some(A(conn));
+            self
+        })
+    }
+}
+
+/// Returns a sample value of a `column` (as if it was received using binary protocol).
+///
+/// Strings may hold numbers, so they are sampled as numeric text.
+fn sample_value(column: &Column) -> Value {
+    use crate::consts::ColumnType::*;
+
+    match column.column_type() {
+        MYSQL_TYPE_NULL => NULL,
+        _ if column.is_integer() && column.is_unsigned() => UInt(0),
+        _ if column.is_integer() => Int(0),
+        MYSQL_TYPE_FLOAT | MYSQL_TYPE_DOUBLE => Float(0.0),
+        MYSQL_TYPE_DATE
+        | MYSQL_TYPE_NEWDATE
+        | MYSQL_TYPE_DATETIME
+        | MYSQL_TYPE_DATETIME2
+        | MYSQL_TYPE_TIMESTAMP
+        | MYSQL_TYPE_TIMESTAMP2 => Date(1970, 1, 1, 0, 0, 0, 0),
+        MYSQL_TYPE_TIME | MYSQL_TYPE_TIME2 => Time(false, 0, 0, 0, 0, 0),
+        MYSQL_TYPE_DECIMAL | MYSQL_TYPE_NEWDECIMAL => Bytes(b"0".to_vec()),
+        MYSQL_TYPE_JSON => Bytes(b"null".to_vec()),
+        _ => Bytes(b"0".to_vec()),
+    }
+}
+
+/// Converts values bound to `LIMIT` and `OFFSET` clauses to integers.
+///
+/// Server rejects non-integer values of such parameters (e.g. numbers given as strings),
+/// so integral strings and floats are sent as integers. Other values are left as is.
+fn coerce_limit_params(params: &mut [Value], limit_params: &[usize]) {
+    for &index in limit_params {
+        if let Some(value) = params.get(index).and_then(coerce_limit_param) {
+            params[index] = value;
+        }
+    }
+}
+
+/// Returns integer value of a parameter bound to `LIMIT` or `OFFSET` clause
+/// (`None` if it should be sent as is, see `coerce_limit_params`).
+fn coerce_limit_param(value: &Value) -> Option<Value> {
+    match *value {
+        Bytes(ref bytes) => {
+            let text = String::from_utf8_lossy(bytes);
+            let text = text.trim();
+            text.parse::<u64>()
+                .map(UInt)
+                .or_else(|_| text.parse::<i64>().map(Int))
+                .ok()
+        }
+        Float(x) if x >= 0.0 && x.fract() == 0.0 && x < u64::max_value() as f64 => {
+            Some(UInt(x as u64))
+        }
+        _ => None,
+    }
+}
+
+/// Returns status flags of a (non-deprecated) EOF packet.
+fn eof_status(packet: &RawPacket) -> Option<StatusFlags> {
+    match packet.0[..] {
+        [0xFE, _, _, status_lo, status_hi] => Some(StatusFlags::from_bits_truncate(
+            u16::from(status_lo) | u16::from(status_hi) << 8,
+        )),
+        _ => None,
+    }
+}
+
 impl<T: ConnectionLike + 'static> ConnectionLikeWrapper for Stmt<T> {
     type ConnLike = T;
 
@@ -322,6 +894,9 @@ impl<T: ConnectionLike + 'static> ConnectionLikeWrapper for Stmt<T> {
             conn_like,
             inner,
             cached,
+            fetch_hint,
+            query,
+            generation,
         } = self;
         match conn_like {
             Some(A(conn_like)) => {
@@ -330,6 +905,9 @@ impl<T: ConnectionLike + 'static> ConnectionLikeWrapper for Stmt<T> {
                     conn_like: Some(B(streamless)),
                     inner,
                     cached,
+                    fetch_hint,
+                    query,
+                    generation,
                 };
                 (Streamless::new(this), stream)
             }
@@ -362,23 +940,60 @@ impl<T: ConnectionLike + 'static> ConnectionLikeWrapper for Stmt<T> {
     }
 }
 
+/// Serializes borrowed `params` using the binary protocol.
+///
+/// Values are passed to `serialize_bin_many` one by one, so that they don't have to be cloned
+/// into a slice. `Bytes` that wouldn't fit into the execute packet are marked in the large
+/// bitmap instead (they are sent as long data). `params_def` must be of the same length.
+fn serialize_params_ref(
+    params_def: &[Column],
+    params: &[&Value],
+) -> Result<(Vec<u8>, BitVec<u8>, BitVec<u8>)> {
+    let mut null_bitmap = BitVec::<u8>::default();
+    null_bitmap.grow(params.len(), false);
+    let mut large_bitmap = null_bitmap.clone();
+    let mut row_data = Vec::new();
+    let bitmap_len = (params.len() + 7) / 8;
+    let cap = crate::consts::MAX_PAYLOAD_LEN - 10 - bitmap_len - params.len() * 2;
+
+    for (i, &value) in params.iter().enumerate() {
+        match *value {
+            NULL => null_bitmap.set(i, true),
+            Bytes(ref x) if row_data.len() + x.len() + 9 > cap => large_bitmap.set(i, true),
+            _ => {
+                let (data, _, _) = serialize_bin_many(&params_def[i..=i], slice::from_ref(value))?;
+                row_data.extend_from_slice(&data);
+            }
+        }
+    }
+
+    Ok((row_data, null_bitmap, large_bitmap))
+}
+
+/// Writes the payload of `COM_STMT_EXECUTE`.
+///
+/// Type of a `NULL` value is taken from the parameter definition (`params_def`).
 fn write_data(
     writer: &mut Vec<u8>,
     stmt_id: u32,
+    flags: u8,
     row_data: Vec<u8>,
-    params: Vec<Value>,
-    params_def: &Vec<Column>,
+    params: &[Value],
+    params_def: &[Column],
     null_bitmap: BitVec<u8>,
 ) {
     let capacity = 9 + null_bitmap.storage().len() + 1 + params.len() * 2 + row_data.len();
     writer.reserve(capacity);
     writer.write_u32::<LE>(stmt_id).unwrap();
-    writer.write_u8(0u8).unwrap();
+    writer.write_u8(flags).unwrap();
     writer.write_u32::<LE>(1u32).unwrap();
+    if params.is_empty() {
+        return;
+    }
     writer.write_all(null_bitmap.storage().as_ref()).unwrap();
     writer.write_u8(1u8).unwrap();
-    for i in 0..params.len() {
-        let result = match params[i] {
+    for (i, value) in params.iter().enumerate() {
+        let result = match *value {
             NULL => writer.write_all(&[params_def[i].column_type() as u8, 0u8]),
             Bytes(..) => writer.write_all(&[ColumnType::MYSQL_TYPE_VAR_STRING as u8, 0u8]),
             Int(..) => writer.write_all(&[ColumnType::MYSQL_TYPE_LONGLONG as u8, 0u8]),
@@ -7,19 +7,21 @@
 // modified, or distributed except according to those terms.
 
 use futures::future::{
-    err, ok,
+    err, loop_fn, ok,
     Either::{self, *},
-    Future, IntoFuture,
+    Future, Loop,
 };
+use tokio::executor::{DefaultExecutor, Executor};
 
-use std::fmt;
+use std::{fmt, iter};
 
 use crate::{
     connection_like::{streamless::Streamless, ConnectionLike, ConnectionLikeWrapper},
     error::*,
     io,
+    prelude::FromRow,
     queryable::Queryable,
-    MyFuture,
+    Conn, MyFuture,
 };
 
 /// Options for transaction
@@ -67,6 +69,28 @@ impl TransactionOptions {
     pub fn readonly(&self) -> Option<bool> {
         self.readonly
     }
+
+    /// Returns queries that start a transaction with these options.
+    fn start_queries(&self) -> Vec<String> {
+        let mut queries = Vec::new();
+        if let Some(isolation_level) = self.isolation_level {
+            queries.push(format!(
+                "SET TRANSACTION ISOLATION LEVEL {}",
+                isolation_level
+            ));
+        }
+        match self.readonly {
+            Some(true) => queries.push("SET TRANSACTION READ ONLY".into()),
+            Some(false) => queries.push("SET TRANSACTION READ WRITE".into()),
+            None => (),
+        }
+        if self.consistent_snapshot {
+            queries.push("START TRANSACTION WITH CONSISTENT SNAPSHOT".into());
+        } else {
+            queries.push("START TRANSACTION".into());
+        }
+        queries
+    }
 }
 
 /// Transaction isolation level.
@@ -94,7 +118,14 @@ impl fmt::Display for IsolationLevel {
 /// `Transaction` it's a sugar for `START TRANSACTION`, `ROLLBACK` and `COMMIT` queries, so one
 /// should note that it is easy to mess things up calling this queries manually. Also you will get
 /// `NestedTransaction` error if you call `transaction.start_transaction(_)`.
-pub struct Transaction<T>(Option<Either<T, Streamless<T>>>);
+///
+/// Transaction that is dropped without `commit` or `rollback` (e.g. because the future
+/// that owns it was cancelled) spawns `ROLLBACK` on the default executor right away, so that
+/// a pooled connection doesn't hold locks until the pool is polled again. The wrapped
+/// `Queryable` is dropped once it is rolled back. If there is no executor or a result set
+/// of the transaction wasn't consumed, the `Queryable` is dropped as is (a pooled connection
+/// is then rolled back by the pool, and a standalone connection is closed).
+pub struct Transaction<T>(Option<Either<T, Streamless<T>>>, fn(T));
 
 pub fn new<T>(conn_like: T, options: TransactionOptions) -> impl MyFuture<Transaction<T>>
 where
@@ -105,60 +136,35 @@ where
 
 impl<T: Queryable + ConnectionLike> Transaction<T> {
     fn new(conn_like: T, options: TransactionOptions) -> impl MyFuture<Transaction<T>> {
-        let TransactionOptions {
-            consistent_snapshot,
-            isolation_level,
-            readonly,
-        } = options;
-
         if conn_like.get_in_transaction() {
             return A(err(DriverError::NestedTransaction.into()));
         }
 
-        if readonly.is_some() && conn_like.get_server_version() < (5, 6, 5) {
+        if options.readonly.is_some() && conn_like.get_server_version() < (5, 6, 5) {
             return A(err(DriverError::ReadOnlyTransNotSupported.into()));
         }
 
-        let fut = if let Some(isolation_level) = isolation_level {
-            A(conn_like.drop_query(format!(
-                "SET TRANSACTION ISOLATION LEVEL {}",
-                isolation_level
-            )))
-        } else {
-            B(ok(conn_like))
-        };
-
-        let fut = fut
-            .into_future()
-            .and_then(move |conn_like| {
-                if let Some(readonly) = readonly {
-                    if readonly {
-                        A(conn_like.drop_query("SET TRANSACTION READ ONLY"))
-                    } else {
-                        A(conn_like.drop_query("SET TRANSACTION READ WRITE"))
-                    }
-                } else {
-                    B(ok(conn_like))
-                }
-            })
-            .and_then(move |conn_like| {
-                if consistent_snapshot {
-                    conn_like.drop_query("START TRANSACTION WITH CONSISTENT SNAPSHOT")
-                } else {
-                    conn_like.drop_query("START TRANSACTION")
-                }
-            })
-            .map(|mut conn_like| {
-                conn_like.set_in_transaction(true);
-                Transaction(Some(A(conn_like)))
-            });
+        let queries = options.start_queries().into_iter();
+        let fut = loop_fn(
+            (conn_like, queries),
+            |(conn_like, mut queries)| match queries.next() {
+                Some(query) => A(conn_like
+                    .drop_query(query)
+                    .map(|conn_like| Loop::Continue((conn_like, queries)))),
+                None => B(ok(Loop::Break(conn_like))),
+            },
+        )
+        .map(|mut conn_like| {
+            conn_like.set_in_transaction(true);
+            Transaction(Some(A(conn_like)), rollback_on_drop::<T>)
+        });
 
         B(fut)
     }
 
-    fn unwrap(self) -> T {
-        match self {
-            Transaction(Some(A(conn_like))) => conn_like,
+    fn unwrap(mut self) -> T {
+        match self.0.take() {
+            Some(A(conn_like)) => conn_like,
             _ => unreachable!(),
         }
     }
@@ -180,19 +186,62 @@ impl<T: Queryable + ConnectionLike> Transaction<T> {
     }
 }
 
+impl Transaction<Conn> {
+    /// Returns future that commits this transaction, starts the next one with the given
+    /// `options` and performs `queries` in it using a single round trip (see `Conn::pipeline`).
+    ///
+    /// Resolves to the new transaction and the first result set of every query. This allows
+    /// consumers that commit many small units of work in a row to overlap `COMMIT` of a unit
+    /// with `START TRANSACTION` and the work of the next one on high-latency links.
+    ///
+    /// If any of the pipelined queries fails, the connection is dropped (and the new transaction
+    /// is rolled back by the server). Note that the error doesn't tell whether this transaction
+    /// was committed, unless it is the error of the `COMMIT` itself, so units of work should be
+    /// idempotent (see `Conn::exec_idempotent`).
+    pub fn commit_pipelined<Q, R>(
+        self,
+        options: TransactionOptions,
+        queries: Vec<Q>,
+    ) -> impl MyFuture<(Transaction<Conn>, Vec<Vec<R>>)>
+    where
+        Q: AsRef<str>,
+        R: FromRow + Send + 'static,
+    {
+        if options.readonly.is_some() && self.get_server_version() < (5, 6, 5) {
+            return A(err(DriverError::ReadOnlyTransNotSupported.into()));
+        }
+
+        let start_queries = options.start_queries();
+        let skip = 1 + start_queries.len();
+        let pipeline = iter::once("COMMIT".to_string())
+            .chain(start_queries)
+            .chain(queries.iter().map(|query| query.as_ref().to_string()))
+            .collect::<Vec<_>>();
+
+        let fut = self
+            .unwrap()
+            .pipeline::<_, R>(pipeline)
+            .map(move |(mut conn, mut results)| {
+                conn.set_in_transaction(true);
+                let transaction = Transaction(Some(A(conn)), rollback_on_drop::<Conn>);
+                (transaction, results.split_off(skip))
+            });
+        B(fut)
+    }
+}
+
 impl<T: ConnectionLike + 'static> ConnectionLikeWrapper for Transaction<T> {
     type ConnLike = T;
 
-    fn take_stream(self) -> (Streamless<Self>, io::Stream)
+    fn take_stream(mut self) -> (Streamless<Self>, io::Stream)
     where
         Self: Sized,
     {
-        let Transaction(conn_like) = self;
-        match conn_like {
+        match self.0.take() {
             Some(A(conn_like)) => {
                 let (streamless, stream) = conn_like.take_stream();
-                let this = Transaction(Some(B(streamless)));
-                (Streamless::new(this), stream)
+                self.0 = Some(B(streamless));
+                (Streamless::new(self), stream)
             }
             _ => unreachable!(),
         }
@@ -222,3 +271,27 @@ impl<T: ConnectionLike + 'static> ConnectionLikeWrapper for Transaction<T> {
         }
     }
 }
+
+impl<T> Drop for Transaction<T> {
+    fn drop(&mut self) {
+        if let Some(A(conn_like)) = self.0.take() {
+            (self.1)(conn_like);
+        }
+    }
+}
+
+/// Rolls back a transaction that was dropped uncommitted (see `Transaction`).
+fn rollback_on_drop<T>(conn_like: T)
+where
+    T: Queryable + ConnectionLike,
+{
+    if !conn_like.get_in_transaction() || conn_like.get_pending_result().is_some() {
+        return;
+    }
+    let fut = conn_like
+        .drop_query("ROLLBACK")
+        .map(|mut conn_like| conn_like.set_in_transaction(false))
+        .map_err(drop);
+    // Future (and the wrapped `Queryable`) is dropped if there is no executor.
+    let _ = DefaultExecutor::current().spawn(Box::new(fut));
+}
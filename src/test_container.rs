@@ -0,0 +1,161 @@
+// Copyright (c) 2019 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Disposable MySql servers for integration tests (requires `test-container` feature).
+//!
+//! Server is started in a Docker container (`docker` executable must be available)
+//! and is removed when `MySqlContainer` is dropped:
+//!
+//! ```rust,ignore
+//! let container = MySqlContainer::start("mysql:5.7").unwrap();
+//! let opts = container.opts();
+//! let fut = container
+//!     .wait_ready(Duration::from_secs(60))
+//!     .and_then(move |_| Conn::new(opts))
+//!     .and_then(|conn| conn.drop_query("CREATE TABLE tmp (id INT)"));
+//! ```
+
+use futures::future::{err, loop_fn, ok, Either::*, Future, Loop};
+use tokio::timer::Delay;
+
+use std::{
+    process::Command,
+    time::{Duration, Instant},
+};
+
+use crate::{error::*, Conn, MyFuture, Opts, OptsBuilder};
+
+const PASSWORD: &str = "password";
+const DATABASE: &str = "test";
+
+/// MySql server running in a Docker container.
+#[derive(Debug)]
+pub struct MySqlContainer {
+    id: String,
+    port: u16,
+}
+
+impl MySqlContainer {
+    /// Starts a container from the given `image` (e.g. `mysql:5.7` or `mariadb:10.3`)
+    /// with the server port published on a random local port.
+    ///
+    /// Server needs some time to initialize, so use `MySqlContainer::wait_ready`
+    /// before connecting.
+    pub fn start(image: &str) -> Result<MySqlContainer> {
+        let id = docker(&[
+            "run",
+            "-d",
+            "-p",
+            "127.0.0.1::3306",
+            "-e",
+            &format!("MYSQL_ROOT_PASSWORD={}", PASSWORD),
+            "-e",
+            &format!("MYSQL_DATABASE={}", DATABASE),
+            image,
+        ])?;
+
+        let port = docker(&["port", &id, "3306"]).and_then(|mapping| {
+            mapping
+                .lines()
+                .next()
+                .and_then(|line| line.rsplit(':').next())
+                .and_then(|port| port.trim().parse::<u16>().ok())
+                .ok_or_else(|| Error::from(format!("can't parse published port `{}'", mapping)))
+        });
+
+        match port {
+            Ok(port) => Ok(MySqlContainer { id, port }),
+            Err(e) => {
+                let _ = docker(&["rm", "-f", "-v", &id]);
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns id of the container.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns local port of the server.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Returns connection URL of the server (root user, `test` database).
+    pub fn url(&self) -> String {
+        format!(
+            "mysql://root:{}@127.0.0.1:{}/{}",
+            PASSWORD, self.port, DATABASE
+        )
+    }
+
+    /// Returns connection options of the server (root user, `test` database).
+    pub fn opts(&self) -> Opts {
+        let mut builder = OptsBuilder::new();
+        builder
+            .ip_or_hostname("127.0.0.1")
+            .tcp_port(self.port)
+            .user(Some("root"))
+            .pass(Some(PASSWORD))
+            .db_name(Some(DATABASE));
+        builder.into()
+    }
+
+    /// Returns future that resolves when the server accepts connections
+    /// or to the last connection error if the server isn't ready within `timeout`.
+    pub fn wait_ready(&self, timeout: Duration) -> impl MyFuture<()> {
+        let opts = self.opts();
+        let deadline = Instant::now() + timeout;
+        loop_fn((), move |_| {
+            Conn::new(opts.clone())
+                .and_then(|conn| conn.disconnect())
+                .then(move |result| match result {
+                    Ok(()) => A(ok(Loop::Break(()))),
+                    Err(e) => {
+                        if Instant::now() >= deadline {
+                            A(err(e))
+                        } else {
+                            let delay = Delay::new(Instant::now() + Duration::from_millis(500))
+                                .then(|_| Ok(Loop::Continue(())));
+                            B(delay)
+                        }
+                    }
+                })
+        })
+    }
+
+    /// Stops and removes the container.
+    pub fn stop(mut self) -> Result<()> {
+        let id = std::mem::replace(&mut self.id, String::new());
+        docker(&["rm", "-f", "-v", &id]).map(drop)
+    }
+}
+
+impl Drop for MySqlContainer {
+    fn drop(&mut self) {
+        if !self.id.is_empty() {
+            let _ = docker(&["rm", "-f", "-v", &self.id]);
+        }
+    }
+}
+
+/// Runs docker with the given `args` and returns its trimmed stdout.
+fn docker(args: &[&str]) -> Result<String> {
+    let output = Command::new("docker").args(args).output()?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+    } else {
+        Err(format!(
+            "`docker {}' failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into())
+    }
+}